@@ -0,0 +1,212 @@
+use std::{fs, io, path::Path};
+
+use serde::Deserialize;
+use thiserror::Error;
+use toml::de;
+
+use crate::{
+    chatbots, config::Config, Chatbot, ChatbotCreationError, Message, Role,
+};
+
+pub const DEFAULT_MAX_TURNS: usize = 6;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum AgentsError {
+    #[error("Failed to read agents file: {0}.")]
+    Read(#[from] io::Error),
+    #[error("Failed to parse agents file: {0}")]
+    Parse(#[from] de::Error),
+    #[error("At least two agents are required.")]
+    NotEnoughAgents,
+    #[error("Failed to create agent {name}: {source}")]
+    Creation {
+        name: String,
+        source: ChatbotCreationError,
+    },
+}
+
+#[non_exhaustive]
+#[derive(Deserialize)]
+pub struct AgentConfig {
+    pub name: String,
+    pub chatbot: String,
+    pub model: Option<String>,
+    pub persona: Option<String>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize)]
+pub struct AgentsConfig {
+    pub topic: String,
+    pub max_turns: Option<usize>,
+    pub agents: Vec<AgentConfig>,
+}
+
+impl AgentsConfig {
+    #[inline]
+    pub fn load(path: &Path) -> Result<Self, AgentsError> {
+        let config_str = fs::read_to_string(path)?;
+        Ok(toml::from_str(&config_str)?)
+    }
+}
+
+pub struct Agent {
+    name: String,
+    persona: Option<String>,
+    chatbot: Box<dyn Chatbot>,
+}
+
+impl Agent {
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn chatbot(&self) -> &dyn Chatbot {
+        &*self.chatbot
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Speaker {
+    Agent(usize),
+    User,
+}
+
+struct Turn {
+    speaker: Speaker,
+    content: String,
+}
+
+pub struct Conversation {
+    agents: Vec<Agent>,
+    topic: String,
+    turns: Vec<Turn>,
+}
+
+impl Conversation {
+    #[inline]
+    pub fn new(
+        agents_config: AgentsConfig,
+        config: &Config,
+    ) -> Result<Self, AgentsError> {
+        if agents_config.agents.len() < 2 {
+            return Err(AgentsError::NotEnoughAgents);
+        }
+
+        let agents = agents_config
+            .agents
+            .into_iter()
+            .map(|agent| {
+                let chatbot =
+                    chatbots::create(&agent.chatbot, agent.model, config)
+                        .map_err(|source| AgentsError::Creation {
+                            name: agent.name.clone(),
+                            source,
+                        })?;
+                Ok(Agent {
+                    name: agent.name,
+                    persona: agent.persona,
+                    chatbot,
+                })
+            })
+            .collect::<Result<Vec<_>, AgentsError>>()?;
+
+        Ok(Self {
+            agents,
+            topic: agents_config.topic,
+            turns: Vec::new(),
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn agents(&self) -> &[Agent] {
+        &self.agents
+    }
+
+    #[inline]
+    pub fn add_agent_turn(&mut self, agent: usize, content: String) {
+        self.turns.push(Turn {
+            speaker: Speaker::Agent(agent),
+            content,
+        });
+    }
+
+    #[inline]
+    pub fn add_user_turn(&mut self, content: String) {
+        self.turns.push(Turn {
+            speaker: Speaker::User,
+            content,
+        });
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn messages_for(&self, agent: usize) -> Vec<Message> {
+        let name = self.agent_name(agent);
+        let others: Vec<&str> = self
+            .agents
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| idx != agent)
+            .map(|(_, other)| other.name.as_str())
+            .collect();
+
+        let mut system = format!(
+            "You are {name}, taking part in a conversation with {}. Reply \
+             only with your own next message.",
+            others.join(", ")
+        );
+        if let Some(persona) = self
+            .agents
+            .get(agent)
+            .and_then(|current| current.persona.as_deref())
+        {
+            system.push_str("\n\n");
+            system.push_str(persona);
+        }
+
+        let mut messages = vec![
+            Message::new(Role::System, system),
+            Message::new(Role::User, format!("Topic: {}", self.topic)),
+        ];
+
+        for turn in &self.turns {
+            let (role, content) = match turn.speaker {
+                Speaker::Agent(idx) if idx == agent => {
+                    (Role::Assistant, turn.content.clone())
+                }
+                Speaker::Agent(idx) => (
+                    Role::User,
+                    format!("{}: {}", self.agent_name(idx), turn.content),
+                ),
+                Speaker::User => {
+                    (Role::User, format!("Moderator: {}", turn.content))
+                }
+            };
+
+            // Consecutive messages from other speakers are merged so the
+            // request keeps alternating between user and assistant turns.
+            match messages.last_mut() {
+                Some(last) if last.role == role => {
+                    last.content.push_str("\n\n");
+                    last.content.push_str(&content);
+                }
+                _ => messages.push(Message::new(role, content)),
+            }
+        }
+
+        messages
+    }
+
+    fn agent_name(&self, agent: usize) -> &str {
+        self.agents
+            .get(agent)
+            .map_or("", |current| current.name.as_str())
+    }
+}
@@ -0,0 +1,114 @@
+use core::cmp::Reverse;
+
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    map_reduce, pricing,
+    session::{self, Session},
+    Role,
+};
+
+/// How many turns ahead the cost of continuing is projected.
+pub const PROJECTED_TURNS: usize = 10;
+
+/// Where the tokens of a saved session go, and what continuing it would
+/// cost. Token counts are estimates; notes are left out as they are never
+/// sent.
+#[non_exhaustive]
+#[derive(Debug, Serialize)]
+pub struct SessionAnalysis {
+    pub total_tokens: usize,
+    /// The messages using the most tokens, heaviest first.
+    pub heaviest: Vec<MessageTokens>,
+    /// The context sent for each response, in order.
+    pub growth: Vec<usize>,
+    /// The model of the last response, which prices the projection.
+    pub model: Option<String>,
+    /// Prompt tokens for `PROJECTED_TURNS` more turns, assuming the context
+    /// keeps growing at its average rate so far.
+    pub projected_tokens: usize,
+    /// The input cost of `projected_tokens` in USD, if the model's price is
+    /// known.
+    pub projected_cost: Option<f64>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Serialize)]
+pub struct MessageTokens {
+    /// Position of the message in the session, counting from 1.
+    pub number: usize,
+    pub speaker: String,
+    pub tokens: usize,
+    /// Share of all the session's tokens, in whole percent.
+    pub percent: usize,
+}
+
+impl SessionAnalysis {
+    /// Analyzes `session`, listing at most `top` of the heaviest messages.
+    #[inline]
+    #[must_use]
+    pub fn new(session: &Session, top: usize, config: &Config) -> Self {
+        let mut heaviest = Vec::new();
+        let mut growth = Vec::new();
+        let mut total_tokens = 0_usize;
+        for (idx, msg) in session.messages.iter().enumerate() {
+            if msg.role == Role::Note {
+                continue;
+            }
+            if msg.role == Role::Assistant {
+                growth.push(total_tokens);
+            }
+            let tokens = map_reduce::estimate_tokens(&msg.content);
+            total_tokens = total_tokens.saturating_add(tokens);
+            heaviest.push(MessageTokens {
+                number: idx.saturating_add(1),
+                speaker: session::speaker(msg, "Assistant"),
+                tokens,
+                percent: 0,
+            });
+        }
+
+        heaviest.sort_by_key(|msg| Reverse(msg.tokens));
+        heaviest.truncate(top);
+        for msg in &mut heaviest {
+            msg.percent = msg
+                .tokens
+                .saturating_mul(100)
+                .checked_div(total_tokens)
+                .unwrap_or(0);
+        }
+
+        let model = session
+            .messages
+            .iter()
+            .rev()
+            .find_map(|msg| msg.provenance.as_ref())
+            .map(|provenance| provenance.model.clone());
+        let projected_tokens = project(total_tokens, &growth);
+        let projected_cost = model
+            .as_deref()
+            .and_then(|model| pricing::input_price(model, config))
+            .map(|price| pricing::cost(projected_tokens, price));
+
+        Self {
+            total_tokens,
+            heaviest,
+            growth,
+            model,
+            projected_tokens,
+            projected_cost,
+        }
+    }
+}
+
+/// Sums the context of `PROJECTED_TURNS` more turns, each larger than the
+/// last by the average growth per turn of `growth`.
+fn project(total_tokens: usize, growth: &[usize]) -> usize {
+    let per_turn = total_tokens.checked_div(growth.len()).unwrap_or(0);
+    (0..PROJECTED_TURNS).fold(0_usize, |sum, turn| {
+        sum.saturating_add(
+            total_tokens.saturating_add(per_turn.saturating_mul(turn)),
+        )
+    })
+}
@@ -0,0 +1,395 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{auth, config::Config};
+
+const GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+const KEY_VAR: &str = "LLMCLI_AUDIT_KEY";
+
+static LOG: OnceLock<Mutex<AuditLog>> = OnceLock::new();
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error(
+        "Audit logging is disabled, set `audit.enabled = true` to use it."
+    )]
+    Disabled,
+    #[error(
+        "Audit logging needs a key to sign entries with, set `audit.key` or \
+         LLMCLI_AUDIT_KEY."
+    )]
+    MissingKey,
+    #[error("Invalid audit key.")]
+    InvalidKey,
+    #[error("Failed to locate data directory.")]
+    DataDir,
+    #[error("The audit log is already open.")]
+    AlreadyInstalled,
+    #[error("Failed to read the audit log: {0}")]
+    Read(io::Error),
+    #[error("Failed to write the audit log: {0}")]
+    Write(io::Error),
+    #[error("Audit log entry {0} is malformed: {1}")]
+    Parse(usize, serde_json::Error),
+    #[error(
+        "Audit log entry {0} does not match the chain, the log was changed."
+    )]
+    Tampered(usize),
+}
+
+/// One request and its response.
+///
+/// Each entry includes the hash of the one before it and its own hash, an
+/// HMAC under the configured key, covers every other field. Without the key,
+/// changing or reordering entries, or removing any but the newest, breaks the
+/// chain.
+///
+/// Removing the newest entries leaves a valid, shorter chain, so the chain
+/// alone cannot tell a truncated log from one that simply ends there.
+/// Keeping the count or last hash reported by `verify` elsewhere, and
+/// comparing against it later, catches that too.
+#[non_exhaustive]
+#[derive(Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub user: String,
+    pub chatbot: String,
+    pub model: String,
+    pub request_sha256: String,
+    pub response_sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    pub prev: String,
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self, key: &str) -> Result<String, AuditError> {
+        let mut unsealed = serde_json::to_value(self)
+            .map_err(|err| AuditError::Write(err.into()))?;
+        if let Some(hash) = unsealed.get_mut("hash") {
+            *hash = Value::String(String::new());
+        }
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                The error would only repeat that the key is unusable.
+            "#
+        )]
+        auth::hmac_sha256_hex(key.as_bytes(), unsealed.to_string().as_bytes())
+            .map_err(|_| AuditError::InvalidKey)
+    }
+}
+
+/// An append-only, hash-chained log of every request, kept as JSON lines.
+/// Only digests are written unless `audit.content` is set.
+pub struct AuditLog {
+    path: PathBuf,
+    key: String,
+    content: bool,
+    next_seq: u64,
+    last_hash: String,
+}
+
+impl AuditLog {
+    #[inline]
+    pub fn open(config: &Config) -> Result<Self, AuditError> {
+        let audit_config = config
+            .audit
+            .as_ref()
+            .filter(|audit| audit.enabled.unwrap_or(false))
+            .ok_or(AuditError::Disabled)?;
+
+        Self::open_at(
+            Self::path(config)?,
+            Self::key(config)?,
+            audit_config.content.unwrap_or(false),
+        )
+    }
+
+    fn open_at(
+        path: PathBuf,
+        key: String,
+        content: bool,
+    ) -> Result<Self, AuditError> {
+        let (next_seq, last_hash) = match fs::read_to_string(&path) {
+            Ok(content) => match content.lines().rfind(|line| !line.is_empty())
+            {
+                Some(line) => {
+                    let last: AuditEntry =
+                        serde_json::from_str(line).map_err(|err| {
+                            AuditError::Parse(content.lines().count(), err)
+                        })?;
+                    (last.seq.saturating_add(1), last.hash)
+                }
+                None => (0, GENESIS_HASH.to_owned()),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                (0, GENESIS_HASH.to_owned())
+            }
+            Err(err) => return Err(AuditError::Read(err)),
+        };
+
+        Ok(Self {
+            path,
+            key,
+            content,
+            next_seq,
+            last_hash,
+        })
+    }
+
+    #[inline]
+    pub fn key(config: &Config) -> Result<String, AuditError> {
+        config
+            .audit
+            .as_ref()
+            .and_then(|audit| audit.key.clone())
+            .or_else(|| env::var(KEY_VAR).ok())
+            .filter(|key| !key.is_empty())
+            .ok_or(AuditError::MissingKey)
+    }
+
+    #[inline]
+    pub fn path(config: &Config) -> Result<PathBuf, AuditError> {
+        match config.audit.as_ref().and_then(|audit| audit.path.clone()) {
+            Some(path) => Ok(path),
+            None => Ok(dirs::data_dir()
+                .ok_or(AuditError::DataDir)?
+                .join("llmcli_audit.jsonl")),
+        }
+    }
+
+    /// Appends an entry for `request` sent to `model` of `chatbot` and its
+    /// `response`, and syncs it to disk before returning.
+    #[inline]
+    pub fn record(
+        &mut self,
+        chatbot: &str,
+        model: &str,
+        request: &str,
+        response: &str,
+    ) -> Result<(), AuditError> {
+        let mut entry = AuditEntry {
+            seq: self.next_seq,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            user: env::var("USER")
+                .or_else(|_| env::var("USERNAME"))
+                .unwrap_or_default(),
+            chatbot: chatbot.to_owned(),
+            model: model.to_owned(),
+            request_sha256: auth::sha256_hex(request.as_bytes()),
+            response_sha256: auth::sha256_hex(response.as_bytes()),
+            request: self.content.then(|| request.to_owned()),
+            response: self.content.then(|| response.to_owned()),
+            prev: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash(&self.key)?;
+
+        let line = serde_json::to_string(&entry)
+            .map_err(|err| AuditError::Write(err.into()))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(AuditError::Write)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(AuditError::Write)?;
+        writeln!(file, "{line}")
+            .and_then(|()| file.sync_data())
+            .map_err(AuditError::Write)?;
+
+        self.next_seq = self.next_seq.saturating_add(1);
+        self.last_hash = entry.hash;
+        Ok(())
+    }
+}
+
+/// Opens the log every chatbot records to, if auditing is enabled. Called
+/// once at startup, before any chatbot is created.
+#[inline]
+pub fn install(config: &Config) -> Result<(), AuditError> {
+    match AuditLog::open(config) {
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                The error only hands back the log that was not installed.
+            "#
+        )]
+        Ok(log) => LOG
+            .set(Mutex::new(log))
+            .map_err(|_| AuditError::AlreadyInstalled),
+        Err(AuditError::Disabled) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The log installed at startup, if auditing is enabled.
+#[inline]
+#[must_use]
+pub fn global() -> Option<&'static Mutex<AuditLog>> {
+    LOG.get()
+}
+
+/// Records a request made outside of a chatbot, such as embeddings.
+#[inline]
+pub fn record(
+    chatbot: &str,
+    model: &str,
+    request: &str,
+    response: &str,
+) -> Result<(), AuditError> {
+    let Some(log) = global() else {
+        return Ok(());
+    };
+    log.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .record(chatbot, model, request, response)
+}
+
+/// Walks the chain in `path` and returns the number of entries, or the
+/// first entry that does not follow from the ones before it. A log cut
+/// short at the end still verifies, see `AuditEntry`.
+#[inline]
+pub fn verify(path: &Path, key: &str) -> Result<usize, AuditError> {
+    let content = fs::read_to_string(path).map_err(AuditError::Read)?;
+
+    let mut prev = GENESIS_HASH.to_owned();
+    let mut count = 0_usize;
+    for (idx, line) in
+        content.lines().filter(|line| !line.is_empty()).enumerate()
+    {
+        let entry: AuditEntry = serde_json::from_str(line)
+            .map_err(|err| AuditError::Parse(idx.saturating_add(1), err))?;
+        let hash = entry.compute_hash(key)?;
+        if entry.prev != prev
+            || entry.hash != hash
+            || usize::try_from(entry.seq).ok() != Some(idx)
+        {
+            return Err(AuditError::Tampered(idx.saturating_add(1)));
+        }
+        prev = entry.hash;
+        count = idx.saturating_add(1);
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{verify, AuditError, AuditLog};
+
+    const KEY: &str = "test-key";
+
+    fn log_path(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("llmcli-audit-{name}-{}.jsonl", process::id()))
+    }
+
+    fn write_entries(path: &PathBuf, count: usize) -> Option<()> {
+        if path.exists() {
+            fs::remove_file(path).ok()?;
+        }
+        let mut log =
+            AuditLog::open_at(path.clone(), KEY.to_owned(), true).ok()?;
+        for idx in 0..count {
+            log.record("Gemini", "gemini-2.0-flash", "[]", &format!("#{idx}"))
+                .ok()?;
+        }
+        Some(())
+    }
+
+    #[test]
+    fn verifies_an_intact_chain_across_reopens() {
+        let path = log_path("intact");
+        assert_eq!(write_entries(&path, 2), Some(()));
+        let log = AuditLog::open_at(path.clone(), KEY.to_owned(), false).ok();
+        assert_eq!(
+            log.and_then(|mut log| {
+                log.record("Gemini", "gemini-2.0-flash", "[]", "#2").ok()
+            }),
+            Some(())
+        );
+
+        assert_eq!(verify(&path, KEY).ok(), Some(3));
+
+        assert_eq!(fs::remove_file(path).ok(), Some(()));
+    }
+
+    #[test]
+    fn detects_edited_and_removed_entries() {
+        let path = log_path("tampered");
+        assert_eq!(write_entries(&path, 3), Some(()));
+        let content = fs::read_to_string(&path).unwrap_or_default();
+
+        assert_eq!(
+            fs::write(&path, content.replacen("#1", "#9", 1)).ok(),
+            Some(())
+        );
+        assert!(matches!(verify(&path, KEY), Err(AuditError::Tampered(2))));
+
+        let without_second = content
+            .lines()
+            .enumerate()
+            .filter(|&(idx, _)| idx != 1)
+            .map(|(_, line)| format!("{line}\n"))
+            .collect::<Vec<_>>()
+            .concat();
+        assert_eq!(fs::write(&path, without_second).ok(), Some(()));
+        assert!(matches!(verify(&path, KEY), Err(AuditError::Tampered(2))));
+
+        assert_eq!(fs::remove_file(path).ok(), Some(()));
+    }
+
+    #[test]
+    fn rejects_a_chain_rebuilt_without_the_key() {
+        let path = log_path("key");
+        assert_eq!(write_entries(&path, 1), Some(()));
+
+        assert!(matches!(
+            verify(&path, "another-key"),
+            Err(AuditError::Tampered(1))
+        ));
+
+        assert_eq!(fs::remove_file(path).ok(), Some(()));
+    }
+
+    #[test]
+    fn truncating_the_newest_entries_is_not_detected() {
+        let path = log_path("truncated");
+        assert_eq!(write_entries(&path, 3), Some(()));
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let kept: Vec<&str> = content.lines().take(2).collect();
+        assert_eq!(
+            fs::write(&path, format!("{}\n", kept.join("\n"))).ok(),
+            Some(())
+        );
+
+        assert_eq!(verify(&path, KEY).ok(), Some(2));
+
+        assert_eq!(fs::remove_file(path).ok(), Some(()));
+    }
+}
@@ -0,0 +1,676 @@
+use alloc::collections::BTreeMap;
+use core::time::Duration;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac as _};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::{
+    header::{HeaderName, HeaderValue, AUTHORIZATION, HOST},
+    Client, Request,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use thiserror::Error;
+
+const DEFAULT_PROFILE: &str = "default";
+
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+const GOOGLE_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+const JWT_LIFETIME_SECS: u64 = 3_600;
+
+/// Tokens are refreshed this long before they expire, so one never runs out
+/// in the middle of a request.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_mins(1);
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Invalid credential value for header {0}.")]
+    InvalidHeader(String),
+    #[error("Request has no host to sign.")]
+    MissingHost,
+    #[error("Invalid service account private key.")]
+    InvalidPrivateKey,
+    #[error("Invalid request signing key.")]
+    InvalidSigningKey,
+    #[error("Failed to fetch an access token: {0}.")]
+    Token(#[from] reqwest::Error),
+}
+
+/// How a backend proves who it is. Most providers take a static key in a
+/// header, while AWS services need every request signed.
+#[non_exhaustive]
+pub enum Credentials {
+    Bearer(String),
+    Header {
+        name: String,
+        value: String,
+    },
+    AwsSigV4 {
+        credentials: AwsCredentials,
+        region: String,
+        service: &'static str,
+    },
+}
+
+impl Credentials {
+    /// Adds authentication to a fully built request. `SigV4` covers the body,
+    /// so this has to run after the body is set.
+    #[inline]
+    pub fn authorize(&self, request: &mut Request) -> Result<(), AuthError> {
+        match *self {
+            Self::Bearer(ref token) => insert_header(
+                request,
+                AUTHORIZATION.as_str(),
+                &format!("Bearer {token}"),
+            ),
+            Self::Header {
+                ref name,
+                ref value,
+            } => insert_header(request, name, value),
+            Self::AwsSigV4 {
+                ref credentials,
+                ref region,
+                service,
+            } => sign_v4(
+                request,
+                credentials,
+                region,
+                service,
+                SystemTime::now(),
+            ),
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    /// Resolves credentials like the AWS CLI: the `AWS_ACCESS_KEY_ID` family
+    /// of environment variables first, then the shared credentials file for
+    /// `profile`, `AWS_PROFILE` or the default profile.
+    #[inline]
+    #[must_use]
+    pub fn from_chain(profile: Option<&str>) -> Option<Self> {
+        if let (Ok(access_key_id), Ok(secret_access_key)) = (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Some(Self {
+                access_key_id,
+                secret_access_key,
+                session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            });
+        }
+
+        let path = env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| {
+                dirs::home_dir().map(|home| home.join(".aws/credentials"))
+            })?;
+        let content = fs::read_to_string(path).ok()?;
+        let mut section = ini_section(&content, &aws_profile(profile));
+
+        Some(Self {
+            access_key_id: section.remove("aws_access_key_id")?,
+            secret_access_key: section.remove("aws_secret_access_key")?,
+            session_token: section.remove("aws_session_token"),
+        })
+    }
+}
+
+/// Resolves the region from `AWS_REGION`, `AWS_DEFAULT_REGION` or the
+/// profile in the shared config file.
+#[inline]
+#[must_use]
+pub fn aws_region(profile: Option<&str>) -> Option<String> {
+    if let Ok(region) =
+        env::var("AWS_REGION").or_else(|_| env::var("AWS_DEFAULT_REGION"))
+    {
+        return Some(region);
+    }
+
+    let path = env::var_os("AWS_CONFIG_FILE")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".aws/config")))?;
+    let content = fs::read_to_string(path).ok()?;
+    let profile = aws_profile(profile);
+    let section = if profile == DEFAULT_PROFILE {
+        profile
+    } else {
+        format!("profile {profile}")
+    };
+    ini_section(&content, &section).remove("region")
+}
+
+/// Google Cloud credentials in the JSON formats `gcloud` and the IAM console
+/// produce.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GoogleKey {
+    ServiceAccount {
+        client_email: String,
+        private_key: String,
+        token_uri: Option<String>,
+    },
+    AuthorizedUser {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+    },
+}
+
+#[derive(Serialize)]
+struct JwtClaims<'email> {
+    iss: &'email str,
+    scope: &'static str,
+    aud: &'email str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// OAuth access tokens for Google Cloud APIs, exchanged from a service
+/// account key or the user's application default credentials and cached
+/// until they expire.
+#[non_exhaustive]
+pub struct GoogleCredentials {
+    key: GoogleKey,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl GoogleCredentials {
+    /// Resolves credentials like the Google client libraries: `path` if
+    /// given, then `GOOGLE_APPLICATION_CREDENTIALS`, then the application
+    /// default credentials written by `gcloud auth application-default
+    /// login`.
+    #[inline]
+    #[must_use]
+    pub fn from_chain(path: Option<&Path>) -> Option<Self> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| {
+                env::var_os("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from)
+            })
+            .or_else(|| {
+                dirs::config_dir().map(|config| {
+                    config.join("gcloud/application_default_credentials.json")
+                })
+            })?;
+        let content = fs::read_to_string(path).ok()?;
+
+        Some(Self {
+            key: serde_json::from_str(&content).ok()?,
+            token: Mutex::new(None),
+        })
+    }
+
+    #[inline]
+    pub async fn access_token(
+        &self,
+        client: &Client,
+    ) -> Result<String, AuthError> {
+        if let Ok(token) = self.token.lock() {
+            if let Some(&(ref token, expires)) = token.as_ref() {
+                if Instant::now() < expires {
+                    return Ok(token.clone());
+                }
+            }
+        }
+
+        let request = match self.key {
+            GoogleKey::ServiceAccount {
+                ref client_email,
+                ref private_key,
+                ref token_uri,
+            } => {
+                let token_uri =
+                    token_uri.as_deref().unwrap_or(GOOGLE_TOKEN_URL);
+                let assertion = sign_jwt(client_email, private_key, token_uri)?;
+                client.post(token_uri).form(&[
+                    (
+                        "grant_type",
+                        "urn:ietf:params:oauth:grant-type:jwt-bearer",
+                    ),
+                    ("assertion", &assertion),
+                ])
+            }
+            GoogleKey::AuthorizedUser {
+                ref client_id,
+                ref client_secret,
+                ref refresh_token,
+            } => client.post(GOOGLE_TOKEN_URL).form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("refresh_token", refresh_token),
+            ]),
+        };
+        let resp: TokenResponse =
+            request.send().await?.error_for_status()?.json().await?;
+
+        let expires = Instant::now()
+            .checked_add(Duration::from_secs(resp.expires_in))
+            .and_then(|expires| expires.checked_sub(TOKEN_EXPIRY_MARGIN))
+            .unwrap_or_else(Instant::now);
+        if let Ok(mut token) = self.token.lock() {
+            *token = Some((resp.access_token.clone(), expires));
+        }
+
+        Ok(resp.access_token)
+    }
+}
+
+fn sign_jwt(
+    client_email: &str,
+    private_key: &str,
+    token_uri: &str,
+) -> Result<String, AuthError> {
+    let iat = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let claims = JwtClaims {
+        iss: client_email,
+        scope: GOOGLE_SCOPE,
+        aud: token_uri,
+        iat,
+        exp: iat.saturating_add(JWT_LIFETIME_SECS),
+    };
+
+    #[expect(
+        clippy::map_err_ignore,
+        reason = r#"
+            The parser error could echo the key, so only the failure is
+            reported.
+        "#
+    )]
+    let key = EncodingKey::from_rsa_pem(private_key.as_bytes())
+        .map_err(|_| AuthError::InvalidPrivateKey)?;
+    #[expect(
+        clippy::map_err_ignore,
+        reason = r#"
+            Signing only fails on a key that is not usable for RS256.
+        "#
+    )]
+    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)
+        .map_err(|_| AuthError::InvalidPrivateKey)
+}
+
+fn aws_profile(profile: Option<&str>) -> String {
+    profile
+        .map(str::to_owned)
+        .or_else(|| env::var("AWS_PROFILE").ok())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_owned())
+}
+
+fn ini_section(content: &str, name: &str) -> BTreeMap<String, String> {
+    let mut in_section = false;
+    let mut values = BTreeMap::new();
+
+    for line in content.lines().map(str::trim) {
+        if let Some(header) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            in_section = header.trim() == name;
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=').filter(|_| in_section)
+        {
+            values.insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+    }
+
+    values
+}
+
+fn insert_header(
+    request: &mut Request,
+    name: &str,
+    value: &str,
+) -> Result<(), AuthError> {
+    #[expect(
+        clippy::map_err_ignore,
+        reason = r#"
+            The header name is more useful to the user than the parser
+            error, which does not mention it.
+        "#
+    )]
+    let header_name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| AuthError::InvalidHeader(name.to_owned()))?;
+    #[expect(
+        clippy::map_err_ignore,
+        reason = r#"
+            The parser error could echo the secret, so only the header name
+            is reported.
+        "#
+    )]
+    let mut header_value = HeaderValue::from_str(value)
+        .map_err(|_| AuthError::InvalidHeader(name.to_owned()))?;
+    header_value.set_sensitive(true);
+    request.headers_mut().insert(header_name, header_value);
+    Ok(())
+}
+
+#[inline]
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+#[inline]
+pub fn hmac_sha256_hex(key: &[u8], data: &[u8]) -> Result<String, AuthError> {
+    hmac_sha256(key, data).map(|mac| hex(&mac))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>, AuthError> {
+    #[expect(
+        clippy::map_err_ignore,
+        reason = r#"
+            The only error is an invalid key length, which the variant
+            already says.
+        "#
+    )]
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .map_err(|_| AuthError::InvalidSigningKey)?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// Derives the `SigV4` key for one day, region and service from the secret
+/// access key.
+fn signing_key(
+    secret_access_key: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+) -> Result<Vec<u8>, AuthError> {
+    [region, service, "aws4_request"].iter().try_fold(
+        hmac_sha256(
+            format!("AWS4{secret_access_key}").as_bytes(),
+            date.as_bytes(),
+        )?,
+        |key, part| hmac_sha256(&key, part.as_bytes()),
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().flat_map(|&byte| hex_digits(byte)).collect()
+}
+
+/// The two lowercase hex digits of `byte`.
+fn hex_digits(byte: u8) -> [char; 2] {
+    [byte >> 4, byte & 0x0F]
+        .map(|nibble| char::from_digit(u32::from(nibble), 16).unwrap_or('0'))
+}
+
+/// Percent-encodes everything but the unreserved characters, as `SigV4`
+/// expects.
+fn uri_encode(text: &str) -> String {
+    text.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric()
+            || matches!(byte, b'-' | b'_' | b'.' | b'~')
+        {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push('%');
+            encoded.extend(
+                hex_digits(byte).map(|digit| digit.to_ascii_uppercase()),
+            );
+        }
+        encoded
+    })
+}
+
+/// Formats a Unix timestamp as the `YYYYMMDD` date and `YYYYMMDDTHHMMSSZ`
+/// time `SigV4` uses. `iCalendar` uses the same UTC forms.
+#[inline]
+#[must_use]
+pub fn amz_dates(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let days = secs.checked_div(86_400).unwrap_or_default();
+    let secs_of_day = secs.checked_rem(86_400).unwrap_or_default();
+
+    // Howard Hinnant's civil-from-days algorithm.
+    let shifted = days.saturating_add(719_468);
+    let era = shifted.checked_div(146_097).unwrap_or_default();
+    let day_of_era = shifted.saturating_sub(era.saturating_mul(146_097));
+    let year_of_era = day_of_era
+        .saturating_sub(day_of_era.checked_div(1_460).unwrap_or_default())
+        .saturating_add(day_of_era.checked_div(36_524).unwrap_or_default())
+        .saturating_sub(day_of_era.checked_div(146_096).unwrap_or_default())
+        .checked_div(365)
+        .unwrap_or_default();
+    let day_of_year = day_of_era.saturating_sub(
+        year_of_era
+            .saturating_mul(365)
+            .saturating_add(year_of_era.checked_div(4).unwrap_or_default())
+            .saturating_sub(year_of_era.checked_div(100).unwrap_or_default()),
+    );
+    let month_index = day_of_year
+        .saturating_mul(5)
+        .saturating_add(2)
+        .checked_div(153)
+        .unwrap_or_default();
+    let day = day_of_year
+        .saturating_sub(
+            month_index
+                .saturating_mul(153)
+                .saturating_add(2)
+                .checked_div(5)
+                .unwrap_or_default(),
+        )
+        .saturating_add(1);
+    let month = if month_index < 10 {
+        month_index.saturating_add(3)
+    } else {
+        month_index.saturating_sub(9)
+    };
+    let year = year_of_era
+        .saturating_add(era.saturating_mul(400))
+        .saturating_add(u64::from(month <= 2));
+
+    let date = format!("{year:04}{month:02}{day:02}");
+    let hours = secs_of_day.checked_div(3_600).unwrap_or_default();
+    let minutes = secs_of_day
+        .checked_rem(3_600)
+        .and_then(|rest| rest.checked_div(60))
+        .unwrap_or_default();
+    let seconds = secs_of_day.checked_rem(60).unwrap_or_default();
+    let timestamp = format!("{date}T{hours:02}{minutes:02}{seconds:02}Z");
+
+    (date, timestamp)
+}
+
+fn sign_v4(
+    request: &mut Request,
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    now: SystemTime,
+) -> Result<(), AuthError> {
+    let (date, timestamp) = amz_dates(now);
+    let host = request
+        .url()
+        .host_str()
+        .ok_or(AuthError::MissingHost)?
+        .to_owned();
+    let payload_hash = sha256_hex(
+        request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .unwrap_or_default(),
+    );
+
+    insert_header(request, HOST.as_str(), &host)?;
+    insert_header(request, "x-amz-date", &timestamp)?;
+    insert_header(request, "x-amz-content-sha256", &payload_hash)?;
+    if let Some(ref token) = credentials.session_token {
+        insert_header(request, "x-amz-security-token", token)?;
+    }
+
+    let mut headers: Vec<(String, String)> = request
+        .headers()
+        .iter()
+        .filter(|&(name, _)| *name != AUTHORIZATION)
+        .filter_map(|(name, value)| {
+            value.to_str().ok().map(|value| {
+                (name.as_str().to_lowercase(), value.trim().to_owned())
+            })
+        })
+        .collect();
+    headers.sort();
+    let canonical_headers = headers
+        .iter()
+        .map(|header| format!("{}:{}", header.0, header.1))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let signed_headers = headers
+        .iter()
+        .map(|header| header.0.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_uri = request
+        .url()
+        .path()
+        .split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut query: Vec<(String, String)> = request
+        .url()
+        .query_pairs()
+        .map(|(key, value)| (uri_encode(&key), uri_encode(&value)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|pair| format!("{}={}", pair.0, pair.1))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let canonical_request = format!(
+        "{}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n\n\
+         {signed_headers}\n{payload_hash}",
+        request.method().as_str()
+    );
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let signing_key =
+        signing_key(&credentials.secret_access_key, &date, region, service)?;
+    let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes())?);
+
+    insert_header(
+        request,
+        AUTHORIZATION.as_str(),
+        &format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, \
+             SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::time::UNIX_EPOCH;
+
+    use reqwest::{header::AUTHORIZATION, Method, Request};
+
+    use super::{hex, sign_v4, signing_key, uri_encode, AwsCredentials};
+
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    #[test]
+    fn derives_the_documented_signing_key() {
+        // The example from the AWS SigV4 documentation.
+        let key = signing_key(SECRET, "20120215", "us-east-1", "iam").ok();
+
+        assert_eq!(
+            key.map(|key| hex(&key)).as_deref(),
+            Some(
+                "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+            )
+        );
+    }
+
+    #[test]
+    fn percent_encodes_all_but_unreserved_characters() {
+        assert_eq!(uri_encode("a-z_0.9~"), "a-z_0.9~");
+        assert_eq!(uri_encode("model:v1/x y"), "model%3Av1%2Fx%20y");
+    }
+
+    /// Signs a request at 2015-08-30T12:36:00Z and returns its
+    /// `x-amz-date` and `authorization` headers.
+    fn signed_headers() -> Option<(String, String)> {
+        let mut request = Request::new(
+            Method::POST,
+            "https://bedrock-runtime.us-east-1.amazonaws.com/model/\
+             anthropic.claude-v2/converse?b=2&a=1"
+                .parse()
+                .ok()?,
+        );
+        *request.body_mut() = Some("{}".into());
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_owned(),
+            secret_access_key: SECRET.to_owned(),
+            session_token: None,
+        };
+        let now = UNIX_EPOCH.checked_add(Duration::from_mins(24_015_636))?;
+
+        sign_v4(&mut request, &credentials, "us-east-1", "bedrock", now)
+            .ok()?;
+
+        let header = |name| {
+            request
+                .headers()
+                .get(name)?
+                .to_str()
+                .ok()
+                .map(str::to_owned)
+        };
+        Some((header("x-amz-date")?, header(AUTHORIZATION.as_str())?))
+    }
+
+    #[test]
+    fn signs_a_request() {
+        assert_eq!(
+            signed_headers(),
+            Some((
+                "20150830T123600Z".to_owned(),
+                "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/\
+                 bedrock/aws4_request, SignedHeaders=host;\
+                 x-amz-content-sha256;x-amz-date, \
+                 Signature=c198e55d7ffead677a636bce2f182548de4dc844a7320e97d2\
+                 db9180988c810f"
+                    .to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,88 @@
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[inline]
+#[must_use]
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded =
+        String::with_capacity(bytes.len().div_ceil(3).saturating_mul(4));
+
+    for chunk in bytes.chunks(3) {
+        let mut group = [0_u8; 3];
+        for (slot, &byte) in group.iter_mut().zip(chunk) {
+            *slot = byte;
+        }
+        let sextets = [
+            group[0] >> 2,
+            ((group[0] & 0b11) << 4) | (group[1] >> 4),
+            ((group[1] & 0b1111) << 2) | (group[2] >> 6),
+            group[2] & 0b11_1111,
+        ];
+        for (idx, &sextet) in sextets.iter().enumerate() {
+            if idx <= chunk.len() {
+                encoded.push(char::from(
+                    BASE64_ALPHABET
+                        .get(usize::from(sextet))
+                        .copied()
+                        .unwrap_or(b'A'),
+                ));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[inline]
+#[must_use]
+pub fn decode(data: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(
+        data.len().saturating_mul(3).checked_div(4).unwrap_or(0),
+    );
+    let mut buffer = 0_u32;
+    let mut bits = 0_u32;
+
+    for byte in data.bytes().filter(|byte| !byte.is_ascii_whitespace()) {
+        if byte == b'=' {
+            break;
+        }
+        let value =
+            BASE64_ALPHABET.iter().position(|&symbol| symbol == byte)?;
+        buffer = (buffer << 6) | u32::try_from(value).ok()?;
+        bits = bits.saturating_add(6);
+        if bits >= 8 {
+            bits = bits.saturating_sub(8);
+            bytes.push(u8::try_from((buffer >> bits) & 0xFF).ok()?);
+        }
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+
+    #[test]
+    fn round_trips_every_padding_length() {
+        for input in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba"] {
+            assert_eq!(decode(&encode(input)).as_deref(), Some(input));
+        }
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode(&bytes)), Some(bytes));
+    }
+
+    #[test]
+    fn matches_known_encodings() {
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(decode("Zm9vYg==").as_deref(), Some(&b"foob"[..]));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert_eq!(decode("Zm9v!"), None);
+    }
+}
@@ -0,0 +1,426 @@
+use alloc::sync::Arc;
+use core::{
+    future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+    sync::{Mutex, OnceLock, PoisonError},
+    thread::{self, JoinHandle},
+};
+
+use crossterm::terminal;
+use futures::channel::oneshot;
+use rustyline::{
+    Cmd, ConditionalEventHandler, Event, EventContext, KeyCode, KeyEvent,
+    Modifiers, Movement, RepeatCount,
+};
+use serde::{Deserialize, Serialize};
+use tokio::signal;
+
+use crate::temp;
+
+#[non_exhaustive]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum EditMode {
+    #[default]
+    Emacs,
+    Vi,
+}
+
+impl From<EditMode> for rustyline::EditMode {
+    #[inline]
+    fn from(mode: EditMode) -> Self {
+        match mode {
+            EditMode::Emacs => Self::Emacs,
+            EditMode::Vi => Self::Vi,
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyAction {
+    OpenEditor,
+    InsertLastResponsePath,
+    /// Stops the response being streamed. It does nothing at the prompt.
+    CancelStream,
+}
+
+/// The last assistant response, shared between the REPL and the key
+/// handlers that need it.
+pub type LastResponse = Arc<Mutex<String>>;
+
+/// Parses key names such as `ctrl-e`, `alt-p` or `f2`.
+#[inline]
+#[must_use]
+pub fn parse_key(name: &str) -> Option<KeyEvent> {
+    let name = name.to_lowercase();
+    let single_char = |rest: &str| {
+        let mut chars = rest.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    };
+
+    if let Some(rest) = name.strip_prefix("ctrl-") {
+        return single_char(rest).map(KeyEvent::ctrl);
+    }
+    if let Some(rest) = name.strip_prefix("alt-") {
+        return single_char(rest).map(KeyEvent::alt);
+    }
+    name.strip_prefix('f')?
+        .parse()
+        .ok()
+        .filter(|number| (1..=12).contains(number))
+        .map(|number| KeyEvent(KeyCode::F(number), Modifiers::NONE))
+}
+
+/// The bytes a terminal sends for a key named like in `parse_key`, for
+/// keys read outside of rustyline.
+#[inline]
+#[must_use]
+pub fn key_bytes(name: &str) -> Option<Vec<u8>> {
+    let name = name.to_lowercase();
+    let single_byte = |rest: &str| match *rest.as_bytes() {
+        [byte] if byte.is_ascii_graphic() => Some(byte),
+        _ => None,
+    };
+
+    if let Some(rest) = name.strip_prefix("ctrl-") {
+        single_byte(rest).map(|byte| vec![byte & 0x1f])
+    } else if let Some(rest) = name.strip_prefix("alt-") {
+        single_byte(rest).map(|byte| vec![0x1b, byte])
+    } else if let Some(number) = name.strip_prefix('f') {
+        let sequence = match number {
+            "1" => "\x1bOP",
+            "2" => "\x1bOQ",
+            "3" => "\x1bOR",
+            "4" => "\x1bOS",
+            "5" => "\x1b[15~",
+            "6" => "\x1b[17~",
+            "7" => "\x1b[18~",
+            "8" => "\x1b[19~",
+            "9" => "\x1b[20~",
+            "10" => "\x1b[21~",
+            "11" => "\x1b[23~",
+            "12" => "\x1b[24~",
+            _ => return None,
+        };
+        Some(sequence.as_bytes().to_vec())
+    } else {
+        None
+    }
+}
+
+static CANCEL_KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Makes the key named `name` stop streamed responses. Only the first key
+/// set is used.
+#[inline]
+#[must_use]
+pub fn set_cancel_key(name: &str) -> bool {
+    key_bytes(name).is_some_and(|bytes| CANCEL_KEY.set(bytes).is_ok())
+}
+
+/// Watches the terminal for the cancel key while a response streams.
+///
+/// Only line editing and echo are turned off meanwhile, so Ctrl+C keeps
+/// working and output is unaffected. Anything else typed is consumed.
+pub struct CancelWatch {
+    pressed: oneshot::Receiver<()>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CancelWatch {
+    /// Starts watching, unless no cancel key is bound or stdin is not a
+    /// terminal.
+    #[inline]
+    #[must_use]
+    pub fn start() -> Option<Self> {
+        let key = CANCEL_KEY.get()?.clone();
+        if !io::IsTerminal::is_terminal(&io::stdin()) {
+            return None;
+        }
+
+        let (sender, pressed) = oneshot::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || watch_stdin(&key, &stop, sender)
+        });
+
+        Some(Self {
+            pressed,
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for CancelWatch {
+    #[inline]
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                eprintln!("llmcli: the cancel key watch stopped unexpectedly");
+            }
+        }
+    }
+}
+
+/// Resolves when the cancel key is pressed, or never without a watch.
+#[inline]
+pub async fn cancelled(watch: &mut Option<CancelWatch>) {
+    if let Some(watch) = watch.as_mut() {
+        if (&mut watch.pressed).await.is_ok() {
+            return;
+        }
+    }
+    future::pending::<()>().await;
+}
+
+/// Where Ctrl+C is delivered while a response streams, empty otherwise.
+static INTERRUPT: Mutex<Option<oneshot::Sender<()>>> = Mutex::new(None);
+
+/// Installs the Ctrl+C handler, once for the whole process.
+///
+/// While an `InterruptWatch` is alive Ctrl+C only stops the response; at any
+/// other time the returned receiver resolves, and the caller is expected to
+/// exit with `exit::INTERRUPTED` as it would without a handler. Must be called
+/// from within the Tokio runtime.
+#[inline]
+pub fn watch_interrupt() -> oneshot::Receiver<()> {
+    let (sender, interrupted) = oneshot::channel();
+    tokio::spawn(async {
+        let mut sender = Some(sender);
+        while signal::ctrl_c().await.is_ok() {
+            let watch = INTERRUPT
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            if let Some(sender) = watch.or_else(|| sender.take()) {
+                // Nobody is told once the response or the app has ended.
+                sender.send(()).unwrap_or_default();
+            }
+        }
+    });
+    interrupted
+}
+
+/// Takes Ctrl+C over from the default handling for as long as it is alive.
+pub struct InterruptWatch {
+    pressed: oneshot::Receiver<()>,
+}
+
+impl InterruptWatch {
+    #[inline]
+    #[must_use]
+    pub fn start() -> Self {
+        let (sender, pressed) = oneshot::channel();
+        *INTERRUPT.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some(sender);
+        Self { pressed }
+    }
+}
+
+impl Drop for InterruptWatch {
+    #[inline]
+    fn drop(&mut self) {
+        INTERRUPT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+    }
+}
+
+/// Resolves when Ctrl+C is pressed while `watch` is alive.
+#[inline]
+pub async fn interrupted(watch: &mut InterruptWatch) {
+    if (&mut watch.pressed).await.is_ok() {
+        return;
+    }
+    future::pending::<()>().await;
+}
+
+#[cfg(unix)]
+fn watch_stdin(key: &[u8], stop: &AtomicBool, sender: oneshot::Sender<()>) {
+    use std::io::Read as _;
+
+    use nix::sys::termios::{
+        self, LocalFlags, SetArg, SpecialCharacterIndices,
+    };
+
+    let stdin = io::stdin();
+    let Ok(original) = termios::tcgetattr(&stdin) else {
+        return;
+    };
+    let mut watching = original.clone();
+    watching
+        .local_flags
+        .remove(LocalFlags::ICANON | LocalFlags::ECHO);
+    // Reads give up after a tenth of a second, so the stop flag is seen.
+    #[expect(
+        clippy::as_conversions,
+        reason = r#"
+            The control character indices are a C enum used to index the
+            control character array.
+        "#
+    )]
+    for (index, value) in [
+        (SpecialCharacterIndices::VMIN as usize, 0),
+        (SpecialCharacterIndices::VTIME as usize, 1),
+    ] {
+        if let Some(control_char) = watching.control_chars.get_mut(index) {
+            *control_char = value;
+        }
+    }
+    if termios::tcsetattr(&stdin, SetArg::TCSANOW, &watching).is_err() {
+        return;
+    }
+
+    let mut recent = Vec::with_capacity(key.len());
+    let mut byte = [0_u8; 1];
+    let mut sender = Some(sender);
+    while !stop.load(Ordering::Relaxed) {
+        if !matches!(stdin.lock().read(&mut byte), Ok(1)) {
+            continue;
+        }
+        if recent.len() == key.len() {
+            recent.remove(0);
+        }
+        recent.extend_from_slice(&byte);
+        if recent == key {
+            if let Some(sender) = sender.take() {
+                // The response may have ended before the key was pressed.
+                sender.send(()).unwrap_or_default();
+            }
+        }
+    }
+
+    if let Err(err) = termios::tcsetattr(&stdin, SetArg::TCSANOW, &original) {
+        eprintln!("llmcli: failed to restore the terminal: {err}");
+    }
+}
+
+#[cfg(not(unix))]
+fn watch_stdin(_key: &[u8], _stop: &AtomicBool, _sender: oneshot::Sender<()>) {}
+
+pub struct ActionHandler {
+    action: KeyAction,
+    last_response: LastResponse,
+    /// The file last written for `InsertLastResponsePath`, replaced by the
+    /// next one.
+    last_response_file: Mutex<Option<PathBuf>>,
+}
+
+impl ActionHandler {
+    #[inline]
+    #[must_use]
+    pub const fn new(action: KeyAction, last_response: LastResponse) -> Self {
+        Self {
+            action,
+            last_response,
+            last_response_file: Mutex::new(None),
+        }
+    }
+
+    fn open_editor(line: &str) -> Option<String> {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_owned());
+        let (path, file) = temp::private_file("llmcli-input", "md").ok()?;
+        let edited = Self::edit_file(&editor, &path, file, line);
+        // Losing the text the user just wrote would be worse than leaving a
+        // copy behind that only they can read.
+        fs::remove_file(&path).unwrap_or_default();
+
+        edited
+    }
+
+    /// Writes `line` to `file`, opens it in `editor` and reads it back once
+    /// the editor exits successfully.
+    fn edit_file(
+        editor: &str,
+        path: &Path,
+        mut file: File,
+        line: &str,
+    ) -> Option<String> {
+        file.write_all(line.as_bytes()).ok()?;
+        drop(file);
+
+        // The editor needs a cooked terminal while rustyline holds it in raw
+        // mode.
+        let status = terminal::disable_raw_mode().and_then(|()| {
+            let status = Command::new(editor).arg(path).status();
+            terminal::enable_raw_mode()?;
+            status
+        });
+        status.ok().filter(ExitStatus::success)?;
+
+        fs::read_to_string(path)
+            .ok()
+            .map(|text| text.trim_end().to_owned())
+    }
+
+    fn last_response_path(&self) -> Option<String> {
+        let response = self.last_response.lock().ok()?.clone();
+        if response.is_empty() {
+            return None;
+        }
+
+        let (path, mut file) =
+            temp::private_file("llmcli-last-response", "md").ok()?;
+        file.write_all(response.as_bytes()).ok()?;
+
+        let previous =
+            self.last_response_file.lock().ok()?.replace(path.clone());
+        if let Some(previous) = previous {
+            // A copy left behind is only readable by the user, which is not
+            // worth failing the key binding over.
+            fs::remove_file(previous).unwrap_or_default();
+        }
+
+        Some(path.to_string_lossy().into_owned())
+    }
+}
+
+impl ConditionalEventHandler for ActionHandler {
+    #[inline]
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext<'_>,
+    ) -> Option<Cmd> {
+        match self.action {
+            KeyAction::OpenEditor => Self::open_editor(ctx.line())
+                .map(|text| Cmd::Replace(Movement::WholeLine, Some(text))),
+            KeyAction::InsertLastResponsePath => {
+                self.last_response_path().map(|path| Cmd::Insert(1, path))
+            }
+            KeyAction::CancelStream => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::key_bytes;
+
+    #[test]
+    fn maps_key_names_to_terminal_bytes() {
+        assert_eq!(key_bytes("ctrl-g"), Some(vec![0x07]));
+        assert_eq!(key_bytes("Alt-x"), Some(vec![0x1b, b'x']));
+        assert_eq!(key_bytes("f5"), Some(b"\x1b[15~".to_vec()));
+        assert_eq!(key_bytes("ctrl-"), None);
+        assert_eq!(key_bytes("f13"), None);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{detect, map_reduce, Message, Role};
+
+const IMAGE_EXTENSIONS: [&str; 5] = [".png", ".jpg", ".jpeg", ".gif", ".webp"];
+
+/// What a model can take and how well it copes with code. Only the models
+/// listed here are advised on; anything else is assumed to fit.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub context_tokens: usize,
+    pub vision: bool,
+    pub json: bool,
+    pub code: bool,
+}
+
+impl Capabilities {
+    const fn new(context_tokens: usize, vision: bool, json: bool) -> Self {
+        Self {
+            context_tokens,
+            vision,
+            json,
+            code: true,
+        }
+    }
+
+    const fn weak_at_code(mut self) -> Self {
+        self.code = false;
+        self
+    }
+}
+
+#[expect(
+    clippy::decimal_literal_representation,
+    reason = r#"
+        16_385 and 32_760 are the limits as their providers publish them.
+    "#
+)]
+const MODELS: [(&str, Capabilities); 25] = [
+    ("gpt-4o", Capabilities::new(128_000, true, true)),
+    ("gpt-4o-mini", Capabilities::new(128_000, true, true)),
+    ("gpt-4-turbo", Capabilities::new(128_000, true, true)),
+    ("o1", Capabilities::new(200_000, true, true)),
+    ("o1-mini", Capabilities::new(128_000, false, false)),
+    (
+        "gpt-3.5-turbo",
+        Capabilities::new(16_385, false, true).weak_at_code(),
+    ),
+    (
+        "gemini-2.0-flash-exp",
+        Capabilities::new(1024 * 1024, true, true),
+    ),
+    (
+        "gemini-1.5-flash",
+        Capabilities::new(1024 * 1024, true, true),
+    ),
+    (
+        "gemini-1.5-flash-8b",
+        Capabilities::new(1024 * 1024, true, true).weak_at_code(),
+    ),
+    (
+        "gemini-1.5-pro",
+        Capabilities::new(2 * 1024 * 1024, true, true),
+    ),
+    (
+        "gemini-1.0-pro",
+        Capabilities::new(32_760, false, false).weak_at_code(),
+    ),
+    ("deepseek-chat", Capabilities::new(64_000, false, true)),
+    ("deepseek-reasoner", Capabilities::new(64_000, false, false)),
+    (
+        "mistral-large-latest",
+        Capabilities::new(128_000, false, true),
+    ),
+    (
+        "mistral-small-latest",
+        Capabilities::new(32_000, false, true).weak_at_code(),
+    ),
+    ("codestral-latest", Capabilities::new(256_000, false, true)),
+    (
+        "pixtral-large-latest",
+        Capabilities::new(128_000, true, true),
+    ),
+    (
+        "ministral-8b-latest",
+        Capabilities::new(128_000, false, true).weak_at_code(),
+    ),
+    ("grok-3", Capabilities::new(128 * 1024, false, true)),
+    ("grok-3-mini", Capabilities::new(128 * 1024, false, true)),
+    (
+        "grok-2-vision-1212",
+        Capabilities::new(32 * 1024, true, true),
+    ),
+    (
+        "llama-3.3-70b-versatile",
+        Capabilities::new(128_000, false, true),
+    ),
+    (
+        "llama-3.1-8b-instant",
+        Capabilities::new(128_000, false, true).weak_at_code(),
+    ),
+    (
+        "gemma2-9b-it",
+        Capabilities::new(8_192, false, false).weak_at_code(),
+    ),
+    (
+        "sonar",
+        Capabilities::new(127_072, false, false).weak_at_code(),
+    ),
+];
+
+#[inline]
+#[must_use]
+pub fn lookup(model: &str) -> Option<Capabilities> {
+    MODELS
+        .iter()
+        .find(|&&(name, _)| name == model)
+        .map(|&(_, caps)| caps)
+}
+
+/// Warnings about `model` being a poor fit for `messages`, each naming a
+/// model from `alternatives` that would fit when there is one. This is
+/// only advice: the request is sent unchanged.
+#[inline]
+#[must_use]
+pub fn advise(
+    model: &str,
+    alternatives: &[&str],
+    messages: &[Message],
+) -> Vec<String> {
+    let Some(caps) = lookup(model) else {
+        return Vec::new();
+    };
+    let prompt = messages
+        .iter()
+        .rev()
+        .find(|msg| msg.role == Role::User)
+        .map_or("", |msg| msg.content.as_str());
+    let tokens: usize = messages
+        .iter()
+        .map(|msg| map_reduce::estimate_tokens(&msg.content))
+        .sum();
+    let mut notes = Vec::new();
+
+    if tokens > caps.context_tokens {
+        notes.push(with_alternative(
+            &format!(
+                "The prompt of about {tokens} tokens exceeds the {} token \
+                 context of {model}",
+                caps.context_tokens
+            ),
+            best_alternative(model, alternatives, |alt| {
+                alt.context_tokens >= tokens
+            }),
+        ));
+    }
+    if !caps.vision && mentions_image(prompt) {
+        notes.push(with_alternative(
+            &format!("{model} cannot see images"),
+            best_alternative(model, alternatives, |alt| alt.vision),
+        ));
+    }
+    if !caps.json && asks_for_json(prompt) {
+        notes.push(with_alternative(
+            &format!("{model} has no JSON output mode"),
+            best_alternative(model, alternatives, |alt| alt.json),
+        ));
+    }
+    if !caps.code && detect::looks_like_code(prompt) {
+        notes.push(with_alternative(
+            &format!("{model} is weak at code"),
+            best_alternative(model, alternatives, |alt| alt.code),
+        ));
+    }
+
+    notes
+}
+
+fn best_alternative<'models>(
+    model: &str,
+    alternatives: &[&'models str],
+    fits: impl Fn(&Capabilities) -> bool,
+) -> Option<&'models str> {
+    alternatives
+        .iter()
+        .copied()
+        .filter(|&alt| alt != model)
+        .find(|&alt| lookup(alt).is_some_and(|caps| fits(&caps)))
+}
+
+fn with_alternative(note: &str, alternative: Option<&str>) -> String {
+    alternative.map_or_else(
+        || format!("{note}."),
+        |alt| format!("{note}; consider {alt}."),
+    )
+}
+
+fn mentions_image(prompt: &str) -> bool {
+    prompt.contains("data:image/")
+        || prompt.split_whitespace().any(|word| {
+            let word = word
+                .trim_end_matches(|ch: char| !ch.is_alphanumeric())
+                .to_lowercase();
+            IMAGE_EXTENSIONS.iter().any(|ext| word.ends_with(ext))
+        })
+}
+
+fn asks_for_json(prompt: &str) -> bool {
+    prompt
+        .split(|ch: char| !ch.is_alphanumeric())
+        .any(|word| word.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::advise;
+    use crate::{chatbots::gemini::GeminiChatbot, Chatbot as _, Message, Role};
+
+    fn prompt(content: &str) -> Vec<Message> {
+        vec![Message::new(Role::User, content.to_owned())]
+    }
+
+    #[test]
+    fn advises_on_the_default_gemini_backend() {
+        let notes = GeminiChatbot::create(
+            "gemini-1.0-pro".to_owned(),
+            Some("key".to_owned()),
+        )
+        .ok()
+        .map(|chatbot| {
+            advise(
+                chatbot.model_id(),
+                &chatbot.available_models(),
+                &prompt("List the planets as JSON."),
+            )
+        });
+
+        assert_eq!(
+            notes,
+            Some(vec!["gemini-1.0-pro has no JSON output mode; consider \
+                 gemini-2.0-flash-exp."
+                .to_owned()])
+        );
+    }
+
+    #[test]
+    fn long_prompts_name_a_model_with_enough_context() {
+        let notes = advise(
+            "gpt-3.5-turbo",
+            &["gpt-3.5-turbo", "gpt-4o"],
+            &prompt(&"word ".repeat(20_000)),
+        );
+
+        assert_eq!(
+            notes,
+            ["The prompt of about 25000 tokens exceeds the 16385 token \
+                 context of gpt-3.5-turbo; consider gpt-4o."]
+        );
+    }
+
+    #[test]
+    fn images_need_a_model_that_can_see() {
+        let notes =
+            advise("o1-mini", &["o1-mini"], &prompt("What is in cat.PNG?"));
+
+        assert_eq!(notes, ["o1-mini cannot see images."]);
+    }
+
+    #[test]
+    fn unknown_models_get_no_advice() {
+        let notes =
+            advise("my-local-model", &[], &prompt("Reply in JSON, please."));
+
+        assert!(notes.is_empty());
+    }
+}
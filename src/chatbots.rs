@@ -1,2 +1,262 @@
+use alloc::collections::BTreeMap;
+use core::time::Duration;
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, RequestBuilder,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    audit,
+    chatbots::{
+        audited::AuditedChatbot, rate_limit::RateLimitedChatbot,
+        retry::RetryChatbot, system_prompt::SystemPromptChatbot,
+        timeout::TimeoutChatbot,
+    },
+    config::Config,
+    Chatbot, ChatbotCreationError,
+};
+
+pub mod audited;
+pub mod auto;
+pub mod bedrock;
+pub mod cohere;
+pub mod compat;
+pub mod deepseek;
 pub mod dummy;
+pub mod fallback;
 pub mod gemini;
+pub mod github;
+pub mod groq;
+pub mod huggingface;
+pub mod mistral;
+pub mod ollama;
+pub mod openai;
+pub mod openrouter;
+pub mod perplexity;
+pub mod race;
+pub mod rate_limit;
+pub mod registry;
+pub mod replicate;
+pub mod retry;
+#[cfg(test)]
+mod scripted;
+pub mod system_prompt;
+pub mod timeout;
+pub mod together;
+pub mod xai;
+
+/// Creates a chatbot that retries failed requests `retries` times from the
+/// config, or `retry::DEFAULT_RETRIES` times.
+#[inline]
+pub fn create(
+    name: &str,
+    model: Option<String>,
+    config: &Config,
+) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+    let chatbot = create_without_retries(name, model, config)?;
+    match config.retries.unwrap_or(retry::DEFAULT_RETRIES) {
+        0 => Ok(chatbot),
+        retries => Ok(RetryChatbot::wrap(chatbot, retries)),
+    }
+}
+
+/// Creates a chatbot that fails on the first error, for wrappers such as
+/// the fallback chain that handle failures themselves.
+///
+/// Requests still time out after `timeout` seconds from the config without a
+/// response, and are recorded to the audit log when one is installed.
+#[inline]
+pub fn create_without_retries(
+    name: &str,
+    model: Option<String>,
+    config: &Config,
+) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+    let registry = registry::global();
+    let composite = registry.get(name).is_some_and(|chatbot| chatbot.composite);
+    let mut chatbot = registry.build(name, model, config)?;
+
+    if let Some(chatbot_config) = config.chatbot(name) {
+        chatbot.configure(chatbot_config)?;
+        if let Some(ref system_prompt) = chatbot_config.system_prompt {
+            chatbot = SystemPromptChatbot::wrap(chatbot, system_prompt.clone());
+        }
+    }
+    // The parts of composite chatbots record their own requests.
+    if let Some(log) = audit::global().filter(|_| !composite) {
+        chatbot = AuditedChatbot::wrap(chatbot, log);
+    }
+    // Chatbots made of others rely on the timeouts of their parts, which
+    // may need to fail over before an overall timeout would end it all.
+    if !composite && config.timeout != Some(0) {
+        let timeout = config
+            .timeout
+            .map_or(timeout::DEFAULT_TIMEOUT, Duration::from_secs);
+        chatbot = TimeoutChatbot::wrap(chatbot, timeout);
+    }
+    // Outside the timeout, so waiting for the allowance does not count
+    // against it.
+    if let Some(chatbot_config) = config.chatbot(name) {
+        chatbot = RateLimitedChatbot::wrap(chatbot, chatbot_config);
+    }
+
+    Ok(chatbot)
+}
+
+/// Creates the chatbot serving `model`, given either as `chatbot:model` or
+/// as a model one of the chatbots lists. Chatbots that take any model name
+/// are only used when named.
+#[inline]
+pub fn create_for_model(
+    model: &str,
+    config: &Config,
+) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+    if let Some((name, rest)) = model.split_once(':') {
+        if registry::global().get(name).is_some()
+            || config.compat(name).is_some()
+        {
+            return create(name, Some(rest.to_owned()), config);
+        }
+    }
+
+    // Compat servers without a model list accept any name, so they are only
+    // considered when they list the model explicitly.
+    let compat_names = config
+        .compat
+        .iter()
+        .flatten()
+        .filter(|&(_, compat)| !compat.models.is_empty())
+        .map(|(name, _)| name.as_str());
+    let builtin_names = registry::global()
+        .iter()
+        .filter(|chatbot| !chatbot.composite && !chatbot.any_model)
+        .map(|chatbot| chatbot.name);
+    for name in builtin_names.chain(compat_names) {
+        let res = create(name, Some(model.to_owned()), config);
+        if !matches!(res, Err(ChatbotCreationError::UnknownModel)) {
+            return res;
+        }
+    }
+
+    Err(ChatbotCreationError::UnknownModel)
+}
+
+#[inline]
+pub fn client_with_headers(
+    headers: &BTreeMap<String, String>,
+) -> Result<Client, ChatbotCreationError> {
+    let mut header_map = HeaderMap::new();
+
+    for (name, value) in headers {
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                The header name is more useful to the user than the parser
+                error, which does not mention it.
+            "#
+        )]
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| ChatbotCreationError::InvalidHeader(name.clone()))?;
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                The header name is more useful to the user than the parser
+                error, which does not mention it.
+            "#
+        )]
+        let header_value = HeaderValue::from_str(value)
+            .map_err(|_| ChatbotCreationError::InvalidHeader(name.clone()))?;
+        header_map.insert(header_name, header_value);
+    }
+
+    Ok(Client::builder().default_headers(header_map).build()?)
+}
+
+/// Sets `body` as the JSON body of `request`, with `extra_body` from the
+/// config merged into it as a JSON merge patch (RFC 7396).
+#[inline]
+pub fn json_body<T: Serialize + ?Sized>(
+    request: RequestBuilder,
+    body: &T,
+    extra_body: Option<&Value>,
+) -> RequestBuilder {
+    let Some(extra_body) = extra_body else {
+        return request.json(body);
+    };
+    match serde_json::to_value(body) {
+        Ok(mut value) => {
+            merge_patch(&mut value, extra_body);
+            request.json(&value)
+        }
+        // Serializing again reports the error when the request is sent.
+        Err(_) => request.json(body),
+    }
+}
+
+/// Objects are merged key by key, `null` removes a key and anything else
+/// replaces the target.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(ref patch) = *patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(ref mut target) = *target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(
+                    target.entry(key.clone()).or_insert(Value::Null),
+                    value,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::merge_patch;
+
+    #[test]
+    fn null_removes_a_key() {
+        let mut body = json!({ "model": "gpt-4o", "temperature": 0.7 });
+        merge_patch(&mut body, &json!({ "temperature": null }));
+        assert_eq!(body, json!({ "model": "gpt-4o" }));
+    }
+
+    #[test]
+    fn nested_objects_are_merged() {
+        let mut body = json!({
+            "generationConfig": { "topK": 40, "temperature": 0.7 },
+        });
+        merge_patch(
+            &mut body,
+            &json!({ "generationConfig": { "temperature": 0.2 } }),
+        );
+        assert_eq!(
+            body,
+            json!({
+                "generationConfig": { "topK": 40, "temperature": 0.2 },
+            })
+        );
+    }
+
+    #[test]
+    fn anything_but_an_object_replaces_the_value() {
+        let mut body = json!({ "stop": { "after": 3 }, "tags": ["a"] });
+        merge_patch(&mut body, &json!({ "stop": ["\n"], "tags": "b" }));
+        assert_eq!(body, json!({ "stop": ["\n"], "tags": "b" }));
+
+        let mut body = json!({ "model": "gpt-4o" });
+        merge_patch(&mut body, &json!(["replaced"]));
+        assert_eq!(body, json!(["replaced"]));
+    }
+}
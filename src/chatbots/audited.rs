@@ -0,0 +1,194 @@
+use std::sync::{Mutex, PoisonError};
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt as _};
+
+use crate::{
+    audit::{AuditError, AuditLog},
+    config::ChatbotConfig,
+    params::GenerationParams,
+    ui, Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, Provenance, ResponseStream, StreamItem,
+};
+
+/// Records every request to the audit log with the text of its response.
+/// Wrapping each backend, rather than the places that send requests, means
+/// nothing sent on the user's behalf goes unrecorded.
+#[non_exhaustive]
+pub struct AuditedChatbot {
+    inner: Box<dyn Chatbot>,
+    log: &'static Mutex<AuditLog>,
+}
+
+impl AuditedChatbot {
+    #[inline]
+    #[must_use]
+    pub fn wrap(
+        inner: Box<dyn Chatbot>,
+        log: &'static Mutex<AuditLog>,
+    ) -> Box<dyn Chatbot> {
+        Box::new(Self { inner, log })
+    }
+}
+
+/// A request waiting for the end of its response. Streams that are dropped
+/// early, such as cancelled ones, are recorded with what arrived so far.
+struct Pending {
+    log: &'static Mutex<AuditLog>,
+    chatbot: String,
+    model: String,
+    request: String,
+    response: String,
+    recorded: bool,
+}
+
+impl Pending {
+    fn record(&mut self) -> Result<(), AuditError> {
+        if self.recorded {
+            return Ok(());
+        }
+        self.recorded = true;
+        self.log
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(&self.chatbot, &self.model, &self.request, &self.response)
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let Err(err) = self.record() {
+            ui::notify_error("audit", &err.to_string());
+        }
+    }
+}
+
+#[async_trait]
+impl Chatbot for AuditedChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    #[inline]
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.inner.available_models()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        self.inner.change_model(new_model)
+    }
+
+    /// Failed requests are recorded too, with an empty response, as they
+    /// may have reached the provider.
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let request = serde_json::to_string(messages).map_err(|err| {
+            ChatbotChatError::Audit(AuditError::Write(err.into()))
+        })?;
+        let mut pending = Pending {
+            log: self.log,
+            chatbot: self.inner.name().to_owned(),
+            model: self.inner.model().to_owned(),
+            request,
+            response: String::new(),
+            recorded: false,
+        };
+
+        let stream = match self.inner.send_message(messages, params).await {
+            Ok(stream) => stream,
+            Err(err) => {
+                pending.record().map_err(ChatbotChatError::Audit)?;
+                return Err(err);
+            }
+        };
+
+        Ok(stream::unfold(Some((stream, pending)), |state| async move {
+            let (mut stream, mut pending) = state?;
+            match stream.next().await {
+                Some(item) => {
+                    if let Ok(StreamItem::Text(ref text)) = item {
+                        pending.response.push_str(text);
+                    }
+                    Some((item, Some((stream, pending))))
+                }
+                // The audit log has to be written before the response
+                // counts as complete.
+                None => pending
+                    .record()
+                    .err()
+                    .map(|err| (Err(ChatbotChatError::Audit(err)), None)),
+            }
+        })
+        .boxed())
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.list_models().await
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.inner.warm_up().await
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.status().await
+    }
+
+    #[inline]
+    fn escalate(&self) -> bool {
+        self.inner.escalate()
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        self.inner.provenance()
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.inner.key_index()
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        self.inner.supported_params()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        self.inner.configure(chatbot_config)
+    }
+}
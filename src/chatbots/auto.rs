@@ -0,0 +1,182 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+
+use crate::{
+    chatbots,
+    config::Config,
+    detect,
+    params::{self, GenerationParams},
+    ui, Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, Provenance, ResponseStream, Role,
+};
+
+const DEFAULT_MAX_PROMPT_CHARS: usize = 4000;
+
+#[non_exhaustive]
+pub struct AutoChatbot {
+    cheap: Box<dyn Chatbot>,
+    strong: Box<dyn Chatbot>,
+    max_prompt_chars: usize,
+    escalate_on_code: bool,
+    log: bool,
+    escalate_next: AtomicBool,
+    routed_strong: AtomicBool,
+}
+
+impl AutoChatbot {
+    #[inline]
+    pub fn from_config(
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let router = config
+            .router
+            .as_ref()
+            .ok_or(ChatbotCreationError::MissingConfig)?;
+
+        if router.cheap_chatbot == "auto" || router.strong_chatbot == "auto" {
+            return Err(ChatbotCreationError::UnknownChatbot);
+        }
+
+        let cheap = chatbots::create(
+            &router.cheap_chatbot,
+            router.cheap_model.clone(),
+            config,
+        )?;
+        let strong = chatbots::create(
+            &router.strong_chatbot,
+            router.strong_model.clone(),
+            config,
+        )?;
+
+        Ok(Box::new(Self {
+            cheap,
+            strong,
+            max_prompt_chars: router
+                .max_prompt_chars
+                .unwrap_or(DEFAULT_MAX_PROMPT_CHARS),
+            escalate_on_code: router.escalate_on_code.unwrap_or(true),
+            log: router.log.unwrap_or(true),
+            escalate_next: AtomicBool::new(false),
+            routed_strong: AtomicBool::new(false),
+        }))
+    }
+
+    fn escalation_reason(&self, messages: &[Message]) -> Option<&'static str> {
+        let prompt = messages
+            .iter()
+            .rev()
+            .find(|msg| msg.role == Role::User)
+            .map_or("", |msg| msg.content.as_str());
+
+        if self.escalate_next.swap(false, Ordering::Relaxed) {
+            Some("escalation requested")
+        } else if prompt.chars().count() > self.max_prompt_chars {
+            Some("long prompt")
+        } else if self.escalate_on_code && detect::looks_like_code(prompt) {
+            Some("code detected")
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait]
+impl Chatbot for AutoChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Auto"
+    }
+
+    #[inline]
+    fn model(&self) -> &'static str {
+        "Router"
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        _new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        Err(InvalidModelError)
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let reason = self.escalation_reason(messages);
+        let chatbot = if reason.is_some() {
+            &self.strong
+        } else {
+            &self.cheap
+        };
+        self.routed_strong
+            .store(reason.is_some(), Ordering::Relaxed);
+
+        if self.log {
+            ui::notify(&format!(
+                "routed to {} ({}): {}",
+                chatbot.name(),
+                chatbot.model(),
+                reason.unwrap_or("default")
+            ));
+        }
+
+        chatbot.send_message(messages, params).await
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        if self.routed_strong.load(Ordering::Relaxed) {
+            self.strong.provenance()
+        } else {
+            self.cheap.provenance()
+        }
+    }
+
+    /// Parameters are passed on to each chatbot, which leaves out what it
+    /// does not support.
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &params::NAMES
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.cheap.warm_up().await?;
+        self.strong.warm_up().await
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        let mut status = vec![
+            format!("cheap: {} ({})", self.cheap.name(), self.cheap.model()),
+            format!("strong: {} ({})", self.strong.name(), self.strong.model()),
+        ];
+        status.extend(self.cheap.status().await?);
+        status.extend(self.strong.status().await?);
+        Ok(status)
+    }
+
+    #[inline]
+    fn escalate(&self) -> bool {
+        self.escalate_next.store(true, Ordering::Relaxed);
+        true
+    }
+}
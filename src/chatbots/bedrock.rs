@@ -0,0 +1,451 @@
+use async_trait::async_trait;
+use futures::{future, StreamExt as _, TryStreamExt as _};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{self, AwsCredentials, Credentials},
+    chatbots,
+    config::{ChatbotConfig, Config},
+    params::GenerationParams,
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream, Role, StreamItem,
+};
+
+const SERVICE: &str = "bedrock";
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+const SAMPLING_PARAMS: [&str; 3] = ["temperature", "top_p", "max_tokens"];
+
+const KNOWN_MODELS: [&str; 5] = [
+    "anthropic.claude-3-5-sonnet-20240620-v1:0",
+    "anthropic.claude-3-haiku-20240307-v1:0",
+    "meta.llama3-1-70b-instruct-v1:0",
+    "mistral.mistral-large-2407-v1:0",
+    "amazon.titan-text-premier-v1:0",
+];
+
+#[derive(Serialize)]
+struct ContentBlock<'text> {
+    text: &'text str,
+}
+
+#[derive(Serialize)]
+struct ConverseMessage<'text> {
+    role: &'static str,
+    content: Vec<ContentBlock<'text>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InferenceConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConverseRequest<'text> {
+    messages: Vec<ConverseMessage<'text>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    system: Vec<ContentBlock<'text>>,
+    inference_config: InferenceConfig,
+}
+
+#[derive(Deserialize)]
+struct Delta {
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlockDelta {
+    delta: Delta,
+}
+
+#[expect(
+    clippy::big_endian_bytes,
+    reason = r#"
+        Event stream prelude lengths are big-endian.
+    "#
+)]
+fn be_u32(bytes: &[u8], at: usize) -> Option<usize> {
+    let bytes: [u8; 4] = bytes.get(at..at.checked_add(4)?)?.try_into().ok()?;
+    usize::try_from(u32::from_be_bytes(bytes)).ok()
+}
+
+/// Reads the string headers of an event stream message, skipping values of
+/// other types.
+#[expect(
+    clippy::big_endian_bytes,
+    reason = r#"
+        Event stream header value lengths are big-endian.
+    "#
+)]
+fn event_headers(mut headers: &[u8]) -> Option<Vec<(&str, &str)>> {
+    let mut parsed = Vec::new();
+
+    while let Some((&name_len, rest)) = headers.split_first() {
+        let (name, rest) = rest.split_at_checked(usize::from(name_len))?;
+        let (&kind, rest) = rest.split_first()?;
+        let value_len = match kind {
+            0 | 1 => 0,
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            6 | 7 => {
+                let len: [u8; 2] = rest.get(..2)?.try_into().ok()?;
+                usize::from(u16::from_be_bytes(len)).checked_add(2)?
+            }
+            _ => return None,
+        };
+        let (value, rest) = rest.split_at_checked(value_len)?;
+        if kind == 7 {
+            parsed.push((
+                core::str::from_utf8(name).ok()?,
+                core::str::from_utf8(value.get(2..)?).ok()?,
+            ));
+        }
+        headers = rest;
+    }
+
+    Some(parsed)
+}
+
+/// Drains the complete `application/vnd.amazon.eventstream` messages from
+/// `buffer` and returns the text they carry. Each message is a 12-byte
+/// prelude with the total and header lengths, the headers, a JSON payload
+/// and a trailing CRC.
+#[expect(
+    clippy::map_err_ignore,
+    reason = r#"
+        Invalid JSON from the API indicates a critical error so we hide that
+        detail from the end user, as they cannot address this issue.
+    "#
+)]
+fn drain_events(buffer: &mut Vec<u8>) -> Result<String, ChatbotChatError> {
+    let mut text = String::new();
+
+    while let (Some(total_len), Some(headers_len)) =
+        (be_u32(buffer, 0), be_u32(buffer, 4))
+    {
+        if buffer.len() < total_len {
+            break;
+        }
+        let message: Vec<u8> = buffer.drain(..total_len).collect();
+        let headers_end = headers_len
+            .checked_add(12)
+            .ok_or(ChatbotChatError::UnexpectedResponse)?;
+        let (Some(headers), Some(payload)) = (
+            message.get(12..headers_end),
+            message.get(headers_end..total_len.saturating_sub(4)),
+        ) else {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        };
+        let headers = event_headers(headers)
+            .ok_or(ChatbotChatError::UnexpectedResponse)?;
+
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|&&(header_name, _)| header_name == name)
+                .map(|&(_, value)| value)
+        };
+        if header(":message-type") != Some("event") {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        }
+        if header(":event-type") == Some("contentBlockDelta") {
+            let event: ContentBlockDelta = serde_json::from_slice(payload)
+                .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+            text.extend(event.delta.text);
+        }
+    }
+
+    Ok(text)
+}
+
+/// Models on Amazon Bedrock through the Converse API. Requests are signed
+/// with `SigV4` using credentials from the standard AWS chain.
+#[non_exhaustive]
+pub struct BedrockChatbot {
+    credentials: Credentials,
+    region: String,
+    model: String,
+    client: Client,
+    extra_body: Option<serde_json::Value>,
+}
+
+impl BedrockChatbot {
+    #[inline]
+    pub fn from_config(
+        model: String,
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let bedrock = config.bedrock.as_ref();
+        let profile = bedrock.and_then(|bedrock| bedrock.profile.as_deref());
+        let region = bedrock
+            .and_then(|bedrock| bedrock.region.clone())
+            .or_else(|| auth::aws_region(profile))
+            .unwrap_or_else(|| DEFAULT_REGION.to_owned());
+
+        Self::with_credentials(model, profile, region)
+    }
+
+    fn with_credentials(
+        model: String,
+        profile: Option<&str>,
+        region: String,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if model.trim().is_empty() {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let credentials = AwsCredentials::from_chain(profile)
+            .ok_or(ChatbotCreationError::MissingCredentials)?;
+
+        Ok(Box::new(Self {
+            credentials: Credentials::AwsSigV4 {
+                credentials,
+                region: region.clone(),
+                service: SERVICE,
+            },
+            region,
+            model,
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "https://bedrock-runtime.{}.amazonaws.com/model/{}/converse-stream",
+            self.region,
+            self.model.replace(':', "%3A")
+        )
+    }
+}
+
+#[async_trait]
+impl Chatbot for BedrockChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let region =
+            auth::aws_region(None).unwrap_or_else(|| DEFAULT_REGION.to_owned());
+        Self::with_credentials(model, None, region)
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Bedrock"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        KNOWN_MODELS.to_vec()
+    }
+
+    /// Any model id is accepted, as the models enabled for an account vary
+    /// by region and include cross-region inference profiles.
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        if new_model.trim().is_empty() {
+            return Err(InvalidModelError);
+        }
+
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &SAMPLING_PARAMS
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let system = messages
+            .iter()
+            .filter(|msg| msg.role == Role::System)
+            .map(|msg| ContentBlock { text: &msg.content })
+            .collect();
+        let converse_messages = messages
+            .iter()
+            .filter_map(|msg| {
+                let role = match msg.role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                    Role::System | Role::Note => return None,
+                };
+                Some(ConverseMessage {
+                    role,
+                    content: vec![ContentBlock { text: &msg.content }],
+                })
+            })
+            .collect();
+
+        let mut request = chatbots::json_body(
+            self.client.post(self.url()),
+            &ConverseRequest {
+                messages: converse_messages,
+                system,
+                inference_config: InferenceConfig {
+                    temperature: params.temperature,
+                    top_p: params.top_p,
+                    max_tokens: params.max_tokens,
+                },
+            },
+            self.extra_body.as_ref(),
+        )
+        .build()?;
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                Signing only fails on malformed credentials, which the user
+                has to fix in their AWS configuration.
+            "#
+        )]
+        self.credentials
+            .authorize(&mut request)
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+
+        let resp = self.client.execute(request).await.map_err(|err| {
+            if err.is_timeout() {
+                ChatbotChatError::Timeout
+            } else {
+                ChatbotChatError::NetworkError(err)
+            }
+        })?;
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        }
+
+        let stream = resp
+            .bytes_stream()
+            .scan(Vec::new(), |buffer, item| {
+                future::ready(Some(match item {
+                    Ok(bytes) => {
+                        buffer.extend_from_slice(&bytes);
+                        drain_events(buffer)
+                    }
+                    Err(err) => Err(ChatbotChatError::NetworkError(err)),
+                }))
+            })
+            .try_filter(|text| future::ready(!text.is_empty()))
+            .map_ok(StreamItem::Text)
+            .boxed();
+
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::drain_events;
+
+    /// Frames `payload` as an event stream message with zeroed CRCs, which
+    /// the parser does not check.
+    #[expect(
+        clippy::big_endian_bytes,
+        reason = r#"
+            Event stream lengths are big-endian.
+        "#
+    )]
+    fn message(message_type: &str, event_type: &str, payload: &str) -> Vec<u8> {
+        let mut headers = Vec::new();
+        for (name, value) in
+            [(":message-type", message_type), (":event-type", event_type)]
+        {
+            headers.push(u8::try_from(name.len()).unwrap_or_default());
+            headers.extend_from_slice(name.as_bytes());
+            headers.push(7);
+            headers.extend_from_slice(
+                &u16::try_from(value.len()).unwrap_or_default().to_be_bytes(),
+            );
+            headers.extend_from_slice(value.as_bytes());
+        }
+        let total_len = headers
+            .len()
+            .saturating_add(payload.len())
+            .saturating_add(16);
+
+        let mut message = Vec::new();
+        message.extend_from_slice(
+            &u32::try_from(total_len).unwrap_or_default().to_be_bytes(),
+        );
+        message.extend_from_slice(
+            &u32::try_from(headers.len())
+                .unwrap_or_default()
+                .to_be_bytes(),
+        );
+        message.extend_from_slice(&[0; 4]);
+        message.extend_from_slice(&headers);
+        message.extend_from_slice(payload.as_bytes());
+        message.extend_from_slice(&[0; 4]);
+        message
+    }
+
+    #[test]
+    fn waits_for_a_whole_message_and_skips_other_events() {
+        let mut stream =
+            message("event", "messageStart", r#"{"role": "assistant"}"#);
+        stream.extend(message(
+            "event",
+            "contentBlockDelta",
+            r#"{"contentBlockIndex": 0, "delta": {"text": "Hi"}}"#,
+        ));
+        let (first, second) = stream.split_at(stream.len().saturating_sub(5));
+        let mut buffer = first.to_vec();
+
+        assert_eq!(drain_events(&mut buffer).ok(), Some(String::new()));
+        assert!(!buffer.is_empty());
+
+        buffer.extend_from_slice(second);
+        assert_eq!(drain_events(&mut buffer).ok(), Some("Hi".to_owned()));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn rejects_exception_messages() {
+        let mut buffer = message(
+            "exception",
+            "throttlingException",
+            r#"{"message": "Too many requests"}"#,
+        );
+
+        assert!(drain_events(&mut buffer).ok().is_none());
+    }
+}
@@ -0,0 +1,309 @@
+use std::env;
+
+use async_trait::async_trait;
+use futures::{future, StreamExt as _, TryStreamExt as _};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chatbots, config::ChatbotConfig, keys::KeyPool, params::GenerationParams,
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream, Role, StreamItem,
+};
+
+const COHERE_URL: &str = "https://api.cohere.com/v1/chat";
+
+const SAMPLING_PARAMS: [&str; 6] = [
+    "temperature",
+    "top_p",
+    "top_k",
+    "max_tokens",
+    "presence_penalty",
+    "frequency_penalty",
+];
+
+pub const AVAILABLE_MODELS: [&str; 5] = [
+    "command-a-03-2025",
+    "command-r-plus",
+    "command-r",
+    "command-r7b-12-2024",
+    "command-light",
+];
+
+#[derive(Serialize)]
+struct CohereMessage<'text> {
+    role: &'static str,
+    message: &'text str,
+}
+
+/// Cohere takes the latest user message separately from the turns before
+/// it, and the system prompt as a preamble.
+#[derive(Serialize)]
+struct CohereRequest<'model, 'text> {
+    model: &'model str,
+    message: &'text str,
+    chat_history: Vec<CohereMessage<'text>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preamble: Option<&'text str>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(rename = "p", skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(rename = "k", skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct CohereEvent {
+    event_type: String,
+    text: Option<String>,
+}
+
+/// Drains the complete JSON lines from `buffer` and returns the generated
+/// text they carry. A trailing partial line is left for the next network
+/// chunk.
+#[expect(
+    clippy::map_err_ignore,
+    reason = r#"
+        Invalid JSON from the API indicates a critical error so we hide that
+        detail from the end user, as they cannot address this issue.
+    "#
+)]
+fn drain_lines(buffer: &mut Vec<u8>) -> Result<String, ChatbotChatError> {
+    let Some(end) = buffer.iter().rposition(|&byte| byte == b'\n') else {
+        return Ok(String::new());
+    };
+    let complete: Vec<u8> = buffer.drain(..=end).collect();
+    let complete = String::from_utf8_lossy(&complete);
+
+    let mut text = String::new();
+    for line in complete.lines().filter(|line| !line.trim().is_empty()) {
+        let event: CohereEvent = serde_json::from_str(line)
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+        if event.event_type == "text-generation" {
+            text.extend(event.text);
+        }
+    }
+
+    Ok(text)
+}
+
+#[non_exhaustive]
+pub struct CohereChatbot {
+    keys: KeyPool,
+    model: &'static str,
+    client: Client,
+    extra_body: Option<serde_json::Value>,
+}
+
+#[async_trait]
+impl Chatbot for CohereChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let model = AVAILABLE_MODELS
+            .into_iter()
+            .find(|&known| known == model)
+            .ok_or(ChatbotCreationError::UnknownModel)?;
+
+        let api_key = if let Some(api_key) = api_key {
+            api_key
+        } else {
+            env::var("COHERE_API_KEY")?
+        };
+
+        Ok(Box::new(Self {
+            keys: KeyPool::single(api_key),
+            model,
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Cohere"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        AVAILABLE_MODELS.to_vec()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        self.model = AVAILABLE_MODELS
+            .into_iter()
+            .find(|&known| known == new_model)
+            .ok_or(InvalidModelError)?;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &SAMPLING_PARAMS
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let last_user = messages.iter().rposition(|msg| msg.role == Role::User);
+        let preamble = messages
+            .iter()
+            .find(|msg| msg.role == Role::System)
+            .map(|msg| msg.content.as_str());
+        let chat_history = messages
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| Some(idx) != last_user)
+            .filter_map(|(_, msg)| {
+                let role = match msg.role {
+                    Role::User => "USER",
+                    Role::Assistant => "CHATBOT",
+                    Role::System | Role::Note => return None,
+                };
+                Some(CohereMessage {
+                    role,
+                    message: &msg.content,
+                })
+            })
+            .collect();
+
+        let request_body = CohereRequest {
+            model: self.model,
+            message: last_user
+                .and_then(|idx| messages.get(idx))
+                .map_or("", |msg| msg.content.as_str()),
+            chat_history,
+            preamble,
+            stream: true,
+            temperature: params.temperature,
+            top_p: params.top_p,
+            top_k: params.top_k,
+            presence_penalty: params.presence_penalty,
+            frequency_penalty: params.frequency_penalty,
+            max_tokens: params.max_tokens,
+        };
+
+        let mut resp = None;
+        for (idx, api_key) in self.keys.candidates() {
+            let attempt = chatbots::json_body(
+                self.client.post(COHERE_URL).bearer_auth(api_key),
+                &request_body,
+                self.extra_body.as_ref(),
+            )
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+            resp = Some(attempt);
+            if !limited {
+                self.keys.mark_working(idx);
+                break;
+            }
+        }
+
+        let resp = resp.ok_or(ChatbotChatError::RateLimited)?;
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+
+        let stream = resp
+            .bytes_stream()
+            .scan(Vec::new(), |buffer, item| {
+                future::ready(Some(match item {
+                    Ok(bytes) => {
+                        buffer.extend_from_slice(&bytes);
+                        drain_lines(buffer)
+                    }
+                    Err(err) => Err(ChatbotChatError::NetworkError(err)),
+                }))
+            })
+            .try_filter(|text| future::ready(!text.is_empty()))
+            .map_ok(StreamItem::Text)
+            .boxed();
+
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::drain_lines;
+
+    #[test]
+    fn keeps_generated_text_and_a_partial_line() {
+        let mut buffer = b"{\"event_type\": \"stream-start\", \
+                           \"generation_id\": \"1\"}\n\
+                           {\"event_type\": \"text-generation\", \"text\": \
+                           \"Hel\"}\n\
+                           {\"event_type\": \"text-generation\", \"text\": \
+                           \"lo\"}\n\
+                           {\"event_type\": \"text-gen"
+            .to_vec();
+
+        assert_eq!(drain_lines(&mut buffer).ok(), Some("Hello".to_owned()));
+        assert_eq!(buffer, b"{\"event_type\": \"text-gen");
+
+        buffer.extend_from_slice(
+            b"eration\", \"text\": \"!\"}\n\
+              {\"event_type\": \"stream-end\", \"finish_reason\": \
+              \"COMPLETE\"}\n",
+        );
+        assert_eq!(drain_lines(&mut buffer).ok(), Some("!".to_owned()));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn rejects_lines_that_are_not_events() {
+        let mut buffer = b"<html>\n".to_vec();
+
+        assert!(drain_lines(&mut buffer).ok().is_none());
+    }
+}
@@ -0,0 +1,511 @@
+use alloc::collections::BTreeMap;
+use std::env;
+
+use async_trait::async_trait;
+use futures::{future, stream, StreamExt as _};
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Client, Request, StatusCode,
+};
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest as _;
+
+use crate::{
+    auth::Credentials,
+    chatbots::{self, openai},
+    config::{ChatbotConfig, Config},
+    keys::{KeyPool, KeyRotation},
+    params::{self, GenerationParams},
+    transport, Chatbot, ChatbotChatError, ChatbotCreationError,
+    InvalidModelError, Message, ResponseStream, Role, StreamItem,
+};
+
+const DEFAULT_AUTH_HEADER: &str = "Authorization";
+
+const DEFAULT_RESPONSE_POINTER: &str = "/choices/0/message/content";
+
+const DEFAULT_FRAME_POINTER: &str = "/choices/0/delta/content";
+
+/// Marks the end of an answer from servers that send chat completions chunks.
+const DONE_FRAME: &str = "[DONE]";
+
+fn is_websocket(url: &str) -> bool {
+    url.starts_with("ws://") || url.starts_with("wss://")
+}
+
+/// The text a WebSocket frame adds to the answer and whether it is the
+/// last one.
+fn read_frame(
+    frame: &str,
+    pointer: &str,
+    done_pointer: Option<&str>,
+) -> Result<(Option<String>, bool), ChatbotChatError> {
+    if frame.trim() == DONE_FRAME {
+        return Ok((None, true));
+    }
+    let frame: Value = serde_json::from_str(frame)
+        .map_err(|_err| ChatbotChatError::UnexpectedResponse)?;
+    let text = frame
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .filter(|text| !text.is_empty())
+        .map(str::to_owned);
+    let done = done_pointer.is_some_and(|done| {
+        frame.pointer(done).and_then(Value::as_bool) == Some(true)
+    });
+    Ok((text, done))
+}
+
+/// Where requests go for a server at `base_url`. Only servers speaking the
+/// chat completions API over HTTP get its path appended.
+fn endpoint(base_url: &str, templated: bool) -> String {
+    if templated || is_websocket(base_url) {
+        base_url.to_owned()
+    } else {
+        format!("{}/chat/completions", base_url.trim_end_matches('/'))
+    }
+}
+
+/// Fills in a request template. A string that is only a placeholder becomes
+/// the value itself, so `"{{messages}}"` turns into an array, while
+/// placeholders inside longer strings are replaced by their text.
+fn render(template: &Value, vars: &BTreeMap<&str, Value>) -> Value {
+    match *template {
+        Value::String(ref text) => {
+            let whole = text
+                .strip_prefix("{{")
+                .and_then(|rest| rest.strip_suffix("}}"))
+                .and_then(|name| vars.get(name.trim()));
+            if let Some(value) = whole {
+                return value.clone();
+            }
+
+            let mut rendered = text.clone();
+            for (name, value) in vars {
+                let placeholder = format!("{{{{{name}}}}}");
+                if rendered.contains(&placeholder) {
+                    let replacement = match *value {
+                        Value::String(ref text) => text.clone(),
+                        ref other @ (Value::Null
+                        | Value::Bool(_)
+                        | Value::Number(_)
+                        | Value::Array(_)
+                        | Value::Object(_)) => other.to_string(),
+                    };
+                    rendered = rendered.replace(&placeholder, &replacement);
+                }
+            }
+            Value::String(rendered)
+        }
+        Value::Array(ref items) => {
+            Value::Array(items.iter().map(|item| render(item, vars)).collect())
+        }
+        Value::Object(ref fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), render(value, vars)))
+                .collect(),
+        ),
+        ref other @ (Value::Null | Value::Bool(_) | Value::Number(_)) => {
+            other.clone()
+        }
+    }
+}
+
+/// Any server speaking the `OpenAI` chat completions API, such as vLLM,
+/// llama.cpp, LM Studio or `LiteLLM`, configured under `[compat.<name>]`.
+///
+/// Servers with their own format are described by a `request_template`,
+/// sent as is to `base_url` with the placeholders `{{model}}`,
+/// `{{messages}}`, `{{prompt}}`, `{{system}}`, `{{temperature}}`,
+/// `{{top_p}}` and `{{max_tokens}}` filled in. Their answer is not
+/// streamed and is read from `response_pointer`, a JSON pointer such as
+/// `/output/text`.
+///
+/// A `base_url` starting with `ws://` or `wss://` streams over a WebSocket
+/// instead. The request, by default a chat completions body, goes out as
+/// one text frame and every frame that comes back adds the text at
+/// `response_pointer`, `/choices/0/delta/content` unless set. The answer
+/// ends when the server closes the connection, sends `[DONE]` or sends a
+/// frame where `done_pointer` is true.
+#[non_exhaustive]
+pub struct CompatChatbot {
+    name: String,
+    url: String,
+    auth_header: String,
+    /// Empty for servers that take no key.
+    keys: KeyPool,
+    models: Vec<String>,
+    model: String,
+    template: Option<Value>,
+    response_pointer: Option<String>,
+    done_pointer: Option<String>,
+    client: Client,
+    extra_body: Option<Value>,
+}
+
+impl CompatChatbot {
+    #[inline]
+    pub fn from_config(
+        name: &str,
+        model: Option<String>,
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let compat = config
+            .compat(name)
+            .ok_or(ChatbotCreationError::UnknownChatbot)?;
+
+        let models = compat.models.clone();
+        let model = model
+            .or_else(|| compat.default_model.clone())
+            .or_else(|| compat.models.first().cloned())
+            .ok_or(ChatbotCreationError::UnknownModel)?;
+        if !models.is_empty() && !models.contains(&model) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let api_key = compat
+            .api_key
+            .clone()
+            .or_else(|| env::var(compat.api_key_env.as_ref()?).ok());
+
+        Ok(Box::new(Self {
+            name: name.to_owned(),
+            url: endpoint(&compat.base_url, compat.request_template.is_some()),
+            auth_header: compat
+                .auth_header
+                .clone()
+                .unwrap_or_else(|| DEFAULT_AUTH_HEADER.to_owned()),
+            keys: KeyPool::new(
+                api_key.into_iter().collect(),
+                KeyRotation::default(),
+            ),
+            models,
+            model,
+            template: compat.request_template.clone(),
+            response_pointer: compat.response_pointer.clone(),
+            done_pointer: compat.done_pointer.clone(),
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    fn request(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<Request, reqwest::Error> {
+        self.template
+            .as_ref()
+            .map_or_else(
+                || {
+                    openai::chat_request(
+                        &self.client,
+                        &self.url,
+                        &self.model,
+                        messages,
+                        params,
+                        self.extra_body.as_ref(),
+                    )
+                },
+                |template| {
+                    chatbots::json_body(
+                        self.client.post(&self.url),
+                        &render(
+                            template,
+                            &self.template_vars(messages, params),
+                        ),
+                        self.extra_body.as_ref(),
+                    )
+                },
+            )
+            .build()
+    }
+
+    async fn send_websocket(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let default_template = json!({
+            "model": "{{model}}",
+            "messages": "{{messages}}",
+            "stream": true,
+        });
+        let body = render(
+            self.template.as_ref().unwrap_or(&default_template),
+            &self.template_vars(messages, params),
+        );
+
+        let mut request = self
+            .url
+            .as_str()
+            .into_client_request()
+            .map_err(|err| ChatbotChatError::WebSocket(Box::new(err)))?;
+        if let Some((idx, api_key)) = self.keys.candidates().first().copied() {
+            let value =
+                if self.auth_header.eq_ignore_ascii_case(DEFAULT_AUTH_HEADER) {
+                    format!("Bearer {api_key}")
+                } else {
+                    api_key.to_owned()
+                };
+            // A malformed key must not end up in the output.
+            let (name, value) =
+                HeaderName::from_bytes(self.auth_header.as_bytes())
+                    .ok()
+                    .zip(HeaderValue::from_str(&value).ok())
+                    .ok_or(ChatbotChatError::UnexpectedResponse)?;
+            request.headers_mut().insert(name, value);
+            self.keys.mark_working(idx);
+        }
+
+        let pointer = self
+            .response_pointer
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FRAME_POINTER.to_owned());
+        let done_pointer = self.done_pointer.clone();
+        transport::websocket_stream(request, body.to_string(), move |frame| {
+            read_frame(frame, &pointer, done_pointer.as_deref())
+        })
+        .await
+    }
+
+    fn authorize(
+        &self,
+        request: &mut Request,
+        api_key: &str,
+    ) -> Result<(), ChatbotChatError> {
+        let credentials =
+            if self.auth_header.eq_ignore_ascii_case(DEFAULT_AUTH_HEADER) {
+                Credentials::Bearer(api_key.to_owned())
+            } else {
+                Credentials::Header {
+                    name: self.auth_header.clone(),
+                    value: api_key.to_owned(),
+                }
+            };
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                Authorization only fails on a malformed header name or key,
+                and the key must not end up in the output.
+            "#
+        )]
+        credentials
+            .authorize(request)
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)
+    }
+
+    fn template_vars(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> BTreeMap<&str, Value> {
+        let content_of = |role: Role| {
+            messages
+                .iter()
+                .rfind(|msg| msg.role == role)
+                .map_or("", |msg| msg.content.as_str())
+        };
+        let chat: Vec<Value> = messages
+            .iter()
+            .filter_map(|msg| {
+                let role = match msg.role {
+                    Role::System => "system",
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                    Role::Note => return None,
+                };
+                Some(json!({ "role": role, "content": msg.content }))
+            })
+            .collect();
+
+        BTreeMap::from([
+            ("model", json!(self.model)),
+            ("messages", Value::Array(chat)),
+            ("prompt", json!(content_of(Role::User))),
+            ("system", json!(content_of(Role::System))),
+            ("temperature", json!(params.temperature)),
+            ("top_p", json!(params.top_p)),
+            ("top_k", json!(params.top_k)),
+            ("max_tokens", json!(params.max_tokens)),
+            ("presence_penalty", json!(params.presence_penalty)),
+            ("frequency_penalty", json!(params.frequency_penalty)),
+            ("repetition_penalty", json!(params.repetition_penalty)),
+        ])
+    }
+}
+
+#[async_trait]
+impl Chatbot for CompatChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.models.iter().map(String::as_str).collect()
+    }
+
+    /// Without a configured model list any model name is accepted, as the
+    /// server is the only one who knows what it serves.
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        if !self.models.is_empty() && !self.models.contains(&new_model) {
+            return Err(InvalidModelError);
+        }
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        if let Some(ref base_url) = chatbot_config.base_url {
+            self.url = endpoint(base_url, self.template.is_some());
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &params::NAMES
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        if is_websocket(&self.url) {
+            return self.send_websocket(messages, params).await;
+        }
+
+        // Servers without a key get a single unauthenticated attempt.
+        let candidates = self.keys.candidates();
+        let attempts: Vec<Option<(usize, &str)>> = if candidates.is_empty() {
+            vec![None]
+        } else {
+            candidates.into_iter().map(Some).collect()
+        };
+
+        let mut resp = None;
+        for attempt in attempts {
+            let mut request = self.request(messages, params)?;
+            if let Some((_, api_key)) = attempt {
+                self.authorize(&mut request, api_key)?;
+            }
+
+            let sent = self.client.execute(request).await.map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = sent.status() == StatusCode::TOO_MANY_REQUESTS;
+            resp = Some(sent);
+            if !limited {
+                if let Some((idx, _)) = attempt {
+                    self.keys.mark_working(idx);
+                }
+                break;
+            }
+        }
+        let resp = resp.ok_or(ChatbotChatError::RateLimited)?;
+
+        if self.template.is_none() {
+            return openai::response_stream(resp).await;
+        }
+
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+        let body: Value = resp.json().await?;
+        let text = body
+            .pointer(
+                self.response_pointer
+                    .as_deref()
+                    .unwrap_or(DEFAULT_RESPONSE_POINTER),
+            )
+            .and_then(Value::as_str)
+            .ok_or(ChatbotChatError::UnexpectedResponse)?
+            .to_owned();
+
+        Ok(stream::once(future::ready(Ok(StreamItem::Text(text)))).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{endpoint, read_frame, DEFAULT_FRAME_POINTER};
+
+    #[test]
+    fn websocket_urls_are_used_as_is() {
+        assert_eq!(
+            endpoint("wss://gw.example/chat", false),
+            "wss://gw.example/chat"
+        );
+        assert_eq!(
+            endpoint("http://localhost:8000/v1/", false),
+            "http://localhost:8000/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn reads_text_and_the_end_from_frames() {
+        let chunk = r#"{"choices":[{"delta":{"content":"Hi"}}]}"#;
+        let role = r#"{"choices":[{"delta":{"role":"assistant"}}]}"#;
+        let last = r#"{"text":"!","final":true}"#;
+
+        assert_eq!(
+            read_frame(chunk, DEFAULT_FRAME_POINTER, None).ok(),
+            Some((Some("Hi".to_owned()), false))
+        );
+        assert_eq!(
+            read_frame(role, DEFAULT_FRAME_POINTER, None).ok(),
+            Some((None, false))
+        );
+        assert_eq!(
+            read_frame("[DONE]", DEFAULT_FRAME_POINTER, None).ok(),
+            Some((None, true))
+        );
+        assert_eq!(
+            read_frame(last, "/text", Some("/final")).ok(),
+            Some((Some("!".to_owned()), true))
+        );
+        assert_eq!(read_frame("not json", "/text", None).ok(), None);
+    }
+}
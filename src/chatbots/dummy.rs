@@ -2,11 +2,11 @@ use async_trait::async_trait;
 use futures::{stream, StreamExt as _};
 
 use crate::{
-    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
-    ResponseStream, Role,
+    params::GenerationParams, Chatbot, ChatbotChatError, ChatbotCreationError,
+    InvalidModelError, ResponseStream, Role, StreamItem,
 };
 
-const AVAILABLE_MODELS: [&str; 2] = ["1", "2"];
+pub const AVAILABLE_MODELS: [&str; 2] = ["1", "2"];
 
 #[non_exhaustive]
 #[derive(Default)]
@@ -36,7 +36,7 @@ impl Chatbot for DummyChatbot {
     }
 
     #[inline]
-    fn model(&self) -> &'static str {
+    fn model(&self) -> &str {
         match self.model.as_str() {
             "1" => "Model 1",
             "2" => "Model 2",
@@ -45,8 +45,13 @@ impl Chatbot for DummyChatbot {
     }
 
     #[inline]
-    fn available_models(&self) -> &[&str] {
-        &AVAILABLE_MODELS
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        AVAILABLE_MODELS.to_vec()
     }
 
     #[inline]
@@ -66,6 +71,7 @@ impl Chatbot for DummyChatbot {
     async fn send_message(
         &self,
         messages: &[crate::Message],
+        _params: GenerationParams,
     ) -> Result<ResponseStream, ChatbotChatError> {
         let msg = messages.last().map_or_else(
             || "Dummy response to empty conversation.".to_owned(),
@@ -78,7 +84,7 @@ impl Chatbot for DummyChatbot {
             },
         );
 
-        let stream = stream::iter(vec![Ok(msg)]).boxed();
+        let stream = stream::iter(vec![Ok(StreamItem::Text(msg))]).boxed();
 
         Ok(stream)
     }
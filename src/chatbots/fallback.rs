@@ -0,0 +1,280 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt as _};
+
+use crate::{
+    chatbots,
+    config::Config,
+    params::{self, GenerationParams},
+    ui, Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, Provenance, ResponseStream,
+};
+
+/// Errors worth trying the next chatbot for: those that may pass with time
+/// and used up quotas. Anything else, such as a rejected key or a refusal,
+/// would most likely not be fixed by asking another provider, or should not
+/// be hidden from the user.
+fn is_transient(err: &ChatbotChatError) -> bool {
+    err.is_retryable()
+        || matches!(
+            *err,
+            ChatbotChatError::Api(ref api) if api.is_quota_exhausted()
+        )
+}
+
+#[non_exhaustive]
+pub struct FallbackChatbot {
+    chain: Vec<Box<dyn Chatbot>>,
+    log: bool,
+    answered: Mutex<Option<Provenance>>,
+}
+
+impl FallbackChatbot {
+    #[inline]
+    pub fn from_config(
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let fallback = config
+            .fallback
+            .as_ref()
+            .filter(|fallback| !fallback.chain.is_empty())
+            .ok_or(ChatbotCreationError::MissingConfig)?;
+
+        let chain = fallback
+            .chain
+            .iter()
+            .map(|entry| {
+                if entry.chatbot == "fallback" {
+                    return Err(ChatbotCreationError::UnknownChatbot);
+                }
+                chatbots::create_without_retries(
+                    &entry.chatbot,
+                    entry.model.clone(),
+                    config,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Box::new(Self {
+            chain,
+            log: fallback.log.unwrap_or(true),
+            answered: Mutex::new(None),
+        }))
+    }
+}
+
+#[async_trait]
+impl Chatbot for FallbackChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Fallback"
+    }
+
+    #[inline]
+    fn model(&self) -> &'static str {
+        "First to answer in order"
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        _new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        Err(InvalidModelError)
+    }
+
+    /// Sends the messages to each chatbot in turn until one of them starts
+    /// answering. A chatbot only counts as answering once its first item
+    /// arrives, since some providers report failures inside the stream.
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let mut last_err = None;
+        for chatbot in &self.chain {
+            let attempt = match chatbot.send_message(messages, params).await {
+                Ok(mut stream) => match stream.next().await {
+                    Some(Err(err)) => Err(err),
+                    first => Ok((first, stream)),
+                },
+                Err(err) => Err(err),
+            };
+
+            match attempt {
+                Ok((first, rest)) => {
+                    if let Ok(mut answered) = self.answered.lock() {
+                        *answered = Some(chatbot.provenance());
+                    }
+                    if self.log && last_err.is_some() {
+                        ui::notify(&format!(
+                            "{} ({}) answered",
+                            chatbot.name(),
+                            chatbot.model()
+                        ));
+                    }
+                    return Ok(stream::iter(first).chain(rest).boxed());
+                }
+                Err(err) if is_transient(&err) => {
+                    if self.log {
+                        ui::notify(&format!(
+                            "{} ({}) failed: {err}",
+                            chatbot.name(),
+                            chatbot.model()
+                        ));
+                    }
+                    last_err = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_err.unwrap_or(ChatbotChatError::UnexpectedResponse))
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        self.answered
+            .lock()
+            .ok()
+            .and_then(|answered| answered.clone())
+            .unwrap_or_else(|| Provenance::new(self.name(), self.model()))
+    }
+
+    /// Parameters are passed on to each chatbot, which leaves out what it
+    /// does not support.
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &params::NAMES
+    }
+
+    /// Only the first chatbot is warmed up, as the rest are rarely used.
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        match self.chain.first() {
+            Some(chatbot) => chatbot.warm_up().await,
+            None => Ok(()),
+        }
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        let mut status = Vec::new();
+        for (idx, chatbot) in self.chain.iter().enumerate() {
+            status.push(format!(
+                "fallback {}: {} ({})",
+                idx.saturating_add(1),
+                chatbot.name(),
+                chatbot.model()
+            ));
+            status.extend(chatbot.status().await?);
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{sync::atomic::Ordering, time::Duration};
+    use std::sync::Mutex;
+
+    use super::FallbackChatbot;
+    use crate::{
+        chatbots::scripted::{answer, Reply, ScriptedChatbot},
+        Chatbot, ChatbotChatError, Provenance,
+    };
+
+    fn fallback(chain: Vec<Box<dyn Chatbot>>) -> FallbackChatbot {
+        FallbackChatbot {
+            chain,
+            log: false,
+            answered: Mutex::new(None),
+        }
+    }
+
+    #[tokio::test]
+    async fn moves_on_after_a_transient_failure() {
+        let (first, _) = ScriptedChatbot::boxed(
+            "first",
+            vec![Reply::Fail(ChatbotChatError::RateLimited)],
+        );
+        let (second, _) =
+            ScriptedChatbot::boxed("second", vec![Reply::text("Hi")]);
+        let chatbot = fallback(vec![first, second]);
+
+        let res = answer(&chatbot).await;
+
+        assert_eq!(res.ok(), Some("Hi".to_owned()));
+        assert_eq!(chatbot.provenance(), Provenance::new("second", "scripted"));
+    }
+
+    #[tokio::test]
+    async fn moves_on_when_the_stream_fails_before_any_text() {
+        let (first, _) = ScriptedChatbot::boxed(
+            "first",
+            vec![Reply::Stream(vec![(
+                Duration::ZERO,
+                Err(ChatbotChatError::Timeout),
+            )])],
+        );
+        let (second, _) =
+            ScriptedChatbot::boxed("second", vec![Reply::text("Hi")]);
+        let chatbot = fallback(vec![first, second]);
+
+        let res = answer(&chatbot).await;
+
+        assert_eq!(res.ok(), Some("Hi".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn stops_at_a_refusal() {
+        let (first, _) = ScriptedChatbot::boxed(
+            "first",
+            vec![Reply::Fail(ChatbotChatError::Refused)],
+        );
+        let (second, requests) =
+            ScriptedChatbot::boxed("second", vec![Reply::text("Hi")]);
+        let chatbot = fallback(vec![first, second]);
+
+        let res = answer(&chatbot).await;
+
+        assert!(matches!(res, Err(ChatbotChatError::Refused)));
+        assert_eq!(requests.load(Ordering::Relaxed), 0);
+        assert_eq!(
+            chatbot.provenance(),
+            Provenance::new("Fallback", "First to answer in order")
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_the_last_error_when_every_chatbot_fails() {
+        let (first, _) = ScriptedChatbot::boxed(
+            "first",
+            vec![Reply::Fail(ChatbotChatError::RateLimited)],
+        );
+        let (second, _) = ScriptedChatbot::boxed(
+            "second",
+            vec![Reply::Fail(ChatbotChatError::Timeout)],
+        );
+        let chatbot = fallback(vec![first, second]);
+
+        let res = answer(&chatbot).await;
+
+        assert!(matches!(res, Err(ChatbotChatError::Timeout)));
+    }
+}
@@ -1,20 +1,43 @@
 use alloc::borrow::Cow;
-use std::env;
+use core::iter;
+use std::{env, sync::OnceLock};
+
+#[cfg(feature = "grpc")]
+use tonic::transport::Channel;
+
+#[cfg(feature = "grpc")]
+mod grpc;
 
 use async_trait::async_trait;
-use futures::StreamExt as _;
-use reqwest::Client;
+use futures::{future, stream, StreamExt as _, TryStreamExt as _};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    auth::{AuthError, GoogleCredentials},
+    chatbots,
+    config::{ChatbotConfig, Config},
+    keys::{KeyPool, KeyRotation},
+    params::GenerationParams,
     Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
-    ResponseStream, Role,
+    ResponseStream, Role, StreamItem, TokenUsage,
 };
 
 const GEMINI_BASE_URL: &str =
     "https://generativelanguage.googleapis.com/v1beta/models/";
 
-const AVAILABLE_MODELS: [&str; 5] = [
+const DEFAULT_VERTEX_LOCATION: &str = "us-central1";
+
+const SAMPLING_PARAMS: [&str; 6] = [
+    "temperature",
+    "top_p",
+    "top_k",
+    "max_tokens",
+    "presence_penalty",
+    "frequency_penalty",
+];
+
+pub const AVAILABLE_MODELS: [&str; 5] = [
     "gemini-2.0-flash-exp",
     "gemini-1.5-flash",
     "gemini-1.5-flash-8b",
@@ -23,8 +46,39 @@ const AVAILABLE_MODELS: [&str; 5] = [
 ];
 
 #[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InlineData {
+    mime_type: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiPart<'text> {
-    text: Cow<'text, str>,
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    text: Option<Cow<'text, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inline_data: Option<InlineData>,
+}
+
+impl<'text> GeminiPart<'text> {
+    const fn text(text: &'text str) -> Self {
+        Self {
+            text: Some(Cow::Borrowed(text)),
+            inline_data: None,
+        }
+    }
+
+    fn into_chunk(self) -> Result<StreamItem, ChatbotChatError> {
+        match (self.text, self.inline_data) {
+            (Some(text), _) => Ok(StreamItem::Text(text.into_owned())),
+            (None, Some(image)) => Ok(StreamItem::Image {
+                mime_type: image.mime_type,
+                data: image.data,
+            }),
+            (None, None) => Err(ChatbotChatError::UnexpectedResponse),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -39,30 +93,312 @@ struct SystemInstruction<'text> {
     parts: Vec<GeminiPart<'text>>,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+}
+
+impl From<GenerationParams> for GenerationConfig {
+    fn from(params: GenerationParams) -> Self {
+        Self {
+            temperature: params.temperature,
+            top_p: params.top_p,
+            top_k: params.top_k,
+            presence_penalty: params.presence_penalty,
+            frequency_penalty: params.frequency_penalty,
+            max_output_tokens: params.max_tokens,
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct GeminiRequest<'system, 'text> {
     system_instruction: Option<SystemInstruction<'system>>,
     contents: Vec<GeminiMessage<'text>>,
+    #[serde(rename = "generationConfig")]
+    generation_config: GenerationConfig,
 }
 
+/// A candidate stopped by the safety filters comes without content.
 #[derive(Deserialize)]
 struct GeminiCandidate<'text> {
     #[serde(borrow)]
-    content: GeminiMessage<'text>,
+    content: Option<GeminiMessage<'text>>,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    block_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
 }
 
+/// A blocked prompt gets feedback instead of candidates. Every chunk counts
+/// the tokens so far, the one that finishes the answer has the totals.
 #[derive(Deserialize)]
 struct GeminiResponse<'text> {
-    #[serde(borrow)]
+    #[serde(borrow, default)]
     candidates: Vec<GeminiCandidate<'text>>,
+    #[serde(rename = "promptFeedback")]
+    prompt_feedback: Option<PromptFeedback>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+const REFUSAL_REASONS: [&str; 5] = [
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+];
+
+impl GeminiResponse<'_> {
+    /// The first part of the first candidate, followed by the token usage
+    /// when this chunk finishes the answer.
+    fn into_items(self) -> Result<Vec<StreamItem>, ChatbotChatError> {
+        if self
+            .prompt_feedback
+            .is_some_and(|feedback| feedback.block_reason.is_some())
+        {
+            return Err(ChatbotChatError::Refused);
+        }
+
+        let candidate = self
+            .candidates
+            .into_iter()
+            .next()
+            .ok_or(ChatbotChatError::UnexpectedResponse)?;
+        let refused = candidate
+            .finish_reason
+            .as_deref()
+            .is_some_and(|reason| REFUSAL_REASONS.contains(&reason));
+        let usage = self
+            .usage_metadata
+            .filter(|_| candidate.finish_reason.is_some())
+            .map(|usage| {
+                StreamItem::Usage(TokenUsage::new(
+                    usage.prompt_token_count,
+                    usage.candidates_token_count,
+                ))
+            });
+
+        let chunk = match candidate
+            .content
+            .and_then(|content| content.parts.into_iter().next())
+        {
+            Some(part) => part.into_chunk()?,
+            None if refused => return Err(ChatbotChatError::Refused),
+            None => return Err(ChatbotChatError::UnexpectedResponse),
+        };
+        Ok(iter::once(chunk).chain(usage).collect())
+    }
+}
+
+/// Drains the complete events, each ended by a blank line, from `buffer`
+/// and returns the chunks they carry. An inline image usually spans several
+/// network chunks, so a trailing partial event is left for the next one.
+#[expect(
+    clippy::map_err_ignore,
+    reason = r#"
+        Invalid JSON from the API indicates a critical error so we hide that
+        detail from the end user, as they cannot address this issue.
+    "#
+)]
+fn drain_events(
+    buffer: &mut Vec<u8>,
+) -> Result<Vec<StreamItem>, ChatbotChatError> {
+    let Some(end) = events_end(buffer) else {
+        return Ok(Vec::new());
+    };
+    let complete: Vec<u8> = buffer.drain(..end).collect();
+    let complete = String::from_utf8_lossy(&complete);
+
+    let mut items = Vec::new();
+    let mut data = String::new();
+    for line in complete.lines() {
+        if let Some(value) = line.strip_prefix("data:") {
+            if !data.is_empty() {
+                data.push('\n');
+            }
+            data.push_str(value.strip_prefix(' ').unwrap_or(value));
+            continue;
+        }
+        if line.is_empty() && !data.is_empty() {
+            let gemini_resp: GeminiResponse<'_> =
+                serde_json::from_str(&data)
+                    .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+            items.extend(gemini_resp.into_items()?);
+            data.clear();
+        }
+    }
+
+    Ok(items)
+}
+
+/// The length of the complete events at the start of `buffer`, up to and
+/// including the last blank line.
+fn events_end(buffer: &[u8]) -> Option<usize> {
+    let lf = buffer
+        .windows(2)
+        .rposition(|pair| pair == b"\n\n")
+        .map(|pos| pos.saturating_add(2));
+    let crlf = buffer
+        .windows(3)
+        .rposition(|triple| triple == b"\n\r\n")
+        .map(|pos| pos.saturating_add(3));
+    lf.max(crlf)
+}
+
+#[derive(Deserialize)]
+struct GeminiModelList {
+    #[serde(default)]
+    models: Vec<GeminiModel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiModel {
+    name: String,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+}
+
+struct Vertex {
+    credentials: GoogleCredentials,
+    project: String,
+    location: String,
+    /// Set when answers are streamed over gRPC, connected on first use.
+    #[cfg(feature = "grpc")]
+    channel: Option<OnceLock<Channel>>,
+}
+
+#[cfg(feature = "grpc")]
+impl Vertex {
+    /// The channel answers are streamed over, if gRPC is used.
+    fn grpc_channel(&self) -> Option<Result<Channel, ChatbotChatError>> {
+        let channel = self.channel.as_ref()?;
+        if let Some(connected) = channel.get() {
+            return Some(Ok(connected.clone()));
+        }
+
+        let url =
+            format!("https://{}-aiplatform.googleapis.com", self.location);
+        Some(
+            crate::transport::grpc::channel(url)
+                .map(|connected| channel.get_or_init(|| connected).clone()),
+        )
+    }
 }
 
 #[non_exhaustive]
 pub struct GeminiChatbot {
-    api_key: String,
+    keys: KeyPool,
+    vertex: Option<Vertex>,
     model: String,
-    url: String,
     client: Client,
+    extra_body: Option<serde_json::Value>,
+    models: OnceLock<Vec<String>>,
+}
+
+impl GeminiChatbot {
+    /// Uses Vertex AI with Google Cloud credentials when `[vertex]` is
+    /// configured, and an API key for the public API otherwise.
+    #[inline]
+    pub fn from_config(
+        model: String,
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let Some(vertex) = config.vertex.as_ref() else {
+            return Self::create(model, config.api_key("gemini"));
+        };
+        if !AVAILABLE_MODELS.contains(&model.as_str()) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let grpc = vertex.grpc.unwrap_or_default();
+        if grpc && cfg!(not(feature = "grpc")) {
+            return Err(ChatbotCreationError::MissingFeature("grpc"));
+        }
+        let credentials =
+            GoogleCredentials::from_chain(vertex.credentials.as_deref())
+                .ok_or(ChatbotCreationError::MissingCredentials)?;
+
+        Ok(Box::new(Self {
+            keys: KeyPool::new(Vec::new(), KeyRotation::default()),
+            vertex: Some(Vertex {
+                credentials,
+                project: vertex.project.clone(),
+                location: vertex
+                    .location
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_VERTEX_LOCATION.to_owned()),
+                #[cfg(feature = "grpc")]
+                channel: grpc.then(OnceLock::new),
+            }),
+            model,
+            client: Client::new(),
+            extra_body: None,
+            models: OnceLock::new(),
+        }))
+    }
+
+    fn url(&self, api_key: &str) -> String {
+        format!(
+            "{GEMINI_BASE_URL}{}:streamGenerateContent?alt=sse&key={api_key}",
+            self.model
+        )
+    }
+
+    /// Accepts the built-in models and any listed by `list_models`.
+    fn is_known(&self, model: &str) -> bool {
+        AVAILABLE_MODELS.contains(&model)
+            || self.models.get().is_some_and(|models| {
+                models.iter().any(|listed| listed == model)
+            })
+    }
+
+    /// The resource name of the model in Vertex AI.
+    fn vertex_model(&self, vertex: &Vertex) -> String {
+        format!(
+            "projects/{project}/locations/{location}/publishers/google/\
+             models/{model}",
+            location = vertex.location,
+            project = vertex.project,
+            model = self.model
+        )
+    }
+
+    fn vertex_url(&self, vertex: &Vertex) -> String {
+        format!(
+            "https://{location}-aiplatform.googleapis.com/v1/{model}:\
+             streamGenerateContent?alt=sse",
+            location = vertex.location,
+            model = self.vertex_model(vertex)
+        )
+    }
 }
 
 #[async_trait]
@@ -72,26 +408,25 @@ impl Chatbot for GeminiChatbot {
         model: String,
         api_key: Option<String>,
     ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if !AVAILABLE_MODELS.contains(&model.as_str()) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
         let api_key = if let Some(api_key) = api_key {
             api_key
         } else {
             env::var("GEMINI_API_KEY")?
         };
 
-        if !AVAILABLE_MODELS.contains(&model.as_str()) {
-            return Err(ChatbotCreationError::UnknownModel);
-        }
-
-        let url =
-            format!("{GEMINI_BASE_URL}{model}:streamGenerateContent?alt=sse&key={api_key}");
-
         let client = Client::new();
 
         Ok(Box::new(Self {
-            api_key,
+            keys: KeyPool::single(api_key),
+            vertex: None,
             model,
-            url,
             client,
+            extra_body: None,
+            models: OnceLock::new(),
         }))
     }
 
@@ -101,27 +436,28 @@ impl Chatbot for GeminiChatbot {
     }
 
     #[inline]
-    fn model(&self) -> &'static str {
-        #[expect(
-            clippy::unreachable,
-            reason = r#"
-                `model` is validated on initialization and in `change_model`,
-                so it should always be a valid name.
-            "#
-        )]
+    fn model(&self) -> &str {
         match self.model.as_str() {
             "gemini-2.0-flash-exp" => "2.0 Flash (Experimental)",
             "gemini-1.5-flash" => "1.5 Flash",
             "gemini-1.5-flash-8b" => "1.5 Flash-8B",
             "gemini-1.5-pro" => "1.5 Pro",
             "gemini-1.0-pro" => "1.0 Pro (Deprecated)",
-            _ => unreachable!(),
+            model => model,
         }
     }
 
     #[inline]
-    fn available_models(&self) -> &[&str] {
-        &AVAILABLE_MODELS
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.models.get().map_or_else(
+            || AVAILABLE_MODELS.to_vec(),
+            |models| models.iter().map(String::as_str).collect(),
+        )
     }
 
     #[inline]
@@ -129,30 +465,47 @@ impl Chatbot for GeminiChatbot {
         &mut self,
         new_model: String,
     ) -> Result<(), InvalidModelError> {
-        if !AVAILABLE_MODELS.contains(&new_model.as_str()) {
+        if !self.is_known(&new_model) {
             return Err(InvalidModelError);
         }
 
         self.model = new_model;
 
-        self.url = format!(
-            "{GEMINI_BASE_URL}{}:streamGenerateContent?alt=sse&key={}",
-            self.model, self.api_key
-        );
+        Ok(())
+    }
 
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
         Ok(())
     }
 
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &SAMPLING_PARAMS
+    }
+
     #[inline]
     async fn send_message(
         &self,
         messages: &[crate::Message],
+        params: GenerationParams,
     ) -> Result<ResponseStream, ChatbotChatError> {
         let system = messages.iter().find(|msg| msg.role == Role::System).map(
             |system_prompt| SystemInstruction {
-                parts: vec![GeminiPart {
-                    text: Cow::Borrowed(&system_prompt.content),
-                }],
+                parts: vec![GeminiPart::text(&system_prompt.content)],
             },
         );
 
@@ -161,21 +514,50 @@ impl Chatbot for GeminiChatbot {
             .filter(|msg| msg.role != Role::System)
             .map(|msg| GeminiMessage {
                 role: msg.role,
-                parts: vec![GeminiPart {
-                    text: Cow::Borrowed(&msg.content),
-                }],
+                parts: vec![GeminiPart::text(&msg.content)],
             })
             .collect();
 
         let request_body = GeminiRequest {
             system_instruction: system,
             contents: gemini_messages,
+            generation_config: params.into(),
         };
 
-        let resp_stream = self
-            .client
-            .post(&self.url)
-            .json(&request_body)
+        let resp = if let Some(ref vertex) = self.vertex {
+            let token = vertex
+                .credentials
+                .access_token(&self.client)
+                .await
+                .map_err(|err| match err {
+                    AuthError::Token(err) => {
+                        ChatbotChatError::NetworkError(err)
+                    }
+                    AuthError::InvalidHeader(_)
+                    | AuthError::MissingHost
+                    | AuthError::InvalidPrivateKey
+                    | AuthError::InvalidSigningKey => {
+                        ChatbotChatError::UnexpectedResponse
+                    }
+                })?;
+
+            #[cfg(feature = "grpc")]
+            if let Some(channel) = vertex.grpc_channel() {
+                return grpc::stream_generate_content(
+                    channel?,
+                    self.vertex_model(vertex),
+                    &token,
+                    messages,
+                    params,
+                )
+                .await;
+            }
+
+            let attempt = chatbots::json_body(
+                self.client.post(self.vertex_url(vertex)).bearer_auth(token),
+                &request_body,
+                self.extra_body.as_ref(),
+            )
             .send()
             .await
             .map_err(|err| {
@@ -184,53 +566,179 @@ impl Chatbot for GeminiChatbot {
                 } else {
                     ChatbotChatError::NetworkError(err)
                 }
-            })?
-            .bytes_stream();
-
-        let stream = resp_stream
-            .map(move |item| match item {
-                Ok(bytes) => {
-                    #[expect(
-                        clippy::map_err_ignore,
-                        reason = r#"
-                            Invalid JSON from the API indicates a critical error
-                            so we hide that detail from the end user, as they
-                            cannot address this issue.
-                        "#
-                    )]
-                    #[expect(
-                        clippy::indexing_slicing,
-                        reason = r#"
-                            The Gemini API prepends "data: " to each JSON
-                            chunk in the stream. We need to remove this
-                            non-JSON prefix before deserialization.
-                        "#
-                    )]
-                    let gemini_resp: GeminiResponse<'_> =
-                        serde_json::from_slice(&bytes[5..]).map_err(|_| {
-                            ChatbotChatError::UnexpectedResponse
-                        })?;
-
-                    Ok(gemini_resp
-                        .candidates
-                        .into_iter()
-                        .next()
-                        .and_then(|candidate| {
-                            candidate
-                                .content
-                                .parts
-                                .into_iter()
-                                .next()
-                                .map(|part| Ok(part.text.into_owned()))
-                        })
-                        .unwrap_or_else(|| {
-                            Err(ChatbotChatError::UnexpectedResponse)
-                        })?)
+            })?;
+
+            Some(attempt)
+        } else {
+            let mut resp = None;
+            for (idx, api_key) in self.keys.candidates() {
+                let attempt = chatbots::json_body(
+                    self.client.post(self.url(api_key)),
+                    &request_body,
+                    self.extra_body.as_ref(),
+                )
+                .send()
+                .await
+                .map_err(|err| {
+                    if err.is_timeout() {
+                        ChatbotChatError::Timeout
+                    } else {
+                        ChatbotChatError::NetworkError(err)
+                    }
+                })?;
+
+                let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+                resp = Some(attempt);
+                if !limited {
+                    self.keys.mark_working(idx);
+                    break;
                 }
-                Err(_) => Err(ChatbotChatError::UnexpectedResponse),
+            }
+            resp
+        };
+
+        let resp = resp.ok_or(ChatbotChatError::RateLimited)?;
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+        Ok(resp
+            .bytes_stream()
+            .scan(Vec::new(), |buffer, item| {
+                future::ready(Some(match item {
+                    Ok(bytes) => {
+                        buffer.extend_from_slice(&bytes);
+                        drain_events(buffer)
+                    }
+                    Err(err) => Err(ChatbotChatError::NetworkError(err)),
+                }))
             })
-            .boxed();
+            .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed())
+    }
+
+    /// Queries `models.list` once and caches the models that can generate
+    /// content, which can then be selected with `change_model`. Vertex AI
+    /// is not queried, its publisher catalog lists models by region and
+    /// version, so it reports `available_models`.
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        if self.vertex.is_some() {
+            return Ok(AVAILABLE_MODELS
+                .iter()
+                .map(|&model| model.to_owned())
+                .collect());
+        }
+        if let Some(models) = self.models.get() {
+            return Ok(models.clone());
+        }
+
+        let mut last_err = ChatbotChatError::RateLimited;
+        for (idx, api_key) in self.keys.candidates() {
+            let resp = self
+                .client
+                .get(GEMINI_BASE_URL.trim_end_matches('/'))
+                .query(&[("key", api_key), ("pageSize", "1000")])
+                .send()
+                .await?;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                continue;
+            }
+            if !resp.status().is_success() {
+                last_err = ChatbotChatError::from_status(resp.status());
+                continue;
+            }
 
-        Ok(stream)
+            self.keys.mark_working(idx);
+            let list: GeminiModelList = resp.json().await?;
+            let models = list
+                .models
+                .into_iter()
+                .filter(|model| {
+                    model
+                        .supported_generation_methods
+                        .iter()
+                        .any(|method| method == "generateContent")
+                })
+                .map(|model| {
+                    model
+                        .name
+                        .strip_prefix("models/")
+                        .map_or_else(|| model.name.clone(), str::to_owned)
+                })
+                .collect();
+            return Ok(self.models.get_or_init(|| models).clone());
+        }
+
+        Err(last_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::drain_events;
+    use crate::{StreamItem, TokenUsage};
+
+    #[test]
+    fn waits_for_the_end_of_an_event_split_across_chunks() {
+        let event = "data: {\"candidates\": [{\"content\": {\"role\": \
+                     \"model\", \"parts\": [{\"inlineData\": {\"mimeType\": \
+                     \"image/png\", \"data\": \"iVBORw0KGgo=\"}}]}}]}\r\n\r\n";
+        let (first, second) = event.split_at(60);
+        let mut buffer = Vec::new();
+
+        buffer.extend_from_slice(first.as_bytes());
+        assert_eq!(drain_events(&mut buffer).ok(), Some(Vec::new()));
+
+        buffer.extend_from_slice(second.as_bytes());
+        assert_eq!(
+            drain_events(&mut buffer).ok(),
+            Some(vec![StreamItem::Image {
+                mime_type: "image/png".to_owned(),
+                data: "iVBORw0KGgo=".to_owned(),
+            }])
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn reads_every_event_of_a_chunk_and_keeps_the_rest() {
+        let mut buffer = b"data: {\"candidates\": [{\"content\": {\"role\": \
+                           \"model\", \"parts\": [{\"text\": \"Hel\"}]}}]}\n\n\
+                           data: {\"candidates\": [{\"content\": {\"role\": \
+                           \"model\", \"parts\": [{\"text\": \"lo\"}]}}]}\n\n\
+                           data: {\"candi"
+            .to_vec();
+
+        assert_eq!(
+            drain_events(&mut buffer).ok(),
+            Some(vec![
+                StreamItem::Text("Hel".to_owned()),
+                StreamItem::Text("lo".to_owned()),
+            ])
+        );
+        assert_eq!(buffer, b"data: {\"candi");
+    }
+
+    #[test]
+    fn reports_usage_with_the_chunk_that_finishes_the_answer() {
+        let mut buffer = b"data: {\"candidates\": [{\"content\": {\"role\": \
+                           \"model\", \"parts\": [{\"text\": \"Hi\"}]}}], \
+                           \"usageMetadata\": {\"promptTokenCount\": 12}}\r\n\r\n\
+                           data: {\"candidates\": [{\"content\": {\"role\": \
+                           \"model\", \"parts\": [{\"text\": \"!\"}]}, \
+                           \"finishReason\": \"STOP\"}], \"usageMetadata\": \
+                           {\"promptTokenCount\": 12, \"candidatesTokenCount\": \
+                           3, \"totalTokenCount\": 15}}\r\n\r\n"
+            .to_vec();
+
+        assert_eq!(
+            drain_events(&mut buffer).ok(),
+            Some(vec![
+                StreamItem::Text("Hi".to_owned()),
+                StreamItem::Text("!".to_owned()),
+                StreamItem::Usage(TokenUsage::new(12, 3)),
+            ])
+        );
     }
 }
@@ -0,0 +1,282 @@
+use tonic::{metadata::MetadataValue, transport::Channel, Request};
+
+use crate::{
+    base64, params::GenerationParams, transport, ChatbotChatError, Message,
+    ResponseStream, Role, StreamItem, TokenUsage,
+};
+
+const STREAM_GENERATE_CONTENT: &str =
+    "/google.cloud.aiplatform.v1.PredictionService/StreamGenerateContent";
+
+/// `FinishReason` values of candidates held back by the safety filters:
+/// `SAFETY`, `RECITATION`, `BLOCKLIST`, `PROHIBITED_CONTENT` and `SPII`.
+const REFUSAL_REASONS: [i32; 5] = [3, 4, 6, 7, 8];
+
+// The subset of the `google.cloud.aiplatform.v1` messages the backend
+// sends and reads, with the field numbers of the published protos.
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct GenerateContentRequest {
+    #[prost(string, tag = "5")]
+    model: String,
+    #[prost(message, repeated, tag = "2")]
+    contents: Vec<Content>,
+    #[prost(message, optional, tag = "8")]
+    system_instruction: Option<Content>,
+    #[prost(message, optional, tag = "4")]
+    generation_config: Option<GenerationConfig>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Content {
+    #[prost(string, tag = "1")]
+    role: String,
+    #[prost(message, repeated, tag = "2")]
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Part {
+    #[prost(string, optional, tag = "1")]
+    text: Option<String>,
+    #[prost(message, optional, tag = "2")]
+    inline_data: Option<Blob>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Blob {
+    #[prost(string, tag = "1")]
+    mime_type: String,
+    #[prost(bytes = "vec", tag = "2")]
+    data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct GenerationConfig {
+    #[prost(float, optional, tag = "1")]
+    temperature: Option<f32>,
+    #[prost(float, optional, tag = "2")]
+    top_p: Option<f32>,
+    #[prost(float, optional, tag = "3")]
+    top_k: Option<f32>,
+    #[prost(int32, optional, tag = "5")]
+    max_output_tokens: Option<i32>,
+    #[prost(float, optional, tag = "8")]
+    presence_penalty: Option<f32>,
+    #[prost(float, optional, tag = "9")]
+    frequency_penalty: Option<f32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct GenerateContentResponse {
+    #[prost(message, repeated, tag = "2")]
+    candidates: Vec<Candidate>,
+    #[prost(message, optional, tag = "3")]
+    prompt_feedback: Option<PromptFeedback>,
+    #[prost(message, optional, tag = "4")]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Candidate {
+    #[prost(message, optional, tag = "2")]
+    content: Option<Content>,
+    #[prost(int32, tag = "3")]
+    finish_reason: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct PromptFeedback {
+    #[prost(int32, tag = "1")]
+    block_reason: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct UsageMetadata {
+    #[prost(int32, tag = "1")]
+    prompt_token_count: i32,
+    #[prost(int32, tag = "2")]
+    candidates_token_count: i32,
+}
+
+impl From<GenerationParams> for GenerationConfig {
+    fn from(params: GenerationParams) -> Self {
+        Self {
+            temperature: params.temperature,
+            top_p: params.top_p,
+            #[expect(
+                clippy::cast_precision_loss,
+                clippy::as_conversions,
+                reason = r#"
+                    Vertex AI takes top_k as a float and the values in use
+                    are far below where f32 loses precision.
+                "#
+            )]
+            top_k: params.top_k.map(|top_k| top_k as f32),
+            max_output_tokens: params
+                .max_tokens
+                .map(|tokens| i32::try_from(tokens).unwrap_or(i32::MAX)),
+            presence_penalty: params.presence_penalty,
+            frequency_penalty: params.frequency_penalty,
+        }
+    }
+}
+
+impl GenerateContentResponse {
+    /// The usage Vertex AI reports with the last message of a stream, or
+    /// the first part of the first candidate for the others.
+    fn into_item(self) -> Result<Option<StreamItem>, ChatbotChatError> {
+        if self
+            .prompt_feedback
+            .is_some_and(|feedback| feedback.block_reason != 0)
+        {
+            return Err(ChatbotChatError::Refused);
+        }
+
+        let Some(candidate) = self.candidates.into_iter().next() else {
+            return Ok(self.usage_metadata.map(|usage| {
+                StreamItem::Usage(TokenUsage {
+                    prompt_tokens: u64::try_from(usage.prompt_token_count)
+                        .unwrap_or_default(),
+                    completion_tokens: u64::try_from(
+                        usage.candidates_token_count,
+                    )
+                    .unwrap_or_default(),
+                })
+            }));
+        };
+
+        let refused = REFUSAL_REASONS.contains(&candidate.finish_reason);
+        match candidate
+            .content
+            .and_then(|content| content.parts.into_iter().next())
+        {
+            Some(Part {
+                text: Some(text), ..
+            }) => Ok(Some(StreamItem::Text(text))),
+            Some(Part {
+                inline_data: Some(image),
+                ..
+            }) => Ok(Some(StreamItem::Image {
+                mime_type: image.mime_type,
+                data: base64::encode(&image.data),
+            })),
+            Some(_) => Err(ChatbotChatError::UnexpectedResponse),
+            None if refused => Err(ChatbotChatError::Refused),
+            None => Ok(None),
+        }
+    }
+}
+
+fn content(role: &str, text: &str) -> Content {
+    Content {
+        role: role.to_owned(),
+        parts: vec![Part {
+            text: Some(text.to_owned()),
+            inline_data: None,
+        }],
+    }
+}
+
+/// Streams an answer from `model`, the full resource name of a publisher
+/// model, through Vertex AI's `PredictionService`.
+pub(super) async fn stream_generate_content(
+    channel: Channel,
+    model: String,
+    token: &str,
+    messages: &[Message],
+    params: GenerationParams,
+) -> Result<ResponseStream, ChatbotChatError> {
+    let system_instruction = messages
+        .iter()
+        .find(|msg| msg.role == Role::System)
+        .map(|msg| content("user", &msg.content));
+    let contents = messages
+        .iter()
+        .filter_map(|msg| match msg.role {
+            Role::User => Some(content("user", &msg.content)),
+            Role::Assistant => Some(content("model", &msg.content)),
+            Role::System | Role::Note => None,
+        })
+        .collect();
+
+    let mut request = Request::new(GenerateContentRequest {
+        model,
+        contents,
+        system_instruction,
+        generation_config: Some(params.into()),
+    });
+    let authorization = MetadataValue::try_from(format!("Bearer {token}"))
+        .map_err(|_err| ChatbotChatError::UnexpectedResponse)?;
+    request
+        .metadata_mut()
+        .insert("authorization", authorization);
+
+    transport::grpc::server_streaming(
+        channel,
+        STREAM_GENERATE_CONTENT,
+        request,
+        GenerateContentResponse::into_item,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        content, Candidate, GenerateContentResponse, PromptFeedback,
+        UsageMetadata,
+    };
+    use crate::{ChatbotChatError, StreamItem, TokenUsage};
+
+    #[test]
+    fn maps_messages_into_stream_items() {
+        let text = GenerateContentResponse {
+            candidates: vec![Candidate {
+                content: Some(content("model", "Hi")),
+                finish_reason: 0,
+            }],
+            ..Default::default()
+        };
+        let usage = GenerateContentResponse {
+            usage_metadata: Some(UsageMetadata {
+                prompt_token_count: 12,
+                candidates_token_count: 3,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            text.into_item().ok(),
+            Some(Some(StreamItem::Text("Hi".to_owned())))
+        );
+        assert_eq!(
+            usage.into_item().ok(),
+            Some(Some(StreamItem::Usage(TokenUsage {
+                prompt_tokens: 12,
+                completion_tokens: 3,
+            })))
+        );
+    }
+
+    #[test]
+    fn blocked_answers_are_refusals() {
+        let prompt = GenerateContentResponse {
+            prompt_feedback: Some(PromptFeedback { block_reason: 1 }),
+            ..Default::default()
+        };
+        let candidate = GenerateContentResponse {
+            candidates: vec![Candidate {
+                content: None,
+                finish_reason: 3,
+            }],
+            ..Default::default()
+        };
+
+        assert!(matches!(prompt.into_item(), Err(ChatbotChatError::Refused)));
+        assert!(matches!(
+            candidate.into_item(),
+            Err(ChatbotChatError::Refused)
+        ));
+    }
+}
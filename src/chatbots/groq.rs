@@ -0,0 +1,149 @@
+use std::env;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+
+use crate::{
+    chatbots::{self, openai},
+    config::ChatbotConfig,
+    keys::KeyPool,
+    params::GenerationParams,
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream,
+};
+
+const GROQ_URL: &str = "https://api.groq.com/openai/v1/chat/completions";
+
+pub const AVAILABLE_MODELS: [&str; 5] = [
+    "llama-3.3-70b-versatile",
+    "llama-3.1-8b-instant",
+    "llama3-70b-8192",
+    "mixtral-8x7b-32768",
+    "gemma2-9b-it",
+];
+
+#[non_exhaustive]
+pub struct GroqChatbot {
+    keys: KeyPool,
+    model: &'static str,
+    client: Client,
+    extra_body: Option<serde_json::Value>,
+}
+
+#[async_trait]
+impl Chatbot for GroqChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let model = AVAILABLE_MODELS
+            .into_iter()
+            .find(|&known| known == model)
+            .ok_or(ChatbotCreationError::UnknownModel)?;
+
+        let api_key = if let Some(api_key) = api_key {
+            api_key
+        } else {
+            env::var("GROQ_API_KEY")?
+        };
+
+        Ok(Box::new(Self {
+            keys: KeyPool::single(api_key),
+            model,
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Groq"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        AVAILABLE_MODELS.to_vec()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        self.model = AVAILABLE_MODELS
+            .into_iter()
+            .find(|&known| known == new_model)
+            .ok_or(InvalidModelError)?;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &openai::SAMPLING_PARAMS
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let params = params.retain(&openai::SAMPLING_PARAMS);
+        let mut resp = None;
+        for (idx, api_key) in self.keys.candidates() {
+            let attempt = openai::chat_request(
+                &self.client,
+                GROQ_URL,
+                self.model,
+                messages,
+                params,
+                self.extra_body.as_ref(),
+            )
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+            resp = Some(attempt);
+            if !limited {
+                self.keys.mark_working(idx);
+                break;
+            }
+        }
+
+        openai::response_stream(resp.ok_or(ChatbotChatError::RateLimited)?)
+            .await
+    }
+}
@@ -0,0 +1,158 @@
+use std::env;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+
+use crate::{
+    chatbots::{self, openai},
+    config::ChatbotConfig,
+    keys::KeyPool,
+    params::GenerationParams,
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream,
+};
+
+/// The serverless router. A dedicated Inference Endpoint is used by setting
+/// `base_url` to its `/v1` URL under `[chatbots.huggingface]`.
+const DEFAULT_BASE_URL: &str = "https://router.huggingface.co/v1";
+
+fn is_valid_model(model: &str) -> bool {
+    !model.is_empty() && !model.contains(char::is_whitespace)
+}
+
+#[non_exhaustive]
+pub struct HuggingFaceChatbot {
+    keys: KeyPool,
+    base_url: String,
+    model: String,
+    client: Client,
+    extra_body: Option<serde_json::Value>,
+}
+
+#[async_trait]
+impl Chatbot for HuggingFaceChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if !is_valid_model(&model) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let api_key = if let Some(api_key) = api_key {
+            api_key
+        } else {
+            env::var("HF_TOKEN")?
+        };
+
+        Ok(Box::new(Self {
+            keys: KeyPool::single(api_key),
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            model,
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Hugging Face"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Any of the thousands of hosted repos can be used, so only the current
+    /// one is listed.
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        vec![self.model.as_str()]
+    }
+
+    /// Accepts any repo id, such as `Qwen/Qwen2.5-72B-Instruct`. The Hub
+    /// reports unknown or undeployed repos when the next message is sent.
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        if !is_valid_model(&new_model) {
+            return Err(InvalidModelError);
+        }
+
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        if let Some(ref base_url) = chatbot_config.base_url {
+            self.base_url.clone_from(base_url);
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &openai::SAMPLING_PARAMS
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let params = params.retain(&openai::SAMPLING_PARAMS);
+        let url =
+            format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
+        let mut resp = None;
+        for (idx, api_key) in self.keys.candidates() {
+            let attempt = openai::chat_request(
+                &self.client,
+                &url,
+                &self.model,
+                messages,
+                params,
+                self.extra_body.as_ref(),
+            )
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+            resp = Some(attempt);
+            if !limited {
+                self.keys.mark_working(idx);
+                break;
+            }
+        }
+
+        openai::response_stream(resp.ok_or(ChatbotChatError::RateLimited)?)
+            .await
+    }
+}
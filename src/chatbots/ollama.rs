@@ -0,0 +1,392 @@
+use std::sync::OnceLock;
+
+use async_trait::async_trait;
+use futures::{future, stream, StreamExt as _, TryStreamExt as _};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chatbots,
+    config::ChatbotConfig,
+    params::{self, GenerationParams},
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream, Role, StreamItem, TokenUsage,
+};
+
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+#[derive(Serialize)]
+struct OllamaMessage<'text> {
+    role: &'static str,
+    content: &'text str,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repeat_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+}
+
+impl From<GenerationParams> for OllamaOptions {
+    fn from(params: GenerationParams) -> Self {
+        Self {
+            temperature: params.temperature,
+            top_p: params.top_p,
+            top_k: params.top_k,
+            presence_penalty: params.presence_penalty,
+            frequency_penalty: params.frequency_penalty,
+            repeat_penalty: params.repetition_penalty,
+            num_predict: params.max_tokens,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OllamaRequest<'model, 'text> {
+    model: &'model str,
+    messages: Vec<OllamaMessage<'text>>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponseMessage {
+    content: String,
+}
+
+/// The last chunk is marked `done` and carries the token counts.
+#[derive(Deserialize)]
+struct OllamaChunk {
+    message: Option<OllamaResponseMessage>,
+    #[serde(default)]
+    done: bool,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Deserialize)]
+struct OllamaRunningModel {
+    name: String,
+    size_vram: Option<u64>,
+    expires_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OllamaRunning {
+    models: Vec<OllamaRunningModel>,
+}
+
+/// Drains the complete JSON lines from `buffer` and returns the text they
+/// carry, followed by the usage once the response is done. A trailing
+/// partial line is left for the next network chunk.
+#[expect(
+    clippy::map_err_ignore,
+    reason = r#"
+        Invalid JSON from the server indicates a critical error so we hide
+        that detail from the end user, as they cannot address this issue.
+    "#
+)]
+fn drain_lines(
+    buffer: &mut Vec<u8>,
+) -> Result<Vec<StreamItem>, ChatbotChatError> {
+    let Some(end) = buffer.iter().rposition(|&byte| byte == b'\n') else {
+        return Ok(Vec::new());
+    };
+    let complete: Vec<u8> = buffer.drain(..=end).collect();
+    let complete = String::from_utf8_lossy(&complete);
+
+    let mut text = String::new();
+    let mut usage = None;
+    for line in complete.lines().filter(|line| !line.trim().is_empty()) {
+        let chunk: OllamaChunk = serde_json::from_str(line)
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+        text.extend(chunk.message.map(|message| message.content));
+        if chunk.done {
+            usage = Some(TokenUsage::new(
+                chunk.prompt_eval_count.unwrap_or_default(),
+                chunk.eval_count.unwrap_or_default(),
+            ));
+        }
+    }
+
+    let mut items = Vec::new();
+    if !text.is_empty() {
+        items.push(StreamItem::Text(text));
+    }
+    items.extend(usage.map(StreamItem::Usage));
+    Ok(items)
+}
+
+#[non_exhaustive]
+pub struct OllamaChatbot {
+    base_url: String,
+    model: String,
+    models: OnceLock<Vec<String>>,
+    client: Client,
+    extra_body: Option<serde_json::Value>,
+}
+
+impl OllamaChatbot {
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/{path}", self.base_url.trim_end_matches('/'))
+    }
+
+    /// Fetches the installed models from `/api/tags` once and caches them for
+    /// `available_models`.
+    async fn discover_models(&self) -> Result<(), ChatbotChatError> {
+        if self.models.get().is_some() {
+            return Ok(());
+        }
+
+        let tags: OllamaTags = self
+            .client
+            .get(self.url("tags"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        self.models.get_or_init(|| {
+            tags.models.into_iter().map(|model| model.name).collect()
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Chatbot for OllamaChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if model.trim().is_empty() {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        Ok(Box::new(Self {
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            model,
+            models: OnceLock::new(),
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Lists the installed models once they have been discovered by a
+    /// request, the current model before that.
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.models.get().map_or_else(
+            || vec![self.model.as_str()],
+            |models| models.iter().map(String::as_str).collect(),
+        )
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        let known = self
+            .models
+            .get()
+            .is_none_or(|models| models.contains(&new_model));
+        if new_model.trim().is_empty() || !known {
+            return Err(InvalidModelError);
+        }
+
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        if let Some(ref base_url) = chatbot_config.base_url {
+            self.base_url.clone_from(base_url);
+        }
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &params::NAMES
+    }
+
+    /// Loads the model into memory by sending a chat request without
+    /// messages.
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.discover_models().await?;
+        chatbots::json_body(
+            self.client.post(self.url("chat")),
+            &OllamaRequest {
+                model: &self.model,
+                messages: Vec::new(),
+                stream: false,
+                options: GenerationParams::default().into(),
+            },
+            self.extra_body.as_ref(),
+        )
+        .send()
+        .await?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.discover_models().await?;
+        Ok(self
+            .available_models()
+            .iter()
+            .map(|&model| model.to_owned())
+            .collect())
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.discover_models().await?;
+        let running: OllamaRunning = self
+            .client
+            .get(self.url("ps"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if running.models.is_empty() {
+            return Ok(vec!["No models loaded.".to_owned()]);
+        }
+
+        Ok(running
+            .models
+            .into_iter()
+            .map(|model| {
+                let mut parts = vec![format!("{} loaded", model.name)];
+                if let Some(size) = model.size_vram {
+                    parts.push(format!(
+                        "{} MiB VRAM",
+                        size.checked_div(1024 * 1024).unwrap_or_default()
+                    ));
+                }
+                if let Some(expires_at) = model.expires_at {
+                    parts.push(format!("unloads at {expires_at}"));
+                }
+                parts.join(", ")
+            })
+            .collect())
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        // The model list only fills in `available_models`, so a server that
+        // cannot list them still gets the request, and listing is retried on
+        // the next one.
+        self.discover_models().await.unwrap_or_default();
+
+        let ollama_messages = messages
+            .iter()
+            .filter_map(|msg| {
+                let role = match msg.role {
+                    Role::System => "system",
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                    Role::Note => return None,
+                };
+                Some(OllamaMessage {
+                    role,
+                    content: &msg.content,
+                })
+            })
+            .collect();
+
+        let request_body = OllamaRequest {
+            model: &self.model,
+            messages: ollama_messages,
+            stream: true,
+            options: params.into(),
+        };
+
+        let resp = chatbots::json_body(
+            self.client.post(self.url("chat")),
+            &request_body,
+            self.extra_body.as_ref(),
+        )
+        .send()
+        .await
+        .map_err(|err| {
+            if err.is_timeout() {
+                ChatbotChatError::Timeout
+            } else {
+                ChatbotChatError::NetworkError(err)
+            }
+        })?;
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        }
+
+        let stream = resp
+            .bytes_stream()
+            .scan(Vec::new(), |buffer, item| {
+                future::ready(Some(match item {
+                    Ok(bytes) => {
+                        buffer.extend_from_slice(&bytes);
+                        drain_lines(buffer)
+                    }
+                    Err(err) => Err(ChatbotChatError::NetworkError(err)),
+                }))
+            })
+            .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed();
+
+        Ok(stream)
+    }
+}
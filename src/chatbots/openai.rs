@@ -0,0 +1,545 @@
+use std::{env, sync::OnceLock};
+
+use async_trait::async_trait;
+use futures::{future, stream, StreamExt as _, TryStreamExt as _};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    chatbots, config::ChatbotConfig, keys::KeyPool, params::GenerationParams,
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream, Role, StreamItem, TokenUsage,
+};
+
+const OPENAI_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+const OPENAI_MODELS_URL: &str = "https://api.openai.com/v1/models";
+
+/// Model ids containing these are listed by `/v1/models` but cannot be used
+/// for chat completions.
+const NON_CHAT_MARKERS: [&str; 7] = [
+    "instruct",
+    "audio",
+    "realtime",
+    "transcribe",
+    "tts",
+    "search",
+    "image",
+];
+
+/// Sampling parameters of the chat completions API, also all that most
+/// compatible providers accept.
+pub const SAMPLING_PARAMS: [&str; 5] = [
+    "temperature",
+    "top_p",
+    "max_tokens",
+    "presence_penalty",
+    "frequency_penalty",
+];
+
+pub const AVAILABLE_MODELS: [&str; 6] = [
+    "gpt-4o",
+    "gpt-4o-mini",
+    "gpt-4-turbo",
+    "o1",
+    "o1-mini",
+    "gpt-3.5-turbo",
+];
+
+#[derive(Serialize)]
+struct OpenAIMessage<'text> {
+    role: &'static str,
+    content: &'text str,
+}
+
+#[derive(Serialize)]
+struct SamplingParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repetition_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+}
+
+impl From<GenerationParams> for SamplingParams {
+    fn from(params: GenerationParams) -> Self {
+        Self {
+            temperature: params.temperature,
+            top_p: params.top_p,
+            top_k: params.top_k,
+            presence_penalty: params.presence_penalty,
+            frequency_penalty: params.frequency_penalty,
+            repetition_penalty: params.repetition_penalty,
+            max_tokens: params.max_tokens,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Serialize)]
+struct OpenAIRequest<'model, 'text> {
+    model: &'model str,
+    messages: Vec<OpenAIMessage<'text>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(flatten)]
+    params: SamplingParams,
+}
+
+/// Reasoning models served over this format (`DeepSeek`, and others through
+/// `OpenRouter` or local servers) stream their thinking in
+/// `reasoning_content` before the answer.
+#[derive(Deserialize)]
+struct OpenAIDelta {
+    content: Option<String>,
+    #[serde(alias = "reasoning")]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIChoice {
+    delta: OpenAIDelta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+/// Perplexity repeats the `citations` of the answer in every chunk. The
+/// `usage` comes with the last chunk, which has no choices.
+#[derive(Deserialize)]
+struct OpenAIChunk {
+    #[serde(default)]
+    choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    citations: Vec<String>,
+    usage: Option<OpenAIUsage>,
+}
+
+/// Drains the complete `data:` lines from `buffer` and returns the reasoning,
+/// text and citations they carry, merging consecutive pieces of the same
+/// kind. A
+/// trailing partial line is left for the next network chunk.
+#[expect(
+    clippy::map_err_ignore,
+    reason = r#"
+        Invalid JSON from the API indicates a critical error so we hide that
+        detail from the end user, as they cannot address this issue.
+    "#
+)]
+fn drain_events(
+    buffer: &mut Vec<u8>,
+) -> Result<Vec<StreamItem>, ChatbotChatError> {
+    let Some(end) = buffer.iter().rposition(|&byte| byte == b'\n') else {
+        return Ok(Vec::new());
+    };
+    let complete: Vec<u8> = buffer.drain(..=end).collect();
+    let complete = String::from_utf8_lossy(&complete);
+
+    let mut items = Vec::new();
+    for data in complete
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(str::trim)
+        .filter(|data| !data.is_empty() && *data != "[DONE]")
+    {
+        let chunk: OpenAIChunk = serde_json::from_str(data)
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+        for choice in chunk.choices {
+            if choice.finish_reason.as_deref() == Some("content_filter") {
+                return Err(ChatbotChatError::Refused);
+            }
+            let delta = choice.delta;
+            let pieces = [
+                delta
+                    .reasoning_content
+                    .filter(|text| !text.is_empty())
+                    .map(StreamItem::Reasoning),
+                delta
+                    .content
+                    .filter(|text| !text.is_empty())
+                    .map(StreamItem::Text),
+            ];
+            for item in pieces.into_iter().flatten() {
+                push_item(&mut items, item);
+            }
+        }
+        if !chunk.citations.is_empty() {
+            push_item(&mut items, StreamItem::Citations(chunk.citations));
+        }
+        if let Some(usage) = chunk.usage {
+            items.push(StreamItem::Usage(TokenUsage::new(
+                usage.prompt_tokens,
+                usage.completion_tokens,
+            )));
+        }
+    }
+
+    Ok(items)
+}
+
+fn push_item(items: &mut Vec<StreamItem>, item: StreamItem) {
+    match (items.last_mut(), item) {
+        (
+            Some(&mut StreamItem::Text(ref mut last)),
+            StreamItem::Text(ref text),
+        )
+        | (
+            Some(&mut StreamItem::Reasoning(ref mut last)),
+            StreamItem::Reasoning(ref text),
+        ) => last.push_str(text),
+        (
+            Some(&mut StreamItem::Citations(ref mut last)),
+            StreamItem::Citations(urls),
+        ) => *last = urls,
+        (_, item) => items.push(item),
+    }
+}
+
+/// Builds a streaming chat completions request. Shared by every backend
+/// that speaks the `OpenAI` wire format; callers add authentication.
+#[inline]
+pub fn chat_request(
+    client: &Client,
+    url: &str,
+    model: &str,
+    messages: &[Message],
+    params: GenerationParams,
+    extra_body: Option<&Value>,
+) -> RequestBuilder {
+    build_request(client, url, model, messages, params, false, extra_body)
+}
+
+/// Not every compatible server accepts `stream_options`, so only `OpenAI`
+/// itself is asked to report usage. Others that send it anyway are still
+/// understood.
+fn build_request(
+    client: &Client,
+    url: &str,
+    model: &str,
+    messages: &[Message],
+    params: GenerationParams,
+    include_usage: bool,
+    extra_body: Option<&Value>,
+) -> RequestBuilder {
+    let openai_messages = messages
+        .iter()
+        .filter_map(|msg| {
+            let role = match msg.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+                Role::Note => return None,
+            };
+            Some(OpenAIMessage {
+                role,
+                content: &msg.content,
+            })
+        })
+        .collect();
+
+    chatbots::json_body(
+        client.post(url),
+        &OpenAIRequest {
+            model,
+            messages: openai_messages,
+            stream: true,
+            stream_options: include_usage.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            params: params.into(),
+        },
+        extra_body,
+    )
+}
+
+/// Turns a chat completions response into a stream of text, reasoning and
+/// citation chunks.
+#[inline]
+pub async fn response_stream(
+    resp: Response,
+) -> Result<ResponseStream, ChatbotChatError> {
+    if !resp.status().is_success() {
+        return Err(ChatbotChatError::from_response(resp).await);
+    }
+
+    Ok(resp
+        .bytes_stream()
+        .scan(Vec::new(), |buffer, item| {
+            future::ready(Some(match item {
+                Ok(bytes) => {
+                    buffer.extend_from_slice(&bytes);
+                    drain_events(buffer)
+                }
+                Err(err) => Err(ChatbotChatError::NetworkError(err)),
+            }))
+        })
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed())
+}
+
+#[derive(Deserialize)]
+struct OpenAIModelList {
+    data: Vec<OpenAIModel>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIModel {
+    id: String,
+}
+
+fn is_chat_model(id: &str) -> bool {
+    ["gpt-", "chatgpt-", "o1", "o3", "o4"]
+        .iter()
+        .any(|prefix| id.starts_with(prefix))
+        && !NON_CHAT_MARKERS.iter().any(|marker| id.contains(marker))
+}
+
+#[non_exhaustive]
+pub struct OpenAIChatbot {
+    keys: KeyPool,
+    model: String,
+    client: Client,
+    models: OnceLock<Vec<String>>,
+    extra_body: Option<Value>,
+}
+
+#[async_trait]
+impl Chatbot for OpenAIChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if !AVAILABLE_MODELS.contains(&model.as_str()) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let api_key = if let Some(api_key) = api_key {
+            api_key
+        } else {
+            env::var("OPENAI_API_KEY")?
+        };
+
+        Ok(Box::new(Self {
+            keys: KeyPool::single(api_key),
+            model,
+            client: Client::new(),
+            models: OnceLock::new(),
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        #[expect(
+            clippy::unreachable,
+            reason = r#"
+                `model` is validated on initialization and in `change_model`,
+                so it should always be a valid name.
+            "#
+        )]
+        match self.model.as_str() {
+            "gpt-4o" => "GPT-4o",
+            "gpt-4o-mini" => "GPT-4o mini",
+            "gpt-4-turbo" => "GPT-4 Turbo",
+            "o1" => "o1",
+            "o1-mini" => "o1-mini",
+            "gpt-3.5-turbo" => "GPT-3.5 Turbo",
+            _ => unreachable!(),
+        }
+    }
+
+    #[inline]
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        AVAILABLE_MODELS.to_vec()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        if !AVAILABLE_MODELS.contains(&new_model.as_str()) {
+            return Err(InvalidModelError);
+        }
+
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &SAMPLING_PARAMS
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let params = params.retain(&SAMPLING_PARAMS);
+        let mut resp = None;
+        for (idx, api_key) in self.keys.candidates() {
+            let attempt = build_request(
+                &self.client,
+                OPENAI_URL,
+                &self.model,
+                messages,
+                params,
+                true,
+                self.extra_body.as_ref(),
+            )
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+            resp = Some(attempt);
+            if !limited {
+                self.keys.mark_working(idx);
+                break;
+            }
+        }
+
+        response_stream(resp.ok_or(ChatbotChatError::RateLimited)?).await
+    }
+
+    /// Queries `/v1/models` once and caches the chat models it lists.
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        if let Some(models) = self.models.get() {
+            return Ok(models.clone());
+        }
+
+        let mut last_err = ChatbotChatError::RateLimited;
+        for (idx, api_key) in self.keys.candidates() {
+            let resp = self
+                .client
+                .get(OPENAI_MODELS_URL)
+                .bearer_auth(api_key)
+                .send()
+                .await?;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                continue;
+            }
+            if !resp.status().is_success() {
+                last_err = ChatbotChatError::from_status(resp.status());
+                continue;
+            }
+
+            self.keys.mark_working(idx);
+            let list: OpenAIModelList = resp.json().await?;
+            let mut models: Vec<String> = list
+                .data
+                .into_iter()
+                .map(|model| model.id)
+                .filter(|id| is_chat_model(id))
+                .collect();
+            models.sort_unstable();
+            return Ok(self.models.get_or_init(|| models).clone());
+        }
+
+        Err(last_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::drain_events;
+    use crate::{ChatbotChatError, StreamItem, TokenUsage};
+
+    #[test]
+    fn merges_complete_lines_and_keeps_a_partial_one() {
+        let mut buffer = b"data: {\"choices\": [{\"delta\": {\"content\": \
+                           \"Hel\"}}]}\n\n\
+                           data: {\"choices\": [{\"delta\": {\"content\": \
+                           \"lo\"}}]}\n\n\
+                           data: {\"choi"
+            .to_vec();
+
+        assert_eq!(
+            drain_events(&mut buffer).ok(),
+            Some(vec![StreamItem::Text("Hello".to_owned())])
+        );
+        assert_eq!(buffer, b"data: {\"choi");
+
+        buffer.extend_from_slice(
+            b"ces\": [], \"usage\": {\"prompt_tokens\": 9, \
+              \"completion_tokens\": 2}}\n\ndata: [DONE]\n\n",
+        );
+        assert_eq!(
+            drain_events(&mut buffer).ok(),
+            Some(vec![StreamItem::Usage(TokenUsage::new(9, 2))])
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn filtered_answers_are_refused() {
+        let mut buffer = b"data: {\"choices\": [{\"delta\": {}, \
+                           \"finish_reason\": \"content_filter\"}]}\n\n"
+            .to_vec();
+
+        assert!(matches!(
+            drain_events(&mut buffer),
+            Err(ChatbotChatError::Refused)
+        ));
+    }
+}
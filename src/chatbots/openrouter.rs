@@ -0,0 +1,240 @@
+use alloc::collections::BTreeMap;
+use std::{env, sync::OnceLock};
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+use crate::{
+    chatbots::{self, openai},
+    config::ChatbotConfig,
+    keys::KeyPool,
+    params::{self, GenerationParams},
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream,
+};
+
+const OPENROUTER_URL: &str = "https://openrouter.ai/api/v1";
+
+/// `OpenRouter` ranks apps by these headers. Both can be overridden with
+/// `[chatbots.openrouter.headers]`.
+const ATTRIBUTION_HEADERS: [(&str, &str); 2] = [
+    ("HTTP-Referer", "https://github.com/matee8/llmcli"),
+    ("X-Title", "llmcli"),
+];
+
+#[derive(Deserialize)]
+struct OpenRouterModel {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct OpenRouterModels {
+    data: Vec<OpenRouterModel>,
+}
+
+/// Checks the `provider/model` form `OpenRouter` uses for model ids.
+fn is_namespaced(model: &str) -> bool {
+    model.split_once('/').is_some_and(|(provider, name)| {
+        !provider.is_empty()
+            && !name.is_empty()
+            && !model.contains(char::is_whitespace)
+    })
+}
+
+fn client_with_attribution(
+    headers: Option<&BTreeMap<String, String>>,
+) -> Result<Client, ChatbotCreationError> {
+    let mut merged: BTreeMap<String, String> = ATTRIBUTION_HEADERS
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect();
+    merged.extend(
+        headers
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), value.clone())),
+    );
+    chatbots::client_with_headers(&merged)
+}
+
+#[non_exhaustive]
+pub struct OpenRouterChatbot {
+    keys: KeyPool,
+    model: String,
+    models: OnceLock<Vec<String>>,
+    client: Client,
+    extra_body: Option<serde_json::Value>,
+}
+
+impl OpenRouterChatbot {
+    /// Fetches the model catalog once and caches it for `available_models`.
+    async fn discover_models(&self) -> Result<(), ChatbotChatError> {
+        if self.models.get().is_some() {
+            return Ok(());
+        }
+
+        let models: OpenRouterModels = self
+            .client
+            .get(format!("{OPENROUTER_URL}/models"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        self.models.get_or_init(|| {
+            models.data.into_iter().map(|model| model.id).collect()
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Chatbot for OpenRouterChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if !is_namespaced(&model) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let api_key = if let Some(api_key) = api_key {
+            api_key
+        } else {
+            env::var("OPENROUTER_API_KEY")?
+        };
+
+        Ok(Box::new(Self {
+            keys: KeyPool::single(api_key),
+            model,
+            models: OnceLock::new(),
+            client: client_with_attribution(None)?,
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "OpenRouter"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Lists the live catalog once it has been fetched by a request, the
+    /// current model before that.
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.models.get().map_or_else(
+            || vec![self.model.as_str()],
+            |models| models.iter().map(String::as_str).collect(),
+        )
+    }
+
+    /// Accepts any `provider/model` id, checked against the catalog once it
+    /// is known.
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        let known = self
+            .models
+            .get()
+            .is_none_or(|models| models.contains(&new_model));
+        if !is_namespaced(&new_model) || !known {
+            return Err(InvalidModelError);
+        }
+
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = client_with_attribution(Some(headers))?;
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &params::NAMES
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.discover_models().await
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.discover_models().await?;
+        Ok(self
+            .available_models()
+            .iter()
+            .map(|&model| model.to_owned())
+            .collect())
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        // The catalog only fills in `available_models`, so the request goes
+        // ahead without it and it is fetched again next time.
+        self.discover_models().await.unwrap_or_default();
+
+        let url = format!("{OPENROUTER_URL}/chat/completions");
+        let mut resp = None;
+        for (idx, api_key) in self.keys.candidates() {
+            let attempt = openai::chat_request(
+                &self.client,
+                &url,
+                &self.model,
+                messages,
+                params,
+                self.extra_body.as_ref(),
+            )
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+            resp = Some(attempt);
+            if !limited {
+                self.keys.mark_working(idx);
+                break;
+            }
+        }
+
+        openai::response_stream(resp.ok_or(ChatbotChatError::RateLimited)?)
+            .await
+    }
+}
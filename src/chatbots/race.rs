@@ -0,0 +1,243 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use futures::{future, stream, StreamExt as _};
+
+use crate::{
+    chatbots,
+    config::Config,
+    params::{self, GenerationParams},
+    ui, Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, Provenance, ResponseStream,
+};
+
+#[non_exhaustive]
+pub struct RaceChatbot {
+    contenders: Vec<Box<dyn Chatbot>>,
+    log: bool,
+    winner: Mutex<Option<Provenance>>,
+}
+
+impl RaceChatbot {
+    #[inline]
+    pub fn from_config(
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let race = config
+            .race
+            .as_ref()
+            .filter(|race| race.contenders.len() >= 2)
+            .ok_or(ChatbotCreationError::MissingConfig)?;
+
+        let contenders = race
+            .contenders
+            .iter()
+            .map(|contender| {
+                if contender.chatbot == "race" {
+                    return Err(ChatbotCreationError::UnknownChatbot);
+                }
+                chatbots::create(
+                    &contender.chatbot,
+                    contender.model.clone(),
+                    config,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Box::new(Self {
+            contenders,
+            log: race.log.unwrap_or(false),
+            winner: Mutex::new(None),
+        }))
+    }
+}
+
+#[async_trait]
+impl Chatbot for RaceChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Race"
+    }
+
+    #[inline]
+    fn model(&self) -> &'static str {
+        "First to answer"
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        _new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        Err(InvalidModelError)
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let attempts = self.contenders.iter().map(|chatbot| {
+            Box::pin(async move {
+                let mut stream = chatbot.send_message(messages, params).await?;
+                let first = match stream.next().await {
+                    Some(Ok(item)) => Some(item),
+                    Some(Err(err)) => return Err(err),
+                    None => None,
+                };
+                Ok((chatbot, first, stream))
+            })
+        });
+
+        // The losing requests are dropped together with their futures,
+        // which cancels them.
+        let ((winner, first, rest), _) = future::select_ok(attempts).await?;
+
+        if let Ok(mut last_winner) = self.winner.lock() {
+            *last_winner = Some(winner.provenance());
+        }
+
+        if self.log {
+            ui::notify(&format!(
+                "{} ({}) answered first",
+                winner.name(),
+                winner.model()
+            ));
+        }
+
+        Ok(stream::iter(first.map(Ok)).chain(rest).boxed())
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        self.winner
+            .lock()
+            .ok()
+            .and_then(|winner| winner.clone())
+            .unwrap_or_else(|| Provenance::new(self.name(), self.model()))
+    }
+
+    /// Parameters are passed on to each chatbot, which leaves out what it
+    /// does not support.
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &params::NAMES
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        for chatbot in &self.contenders {
+            chatbot.warm_up().await?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        let mut status = Vec::new();
+        for chatbot in &self.contenders {
+            status.push(format!(
+                "contender: {} ({})",
+                chatbot.name(),
+                chatbot.model()
+            ));
+            status.extend(chatbot.status().await?);
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::sync::Mutex;
+
+    use super::RaceChatbot;
+    use crate::{
+        chatbots::scripted::{answer, Reply, ScriptedChatbot},
+        Chatbot, ChatbotChatError, Provenance, StreamItem,
+    };
+
+    fn race(contenders: Vec<Box<dyn Chatbot>>) -> RaceChatbot {
+        RaceChatbot {
+            contenders,
+            log: false,
+            winner: Mutex::new(None),
+        }
+    }
+
+    fn text_after(secs: u64, text: &str) -> Reply {
+        Reply::Stream(vec![(
+            Duration::from_secs(secs),
+            Ok(StreamItem::Text(text.to_owned())),
+        )])
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_first_to_answer_wins() {
+        let (slow, _) =
+            ScriptedChatbot::boxed("slow", vec![text_after(5, "Slow")]);
+        let (fast, _) =
+            ScriptedChatbot::boxed("fast", vec![text_after(1, "Fast")]);
+        let chatbot = race(vec![slow, fast]);
+
+        let res = answer(&chatbot).await;
+
+        assert_eq!(res.ok(), Some("Fast".to_owned()));
+        assert_eq!(chatbot.provenance(), Provenance::new("fast", "scripted"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failing_contender_does_not_end_the_race() {
+        let (broken, _) = ScriptedChatbot::boxed(
+            "broken",
+            vec![Reply::Fail(ChatbotChatError::Refused)],
+        );
+        let (slow, _) =
+            ScriptedChatbot::boxed("slow", vec![text_after(5, "Slow")]);
+        let chatbot = race(vec![broken, slow]);
+
+        let res = answer(&chatbot).await;
+
+        assert_eq!(res.ok(), Some("Slow".to_owned()));
+        assert_eq!(chatbot.provenance(), Provenance::new("slow", "scripted"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fails_when_every_contender_fails() {
+        let (first, _) = ScriptedChatbot::boxed(
+            "first",
+            vec![Reply::Fail(ChatbotChatError::RateLimited)],
+        );
+        let (second, _) = ScriptedChatbot::boxed(
+            "second",
+            vec![Reply::Stream(vec![(
+                Duration::from_secs(1),
+                Err(ChatbotChatError::Timeout),
+            )])],
+        );
+        let chatbot = race(vec![first, second]);
+
+        let res = answer(&chatbot).await;
+
+        assert!(matches!(res, Err(ChatbotChatError::Timeout)));
+        assert_eq!(
+            chatbot.provenance(),
+            Provenance::new("Race", "First to answer")
+        );
+    }
+}
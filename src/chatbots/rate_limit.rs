@@ -0,0 +1,288 @@
+use core::time::Duration;
+use std::{sync::Mutex, time::Instant};
+
+use async_trait::async_trait;
+use tokio::time;
+
+use crate::{
+    config::ChatbotConfig, map_reduce, params::GenerationParams, Chatbot,
+    ChatbotChatError, ChatbotCreationError, InvalidModelError, Message,
+    Provenance, ResponseStream,
+};
+
+const MINUTE: f64 = 60.0;
+
+/// A bucket holding up to a minute's worth of allowance, refilled
+/// continuously, so short bursts pass at once and longer runs are paced.
+struct Bucket {
+    per_minute: f64,
+    level: f64,
+}
+
+#[expect(
+    clippy::float_arithmetic,
+    reason = r#"
+        The allowance refills by fractions of a unit between requests.
+    "#
+)]
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute: f64::from(per_minute),
+            level: f64::from(per_minute),
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.level = self
+            .per_minute
+            .min(self.level + elapsed.as_secs_f64() * self.per_minute / MINUTE);
+    }
+
+    /// How long until `amount` is available. Amounts above the per-minute
+    /// limit would never fit, so they wait for a full bucket instead.
+    fn wait(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.per_minute) - self.level;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * MINUTE / self.per_minute)
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.level = (self.level - amount.min(self.per_minute)).max(0.0);
+    }
+}
+
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    updated: Instant,
+}
+
+/// Delays requests to stay within `requests_per_minute` and
+/// `tokens_per_minute` from the chatbot's config, instead of running into
+/// the provider's rate limit.
+///
+/// Only prompt tokens are counted, as estimated before sending.
+#[non_exhaustive]
+pub struct RateLimitedChatbot {
+    inner: Box<dyn Chatbot>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimitedChatbot {
+    /// Wraps `inner` if `chatbot_config` sets any limit.
+    #[inline]
+    #[must_use]
+    pub fn wrap(
+        inner: Box<dyn Chatbot>,
+        chatbot_config: &ChatbotConfig,
+    ) -> Box<dyn Chatbot> {
+        let requests = chatbot_config
+            .requests_per_minute
+            .filter(|&limit| limit > 0)
+            .map(Bucket::new);
+        let tokens = chatbot_config
+            .tokens_per_minute
+            .filter(|&limit| limit > 0)
+            .map(Bucket::new);
+        if requests.is_none() && tokens.is_none() {
+            return inner;
+        }
+
+        Box::new(Self {
+            inner,
+            buckets: Mutex::new(Buckets {
+                requests,
+                tokens,
+                updated: Instant::now(),
+            }),
+        })
+    }
+
+    async fn acquire(&self, tokens: usize) {
+        let tokens = u32::try_from(tokens)
+            .map_or_else(|_| f64::from(u32::MAX), f64::from);
+        loop {
+            let wait = {
+                let Ok(mut guard) = self.buckets.lock() else {
+                    return;
+                };
+                let buckets = &mut *guard;
+                let now = Instant::now();
+                let elapsed = now.saturating_duration_since(buckets.updated);
+                buckets.updated = now;
+                for bucket in [&mut buckets.requests, &mut buckets.tokens]
+                    .into_iter()
+                    .flatten()
+                {
+                    bucket.refill(elapsed);
+                }
+
+                let wait = buckets
+                    .requests
+                    .as_ref()
+                    .map_or(Duration::ZERO, |bucket| bucket.wait(1.0))
+                    .max(
+                        buckets
+                            .tokens
+                            .as_ref()
+                            .map_or(Duration::ZERO, |bucket| {
+                                bucket.wait(tokens)
+                            }),
+                    );
+                if wait.is_zero() {
+                    if let Some(ref mut bucket) = buckets.requests {
+                        bucket.take(1.0);
+                    }
+                    if let Some(ref mut bucket) = buckets.tokens {
+                        bucket.take(tokens);
+                    }
+                    return;
+                }
+                wait
+            };
+            time::sleep(wait).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Chatbot for RateLimitedChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    #[inline]
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.inner.available_models()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        self.inner.change_model(new_model)
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let tokens = messages
+            .iter()
+            .map(|msg| map_reduce::estimate_tokens(&msg.content))
+            .sum();
+        self.acquire(tokens).await;
+        self.inner.send_message(messages, params).await
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.list_models().await
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.inner.warm_up().await
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.status().await
+    }
+
+    #[inline]
+    fn escalate(&self) -> bool {
+        self.inner.escalate()
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        self.inner.provenance()
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.inner.key_index()
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        self.inner.supported_params()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        self.inner.configure(chatbot_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::Bucket;
+
+    #[test]
+    fn full_bucket_lets_a_burst_through() {
+        let mut bucket = Bucket::new(60);
+        assert_eq!(bucket.wait(60.0), Duration::ZERO);
+        bucket.take(60.0);
+        assert_eq!(bucket.wait(1.0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn refills_in_proportion_to_elapsed_time() {
+        let mut bucket = Bucket::new(60);
+        bucket.take(60.0);
+        bucket.refill(Duration::from_secs(30));
+        assert_eq!(bucket.wait(30.0), Duration::ZERO);
+        assert_eq!(bucket.wait(31.0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn refill_stops_at_a_minute_of_allowance() {
+        let mut bucket = Bucket::new(60);
+        bucket.take(10.0);
+        bucket.refill(Duration::from_secs(90));
+        bucket.take(60.0);
+        assert_eq!(bucket.wait(1.0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn oversized_amounts_wait_for_a_full_bucket() {
+        let mut bucket = Bucket::new(60);
+        bucket.take(30.0);
+        assert_eq!(bucket.wait(1000.0), Duration::from_secs(30));
+        bucket.take(1000.0);
+        assert_eq!(bucket.wait(1.0), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,443 @@
+use std::sync::OnceLock;
+
+use crate::{
+    chatbots::{
+        auto::AutoChatbot,
+        bedrock::BedrockChatbot,
+        cohere::{self, CohereChatbot},
+        compat::CompatChatbot,
+        deepseek::{self, DeepSeekChatbot},
+        dummy::{self, DummyChatbot},
+        fallback::FallbackChatbot,
+        gemini::{self, GeminiChatbot},
+        github::{self, GitHubChatbot},
+        groq::{self, GroqChatbot},
+        huggingface::HuggingFaceChatbot,
+        mistral::{self, MistralChatbot},
+        ollama::OllamaChatbot,
+        openai::{self, OpenAIChatbot},
+        openrouter::OpenRouterChatbot,
+        perplexity::{self, PerplexityChatbot},
+        race::RaceChatbot,
+        replicate::ReplicateChatbot,
+        together::TogetherChatbot,
+        xai::{self, XaiChatbot},
+    },
+    config::Config,
+    Chatbot, ChatbotCreationError,
+};
+
+/// Builds a chatbot from the requested model, if any, and the config.
+pub type Factory = fn(
+    Option<String>,
+    &Config,
+) -> Result<Box<dyn Chatbot>, ChatbotCreationError>;
+
+/// A backend known by name.
+#[non_exhaustive]
+#[expect(
+    clippy::partial_pub_fields,
+    reason = r#"
+        The other fields describe the chatbot for help and selection;
+        building goes through `ChatbotRegistry::build`.
+    "#
+)]
+pub struct RegisteredChatbot {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Whether it reads an API key from `[api_keys]`, which can then be
+    /// asked for when missing.
+    pub api_key: bool,
+    /// Whether it is made of other configured chatbots instead of talking
+    /// to a provider itself.
+    pub composite: bool,
+    /// Whether it takes any well-formed model name, so it is only picked
+    /// for a model when named explicitly as `chatbot:model`.
+    pub any_model: bool,
+    /// The models offered on the command line, empty for chatbots that
+    /// take any model name or none at all.
+    pub models: &'static [&'static str],
+    /// The model used on the command line when none is given.
+    pub default_model: Option<&'static str>,
+    factory: Factory,
+}
+
+impl RegisteredChatbot {
+    #[inline]
+    #[must_use]
+    pub const fn new(
+        name: &'static str,
+        description: &'static str,
+        factory: Factory,
+    ) -> Self {
+        Self {
+            name,
+            description,
+            api_key: false,
+            composite: false,
+            any_model: false,
+            models: &[],
+            default_model: None,
+            factory,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_api_key(mut self) -> Self {
+        self.api_key = true;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn composite(mut self) -> Self {
+        self.composite = true;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn any_model(mut self) -> Self {
+        self.any_model = true;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_models(
+        mut self,
+        models: &'static [&'static str],
+    ) -> Self {
+        self.models = models;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_default_model(mut self, model: &'static str) -> Self {
+        self.default_model = Some(model);
+        self
+    }
+}
+
+/// The chatbots that can be picked by name, in the order they are listed
+/// and tried when looking up a model. `[compat]` servers from the config
+/// are resolved after them.
+#[non_exhaustive]
+#[derive(Default)]
+pub struct ChatbotRegistry {
+    chatbots: Vec<RegisteredChatbot>,
+}
+
+impl ChatbotRegistry {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The chatbots built into llmcli.
+    #[inline]
+    #[must_use]
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            RegisteredChatbot::new(
+                "gemini",
+                "Google Gemini",
+                |model, config| {
+                    GeminiChatbot::from_config(
+                        model_or_default(model, config, "gemini")?,
+                        config,
+                    )
+                },
+            )
+            .with_api_key()
+            .with_models(&gemini::AVAILABLE_MODELS)
+            .with_default_model("gemini-1.5-flash"),
+        );
+        registry.register(
+            RegisteredChatbot::new("openai", "OpenAI", |model, config| {
+                OpenAIChatbot::create(
+                    model_or_default(model, config, "openai")?,
+                    config.api_key("openai"),
+                )
+            })
+            .with_api_key()
+            .with_models(&openai::AVAILABLE_MODELS)
+            .with_default_model("gpt-4o"),
+        );
+        registry.register(
+            RegisteredChatbot::new("mistral", "Mistral AI", |model, config| {
+                MistralChatbot::create(
+                    model_or_default(model, config, "mistral")?,
+                    config.api_key("mistral"),
+                )
+            })
+            .with_api_key()
+            .with_models(&mistral::AVAILABLE_MODELS)
+            .with_default_model("mistral-large-latest"),
+        );
+        registry.register(
+            RegisteredChatbot::new("groq", "Groq", |model, config| {
+                GroqChatbot::create(
+                    model_or_default(model, config, "groq")?,
+                    config.api_key("groq"),
+                )
+            })
+            .with_api_key()
+            .with_models(&groq::AVAILABLE_MODELS)
+            .with_default_model("llama-3.3-70b-versatile"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "openrouter",
+                "OpenRouter (provider/model ids)",
+                |model, config| {
+                    OpenRouterChatbot::create(
+                        model_or_default(model, config, "openrouter")?,
+                        config.api_key("openrouter"),
+                    )
+                },
+            )
+            .with_api_key()
+            .any_model()
+            .with_default_model("openai/gpt-4o-mini"),
+        );
+        registry.register(
+            RegisteredChatbot::new("deepseek", "DeepSeek", |model, config| {
+                DeepSeekChatbot::create(
+                    model_or_default(model, config, "deepseek")?,
+                    config.api_key("deepseek"),
+                )
+            })
+            .with_api_key()
+            .with_models(&deepseek::AVAILABLE_MODELS)
+            .with_default_model("deepseek-chat"),
+        );
+        registry.register(
+            RegisteredChatbot::new("xai", "xAI Grok", |model, config| {
+                XaiChatbot::create(
+                    model_or_default(model, config, "xai")?,
+                    config.api_key("xai"),
+                )
+            })
+            .with_api_key()
+            .with_models(&xai::AVAILABLE_MODELS)
+            .with_default_model("grok-3"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "cohere",
+                "Cohere Command",
+                |model, config| {
+                    CohereChatbot::create(
+                        model_or_default(model, config, "cohere")?,
+                        config.api_key("cohere"),
+                    )
+                },
+            )
+            .with_api_key()
+            .with_models(&cohere::AVAILABLE_MODELS)
+            .with_default_model("command-a-03-2025"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "together",
+                "Together AI",
+                |model, config| {
+                    TogetherChatbot::create(
+                        model_or_default(model, config, "together")?,
+                        config.api_key("together"),
+                    )
+                },
+            )
+            .with_api_key()
+            .any_model()
+            .with_default_model("meta-llama/Llama-3.3-70B-Instruct-Turbo"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "perplexity",
+                "Perplexity",
+                |model, config| {
+                    PerplexityChatbot::create(
+                        model_or_default(model, config, "perplexity")?,
+                        config.api_key("perplexity"),
+                    )
+                },
+            )
+            .with_api_key()
+            .with_models(&perplexity::AVAILABLE_MODELS)
+            .with_default_model("sonar"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "github",
+                "GitHub Models",
+                |model, config| {
+                    GitHubChatbot::create(
+                        model_or_default(model, config, "github")?,
+                        config.api_key("github"),
+                    )
+                },
+            )
+            .with_api_key()
+            .with_models(&github::AVAILABLE_MODELS)
+            .with_default_model("openai/gpt-4.1"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "huggingface",
+                "Hugging Face",
+                |model, config| {
+                    HuggingFaceChatbot::create(
+                        model_or_default(model, config, "huggingface")?,
+                        config.api_key("huggingface"),
+                    )
+                },
+            )
+            .with_api_key()
+            .any_model()
+            .with_default_model("meta-llama/Llama-3.3-70B-Instruct"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "replicate",
+                "Replicate",
+                |model, config| {
+                    ReplicateChatbot::create(
+                        model_or_default(model, config, "replicate")?,
+                        config.api_key("replicate"),
+                    )
+                },
+            )
+            .with_api_key()
+            .any_model()
+            .with_default_model("meta/meta-llama-3-70b-instruct"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "ollama",
+                "Local Ollama server",
+                |model, config| {
+                    OllamaChatbot::create(
+                        model_or_default(model, config, "ollama")?,
+                        None,
+                    )
+                },
+            )
+            .any_model()
+            .with_default_model("llama3.2"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "bedrock",
+                "Amazon Bedrock",
+                |model, config| {
+                    BedrockChatbot::from_config(
+                        model_or_default(model, config, "bedrock")?,
+                        config,
+                    )
+                },
+            )
+            .any_model()
+            .with_default_model("anthropic.claude-3-5-sonnet-20240620-v1:0"),
+        );
+        registry.register(
+            RegisteredChatbot::new("dummy", "Dummy", |model, _| {
+                DummyChatbot::create(
+                    model.unwrap_or_else(|| "1".to_owned()),
+                    None,
+                )
+            })
+            .with_models(&dummy::AVAILABLE_MODELS)
+            .with_default_model("1"),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "auto",
+                "Cost-optimizing router",
+                |_, config| AutoChatbot::from_config(config),
+            )
+            .composite(),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "race",
+                "Fastest of several configured chatbots",
+                |_, config| RaceChatbot::from_config(config),
+            )
+            .composite(),
+        );
+        registry.register(
+            RegisteredChatbot::new(
+                "fallback",
+                "First working chatbot of a configured chain",
+                |_, config| FallbackChatbot::from_config(config),
+            )
+            .composite(),
+        );
+        registry
+    }
+
+    /// Adds a chatbot, replacing any registered under the same name.
+    #[inline]
+    pub fn register(&mut self, chatbot: RegisteredChatbot) {
+        match self
+            .chatbots
+            .iter_mut()
+            .find(|registered| registered.name == chatbot.name)
+        {
+            Some(registered) => *registered = chatbot,
+            None => self.chatbots.push(chatbot),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&RegisteredChatbot> {
+        self.chatbots.iter().find(|chatbot| chatbot.name == name)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredChatbot> {
+        self.chatbots.iter()
+    }
+
+    /// Builds the chatbot called `name`, falling back to the `[compat]`
+    /// servers of the config.
+    #[inline]
+    pub fn build(
+        &self,
+        name: &str,
+        model: Option<String>,
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        match self.get(name) {
+            Some(chatbot) => (chatbot.factory)(model, config),
+            None if config.compat(name).is_some() => {
+                CompatChatbot::from_config(name, model, config)
+            }
+            None => Err(ChatbotCreationError::UnknownChatbot),
+        }
+    }
+}
+
+/// The registry of built-in chatbots, shared by the whole program.
+#[inline]
+pub fn global() -> &'static ChatbotRegistry {
+    static REGISTRY: OnceLock<ChatbotRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(ChatbotRegistry::builtin)
+}
+
+fn model_or_default(
+    model: Option<String>,
+    config: &Config,
+    name: &str,
+) -> Result<String, ChatbotCreationError> {
+    model
+        .or_else(|| config.default_models.as_ref()?.get(name).cloned())
+        .ok_or(ChatbotCreationError::UnknownModel)
+}
@@ -0,0 +1,283 @@
+use core::time::Duration;
+use std::env;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    chatbots,
+    config::ChatbotConfig,
+    keys::KeyPool,
+    params::GenerationParams,
+    transport::{self, PollState},
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream, Role,
+};
+
+const REPLICATE_URL: &str = "https://api.replicate.com/v1/models";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const SAMPLING_PARAMS: [&str; 6] = [
+    "temperature",
+    "top_p",
+    "top_k",
+    "max_tokens",
+    "presence_penalty",
+    "frequency_penalty",
+];
+
+fn is_valid_model(model: &str) -> bool {
+    model
+        .split_once('/')
+        .is_some_and(|(owner, name)| !owner.is_empty() && !name.is_empty())
+        && !model.contains(char::is_whitespace)
+}
+
+/// Language models on Replicate take a single prompt, so the conversation is
+/// written out as a transcript ending with the assistant's turn.
+#[derive(Serialize)]
+struct ReplicateInput<'text> {
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_prompt: Option<&'text str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct ReplicateRequest<'text> {
+    input: ReplicateInput<'text>,
+}
+
+#[derive(Deserialize)]
+struct PredictionUrls {
+    get: String,
+}
+
+#[derive(Deserialize)]
+struct Prediction {
+    status: String,
+    output: Option<Value>,
+    urls: PredictionUrls,
+}
+
+impl Prediction {
+    /// Streaming models report their output as a growing list of tokens,
+    /// others as one string once they are done.
+    fn output(&self) -> String {
+        match self.output {
+            Some(Value::String(ref text)) => text.clone(),
+            Some(Value::Array(ref tokens)) => {
+                tokens.iter().filter_map(Value::as_str).collect()
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn state(&self) -> Result<PollState, ChatbotChatError> {
+        match self.status.as_str() {
+            "succeeded" => Ok(PollState::Finished(self.output())),
+            "failed" | "canceled" => Err(ChatbotChatError::UnexpectedResponse),
+            _ => Ok(PollState::Running(self.output())),
+        }
+    }
+}
+
+/// A trailing assistant message is a prefill, which the model continues
+/// after the final `Assistant:`.
+fn transcript(messages: &[Message]) -> String {
+    let (prefill, history) = match messages.split_last() {
+        Some((last, history)) if last.role == Role::Assistant => {
+            (Some(last.content.as_str()), history)
+        }
+        _ => (None, messages),
+    };
+    let mut prompt = String::new();
+    for msg in history {
+        let speaker = match msg.role {
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::System | Role::Note => continue,
+        };
+        prompt.push_str(speaker);
+        prompt.push_str(": ");
+        prompt.push_str(&msg.content);
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Assistant:");
+    if let Some(prefill) = prefill {
+        prompt.push(' ');
+        prompt.push_str(prefill);
+    }
+    prompt
+}
+
+#[non_exhaustive]
+pub struct ReplicateChatbot {
+    keys: KeyPool,
+    model: String,
+    client: Client,
+    extra_body: Option<Value>,
+}
+
+#[async_trait]
+impl Chatbot for ReplicateChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if !is_valid_model(&model) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let api_key = if let Some(api_key) = api_key {
+            api_key
+        } else {
+            env::var("REPLICATE_API_TOKEN")?
+        };
+
+        Ok(Box::new(Self {
+            keys: KeyPool::single(api_key),
+            model,
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Replicate"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        vec![self.model.as_str()]
+    }
+
+    /// Accepts any `owner/name` model id.
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        if !is_valid_model(&new_model) {
+            return Err(InvalidModelError);
+        }
+
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &SAMPLING_PARAMS
+    }
+
+    /// Creates a prediction and polls it until the model is done, streaming
+    /// the tokens as they appear.
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let request_body = ReplicateRequest {
+            input: ReplicateInput {
+                prompt: transcript(messages),
+                system_prompt: messages
+                    .iter()
+                    .find(|msg| msg.role == Role::System)
+                    .map(|msg| msg.content.as_str()),
+                temperature: params.temperature,
+                top_p: params.top_p,
+                top_k: params.top_k,
+                presence_penalty: params.presence_penalty,
+                frequency_penalty: params.frequency_penalty,
+                max_tokens: params.max_tokens,
+            },
+        };
+
+        let url = format!("{REPLICATE_URL}/{}/predictions", self.model);
+        let mut created = None;
+        for (idx, api_key) in self.keys.candidates() {
+            let attempt = chatbots::json_body(
+                self.client.post(&url).bearer_auth(api_key),
+                &request_body,
+                self.extra_body.as_ref(),
+            )
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+            created = Some((attempt, api_key.to_owned()));
+            if !limited {
+                self.keys.mark_working(idx);
+                break;
+            }
+        }
+
+        let (resp, api_key) = created.ok_or(ChatbotChatError::RateLimited)?;
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+        let prediction: Prediction = resp.json().await?;
+
+        let client = self.client.clone();
+        let poll_url = prediction.urls.get;
+        Ok(transport::polling_stream(POLL_INTERVAL, move || {
+            let request = client.get(&poll_url).bearer_auth(&api_key);
+            async move {
+                let resp = request.send().await?;
+                if !resp.status().is_success() {
+                    return Err(ChatbotChatError::from_response(resp).await);
+                }
+                resp.json::<Prediction>().await?.state()
+            }
+        }))
+    }
+}
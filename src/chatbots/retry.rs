@@ -0,0 +1,262 @@
+use core::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt as _};
+
+use crate::{
+    config::ChatbotConfig, params::GenerationParams, ui, Chatbot,
+    ChatbotChatError, ChatbotCreationError, InvalidModelError, Message,
+    Provenance, ResponseStream,
+};
+
+pub const DEFAULT_RETRIES: u32 = 2;
+
+const BASE_DELAY_MS: u64 = 1_000;
+
+const MAX_DELAY_MS: u64 = 30_000;
+
+/// A `Retry-After` longer than this is reported instead of waited out.
+const MAX_RETRY_AFTER: Duration = Duration::from_mins(2);
+
+/// Picks a delay between half and all of the exponential backoff for
+/// `attempt`, so clients that failed together do not retry together. The
+/// clock's nanoseconds are random enough for that.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BASE_DELAY_MS
+        .saturating_mul(2_u64.saturating_pow(attempt))
+        .min(MAX_DELAY_MS);
+    let half = ceiling.checked_div(2).unwrap_or_default();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let jitter = u64::from(nanos)
+        .checked_rem(half.saturating_add(1))
+        .unwrap_or_default();
+    Duration::from_millis(half.saturating_add(jitter))
+}
+
+/// Retries requests that failed with a rate limit, a server error or a
+/// timeout. Only failures before the first streamed item are retried, as
+/// retrying later would repeat text that was already shown.
+#[non_exhaustive]
+pub struct RetryChatbot {
+    inner: Box<dyn Chatbot>,
+    retries: u32,
+}
+
+impl RetryChatbot {
+    #[inline]
+    #[must_use]
+    pub fn wrap(inner: Box<dyn Chatbot>, retries: u32) -> Box<dyn Chatbot> {
+        Box::new(Self { inner, retries })
+    }
+
+    fn delay(&self, err: &ChatbotChatError, attempt: u32) -> Option<Duration> {
+        if attempt >= self.retries {
+            return None;
+        }
+        if !err.is_retryable() {
+            return None;
+        }
+        err.retry_after().map_or_else(
+            || Some(backoff(attempt)),
+            |delay| (delay <= MAX_RETRY_AFTER).then_some(delay),
+        )
+    }
+}
+
+#[async_trait]
+impl Chatbot for RetryChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    #[inline]
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.inner.available_models()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        self.inner.change_model(new_model)
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let mut attempt = 0_u32;
+        loop {
+            let err = match self.inner.send_message(messages, params).await {
+                Ok(mut stream) => match stream.next().await {
+                    Some(Err(err)) => err,
+                    first => {
+                        return Ok(stream::iter(first).chain(stream).boxed())
+                    }
+                },
+                Err(err) => err,
+            };
+
+            let Some(delay) = self.delay(&err, attempt) else {
+                return Err(err);
+            };
+            attempt = attempt.saturating_add(1);
+            ui::notify(&format!(
+                "{err} Retrying in {:.1}s ({attempt}/{}).",
+                delay.as_secs_f64(),
+                self.retries
+            ));
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.list_models().await
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.inner.warm_up().await
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.status().await
+    }
+
+    #[inline]
+    fn escalate(&self) -> bool {
+        self.inner.escalate()
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        self.inner.provenance()
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.inner.key_index()
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        self.inner.supported_params()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        self.inner.configure(chatbot_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{sync::atomic::Ordering, time::Duration};
+
+    use super::RetryChatbot;
+    use crate::{
+        chatbots::scripted::{answer, Reply, ScriptedChatbot},
+        ChatbotChatError, StreamItem,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_transient_failures_until_one_answers() {
+        let (inner, requests) = ScriptedChatbot::boxed(
+            "flaky",
+            vec![
+                Reply::Fail(ChatbotChatError::RateLimited),
+                Reply::Fail(ChatbotChatError::Timeout),
+                Reply::text("Hi"),
+            ],
+        );
+        let chatbot = RetryChatbot::wrap(inner, 2);
+
+        let res = answer(&*chatbot).await;
+
+        assert_eq!(res.ok(), Some("Hi".to_owned()));
+        assert_eq!(requests.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_the_last_retry() {
+        let (inner, requests) = ScriptedChatbot::boxed(
+            "limited",
+            vec![
+                Reply::Fail(ChatbotChatError::RateLimited),
+                Reply::Fail(ChatbotChatError::RateLimited),
+                Reply::text("Too late"),
+            ],
+        );
+        let chatbot = RetryChatbot::wrap(inner, 1);
+
+        let res = answer(&*chatbot).await;
+
+        assert!(matches!(res, Err(ChatbotChatError::RateLimited)));
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_retry_a_refusal() {
+        let (inner, requests) = ScriptedChatbot::boxed(
+            "careful",
+            vec![Reply::Fail(ChatbotChatError::Refused), Reply::text("Hi")],
+        );
+        let chatbot = RetryChatbot::wrap(inner, 2);
+
+        let res = answer(&*chatbot).await;
+
+        assert!(matches!(res, Err(ChatbotChatError::Refused)));
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_retry_once_text_was_streamed() {
+        let (inner, requests) = ScriptedChatbot::boxed(
+            "dropped",
+            vec![
+                Reply::Stream(vec![
+                    (Duration::ZERO, Ok(StreamItem::Text("Hi".to_owned()))),
+                    (Duration::ZERO, Err(ChatbotChatError::Timeout)),
+                ]),
+                Reply::text("Hi again"),
+            ],
+        );
+        let chatbot = RetryChatbot::wrap(inner, 2);
+
+        let res = answer(&*chatbot).await;
+
+        assert!(matches!(res, Err(ChatbotChatError::Timeout)));
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::sync::{Mutex, PoisonError};
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt as _};
+use tokio::time;
+
+use crate::{
+    params::GenerationParams, Chatbot, ChatbotChatError, ChatbotCreationError,
+    InvalidModelError, Message, ResponseStream, StreamItem,
+};
+
+/// How a `ScriptedChatbot` answers one request.
+pub enum Reply {
+    /// Fails before anything is streamed.
+    Fail(ChatbotChatError),
+    /// Streams each item after waiting its delay.
+    Stream(Vec<(Duration, Result<StreamItem, ChatbotChatError>)>),
+}
+
+impl Reply {
+    pub fn text(text: &str) -> Self {
+        Self::Stream(vec![(
+            Duration::ZERO,
+            Ok(StreamItem::Text(text.to_owned())),
+        )])
+    }
+}
+
+/// Answers requests with the replies it was given, in order, and counts
+/// them. Requests past the end of the script fail.
+pub struct ScriptedChatbot {
+    name: &'static str,
+    replies: Mutex<VecDeque<Reply>>,
+    requests: Arc<AtomicUsize>,
+}
+
+impl ScriptedChatbot {
+    /// The chatbot and the number of requests it has been sent so far.
+    pub fn boxed(
+        name: &'static str,
+        replies: Vec<Reply>,
+    ) -> (Box<dyn Chatbot>, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let chatbot = Self {
+            name,
+            replies: Mutex::new(replies.into()),
+            requests: Arc::clone(&requests),
+        };
+        (Box::new(chatbot), requests)
+    }
+}
+
+#[async_trait]
+impl Chatbot for ScriptedChatbot {
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn model(&self) -> &'static str {
+        "scripted"
+    }
+
+    fn available_models(&self) -> Vec<&str> {
+        vec!["scripted"]
+    }
+
+    fn change_model(
+        &mut self,
+        _new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        Err(InvalidModelError)
+    }
+
+    async fn send_message(
+        &self,
+        _messages: &[Message],
+        _params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let reply = self
+            .replies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop_front();
+
+        match reply {
+            Some(Reply::Stream(items)) => Ok(stream::iter(items)
+                .then(|(delay, item)| async move {
+                    time::sleep(delay).await;
+                    item
+                })
+                .boxed()),
+            Some(Reply::Fail(err)) => Err(err),
+            None => Err(ChatbotChatError::UnexpectedResponse),
+        }
+    }
+}
+
+/// Sends `chatbot` a request and returns the text of the answer, or the
+/// first error on the way.
+pub async fn answer(chatbot: &dyn Chatbot) -> Result<String, ChatbotChatError> {
+    let mut stream = chatbot
+        .send_message(&[], GenerationParams::default())
+        .await?;
+    let mut text = String::new();
+    while let Some(item) = stream.next().await {
+        if let StreamItem::Text(chunk) = item? {
+            text.push_str(&chunk);
+        }
+    }
+    Ok(text)
+}
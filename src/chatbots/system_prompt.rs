@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+
+use crate::{
+    config::ChatbotConfig, guardrail, params::GenerationParams, Chatbot,
+    ChatbotChatError, ChatbotCreationError, InvalidModelError, Message,
+    Provenance, ResponseStream,
+};
+
+/// Wraps a chatbot with the `system_prompt` from its `[chatbots.<name>]`
+/// section.
+///
+/// The standing prompt is merged above the session's own system prompt, so
+/// an explicit `--system-prompt` or `/system` still has the last word.
+#[non_exhaustive]
+pub struct SystemPromptChatbot {
+    inner: Box<dyn Chatbot>,
+    system_prompt: String,
+}
+
+impl SystemPromptChatbot {
+    #[inline]
+    #[must_use]
+    pub fn wrap(
+        inner: Box<dyn Chatbot>,
+        system_prompt: String,
+    ) -> Box<dyn Chatbot> {
+        Box::new(Self {
+            inner,
+            system_prompt,
+        })
+    }
+}
+
+#[async_trait]
+impl Chatbot for SystemPromptChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    #[inline]
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.inner.available_models()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        self.inner.change_model(new_model)
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        self.inner
+            .send_message(
+                &guardrail::apply(messages, Some(&self.system_prompt)),
+                params,
+            )
+            .await
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.list_models().await
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.inner.warm_up().await
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.inner.status().await
+    }
+
+    #[inline]
+    fn escalate(&self) -> bool {
+        self.inner.escalate()
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        self.inner.provenance()
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.inner.key_index()
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        self.inner.supported_params()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        self.inner.configure(chatbot_config)
+    }
+}
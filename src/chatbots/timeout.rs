@@ -0,0 +1,228 @@
+use core::time::Duration;
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt as _};
+use tokio::time;
+
+use crate::{
+    config::ChatbotConfig, params::GenerationParams, Chatbot, ChatbotChatError,
+    ChatbotCreationError, InvalidModelError, Message, Provenance,
+    ResponseStream,
+};
+
+/// Slow models may think for a while before the first token, so this is
+/// generous. It is meant to catch dead connections, not slow answers.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_mins(3);
+
+/// Fails a request with `ChatbotChatError::Timeout` when the response does
+/// not start, or the stream stalls, for longer than the timeout.
+#[non_exhaustive]
+pub struct TimeoutChatbot {
+    inner: Box<dyn Chatbot>,
+    timeout: Duration,
+}
+
+impl TimeoutChatbot {
+    #[inline]
+    #[must_use]
+    pub fn wrap(
+        inner: Box<dyn Chatbot>,
+        timeout: Duration,
+    ) -> Box<dyn Chatbot> {
+        Box::new(Self { inner, timeout })
+    }
+}
+
+#[async_trait]
+impl Chatbot for TimeoutChatbot {
+    #[inline]
+    fn create(
+        _model: String,
+        _api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        Err(ChatbotCreationError::MissingConfig)
+    }
+
+    #[inline]
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    #[inline]
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.inner.available_models()
+    }
+
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        self.inner.change_model(new_model)
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        let timeout = self.timeout;
+        let stream =
+            time::timeout(timeout, self.inner.send_message(messages, params))
+                .await
+                .unwrap_or(Err(ChatbotChatError::Timeout))?;
+
+        Ok(stream::unfold(Some(stream), move |stream| async move {
+            let mut stream = stream?;
+            match time::timeout(timeout, stream.next()).await {
+                Ok(Some(item)) => Some((item, Some(stream))),
+                Ok(None) => None,
+                Err(_) => Some((Err(ChatbotChatError::Timeout), None)),
+            }
+        })
+        .boxed())
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        time::timeout(self.timeout, self.inner.list_models())
+            .await
+            .unwrap_or(Err(ChatbotChatError::Timeout))
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.inner.warm_up().await
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        time::timeout(self.timeout, self.inner.status())
+            .await
+            .unwrap_or(Err(ChatbotChatError::Timeout))
+    }
+
+    #[inline]
+    fn escalate(&self) -> bool {
+        self.inner.escalate()
+    }
+
+    #[inline]
+    fn provenance(&self) -> Provenance {
+        self.inner.provenance()
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.inner.key_index()
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        self.inner.supported_params()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        self.inner.configure(chatbot_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use futures::StreamExt as _;
+
+    use super::TimeoutChatbot;
+    use crate::{
+        chatbots::scripted::{answer, Reply, ScriptedChatbot},
+        params::GenerationParams,
+        ChatbotChatError, StreamItem,
+    };
+
+    fn text_after(
+        secs: u64,
+        text: &str,
+    ) -> (Duration, Result<StreamItem, ChatbotChatError>) {
+        (
+            Duration::from_secs(secs),
+            Ok(StreamItem::Text(text.to_owned())),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fails_when_the_answer_never_starts() {
+        let (inner, _) = ScriptedChatbot::boxed(
+            "silent",
+            vec![Reply::Stream(vec![text_after(60, "Hi")])],
+        );
+        let chatbot = TimeoutChatbot::wrap(inner, Duration::from_secs(10));
+
+        let res = answer(&*chatbot).await;
+
+        assert!(matches!(res, Err(ChatbotChatError::Timeout)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ends_the_stream_when_it_stalls() {
+        let (inner, _) = ScriptedChatbot::boxed(
+            "stalling",
+            vec![Reply::Stream(vec![
+                text_after(1, "Hi"),
+                text_after(60, " there"),
+                text_after(1, "!"),
+            ])],
+        );
+        let chatbot = TimeoutChatbot::wrap(inner, Duration::from_secs(10));
+
+        let items = match chatbot
+            .send_message(&[], GenerationParams::default())
+            .await
+        {
+            Ok(stream) => stream.collect::<Vec<_>>().await,
+            Err(err) => vec![Err(err)],
+        };
+
+        assert_eq!(items.len(), 2);
+        assert!(matches!(
+            items.first(),
+            Some(&Ok(StreamItem::Text(ref text))) if text == "Hi"
+        ));
+        assert!(matches!(
+            items.get(1),
+            Some(&Err(ChatbotChatError::Timeout))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_answers_pass_while_tokens_keep_coming() {
+        let (inner, _) = ScriptedChatbot::boxed(
+            "slow",
+            vec![Reply::Stream(vec![
+                text_after(8, "Slow"),
+                text_after(8, " and"),
+                text_after(8, " steady"),
+            ])],
+        );
+        let chatbot = TimeoutChatbot::wrap(inner, Duration::from_secs(10));
+
+        let res = answer(&*chatbot).await;
+
+        assert_eq!(res.ok(), Some("Slow and steady".to_owned()));
+    }
+}
@@ -0,0 +1,229 @@
+use std::{env, sync::OnceLock};
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+use crate::{
+    chatbots::{self, openai},
+    config::ChatbotConfig,
+    keys::KeyPool,
+    params::{self, GenerationParams},
+    Chatbot, ChatbotChatError, ChatbotCreationError, InvalidModelError,
+    Message, ResponseStream,
+};
+
+const TOGETHER_URL: &str = "https://api.together.xyz/v1";
+
+#[derive(Deserialize)]
+struct TogetherModel {
+    id: String,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+fn is_valid_model(model: &str) -> bool {
+    !model.is_empty() && !model.contains(char::is_whitespace)
+}
+
+#[non_exhaustive]
+pub struct TogetherChatbot {
+    keys: KeyPool,
+    model: String,
+    models: OnceLock<Vec<String>>,
+    client: Client,
+    extra_body: Option<serde_json::Value>,
+}
+
+impl TogetherChatbot {
+    /// Fetches the chat models from the catalog once and caches them for
+    /// `available_models`. The catalog also lists image, embedding and
+    /// moderation models, which are left out.
+    async fn discover_models(&self) -> Result<(), ChatbotChatError> {
+        if self.models.get().is_some() {
+            return Ok(());
+        }
+
+        let mut last_err = ChatbotChatError::RateLimited;
+        for (idx, api_key) in self.keys.candidates() {
+            let resp = self
+                .client
+                .get(format!("{TOGETHER_URL}/models"))
+                .bearer_auth(api_key)
+                .send()
+                .await?;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                continue;
+            }
+            match resp.error_for_status() {
+                Ok(resp) => {
+                    self.keys.mark_working(idx);
+                    let models: Vec<TogetherModel> = resp.json().await?;
+                    self.models.get_or_init(|| {
+                        models
+                            .into_iter()
+                            .filter(|model| {
+                                model.kind.as_deref().is_none_or(|kind| {
+                                    matches!(kind, "chat" | "language" | "code")
+                                })
+                            })
+                            .map(|model| model.id)
+                            .collect()
+                    });
+                    return Ok(());
+                }
+                Err(err) => last_err = ChatbotChatError::NetworkError(err),
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
+#[async_trait]
+impl Chatbot for TogetherChatbot {
+    #[inline]
+    fn create(
+        model: String,
+        api_key: Option<String>,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        if !is_valid_model(&model) {
+            return Err(ChatbotCreationError::UnknownModel);
+        }
+
+        let api_key = if let Some(api_key) = api_key {
+            api_key
+        } else {
+            env::var("TOGETHER_API_KEY")?
+        };
+
+        Ok(Box::new(Self {
+            keys: KeyPool::single(api_key),
+            model,
+            models: OnceLock::new(),
+            client: Client::new(),
+            extra_body: None,
+        }))
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Together AI"
+    }
+
+    #[inline]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Lists the live catalog once it has been fetched by a request, the
+    /// current model before that.
+    #[inline]
+    fn available_models(&self) -> Vec<&str> {
+        self.models.get().map_or_else(
+            || vec![self.model.as_str()],
+            |models| models.iter().map(String::as_str).collect(),
+        )
+    }
+
+    /// Accepts any model id, checked against the catalog once it is known.
+    #[inline]
+    fn change_model(
+        &mut self,
+        new_model: String,
+    ) -> Result<(), InvalidModelError> {
+        let known = self
+            .models
+            .get()
+            .is_none_or(|models| models.contains(&new_model));
+        if !is_valid_model(&new_model) || !known {
+            return Err(InvalidModelError);
+        }
+
+        self.model = new_model;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn key_index(&self) -> Option<usize> {
+        self.keys.last_used()
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        if let Some(ref headers) = chatbot_config.headers {
+            self.client = chatbots::client_with_headers(headers)?;
+        }
+        self.keys.configure(chatbot_config);
+        self.extra_body.clone_from(&chatbot_config.extra_body);
+        Ok(())
+    }
+
+    #[inline]
+    fn supported_params(&self) -> &[&str] {
+        &params::NAMES
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        self.discover_models().await
+    }
+
+    #[inline]
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        self.discover_models().await?;
+        Ok(self
+            .available_models()
+            .iter()
+            .map(|&model| model.to_owned())
+            .collect())
+    }
+
+    #[inline]
+    async fn send_message(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<ResponseStream, ChatbotChatError> {
+        // Without the model list the request still goes ahead, it is only
+        // needed for `available_models` and is fetched again next time.
+        self.discover_models().await.unwrap_or_default();
+
+        let url = format!("{TOGETHER_URL}/chat/completions");
+        let mut resp = None;
+        for (idx, api_key) in self.keys.candidates() {
+            let attempt = openai::chat_request(
+                &self.client,
+                &url,
+                &self.model,
+                messages,
+                params,
+                self.extra_body.as_ref(),
+            )
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+            let limited = attempt.status() == StatusCode::TOO_MANY_REQUESTS;
+            resp = Some(attempt);
+            if !limited {
+                self.keys.mark_working(idx);
+                break;
+            }
+        }
+
+        openai::response_stream(resp.ok_or(ChatbotChatError::RateLimited)?)
+            .await
+    }
+}
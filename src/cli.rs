@@ -1,70 +1,456 @@
-use core::fmt::{self, Display, Formatter};
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{
+    builder::PossibleValuesParser, Arg, Command, CommandFactory as _,
+    FromArgMatches as _, Parser, Subcommand,
+};
+
+use crate::{
+    chatbots::registry::{ChatbotRegistry, RegisteredChatbot},
+    embeddings, exit, params,
+};
 
 #[non_exhaustive]
 #[derive(Parser)]
 #[command(author, version, about)]
-#[command(propagate_version = true)]
+#[command(propagate_version = true, after_help = exit::HELP)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = r#"
+        Each bool is an independent command-line flag.
+    "#
+)]
 pub struct Args {
     #[command(subcommand)]
     pub command: Option<ChatbotArg>,
+    /// The chatbot picked by its subcommand or by `compat`.
+    #[arg(skip)]
+    pub chat: Option<ChatSelection>,
     #[arg(short, long, help = "Set the system prompt")]
     pub system_prompt: Option<String>,
     #[arg(long, help = "Disable colored output")]
     pub no_color: bool,
     #[arg(long, help = "Custom config file path", value_name = "FILE")]
     pub config: Option<PathBuf>,
+    #[arg(long, help = "Exit on any configuration error")]
+    pub strict_config: bool,
+    #[arg(
+        long = "set",
+        help = "Override a config value, e.g. default_chatbot=gemini",
+        value_name = "KEY=VALUE"
+    )]
+    pub overrides: Vec<String>,
+    #[arg(
+        long,
+        help = "Also write streamed responses to a file",
+        value_name = "FILE"
+    )]
+    pub tee: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Append prompts and streamed responses with role headers to a \
+                Markdown file, for a live preview",
+        value_name = "FILE",
+        conflicts_with = "tee"
+    )]
+    pub live_md: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Serve an editor plugin with JSON requests and events over \
+                stdin and stdout"
+    )]
+    pub editor_protocol: bool,
+    #[arg(
+        long,
+        help = "Never prompt, disable colors and print errors as JSON lines \
+                on stderr, for scripts and CI"
+    )]
+    pub non_interactive: bool,
+    #[arg(short, long, help = "Answer yes to every confirmation")]
+    pub yes: bool,
+    #[arg(
+        long,
+        help = "Sampling preset, e.g. creative, balanced or precise",
+        value_name = "NAME"
+    )]
+    pub preset: Option<String>,
+    #[arg(
+        long,
+        help = "Sampling temperature, from 0 to 2",
+        value_name = "VALUE",
+        value_parser = |value: &str| params::parse_float("temperature", value)
+    )]
+    pub temperature: Option<f32>,
+    #[arg(
+        long,
+        help = "Nucleus sampling cutoff, from 0 to 1",
+        value_name = "VALUE",
+        value_parser = |value: &str| params::parse_float("top_p", value)
+    )]
+    pub top_p: Option<f32>,
+    #[arg(
+        long,
+        help = "Sample only from this many of the most likely tokens",
+        value_name = "COUNT",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub top_k: Option<u32>,
+    #[arg(
+        long,
+        help = "Maximum tokens in each response",
+        value_name = "COUNT",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub max_tokens: Option<u32>,
+    #[arg(
+        long,
+        help = "Penalty for tokens already used at all, from -2 to 2",
+        value_name = "VALUE",
+        value_parser = |value: &str| {
+            params::parse_float("presence_penalty", value)
+        }
+    )]
+    pub presence_penalty: Option<f32>,
+    #[arg(
+        long,
+        help = "Penalty for tokens by how often they were used, from -2 to 2",
+        value_name = "VALUE",
+        value_parser = |value: &str| {
+            params::parse_float("frequency_penalty", value)
+        }
+    )]
+    pub frequency_penalty: Option<f32>,
+    #[arg(
+        long,
+        help = "Repetition penalty of local and open models, from 1 (none) \
+                to 2",
+        value_name = "VALUE",
+        value_parser = |value: &str| {
+            params::parse_float("repetition_penalty", value)
+        }
+    )]
+    pub repetition_penalty: Option<f32>,
+    #[arg(
+        long,
+        help = "Make each response start with this text, e.g. { for JSON",
+        value_name = "TEXT"
+    )]
+    pub prefill: Option<String>,
+    #[arg(
+        long,
+        help = "Times to retry a request that was rate limited or failed on \
+                the server, 0 to disable [default: 2]",
+        value_name = "COUNT"
+    )]
+    pub retries: Option<u32>,
+    #[arg(
+        long,
+        help = "Seconds to wait for a response to start or continue before \
+                giving up, 0 to wait forever [default: 180]",
+        value_name = "SECONDS"
+    )]
+    pub timeout: Option<u64>,
+    #[arg(
+        long,
+        help = "Save a spoken version of the response (one-shot mode only)",
+        value_name = "FILE"
+    )]
+    pub audio_out: Option<PathBuf>,
+    #[arg(long, help = "Load the model before the first prompt")]
+    pub warm_up: bool,
+    #[arg(long, help = "Use global memories instead of the project's")]
+    pub global_memory: bool,
+    #[arg(
+        long,
+        help = "Answer the prompt over long stdin input chunk by chunk \
+                (one-shot mode only)"
+    )]
+    pub map_reduce: bool,
+    #[arg(
+        long,
+        help = "Check quotes in the response against stdin (one-shot mode \
+                only)"
+    )]
+    pub verify_quotes: bool,
+    #[arg(
+        long,
+        help = "Print the estimated input tokens and cost of the request \
+                and exit without sending it (one-shot mode only)",
+        conflicts_with_all = ["map_reduce", "verify_quotes"],
+        requires = "prompt"
+    )]
+    pub estimate: bool,
+    #[arg(
+        long,
+        help = "Send a JSON array of role/content messages from a file, or \
+                stdin with -, as is and print the reply",
+        value_name = "FILE",
+        conflicts_with_all = [
+            "prompt",
+            "map_reduce",
+            "verify_quotes",
+            "estimate",
+        ]
+    )]
+    pub messages_json: Option<String>,
+    #[arg(
+        long,
+        help = "When done, write the dated action items of the conversation \
+                to an iCalendar file",
+        value_name = "FILE"
+    )]
+    pub ics: Option<PathBuf>,
     #[arg(
         help = "Input prompt (optional, reads from stdin if `-`, no prompt starts interactive mode, only works if default_chatbot and default_model is set in configuration file)"
     )]
     pub prompt: Option<String>,
 }
 
-#[non_exhaustive]
-#[derive(Debug, Clone, ValueEnum)]
-pub enum GeminiModel {
-    #[clap(name = "gemini-2.0-flash-exp")]
-    Flash2_0Exp,
-    #[clap(name = "gemini-1.5-flash")]
-    Flash1_5,
-    #[clap(name = "gemini-1.5-flash-8b")]
-    Flash1_5_8B,
-    #[clap(name = "gemini-1.5-pro")]
-    Pro1_5,
-    #[clap(name = "gemini-1.0-pro")]
-    Pro1,
-}
-
-impl Display for GeminiModel {
+impl Args {
+    /// Parses the command line with a subcommand for every chatbot in
+    /// `registry`, which offers its models for `--model`.
     #[inline]
-    #[expect(
-        clippy::min_ident_chars,
-        reason = r#"
-            `f` is the default parameter name for `Display` trait 
-            implementation.
-        "#
-    )]
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match *self {
-            Self::Flash2_0Exp => write!(f, "gemini-2.0-flash-exp"),
-            Self::Flash1_5 => write!(f, "gemini-1.5-flash"),
-            Self::Flash1_5_8B => write!(f, "gemini-1.5-flash-8b"),
-            Self::Pro1_5 => write!(f, "gemini-1.5-pro"),
-            Self::Pro1 => write!(f, "gemini-1.0-pro"),
-        }
+    #[must_use]
+    pub fn parse_with(registry: &ChatbotRegistry) -> Self {
+        let mut command =
+            registry.iter().fold(Self::command(), |command, chatbot| {
+                command.subcommand(chat_command(chatbot))
+            });
+        let mut matches = command.get_matches_mut();
+
+        let chat = match matches.subcommand_name() {
+            Some(name) if registry.get(name).is_some() => matches
+                .remove_subcommand()
+                .map(|(name, mut sub)| ChatSelection {
+                    name,
+                    model: sub.try_remove_one::<String>("model").ok().flatten(),
+                    compat: false,
+                }),
+            _ => None,
+        };
+        let mut args = Self::from_arg_matches_mut(&mut matches)
+            .unwrap_or_else(|err| err.format(&mut command).exit());
+        args.chat = chat.or_else(|| match args.command {
+            Some(ChatbotArg::Compat {
+                ref name,
+                ref model,
+            }) => Some(ChatSelection {
+                name: name.clone(),
+                model: model.clone(),
+                compat: true,
+            }),
+            _ => None,
+        });
+        args
     }
 }
 
+/// A chatbot picked on the command line, with the model if one was given.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct ChatSelection {
+    pub name: String,
+    pub model: Option<String>,
+    /// Whether `name` is a `[compat.<name>]` server from the config.
+    pub compat: bool,
+}
+
 #[non_exhaustive]
 #[derive(Subcommand)]
 pub enum ChatbotArg {
-    #[command(about = "Chat with the Google Gemini chatbot")]
-    Gemini {
-        #[arg(short, long, value_enum, default_value_t = GeminiModel::Flash1_5)]
-        model: GeminiModel,
+    #[command(about = "Chat with an OpenAI-compatible server from config")]
+    Compat {
+        #[arg(help = "Name of the `[compat.<name>]` config section")]
+        name: String,
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+    #[command(about = "Let configured chatbots converse with each other \
+                 (experimental)")]
+    Agents {
+        #[arg(long, help = "Agents definition file", value_name = "FILE")]
+        config: PathBuf,
+        #[arg(long, help = "Maximum number of agent turns")]
+        turns: Option<usize>,
+    },
+    #[command(about = "Generate images from a text prompt")]
+    Image {
+        prompt: String,
+        #[arg(
+            long,
+            default_value = "imagen-3",
+            help = "Image model to use: imagen-3, imagen-3-fast, gpt-image-1, \
+                    dall-e-3 or dall-e-2"
+        )]
+        model: String,
+        #[arg(long, help = "Output file", value_name = "FILE")]
+        out: Option<PathBuf>,
+        #[arg(long, default_value_t = 1, help = "Number of images")]
+        count: u8,
+        #[arg(
+            long,
+            help = "Aspect ratio, e.g. 1:1 or 16:9, or a size in pixels \
+                    such as 1024x1024 for OpenAI"
+        )]
+        size: Option<String>,
+    },
+    #[command(about = "Print embedding vectors for text as JSON lines")]
+    Embed {
+        #[arg(
+            help = "Files to embed, stdin if omitted or `-`",
+            value_name = "FILE"
+        )]
+        files: Vec<PathBuf>,
+        #[arg(
+            long,
+            default_value = embeddings::DEFAULT_MODEL,
+            help = "Embedding model to use"
+        )]
+        model: String,
+    },
+    #[command(about = "Manage saved sessions")]
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+    #[command(about = "Manage remembered facts")]
+    Memory {
+        #[command(subcommand)]
+        action: MemoryAction,
+    },
+    #[command(about = "Inspect the effective configuration")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    #[command(
+        about = "Answer prompts written to a FIFO in an ongoing session"
+    )]
+    Listen {
+        #[arg(long, help = "FIFO to read prompts from", value_name = "PATH")]
+        fifo: PathBuf,
+        #[arg(
+            long,
+            help = "File or FIFO to write responses to instead of stdout",
+            value_name = "PATH"
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Line that ends each prompt and response, instead of \
+                    one prompt per line",
+            value_name = "LINE"
+        )]
+        delimiter: Option<String>,
+    },
+    #[command(
+        name = "list-models",
+        about = "List the models a chatbot's provider currently serves"
+    )]
+    ListModels {
+        #[arg(help = "Chatbot to ask, the default chatbot if omitted")]
+        chatbot: Option<String>,
+    },
+    #[command(about = "Inspect the audit log")]
+    Audit {
+        #[command(subcommand)]
+        action: AuditAction,
+    },
+    #[cfg(feature = "update-check")]
+    #[command(
+        name = "self-update",
+        about = "Replace this binary with the latest release"
+    )]
+    SelfUpdate,
+}
+
+#[non_exhaustive]
+#[derive(Subcommand)]
+pub enum AuditAction {
+    #[command(about = "Check that no entry was changed or removed")]
+    Verify {
+        #[arg(long, help = "Audit log to check instead of the configured one")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[non_exhaustive]
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    #[command(about = "Show the effective configuration")]
+    Show {
+        #[arg(long, help = "Show where each value comes from")]
+        origin: bool,
+    },
+}
+
+#[non_exhaustive]
+#[derive(Subcommand)]
+pub enum MemoryAction {
+    #[command(about = "List the facts visible in the current project")]
+    List {
+        #[arg(long, help = "Include facts from every project")]
+        all: bool,
+    },
+    #[command(about = "Remember a fact")]
+    Add { fact: String },
+    #[command(about = "Replace the text of a fact")]
+    Edit { id: usize, fact: String },
+    #[command(about = "Forget a fact")]
+    Delete { id: usize },
+}
+
+#[non_exhaustive]
+#[derive(Subcommand)]
+pub enum SessionsAction {
+    #[command(about = "Remove or archive sessions past the retention policy")]
+    Gc {
+        #[arg(long, help = "Only list the sessions that would be removed")]
+        dry_run: bool,
+    },
+    #[command(about = "Compare two sessions message by message")]
+    Diff {
+        #[arg(help = "The session to compare against")]
+        old: String,
+        #[arg(help = "The session to compare")]
+        new: String,
+    },
+    #[command(about = "Show which messages use the most tokens and what \
+                 continuing would cost")]
+    Analyze {
+        name: String,
+        #[arg(long, help = "Print the report as JSON")]
+        json: bool,
+        #[arg(
+            long,
+            default_value_t = 10,
+            help = "Number of heaviest messages to list"
+        )]
+        top: usize,
     },
-    #[command(about = "Chat with the Dummy chatbot")]
-    Dummy,
+}
+
+/// The subcommand that chats with `chatbot`, listed before the others.
+/// Chatbots made of others are configured in full and take no model.
+fn chat_command(chatbot: &RegisteredChatbot) -> Command {
+    let command = Command::new(chatbot.name)
+        .about(chatbot.description)
+        .display_order(0);
+    if chatbot.composite {
+        return command;
+    }
+
+    let mut model = Arg::new("model")
+        .short('m')
+        .long("model")
+        .value_name("MODEL")
+        .help("Model to use");
+    if !chatbot.models.is_empty() {
+        model = model
+            .value_parser(PossibleValuesParser::new(chatbot.models.iter()));
+    }
+    if let Some(default_model) = chatbot.default_model {
+        model = model.default_value(default_model);
+    }
+    command.arg(model)
 }
@@ -1,13 +1,23 @@
-use std::io;
+use std::{fs, io, path::PathBuf, process};
 
 use thiserror::Error;
 
 use crate::{
-    chatbots::{dummy::DummyChatbot, gemini::GeminiChatbot},
+    chatbots::{self, registry},
     config::Config,
-    session::{Session, SessionError},
+    detect,
+    env::{self, SessionEnv},
+    guardrail, index,
+    jobs::{JobStatus, Jobs},
+    memory::{MemoryError, MemoryStore},
+    params, refine,
+    scrub::{ScrubReport, Scrubber},
+    session::{self, Recovery, Session, SessionError},
+    tasks::{self, TaskError},
+    tee::Tee,
+    tmux::{self, TmuxError},
     ui::Printer,
-    Chatbot, ChatbotCreationError, Message, Role,
+    Chatbot, ChatbotChatError, ChatbotCreationError, Message, Role,
 };
 
 #[non_exhaustive]
@@ -25,6 +35,22 @@ pub enum CommandCreationError {
     MissingModelName,
     #[error("Filename is required.")]
     MissingFilename,
+    #[error("Note text is required.")]
+    MissingNote,
+    #[error("Fact text is required.")]
+    MissingFact,
+    #[error("Invalid number.")]
+    InvalidNumber,
+    #[error("Target pane is required.")]
+    MissingPane,
+    #[error("Expected `/env set KEY=value` or `/env unset KEY`.")]
+    InvalidEnv,
+    #[error("Shell command is required.")]
+    MissingShellCommand,
+    #[error("Expected `/set <parameter> <value>`.")]
+    InvalidSet,
+    #[error("Search pattern is required.")]
+    MissingPattern,
 }
 
 #[non_exhaustive]
@@ -35,30 +61,75 @@ pub enum CommandExecuteError {
     #[error("{0}")]
     ChatbotSwitch(#[from] ChatbotCreationError),
     #[error("{0}")]
+    Chatbot(#[from] ChatbotChatError),
+    #[error("{0}")]
     Session(#[from] SessionError),
+    #[error("Failed to open tee file: {0}")]
+    Tee(io::Error),
+    #[error("Failed to write export: {0}")]
+    Export(io::Error),
+    #[error("{0}")]
+    Usage(#[from] CommandCreationError),
+    #[error("{0}")]
+    Memory(#[from] MemoryError),
+    #[error("{0}")]
+    Tmux(#[from] TmuxError),
+    #[error("{0}")]
+    Tasks(#[from] TaskError),
     #[error("User quit.")]
     Quit,
 }
 
-pub struct CommandContext<'parts, 'session, 'chatbot, 'printer, 'config> {
+pub struct CommandContext<
+    'parts,
+    'session,
+    'chatbot,
+    'printer,
+    'config,
+    'tee,
+    'env,
+    'jobs,
+> {
     parts: &'parts [&'parts str],
     session: &'session mut Session,
     chatbot: &'chatbot mut Box<dyn Chatbot>,
     printer: &'printer Printer,
     config: &'config Config,
+    tee: &'tee mut Option<Tee>,
+    env: &'env mut SessionEnv,
+    jobs: &'jobs mut Jobs,
 }
 
-impl<'parts, 'session, 'chatbot, 'printer, 'config>
-    CommandContext<'parts, 'session, 'chatbot, 'printer, 'config>
+impl<'parts, 'session, 'chatbot, 'printer, 'config, 'tee, 'env, 'jobs>
+    CommandContext<
+        'parts,
+        'session,
+        'chatbot,
+        'printer,
+        'config,
+        'tee,
+        'env,
+        'jobs,
+    >
 {
     #[inline]
     #[must_use]
+    #[expect(
+        clippy::too_many_arguments,
+        reason = r#"
+            The context bundles every piece of REPL state a command may
+            touch, so it takes one argument per piece.
+        "#
+    )]
     pub const fn new(
         parts: &'parts [&'parts str],
         session: &'session mut Session,
         chatbot: &'chatbot mut Box<dyn Chatbot>,
         printer: &'printer Printer,
         config: &'config Config,
+        tee: &'tee mut Option<Tee>,
+        env: &'env mut SessionEnv,
+        jobs: &'jobs mut Jobs,
     ) -> Self {
         Self {
             parts,
@@ -66,6 +137,9 @@ impl<'parts, 'session, 'chatbot, 'printer, 'config>
             chatbot,
             printer,
             config,
+            tee,
+            env,
+            jobs,
         }
     }
 }
@@ -73,83 +147,622 @@ impl<'parts, 'session, 'chatbot, 'printer, 'config>
 #[non_exhaustive]
 pub enum Command<'parts> {
     Clear,
-    System { prompt: Message },
-    SwitchChatbot { name: &'parts str },
+    System {
+        prompt: Message,
+    },
+    SwitchChatbot {
+        name: &'parts str,
+    },
     ListChatbots,
-    SwitchModel { name: &'parts str },
+    SwitchModel {
+        name: &'parts str,
+    },
     ListModels,
+    Warm {
+        model: Option<&'parts str>,
+    },
     Info,
-    Save { filename: &'parts str },
-    Load { filename: &'parts str },
-    Delete { filename: &'parts str },
+    Status,
+    Save {
+        args: &'parts [&'parts str],
+    },
+    Load {
+        filename: &'parts str,
+    },
+    Recover {
+        filename: &'parts str,
+    },
+    Delete {
+        filename: &'parts str,
+    },
     Sessions,
+    Grep {
+        pattern: String,
+    },
+    Open {
+        filename: &'parts str,
+        message: usize,
+    },
+    Tee {
+        filename: Option<&'parts str>,
+    },
+    Refine {
+        rounds: Option<usize>,
+    },
+    Escalate,
+    Retry {
+        args: &'parts [&'parts str],
+    },
+    Note {
+        text: String,
+    },
+    History,
+    Export {
+        args: &'parts [&'parts str],
+    },
+    SendToPane {
+        args: &'parts [&'parts str],
+    },
+    Background {
+        args: &'parts [&'parts str],
+    },
+    Jobs {
+        id: Option<usize>,
+    },
+    Preset {
+        name: Option<&'parts str>,
+    },
+    Set {
+        args: &'parts [&'parts str],
+    },
+    Audio {
+        enabled: bool,
+    },
+    Remember {
+        fact: String,
+    },
+    Memories {
+        args: &'parts [&'parts str],
+    },
+    IndexStatus,
+    Tokens,
+    Tasks {
+        save: bool,
+    },
+    Prefill {
+        text: Option<String>,
+    },
+    ListEnv,
+    SetEnv {
+        key: String,
+        value: String,
+    },
+    UnsetEnv {
+        key: &'parts str,
+    },
+    Run {
+        command: String,
+    },
     Help,
     Quit,
 }
 
-impl<'parts> Command<'parts> {
+type ParseFn = for<'parts> fn(
+    &'parts [&'parts str],
+) -> Result<Command<'parts>, CommandCreationError>;
+
+#[expect(
+    clippy::partial_pub_fields,
+    reason = r#"
+        The other fields describe the command for help and completion;
+        parsing goes through `Command::from_parts`.
+    "#
+)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static str,
+    pub description: &'static str,
+    parse: ParseFn,
+}
+
+impl CommandSpec {
     #[inline]
-    pub fn from_parts(
-        parts: &'parts [&str],
-    ) -> Result<Self, CommandCreationError> {
-        let Some(command_name) = parts.first() else {
-            return Err(CommandCreationError::MissingCommand);
-        };
+    #[must_use]
+    pub fn matches(&self, command_name: &str) -> bool {
+        self.name == command_name || self.aliases.contains(&command_name)
+    }
 
-        match *command_name {
-            "/clear" | "/c" => Ok(Self::Clear),
-            "/system" | "/sys" => {
-                if parts.len() > 1 {
-                    #[expect(
-                        clippy::indexing_slicing,
-                        reason = r#"
-                            Safe to index: `/system` command requires at
-                            least one argument, ensuring `parts` has
-                            length >= 2
-                        "#
-                    )]
-                    let new_msg =
-                        Message::new(Role::System, parts[1..].join(" "));
-                    Ok(Self::System { prompt: new_msg })
-                } else {
-                    Err(CommandCreationError::MissingPrompt)
-                }
+    #[inline]
+    #[must_use]
+    pub fn usage(&self) -> String {
+        let with_args = |name: &str| {
+            if self.args.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{name} {}", self.args)
             }
-            "/chatbot" | "/cb" => parts.get(1).map_or(
-                Err(CommandCreationError::MissingChatbotName),
-                |name| Ok(Self::SwitchChatbot { name }),
-            ),
-            "/list_chatbots" | "/lb" => Ok(Command::ListChatbots),
-            "/model" | "/m" => parts
+        };
+
+        let mut usage = with_args(self.name);
+        for alias in self.aliases {
+            usage.push_str(" or ");
+            usage.push_str(&with_args(alias));
+        }
+        usage
+    }
+}
+
+fn join_rest(parts: &[&str]) -> Option<String> {
+    parts
+        .get(1..)
+        .filter(|rest| !rest.is_empty())
+        .map(|rest| rest.join(" "))
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "/clear",
+        aliases: &["/c"],
+        args: "",
+        description: "Clear the conversation history (including system prompt)",
+        parse: |_| Ok(Command::Clear),
+    },
+    CommandSpec {
+        name: "/system",
+        aliases: &["/sys"],
+        args: "<prompt>",
+        description: "Set the system prompt",
+        parse: |parts| {
+            join_rest(parts).map_or(
+                Err(CommandCreationError::MissingPrompt),
+                |prompt| {
+                    Ok(Command::System {
+                        prompt: Message::new(Role::System, prompt),
+                    })
+                },
+            )
+        },
+    },
+    CommandSpec {
+        name: "/chatbot",
+        aliases: &["/cb"],
+        args: "<chatbot>",
+        description: "Change the chatbot",
+        parse: |parts| {
+            parts
+                .get(1)
+                .map_or(Err(CommandCreationError::MissingChatbotName), |name| {
+                    Ok(Command::SwitchChatbot { name })
+                })
+        },
+    },
+    CommandSpec {
+        name: "/list_chatbots",
+        aliases: &["/lb", "/lc"],
+        args: "",
+        description: "List all available chatbots",
+        parse: |_| Ok(Command::ListChatbots),
+    },
+    CommandSpec {
+        name: "/model",
+        aliases: &["/m"],
+        args: "<model>",
+        description: "Change the chatbot model",
+        parse: |parts| {
+            parts
                 .get(1)
                 .map_or(Err(CommandCreationError::MissingModelName), |name| {
-                    Ok(Self::SwitchModel { name })
-                }),
-            "/list_models" | "/lm" => Ok(Self::ListModels),
-            "/info" | "/i" => Ok(Self::Info),
-            "/save" | "/s" => parts.get(1).map_or(
+                    Ok(Command::SwitchModel { name })
+                })
+        },
+    },
+    CommandSpec {
+        name: "/list_models",
+        aliases: &["/lm"],
+        args: "",
+        description: "List all available models for current chatbot",
+        parse: |_| Ok(Command::ListModels),
+    },
+    CommandSpec {
+        name: "/warm",
+        aliases: &["/w"],
+        args: "[model]",
+        description: "Load a model ahead of the next prompt",
+        parse: |parts| {
+            Ok(Command::Warm {
+                model: parts.get(1).copied(),
+            })
+        },
+    },
+    CommandSpec {
+        name: "/info",
+        aliases: &["/i"],
+        args: "",
+        description: "Display current chatbot and model information",
+        parse: |_| Ok(Command::Info),
+    },
+    CommandSpec {
+        name: "/status",
+        aliases: &["/st"],
+        args: "",
+        description: "Display backend status such as loaded models",
+        parse: |_| Ok(Command::Status),
+    },
+    CommandSpec {
+        name: "/save",
+        aliases: &["/s"],
+        args: "[filename] [--force]",
+        description: "Save the session, or update the loaded one",
+        parse: |parts| {
+            Ok(Command::Save {
+                args: parts.get(1..).unwrap_or_default(),
+            })
+        },
+    },
+    CommandSpec {
+        name: "/load",
+        aliases: &["/l"],
+        args: "<filename>",
+        description: "Load a saved session",
+        parse: |parts| {
+            parts.get(1).map_or(
                 Err(CommandCreationError::MissingFilename),
-                |filename| Ok(Self::Save { filename }),
-            ),
-            "/load" | "/l" => parts.get(1).map_or(
+                |filename| Ok(Command::Load { filename }),
+            )
+        },
+    },
+    CommandSpec {
+        name: "/recover",
+        aliases: &[],
+        args: "<filename>",
+        description: "Restore a corrupted session from a backup",
+        parse: |parts| {
+            parts.get(1).map_or(
                 Err(CommandCreationError::MissingFilename),
-                |filename| Ok(Self::Load { filename }),
-            ),
-            "/delete" | "/d" => parts.get(1).map_or(
+                |filename| Ok(Command::Recover { filename }),
+            )
+        },
+    },
+    CommandSpec {
+        name: "/delete",
+        aliases: &["/d"],
+        args: "<filename>",
+        description: "Delete a session",
+        parse: |parts| {
+            parts.get(1).map_or(
                 Err(CommandCreationError::MissingFilename),
-                |filename| Ok(Self::Delete { filename }),
-            ),
-            "/sessions" | "/se" => Ok(Self::Sessions),
-            "/help" | "/h" => Ok(Self::Help),
-            "/quit" | "/q" => Ok(Self::Quit),
+                |filename| Ok(Command::Delete { filename }),
+            )
+        },
+    },
+    CommandSpec {
+        name: "/sessions",
+        aliases: &["/se"],
+        args: "",
+        description: "List all saved sessions",
+        parse: |_| Ok(Command::Sessions),
+    },
+    CommandSpec {
+        name: "/grep",
+        aliases: &[],
+        args: "<pattern>",
+        description: "Search the messages of every saved session",
+        parse: |parts| {
+            join_rest(parts)
+                .map_or(Err(CommandCreationError::MissingPattern), |pattern| {
+                    Ok(Command::Grep { pattern })
+                })
+        },
+    },
+    CommandSpec {
+        name: "/open",
+        aliases: &[],
+        args: "<session> <message>",
+        description: "Load a saved session and show the message numbered \
+                      by /grep",
+        parse: |parts| {
+            let filename = parts
+                .get(1)
+                .copied()
+                .ok_or(CommandCreationError::MissingFilename)?;
+            let message = parts
+                .get(2)
+                .and_then(|message| message.parse().ok())
+                .ok_or(CommandCreationError::InvalidNumber)?;
+            Ok(Command::Open { filename, message })
+        },
+    },
+    CommandSpec {
+        name: "/tee",
+        aliases: &["/t"],
+        args: "[filename]",
+        description: "Write responses to a file, or stop without a filename",
+        parse: |parts| {
+            Ok(Command::Tee {
+                filename: parts.get(1).copied(),
+            })
+        },
+    },
+    CommandSpec {
+        name: "/refine",
+        aliases: &["/rf"],
+        args: "[rounds]",
+        description: "Critique and revise the last answer",
+        parse: |parts| {
+            let rounds = parts
+                .get(1)
+                .map(|rounds| {
+                    rounds
+                        .parse()
+                        .map_or(Err(CommandCreationError::InvalidNumber), Ok)
+                })
+                .transpose()?;
+            Ok(Command::Refine { rounds })
+        },
+    },
+    CommandSpec {
+        name: "/escalate",
+        aliases: &["/e"],
+        args: "",
+        description: "Send the next prompt to the stronger model",
+        parse: |_| Ok(Command::Escalate),
+    },
+    CommandSpec {
+        name: "/retry",
+        aliases: &["/r"],
+        args: "[original | <chatbot> [model]]",
+        description: "Regenerate the last response",
+        parse: |parts| {
+            Ok(Command::Retry {
+                args: parts.get(1..).unwrap_or_default(),
+            })
+        },
+    },
+    CommandSpec {
+        name: "/note",
+        aliases: &["/n"],
+        args: "<text>",
+        description: "Add a note that is never sent to the model",
+        parse: |parts| {
+            join_rest(parts)
+                .map_or(Err(CommandCreationError::MissingNote), |text| {
+                    Ok(Command::Note { text })
+                })
+        },
+    },
+    CommandSpec {
+        name: "/history",
+        aliases: &["/hi"],
+        args: "",
+        description: "Show the messages in this session",
+        parse: |_| Ok(Command::History),
+    },
+    CommandSpec {
+        name: "/export",
+        aliases: &[],
+        args: "<file> [--raw]",
+        description: "Write the conversation as Markdown, with keys, emails \
+                      and hostnames masked unless --raw is given",
+        parse: |parts| {
+            Ok(Command::Export {
+                args: parts.get(1..).unwrap_or_default(),
+            })
+        },
+    },
+    CommandSpec {
+        name: "/send-to-pane",
+        aliases: &[],
+        args: "<target> [--code] [--enter]",
+        description: "Type the last response, or its last code block, into \
+                      a tmux pane",
+        parse: |parts| {
+            if parts.len() > 1 {
+                Ok(Command::SendToPane {
+                    args: parts.get(1..).unwrap_or_default(),
+                })
+            } else {
+                Err(CommandCreationError::MissingPane)
+            }
+        },
+    },
+    CommandSpec {
+        name: "/bg",
+        aliases: &[],
+        args: "<chatbot> <prompt>",
+        description: "Send a prompt to a chatbot in the background",
+        parse: |parts| {
+            if parts.len() > 2 {
+                Ok(Command::Background {
+                    args: parts.get(1..).unwrap_or_default(),
+                })
+            } else {
+                Err(CommandCreationError::MissingPrompt)
+            }
+        },
+    },
+    CommandSpec {
+        name: "/jobs",
+        aliases: &["/j"],
+        args: "[id]",
+        description: "List background jobs, or show the output of one",
+        parse: |parts| {
+            let id = parts
+                .get(1)
+                .map(|id| {
+                    id.parse()
+                        .map_or(Err(CommandCreationError::InvalidNumber), Ok)
+                })
+                .transpose()?;
+            Ok(Command::Jobs { id })
+        },
+    },
+    CommandSpec {
+        name: "/preset",
+        aliases: &["/p"],
+        args: "[name]",
+        description: "Apply a sampling preset, or list the available ones",
+        parse: |parts| {
+            Ok(Command::Preset {
+                name: parts.get(1).copied(),
+            })
+        },
+    },
+    CommandSpec {
+        name: "/set",
+        aliases: &[],
+        args: "[<parameter> <value | default>]",
+        description: "Set a sampling parameter such as temperature, top_k \
+                      or frequency_penalty, or show the current ones. \
+                      `/set footer on` shows the model, latency and cost \
+                      after each response",
+        parse: |parts| match parts.len() {
+            1 | 3 => Ok(Command::Set {
+                args: parts.get(1..).unwrap_or_default(),
+            }),
+            _ => Err(CommandCreationError::InvalidSet),
+        },
+    },
+    CommandSpec {
+        name: "/audio",
+        aliases: &[],
+        args: "<on | off>",
+        description: "Speak each response aloud",
+        parse: |parts| match parts.get(1).copied() {
+            Some("on") => Ok(Command::Audio { enabled: true }),
+            Some("off") => Ok(Command::Audio { enabled: false }),
             _ => Err(CommandCreationError::Invalid),
-        }
+        },
+    },
+    CommandSpec {
+        name: "/remember",
+        aliases: &[],
+        args: "<fact>",
+        description: "Remember a fact across sessions",
+        parse: |parts| {
+            join_rest(parts)
+                .map(|fact| Command::Remember { fact })
+                .ok_or(CommandCreationError::MissingFact)
+        },
+    },
+    CommandSpec {
+        name: "/memories",
+        aliases: &[],
+        args: "[edit <id> <fact> | delete <id>]",
+        description: "List, edit or delete remembered facts",
+        parse: |parts| Ok(Command::Memories { args: parts }),
+    },
+    CommandSpec {
+        name: "/index",
+        aliases: &[],
+        args: "status",
+        description: "Show repository indexing progress and staleness",
+        parse: |parts| match parts.get(1).copied() {
+            None | Some("status") => Ok(Command::IndexStatus),
+            Some(_) => Err(CommandCreationError::Invalid),
+        },
+    },
+    CommandSpec {
+        name: "/tokens",
+        aliases: &[],
+        args: "",
+        description: "Show the tokens used by the last response and the \
+                      session, as reported by the provider",
+        parse: |_| Ok(Command::Tokens),
+    },
+    CommandSpec {
+        name: "/prefill",
+        aliases: &[],
+        args: "[<text>]",
+        description: "Make each response start with text, or stop when \
+                      no text is given",
+        parse: |parts| {
+            Ok(Command::Prefill {
+                text: join_rest(parts),
+            })
+        },
+    },
+    CommandSpec {
+        name: "/tasks",
+        aliases: &[],
+        args: "[save]",
+        description: "Extract action items from the conversation, and \
+                      append them to the TODO file with save",
+        parse: |parts| match parts.get(1).copied() {
+            None => Ok(Command::Tasks { save: false }),
+            Some("save") => Ok(Command::Tasks { save: true }),
+            Some(_) => Err(CommandCreationError::Invalid),
+        },
+    },
+    CommandSpec {
+        name: "/env",
+        aliases: &[],
+        args: "[set KEY=value | unset KEY]",
+        description: "Manage variables passed to /run",
+        parse: |parts| match (parts.get(1).copied(), parts.get(2..)) {
+            (None, _) => Ok(Command::ListEnv),
+            (Some("set"), Some(assignment)) if !assignment.is_empty() => {
+                env::parse_assignment(&assignment.join(" ")).map_or(
+                    Err(CommandCreationError::InvalidEnv),
+                    |(key, value)| Ok(Command::SetEnv { key, value }),
+                )
+            }
+            (Some("unset"), Some(&[key])) => Ok(Command::UnsetEnv { key }),
+            _ => Err(CommandCreationError::InvalidEnv),
+        },
+    },
+    CommandSpec {
+        name: "/run",
+        aliases: &[],
+        args: "<command>",
+        description: "Run a shell command with the variables set by /env",
+        parse: |parts| {
+            join_rest(parts).map_or(
+                Err(CommandCreationError::MissingShellCommand),
+                |command| Ok(Command::Run { command }),
+            )
+        },
+    },
+    CommandSpec {
+        name: "/help",
+        aliases: &["/h"],
+        args: "",
+        description: "List all available commands",
+        parse: |_| Ok(Command::Help),
+    },
+    CommandSpec {
+        name: "/quit",
+        aliases: &["/q"],
+        args: "",
+        description: "Exit the application",
+        parse: |_| Ok(Command::Quit),
+    },
+];
+
+#[inline]
+#[must_use]
+pub fn find(command_name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.matches(command_name))
+}
+
+impl<'parts> Command<'parts> {
+    #[inline]
+    pub fn from_parts(
+        parts: &'parts [&'parts str],
+    ) -> Result<Self, CommandCreationError> {
+        let Some(command_name) = parts.first() else {
+            return Err(CommandCreationError::MissingCommand);
+        };
+
+        find(command_name).map_or(Err(CommandCreationError::Invalid), |spec| {
+            (spec.parse)(parts)
+        })
     }
 
     #[inline]
-    pub fn execute(
+    pub async fn execute(
         self,
-        context: &mut CommandContext<'_, '_, '_, '_, '_>,
+        context: &mut CommandContext<'_, '_, '_, '_, '_, '_, '_, '_>,
     ) -> Result<(), CommandExecuteError> {
         match self {
             Self::Clear => {
@@ -164,169 +777,1028 @@ impl<'parts> Command<'parts> {
                 context.session.messages.insert(0, prompt);
                 context.printer.print_app_message("System prompt set.")?;
             }
-            Self::SwitchChatbot { name } => {
-                let new_chatbot = match name {
-                    "gemini" => GeminiChatbot::create(
-                        context
-                            .config
-                            .default_models
-                            .as_ref()
-                            .and_then(|models| models.gemini.clone())
-                            .ok_or(ChatbotCreationError::UnknownModel)?,
-                        context
-                            .config
-                            .api_keys
-                            .as_ref()
-                            .and_then(|api_keys| api_keys.gemini.clone()),
-                    )?,
-                    "dummy" => DummyChatbot::create("1".to_owned(), None)?,
-                    _ => {
-                        context
-                            .printer
-                            .print_error_message("Invalid chatbot.")?;
-                        return Ok(());
-                    }
-                };
-                *context.chatbot = new_chatbot;
+            Self::SwitchChatbot { name } => context.switch_chatbot(name)?,
+            Self::ListChatbots => context.list_chatbots()?,
+            Self::SwitchModel { name } => context.switch_model(name)?,
+            Self::ListModels => context.list_models().await?,
+            Self::Warm { model } => context.warm(model).await?,
+            Self::Info => context.info()?,
+            Self::Status => context.status().await?,
+            Self::Save { args } => context.save(args)?,
+            Self::Load { filename } => {
+                let mut loaded_session =
+                    Session::load(filename, context.config)?;
+                loaded_session.footer = context.session.footer;
+                *context.session = loaded_session;
+                context.printer.print_app_message(&format!(
+                    "Session loaded from {filename}.json"
+                ))?;
+            }
+            Self::Recover { filename } => context.recover(filename)?,
+            Self::Delete { filename } => {
+                Session::delete(filename, context.config)?;
                 context.printer.print_app_message(&format!(
-                    "Chatbot changed to {}",
-                    context.chatbot.name()
+                    "Session {filename}.json deleted."
                 ))?;
             }
-            Self::ListChatbots => {
-                context.printer.print_app_message("Available chatbots:")?;
+            Self::Sessions => context.sessions()?,
+            Self::Grep { pattern } => context.grep(&pattern)?,
+            Self::Open { filename, message } => {
+                context.open(filename, message)?;
+            }
+            Self::Tee { filename } => context.tee(filename)?,
+
+            Self::Refine { rounds } => context.refine(rounds).await?,
+            Self::Escalate => {
+                if context.chatbot.escalate() {
+                    context.printer.print_app_message(
+                        "The next prompt will use the stronger model.",
+                    )?;
+                } else {
+                    context.printer.print_error_message(
+                        "Current chatbot does not support escalation.",
+                    )?;
+                }
+            }
+            Self::Retry { args } => context.retry(args).await?,
+            Self::Note { text } => {
+                context.session.add_message(Role::Note, text);
+                context.printer.print_app_message("Note added.")?;
+            }
+            Self::History => context.history()?,
+            Self::Export { args } => context.export(args)?,
+            Self::SendToPane { args } => context.send_to_pane(args)?,
+            Self::Background { args } => context.background(args)?,
+            Self::Jobs { id: Some(id) } => context.job(id)?,
+
+            Self::Jobs { id: None } => context.jobs()?,
+            Self::Preset { name: Some(name) } => context.preset(name)?,
+            Self::Preset { name: None } => {
+                context.printer.print_app_message("Available presets:")?;
+                for name in params::preset_names(context.config) {
+                    context.printer.print_app_message(&format!("\t{name}"))?;
+                }
+            }
+            Self::Set {
+                args: &["footer", value],
+            } => context.set_footer(value)?,
+            Self::Set { args } => context.set(args)?,
+            Self::Audio { enabled } => {
+                context.session.audio = enabled;
+                context.printer.print_app_message(if enabled {
+                    "Audio responses on."
+                } else {
+                    "Audio responses off."
+                })?;
+            }
+            Self::Remember { fact } => {
+                let mut store = MemoryStore::open(context.config)?;
+                let id = store.add(fact);
+                store.save()?;
                 context
                     .printer
-                    .print_app_message("\tgemini - Google Gemini")?;
-                context.printer.print_app_message("\tdummy - Dummy")?;
-            }
-            Self::SwitchModel { name } => {
-                match context.chatbot.change_model(name.to_owned()) {
-                    Ok(()) => {
-                        context.printer.print_app_message(&format!(
-                            "Chatbot model changed to {}",
-                            context.chatbot.model()
-                        ))?;
-                    }
-                    Err(err) => {
-                        context
-                            .printer
-                            .print_error_message(&err.to_string())?;
-                    }
+                    .print_app_message(&format!("Remembered as #{id}."))?;
+            }
+            Self::Memories { args } => context.memories(args)?,
+            Self::IndexStatus => context.index_status()?,
+            Self::Tokens => context.tokens()?,
+            Self::Prefill { text } => {
+                context.printer.print_app_message(
+                    &text.as_ref().map_or_else(
+                        || "Prefill cleared.".to_owned(),
+                        |text| format!("Responses will start with: {text}"),
+                    ),
+                )?;
+                context.session.prefill = text;
+            }
+            Self::Tasks { save } => context.tasks(save).await?,
+            Self::ListEnv => {
+                let mut keys = context.env.keys().peekable();
+                if keys.peek().is_none() {
+                    context.printer.print_app_message(
+                        "No session environment variables set.",
+                    )?;
                 }
+                for key in keys {
+                    context.printer.print_app_message(&format!("\t{key}"))?;
+                }
+            }
+            Self::SetEnv { key, value } => {
+                context.printer.print_app_message(&format!("Set {key}."))?;
+                context.env.set(key, value);
             }
-            Self::ListModels => {
-                context.printer.print_app_message("Available models:")?;
-                for model in context.chatbot.available_models() {
-                    context.printer.print_app_message(&format!("\t{model}"))?;
+            Self::UnsetEnv { key } => {
+                if context.env.unset(key) {
+                    context
+                        .printer
+                        .print_app_message(&format!("Unset {key}."))?;
+                } else {
+                    context
+                        .printer
+                        .print_error_message(&format!("{key} is not set."))?;
                 }
             }
-            Self::Info => {
-                context.printer.print_app_message(&format!(
-                    "Current chatbot: {}",
-                    context.chatbot.name()
-                ))?;
-                context.printer.print_app_message(&format!(
-                    "Current model: {}",
-                    context.chatbot.model()
-                ))?;
-                if let &Some(system_msg) = &context
-                    .session
-                    .messages
-                    .iter()
-                    .find(|msg| msg.role == Role::System)
-                {
+            Self::Run { command } => context.run(&command)?,
+            Self::Help => {
+                context.printer.print_app_message("Available commands:")?;
+                for spec in COMMANDS {
                     context.printer.print_app_message(&format!(
-                        "System prompt: {}",
-                        system_msg.content
+                        "\t{} - {}",
+                        spec.usage(),
+                        spec.description
                     ))?;
                 }
             }
-            Self::Save { filename } => {
-                context.session.save(filename, context.config)?;
-                context.printer.print_app_message(&format!(
-                    "Session saved to {filename}.json"
+            Self::Quit => {
+                context.printer.print_app_message("Quitting...")?;
+                return Err(CommandExecuteError::Quit);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CommandContext<'_, '_, '_, '_, '_, '_, '_, '_> {
+    fn background(&mut self, args: &[&str]) -> Result<(), CommandExecuteError> {
+        let (name, prompt) = match args.split_first() {
+            Some((&name, prompt)) => (name, prompt.join(" ")),
+            None => return Err(CommandCreationError::MissingPrompt.into()),
+        };
+        let chatbot = chatbots::create(name, None, self.config)?;
+        let mut messages = self.session.request_messages();
+        messages.push(Message::new(Role::User, prompt));
+        let messages =
+            guardrail::apply(&messages, self.config.guardrail.as_deref())
+                .into_owned();
+
+        let label = format!("{} ({})", chatbot.name(), chatbot.model());
+        let id = self.jobs.spawn(
+            label,
+            chatbot,
+            messages,
+            self.session.params.unwrap_or_default(),
+        );
+        self.printer
+            .print_app_message(&format!("Started job {id}."))?;
+
+        Ok(())
+    }
+
+    fn export(&self, args: &[&str]) -> Result<(), CommandExecuteError> {
+        let raw = args.contains(&"--raw");
+        let filename = args
+            .iter()
+            .find(|arg| !arg.starts_with("--"))
+            .ok_or(CommandCreationError::MissingFilename)?;
+
+        let transcript = self.session.to_markdown(self.chatbot.name());
+        let mut report = ScrubReport::default();
+        let transcript = if raw {
+            transcript
+        } else {
+            Scrubber::from_config(self.config).scrub(&transcript, &mut report)
+        };
+        fs::write(filename, transcript).map_err(CommandExecuteError::Export)?;
+
+        self.printer
+            .print_app_message(&format!("Exported to {filename}."))?;
+        if !raw {
+            let summary = if report.is_empty() {
+                "Nothing needed masking.".to_owned()
+            } else {
+                format!("Masked {}.", report.summary())
+            };
+            self.printer.print_app_message(&summary)?;
+        }
+
+        Ok(())
+    }
+
+    fn grep(&self, pattern: &str) -> Result<(), CommandExecuteError> {
+        let found = Session::search(pattern, self.config)?;
+        if found.is_empty() {
+            self.printer.print_error_message(&format!(
+                "No saved session mentions {pattern}."
+            ))?;
+            return Ok(());
+        }
+        for matches in found {
+            self.printer.print_app_message(&format!(
+                "{} ({})",
+                matches.name,
+                matches.date.as_deref().unwrap_or("unknown date")
+            ))?;
+            for hit in matches.hits {
+                self.printer.print_app_message(&format!(
+                    "\t#{} {}: {}",
+                    hit.number, hit.speaker, hit.snippet
                 ))?;
             }
-            Self::Load { filename } => {
-                let loaded_session = Session::load(filename, context.config)?;
-                *context.session = loaded_session;
-                context.printer.print_app_message(&format!(
-                    "Session loaded from {filename}.json"
+        }
+        self.printer.print_app_message(
+            "Use /open <session> <message> to jump to a match.",
+        )?;
+
+        Ok(())
+    }
+
+    fn history(&self) -> Result<(), CommandExecuteError> {
+        if self.session.messages.is_empty() {
+            self.printer
+                .print_error_message("No messages in this session.")?;
+        }
+        for msg in &self.session.messages {
+            let speaker = match msg.role {
+                Role::System => "System",
+                Role::User => "You",
+                Role::Assistant => msg
+                    .speaker
+                    .as_deref()
+                    .unwrap_or_else(|| self.chatbot.name()),
+                Role::Note => "Note",
+            };
+            self.printer
+                .print_app_message(&format!("{speaker}: {}", msg.content))?;
+        }
+
+        Ok(())
+    }
+
+    fn index_status(&self) -> Result<(), CommandExecuteError> {
+        let Some(status) = index::status() else {
+            self.printer.print_app_message(
+                "Indexing is off, enable it under [index].",
+            )?;
+            return Ok(());
+        };
+        self.printer.print_app_message(&format!(
+            "Indexed {} files ({} chunks) in {}.",
+            status.files,
+            status.chunks,
+            status.root.display()
+        ))?;
+        let freshness = match status.last_scan {
+            _ if status.pending > 0 => {
+                format!("{} files left to index.", status.pending)
+            }
+            Some(age) => {
+                format!("Up to date as of {}s ago.", age.as_secs())
+            }
+            None => "First scan in progress.".to_owned(),
+        };
+        self.printer.print_app_message(&freshness)?;
+        if let Some(ref error) = status.error {
+            self.printer
+                .print_warning_message(&format!("Last scan failed: {error}"))?;
+        }
+
+        Ok(())
+    }
+
+    fn info(&self) -> Result<(), CommandExecuteError> {
+        self.printer.print_app_message(&format!(
+            "Current chatbot: {}",
+            self.chatbot.name()
+        ))?;
+        self.printer.print_app_message(&format!(
+            "Current model: {}",
+            self.chatbot.model()
+        ))?;
+        if let Some(last) = self
+            .session
+            .messages
+            .iter()
+            .rev()
+            .find(|msg| msg.role == Role::Assistant)
+        {
+            if let Some(ref provenance) = last.provenance {
+                self.printer.print_app_message(&format!(
+                    "Last response from: {provenance}"
                 ))?;
             }
-            Self::Delete { filename } => {
-                Session::delete(filename, context.config)?;
-                context.printer.print_app_message(&format!(
-                    "Session {filename}.json deleted."
+            for call in &last.tool_calls {
+                self.printer.print_app_message(&format!(
+                    "Computed by {}: {} => {}",
+                    call.tool, call.input, call.output
                 ))?;
             }
-            Self::Sessions => {
-                let sessions = Session::list_all(context.config)?;
-                if sessions.is_empty() {
-                    context
-                        .printer
-                        .print_error_message("No saved sessions found.")?;
-                } else {
-                    context.printer.print_app_message("Saved sessions:")?;
-                    for elem in sessions {
-                        context
-                            .printer
-                            .print_app_message(&format!("\t{elem}"))?;
-                    }
+        }
+        if let &Some(system_msg) = &self
+            .session
+            .messages
+            .iter()
+            .find(|msg| msg.role == Role::System)
+        {
+            self.printer.print_app_message(&format!(
+                "System prompt: {}",
+                system_msg.content
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn job(&self, id: usize) -> Result<(), CommandExecuteError> {
+        let Some(job) = self.jobs.get(id) else {
+            self.printer
+                .print_error_message(&format!("No job with id {id}."))?;
+            return Ok(());
+        };
+        match job.status {
+            JobStatus::Running => self
+                .printer
+                .print_app_message(&format!("Job {id} is still running.")),
+            JobStatus::Done(ref response) => {
+                self.printer.print_chatbot_message(&job.label, response)
+            }
+            JobStatus::Failed(ref err) => self
+                .printer
+                .print_error_message(&format!("Job {id} failed: {err}")),
+        }?;
+
+        Ok(())
+    }
+
+    fn jobs(&self) -> Result<(), CommandExecuteError> {
+        let mut jobs = self.jobs.iter().peekable();
+        if jobs.peek().is_none() {
+            self.printer.print_app_message("No background jobs.")?;
+        }
+        for job in jobs {
+            let status = match job.status {
+                JobStatus::Running => "running",
+                JobStatus::Done(_) => "done",
+                JobStatus::Failed(_) => "failed",
+            };
+            self.printer.print_app_message(&format!(
+                "\t{} - {} - {status}",
+                job.id, job.label
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn list_chatbots(&self) -> Result<(), CommandExecuteError> {
+        self.printer.print_app_message("Available chatbots:")?;
+        for chatbot in registry::global().iter() {
+            self.printer.print_app_message(&format!(
+                "\t{} - {}",
+                chatbot.name, chatbot.description
+            ))?;
+        }
+        for (name, compat) in self.config.compat.iter().flatten() {
+            let kind = if compat.request_template.is_some() {
+                "Custom provider"
+            } else {
+                "OpenAI-compatible server"
+            };
+            self.printer.print_app_message(&format!(
+                "\t{name} - {kind} at {}",
+                compat.base_url
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    async fn list_models(&self) -> Result<(), CommandExecuteError> {
+        let models = match self.chatbot.list_models().await {
+            Ok(models) => models,
+            Err(err) => {
+                self.printer.print_error_message(&format!(
+                    "Failed to query the provider, showing known \
+                     models: {err}"
+                ))?;
+                self.chatbot
+                    .available_models()
+                    .iter()
+                    .map(|&model| model.to_owned())
+                    .collect()
+            }
+        };
+        self.printer.print_app_message("Available models:")?;
+        for model in models {
+            self.printer.print_app_message(&format!("\t{model}"))?;
+        }
+
+        Ok(())
+    }
+
+    fn memories(&self, args: &[&str]) -> Result<(), CommandExecuteError> {
+        let mut store = MemoryStore::open(self.config)?;
+        let id = || {
+            args.get(2)
+                .and_then(|id| id.parse::<usize>().ok())
+                .ok_or(CommandCreationError::InvalidNumber)
+        };
+        match args.get(1).copied() {
+            None => {
+                if store.memories().is_empty() {
+                    self.printer.print_app_message("No memories stored.")?;
+                }
+                for memory in store.memories() {
+                    self.printer.print_app_message(&format!(
+                        "\t#{} {}",
+                        memory.id, memory.fact
+                    ))?;
                 }
             }
-            Self::Help => {
-                context.printer.print_app_message("Available commands:")?;
-                context.printer.print_app_message(
-                "\t/clear or /c - Clear the conversation history (including system prompt)",
-            )?;
-                context.printer.print_app_message(
-                "\t/system <prompt> or /sys <prompt> - Set the system prompt",
-            )?;
-                context.printer.print_app_message(
-                "\t/chatbot <chatbot> or /cb <chatbot> - Change the chatbot",
-            )?;
-                context.printer.print_app_message(
-                    "\t/list_chatbots or /lc - List all available chatbots",
-                )?;
-                context.printer.print_app_message(
-                    "\t/model <model> or /m <model> - Change the chatbot model",
-                )?;
-                context.printer.print_app_message(
-                "\t/list_models or /lm - List all available models for current chatbot"
-            )?;
-                context.printer.print_app_message(
-                "\t/info or /i - Display current chatbot and model information",
-            )?;
-                context.printer.print_app_message(
-                    "\t/save <filename> or /s <filename> - Save the session",
+            Some("edit") => {
+                let id = id()?;
+                let fact = args
+                    .get(3..)
+                    .filter(|rest| !rest.is_empty())
+                    .map(|rest| rest.join(" "))
+                    .ok_or(CommandCreationError::MissingFact)?;
+                store.edit(id, fact)?;
+                store.save()?;
+                self.printer.print_app_message(&format!("Updated #{id}."))?;
+            }
+            Some("delete") => {
+                let id = id()?;
+                store.remove(id)?;
+                store.save()?;
+                self.printer.print_app_message(&format!("Forgot #{id}."))?;
+            }
+            Some(_) => return Err(CommandCreationError::Invalid.into()),
+        }
+
+        Ok(())
+    }
+
+    fn open(
+        &mut self,
+        filename: &str,
+        message: usize,
+    ) -> Result<(), CommandExecuteError> {
+        let mut loaded_session = Session::load(filename, self.config)?;
+        let Some(msg) = message
+            .checked_sub(1)
+            .and_then(|idx| loaded_session.messages.get(idx))
+        else {
+            self.printer.print_error_message(&format!(
+                "Session {filename} has no message {message}."
+            ))?;
+            return Ok(());
+        };
+        let shown = format!(
+            "#{message} {}: {}",
+            session::speaker(msg, self.chatbot.name()),
+            msg.content
+        );
+        loaded_session.footer = self.session.footer;
+        *self.session = loaded_session;
+        self.printer.print_app_message(&format!(
+            "Session loaded from {filename}.json"
+        ))?;
+        self.printer.print_app_message(&shown)?;
+
+        Ok(())
+    }
+
+    fn preset(&mut self, name: &str) -> Result<(), CommandExecuteError> {
+        let Some(params) = params::preset(name, self.config) else {
+            self.printer
+                .print_error_message(&format!("Unknown preset {name}."))?;
+            return Ok(());
+        };
+        self.session.params = Some(params);
+        self.printer
+            .print_app_message(&format!("Preset {name} applied."))?;
+        if let Some(note) = params.ignored_note(&**self.chatbot) {
+            self.printer.print_warning_message(&note)?;
+        }
+
+        Ok(())
+    }
+
+    fn recover(&mut self, filename: &str) -> Result<(), CommandExecuteError> {
+        let (mut recovered, recovery) =
+            Session::recover(filename, self.config)?;
+        recovered.footer = self.session.footer;
+        *self.session = recovered;
+        self.session.name = Some(filename.to_owned());
+        let message = match recovery {
+            Recovery::Backup(idx) => {
+                format!("Session restored from backup {idx}.")
+            }
+            Recovery::Salvaged(count) => format!(
+                "Salvaged {count} messages from {filename}.json. Save \
+                 to keep them."
+            ),
+        };
+        self.printer.print_app_message(&message)?;
+
+        Ok(())
+    }
+
+    async fn refine(
+        &mut self,
+        rounds: Option<usize>,
+    ) -> Result<(), CommandExecuteError> {
+        let refine_config = self.config.refine.as_ref();
+        let rounds = rounds
+            .or_else(|| refine_config?.rounds)
+            .unwrap_or(refine::DEFAULT_ROUNDS);
+        let show_steps = refine_config
+            .and_then(|refine| refine.show_steps)
+            .unwrap_or(false);
+        let critic = match refine_config
+            .and_then(|refine| refine.critic_chatbot.as_deref())
+        {
+            Some(name) => Some(chatbots::create(
+                name,
+                refine_config.and_then(|refine| refine.critic_model.clone()),
+                self.config,
+            )?),
+            None => None,
+        };
+
+        let Some(request_idx) = self
+            .session
+            .messages
+            .iter()
+            .rposition(|msg| msg.role == Role::User)
+        else {
+            self.printer.print_error_message("Nothing to refine.")?;
+            return Ok(());
+        };
+        let guardrail = self.config.guardrail.as_deref();
+        let params = self.session.params.unwrap_or_default();
+        let history: Vec<Message> = self
+            .session
+            .messages
+            .get(..=request_idx)
+            .unwrap_or_default()
+            .iter()
+            .filter(|msg| msg.role != Role::Note)
+            .cloned()
+            .collect();
+        let request = history.last().map_or("", |msg| msg.content.as_str());
+
+        let mut answer =
+            match self.session.messages.get(request_idx.saturating_add(1)) {
+                Some(msg) if msg.role == Role::Assistant => msg.content.clone(),
+                _ => {
+                    refine::complete(
+                        &**self.chatbot,
+                        &guardrail::apply(&history, guardrail),
+                        params,
+                    )
+                    .await?
+                }
+            };
+
+        for round in 1..=rounds {
+            let critique = refine::complete(
+                critic.as_deref().unwrap_or(&**self.chatbot),
+                &guardrail::apply(
+                    &refine::critique_messages(request, &answer),
+                    guardrail,
+                ),
+                params,
+            )
+            .await?;
+
+            if refine::is_approval(&critique) {
+                if show_steps {
+                    self.printer.print_app_message(&format!(
+                        "Critic approved the answer in round {round}."
+                    ))?;
+                }
+                break;
+            }
+
+            if show_steps {
+                self.printer.print_chatbot_message("Critic", &critique)?;
+            }
+
+            answer = refine::complete(
+                &**self.chatbot,
+                &guardrail::apply(
+                    &refine::revision_messages(&history, &answer, &critique),
+                    guardrail,
+                ),
+                params,
+            )
+            .await?;
+
+            if show_steps && round < rounds {
+                self.printer
+                    .print_chatbot_message(self.chatbot.name(), &answer)?;
+            }
+        }
+
+        self.printer
+            .print_chatbot_message(self.chatbot.name(), &answer)?;
+        self.session
+            .messages
+            .truncate(request_idx.saturating_add(1));
+        self.session.add_message(Role::Assistant, answer);
+
+        Ok(())
+    }
+
+    async fn retry(
+        &mut self,
+        args: &[&str],
+    ) -> Result<(), CommandExecuteError> {
+        let chatbot = args.first().copied();
+        let Some(answer_idx) = self
+            .session
+            .messages
+            .iter()
+            .rposition(|msg| msg.role == Role::Assistant)
+        else {
+            self.printer.print_error_message("Nothing to retry.")?;
+            return Ok(());
+        };
+
+        let fallback = match chatbot {
+            None | Some("original") => None,
+            Some(name) => Some(chatbots::create(
+                name,
+                args.get(1).map(|&model| model.to_owned()),
+                self.config,
+            )?),
+        };
+        let target = fallback.as_deref().unwrap_or(&**self.chatbot);
+
+        if let Some(previous) = self
+            .session
+            .messages
+            .get(answer_idx)
+            .and_then(|msg| msg.provenance.as_ref())
+        {
+            if chatbot.is_none() && previous.chatbot != self.chatbot.name() {
+                self.printer.print_app_message(&format!(
+                    "Last response came from {previous}. Retrying \
+                     against {}; use /retry <chatbot> [model] to \
+                     pick another.",
+                    self.chatbot.name()
+                ))?;
+            }
+        }
+
+        let history: Vec<Message> = self
+            .session
+            .messages
+            .get(..answer_idx)
+            .unwrap_or_default()
+            .iter()
+            .filter(|msg| msg.role != Role::Note)
+            .cloned()
+            .collect();
+        let answer = refine::complete(
+            target,
+            &guardrail::apply(&history, self.config.guardrail.as_deref()),
+            self.session.params.unwrap_or_default(),
+        )
+        .await?;
+
+        self.printer.print_chatbot_message(target.name(), &answer)?;
+        if let Some(msg) = self.session.messages.get_mut(answer_idx) {
+            *msg = Message::new(Role::Assistant, answer)
+                .with_provenance(target.provenance());
+        }
+
+        Ok(())
+    }
+
+    fn run(&self, command: &str) -> Result<(), CommandExecuteError> {
+        let mut shell = process::Command::new("sh");
+        shell.arg("-c").arg(command);
+        self.env.apply(&mut shell);
+        match shell.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => self
+                .printer
+                .print_error_message(&format!("Command failed ({status})."))?,
+            Err(err) => self.printer.print_error_message(&format!(
+                "Failed to run command: {err}."
+            ))?,
+        }
+
+        Ok(())
+    }
+
+    fn save(&mut self, args: &[&str]) -> Result<(), CommandExecuteError> {
+        let force = args.contains(&"--force");
+        let filename = if let Some(&filename) =
+            args.iter().find(|arg| !arg.starts_with("--"))
+        {
+            filename.to_owned()
+        } else if let Some(ref name) = self.session.name {
+            name.clone()
+        } else {
+            return Err(CommandCreationError::MissingFilename.into());
+        };
+
+        let is_update = self.session.name.as_deref() == Some(filename.as_str());
+        if !force
+            && !is_update
+            && Session::exists(&filename, self.config)?
+            && !confirm_overwrite(*self.printer, &filename)?
+        {
+            self.printer.print_app_message(&format!(
+                "Session {filename}.json not saved. Use --force to \
+                 overwrite it."
+            ))?;
+            return Ok(());
+        }
+
+        self.session.save(&filename, self.config)?;
+        self.printer
+            .print_app_message(&format!("Session saved to {filename}.json"))?;
+        self.session.name = Some(filename);
+
+        Ok(())
+    }
+
+    fn send_to_pane(&self, args: &[&str]) -> Result<(), CommandExecuteError> {
+        let code = args.contains(&"--code");
+        let enter = args.contains(&"--enter");
+        let target = args
+            .iter()
+            .find(|arg| !arg.starts_with("--"))
+            .ok_or(CommandCreationError::MissingPane)?;
+
+        let Some(response) = self
+            .session
+            .messages
+            .iter()
+            .rev()
+            .find(|msg| msg.role == Role::Assistant)
+        else {
+            self.printer.print_app_message("No response to send.")?;
+            return Ok(());
+        };
+        let text = if code {
+            let Some(block) = detect::last_code_block(&response.content) else {
+                self.printer.print_app_message(
+                    "The last response has no code block.",
                 )?;
-                context.printer.print_app_message(
-                "\t/load <filename> or /l <filename> - Load a saved session",
+                return Ok(());
+            };
+            block
+        } else {
+            response.content.trim_end()
+        };
+
+        self.printer.print_code_block(text)?;
+        if !self
+            .printer
+            .confirm(&format!("Send this to pane {target}?"))?
+        {
+            self.printer.print_app_message("Not sent.")?;
+            return Ok(());
+        }
+        tmux::send_keys(target, text, enter)?;
+        self.printer
+            .print_app_message(&format!("Sent to pane {target}."))?;
+
+        Ok(())
+    }
+
+    fn sessions(&self) -> Result<(), CommandExecuteError> {
+        let sessions = Session::list_all(self.config)?;
+        if sessions.is_empty() {
+            self.printer
+                .print_error_message("No saved sessions found.")?;
+        } else {
+            self.printer.print_app_message("Saved sessions:")?;
+            for elem in sessions {
+                self.printer.print_app_message(&format!("\t{elem}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, args: &[&str]) -> Result<(), CommandExecuteError> {
+        let mut params = self.session.params.unwrap_or_default();
+        if let [name, value] = *args {
+            if let Err(err) = params.set(name, value) {
+                self.printer.print_error_message(&err.to_string())?;
+                return Ok(());
+            }
+            self.session.params = Some(params);
+        }
+        self.printer
+            .print_app_message(&format!("Sampling: {params}."))?;
+        if let Some(note) = params.ignored_note(&**self.chatbot) {
+            self.printer.print_warning_message(&note)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_footer(&mut self, value: &str) -> Result<(), CommandExecuteError> {
+        self.session.footer = match value {
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.printer.print_error_message("Expected on or off.")?;
+                return Ok(());
+            }
+        };
+        self.printer.print_app_message(if self.session.footer {
+            "Response footer on."
+        } else {
+            "Response footer off."
+        })?;
+
+        Ok(())
+    }
+
+    async fn status(&self) -> Result<(), CommandExecuteError> {
+        self.printer.print_app_message(&format!(
+            "{} ({})",
+            self.chatbot.name(),
+            self.chatbot.model()
+        ))?;
+        let status = self.chatbot.status().await?;
+        if status.is_empty() {
+            self.printer.print_app_message(
+                "No backend status available for this chatbot.",
             )?;
-                context.printer.print_app_message(
-                    "\t/delete <filename> or /d - Delete a session",
-                )?;
-                context.printer.print_app_message(
-                    "\t/sessions or /se - List all saved session",
-                )?;
-                context.printer.print_app_message(
-                    "\t/delete <filename> or /d - Delete a session",
-                )?;
-                context.printer.print_app_message(
-                    "\t/help or /h - List all available commands",
-                )?;
-                context.printer.print_app_message(
-                    "\t/quit or /q - Exit the application",
-                )?;
+        } else {
+            for line in status {
+                self.printer.print_app_message(&format!("\t{line}"))?;
             }
-            Self::Quit => {
-                context.printer.print_app_message("Quitting...")?;
-                return Err(CommandExecuteError::Quit);
+        }
+
+        Ok(())
+    }
+
+    fn switch_chatbot(
+        &mut self,
+        name: &str,
+    ) -> Result<(), CommandExecuteError> {
+        *self.chatbot = chatbots::create(name, None, self.config)?;
+        self.printer.print_app_message(&format!(
+            "Chatbot changed to {}",
+            self.chatbot.name()
+        ))?;
+        if let Some(note) = self
+            .session
+            .params
+            .and_then(|params| params.ignored_note(&**self.chatbot))
+        {
+            self.printer.print_warning_message(&note)?;
+        }
+
+        Ok(())
+    }
+
+    fn switch_model(&mut self, name: &str) -> Result<(), CommandExecuteError> {
+        match self.chatbot.change_model(name.to_owned()) {
+            Ok(()) => {
+                self.printer.print_app_message(&format!(
+                    "Chatbot model changed to {}",
+                    self.chatbot.model()
+                ))?;
+            }
+            Err(err) => {
+                self.printer.print_error_message(&err.to_string())?;
             }
         }
 
         Ok(())
     }
+
+    async fn tasks(&self, save: bool) -> Result<(), CommandExecuteError> {
+        let tasks_config = self.config.tasks.as_ref();
+        let path = tasks_config.and_then(|tasks| tasks.file.as_ref());
+        if save && path.is_none() {
+            return Err(TaskError::NoFile.into());
+        }
+        let history = self.session.request_messages();
+        if !history.iter().any(|msg| msg.role == Role::User) {
+            self.printer
+                .print_error_message("Nothing to extract tasks from.")?;
+            return Ok(());
+        }
+        let history =
+            guardrail::apply(&history, self.config.guardrail.as_deref());
+
+        let items = tasks::extract(&**self.chatbot, &history).await?;
+        if items.is_empty() {
+            self.printer.print_app_message("No action items.")?;
+            return Ok(());
+        }
+        let format = tasks_config
+            .and_then(|tasks| tasks.format)
+            .unwrap_or_default();
+        for item in &items {
+            self.printer
+                .print_app_message(&format!("\t{}", item.to_line(format)))?;
+        }
+        if let Some(path) = path.filter(|_| save) {
+            tasks::append(path, format, &items)?;
+            self.printer.print_app_message(&format!(
+                "Appended {} tasks to {}.",
+                items.len(),
+                path.display()
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn tee(
+        &mut self,
+        filename: Option<&str>,
+    ) -> Result<(), CommandExecuteError> {
+        match filename {
+            Some(filename) => {
+                let tee = Tee::open(PathBuf::from(filename))
+                    .map_err(CommandExecuteError::Tee)?;
+                *self.tee = Some(tee);
+                self.printer.print_app_message(&format!(
+                    "Writing responses to {filename}."
+                ))?;
+            }
+            None => {
+                if let Some(tee) = self.tee.take() {
+                    self.printer.print_app_message(&format!(
+                        "Stopped writing responses to {}.",
+                        tee.path().display()
+                    ))?;
+                } else {
+                    self.printer
+                        .print_error_message("Filename is required.")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Result<(), CommandExecuteError> {
+        let Some(last) = self.session.last_usage else {
+            self.printer.print_app_message(
+                "No token usage reported yet, not every provider \
+                 reports it.",
+            )?;
+            return Ok(());
+        };
+        let total = self.session.total_usage;
+        self.printer.print_app_message(&format!(
+            "Last response: {} prompt + {} completion tokens.",
+            last.prompt_tokens, last.completion_tokens
+        ))?;
+        self.printer.print_app_message(&format!(
+            "Session: {} prompt + {} completion tokens.",
+            total.prompt_tokens, total.completion_tokens
+        ))?;
+        for (&(ref chatbot, key), usage) in &self.session.key_usage {
+            self.printer.print_app_message(&format!(
+                "\t{chatbot} key {}: {} prompt + {} completion tokens.",
+                key.saturating_add(1),
+                usage.prompt_tokens,
+                usage.completion_tokens
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    async fn warm(
+        &self,
+        model: Option<&str>,
+    ) -> Result<(), CommandExecuteError> {
+        // Another model is warmed through its own instance, so the
+        // current one stays selected.
+        let other = match model {
+            Some(model) => {
+                match chatbots::create_for_model(model, self.config) {
+                    Ok(chatbot) => Some(chatbot),
+                    Err(err) => {
+                        self.printer.print_error_message(&err.to_string())?;
+                        return Ok(());
+                    }
+                }
+            }
+            None => None,
+        };
+        let target = other.as_deref().unwrap_or(&**self.chatbot);
+        self.printer
+            .print_app_message(&format!("Warming up {}...", target.model()))?;
+        target.warm_up().await?;
+        self.printer
+            .print_app_message(&format!("{} is ready.", target.model()))?;
+
+        Ok(())
+    }
+}
+
+/// Asks before overwriting an existing session. Without a terminal to ask
+/// on, the answer is always no so scripts have to pass `--force` or `--yes`.
+fn confirm_overwrite(printer: Printer, filename: &str) -> io::Result<bool> {
+    printer.confirm(&format!(
+        "Session {filename}.json already exists. Overwrite?"
+    ))
 }
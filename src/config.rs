@@ -1,9 +1,52 @@
-use std::{env, fs, fs::File, path::PathBuf};
+use alloc::collections::BTreeMap;
+use core::fmt::{self, Display, Formatter};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
 
-use futures::io;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use toml::{de, ser};
+use toml::{de, ser, Table, Value};
+use toml_edit::{DocumentMut, Item, Table as EditTable, TableLike};
+
+use crate::{
+    bindings::{EditMode, KeyAction},
+    chatbots::registry,
+    keys::KeyRotation,
+    params::GenerationParams,
+    tasks::TaskFormat,
+};
+
+const ENV_PREFIX: &str = "LLMCLI_";
+
+const PROJECT_FILE_NAME: &str = ".llmcli.toml";
+
+/// The settings a `.llmcli.toml` may change. A project file comes with the
+/// repository it is in, so anything that could send requests or keys
+/// elsewhere, reach more files, replace the guardrail or turn off the audit
+/// log stays with the user's own config.
+const PROJECT_KEYS: [&str; 17] = [
+    "default_chatbot",
+    "default_models",
+    "detect_repetition",
+    "fence_code",
+    "follow_ups",
+    "footer",
+    "map_reduce",
+    "model_advice",
+    "params",
+    "personas",
+    "preset",
+    "presets",
+    "prices",
+    "refine",
+    "retries",
+    "suggestions",
+    "timeout",
+];
 
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -16,64 +59,684 @@ pub enum ConfigError {
     Ser(#[from] ser::Error),
     #[error("Config directory not found.")]
     NotFound,
+    #[error("Invalid config override `{0}`, expected KEY=VALUE.")]
+    InvalidOverride(String),
+    #[error("{0}")]
+    Edit(#[from] toml_edit::TomlError),
+}
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct ConfigWarning {
+    pub origin: Option<ConfigOrigin>,
+    pub section: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for ConfigWarning {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid config")?;
+        if let Some(ref section) = self.section {
+            write!(f, " section `{section}`")?;
+        }
+        if let Some(ref origin) = self.origin {
+            write!(f, " in {origin}")?;
+        }
+        if let Some(line) = self.line {
+            write!(f, " at line {line}")?;
+        }
+        write!(f, ": {}. ", self.message.trim_end_matches('.'))?;
+        if self.section.is_some() {
+            write!(f, "The section is ignored.")
+        } else {
+            write!(f, "Using defaults.")
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize, Default)]
+pub struct ChatbotConfig {
+    pub base_url: Option<String>,
+    pub headers: Option<BTreeMap<String, String>>,
+    pub api_keys: Option<Vec<String>>,
+    pub key_rotation: Option<KeyRotation>,
+    pub system_prompt: Option<String>,
+    pub extra_body: Option<serde_json::Value>,
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct CompatConfig {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub api_key_env: Option<String>,
+    pub auth_header: Option<String>,
+    #[serde(default)]
+    pub models: Vec<String>,
+    pub default_model: Option<String>,
+    pub request_template: Option<serde_json::Value>,
+    pub response_pointer: Option<String>,
+    pub done_pointer: Option<String>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct ScrubConfig {
+    pub keys: Option<bool>,
+    pub emails: Option<bool>,
+    pub hosts: Option<bool>,
+    pub terms: Option<Vec<String>>,
+}
+
+/// Hash-chained log of every request for compliance. Only digests are
+/// written unless `content` is set. The chain is keyed with `key`, or the
+/// `LLMCLI_AUDIT_KEY` environment variable.
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct AuditConfig {
+    pub enabled: Option<bool>,
+    pub path: Option<PathBuf>,
+    pub content: Option<bool>,
+    pub key: Option<String>,
+}
+
+/// Where `/tasks save` appends action items.
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct TasksConfig {
+    pub file: Option<PathBuf>,
+    pub format: Option<TaskFormat>,
+}
+
+/// Opt-in check for new releases, only used when built with the
+/// `update-check` feature.
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct UpdateConfig {
+    pub check: Option<bool>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct IndexConfig {
+    pub enabled: Option<bool>,
+    pub interval_secs: Option<u64>,
+    pub max_file_bytes: Option<u64>,
+    pub results: Option<usize>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct MapReduceConfig {
+    pub chunk_chars: Option<usize>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct PersonaConfig {
+    pub chatbot: String,
+    pub model: Option<String>,
+    pub system_prompt: Option<String>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct BedrockConfig {
+    pub region: Option<String>,
+    pub profile: Option<String>,
+}
+
+/// Routes the Gemini backend through Vertex AI instead of the public API.
+/// `grpc` streams answers over gRPC rather than server-sent events and
+/// needs a build with the `grpc` feature.
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct VertexConfig {
+    pub project: String,
+    pub location: Option<String>,
+    pub credentials: Option<PathBuf>,
+    pub grpc: Option<bool>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize, Default)]
+pub struct MemoryConfig {
+    pub enabled: Option<bool>,
+    pub path: Option<PathBuf>,
+    pub max_injected: Option<usize>,
+    pub global: Option<bool>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct ToolsConfig {
+    pub enabled: Option<Vec<String>>,
+    pub max_rounds: Option<usize>,
+    pub allowed_roots: Option<Vec<PathBuf>>,
+    pub auto_approve: Option<Vec<String>>,
 }
 
 #[non_exhaustive]
 #[derive(Deserialize, Serialize)]
-pub struct ApiKeys {
-    pub gemini: Option<String>,
+pub struct FollowUpConfig {
+    pub enabled: Option<bool>,
+    pub chatbot: Option<String>,
+    pub model: Option<String>,
+    pub count: Option<usize>,
 }
 
 #[non_exhaustive]
 #[derive(Deserialize, Serialize)]
-pub struct DefaultModels {
-    pub gemini: Option<String>,
+pub struct AudioConfig {
+    pub model: Option<String>,
+    pub voice: Option<String>,
+    pub player: Option<String>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct TerminalConfig {
+    pub title: Option<bool>,
+    pub notifications: Option<bool>,
+    pub progress: Option<bool>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct SessionsConfig {
+    pub max_sessions: Option<usize>,
+    pub max_age_days: Option<u64>,
+    pub archive_path: Option<PathBuf>,
+    pub backups: Option<usize>,
+    pub gc_on_startup: Option<bool>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct RefineConfig {
+    pub rounds: Option<usize>,
+    pub show_steps: Option<bool>,
+    pub critic_chatbot: Option<String>,
+    pub critic_model: Option<String>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct RouterConfig {
+    pub cheap_chatbot: String,
+    pub cheap_model: Option<String>,
+    pub strong_chatbot: String,
+    pub strong_model: Option<String>,
+    pub max_prompt_chars: Option<usize>,
+    pub escalate_on_code: Option<bool>,
+    pub log: Option<bool>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct RaceContender {
+    pub chatbot: String,
+    pub model: Option<String>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct RaceConfig {
+    pub contenders: Vec<RaceContender>,
+    pub log: Option<bool>,
+}
+
+#[non_exhaustive]
+#[derive(Deserialize, Serialize)]
+pub struct FallbackConfig {
+    pub chain: Vec<RaceContender>,
+    pub log: Option<bool>,
 }
 
 #[non_exhaustive]
 #[derive(Deserialize, Serialize, Default)]
 pub struct Config {
     pub default_chatbot: Option<String>,
-    pub default_models: Option<DefaultModels>,
-    pub api_keys: Option<ApiKeys>,
+    /// Default models keyed by chatbot name.
+    pub default_models: Option<BTreeMap<String, String>>,
+    /// API keys keyed by chatbot name.
+    pub api_keys: Option<BTreeMap<String, String>>,
     pub session_path: Option<PathBuf>,
+    pub sessions: Option<SessionsConfig>,
     pub history_path: Option<PathBuf>,
+    pub refine: Option<RefineConfig>,
+    pub guardrail: Option<String>,
+    pub fence_code: Option<bool>,
+    pub detect_repetition: Option<bool>,
+    pub footer: Option<bool>,
+    pub model_advice: Option<bool>,
+    pub suggestions: Option<bool>,
+    pub terminal: Option<TerminalConfig>,
+    pub audio: Option<AudioConfig>,
+    pub follow_ups: Option<FollowUpConfig>,
+    pub tools: Option<ToolsConfig>,
+    pub memory: Option<MemoryConfig>,
+    pub map_reduce: Option<MapReduceConfig>,
+    pub index: Option<IndexConfig>,
+    pub scrub: Option<ScrubConfig>,
+    pub edit_mode: Option<EditMode>,
+    pub preset: Option<String>,
+    pub presets: Option<BTreeMap<String, GenerationParams>>,
+    pub params: Option<GenerationParams>,
+    pub keybindings: Option<BTreeMap<String, KeyAction>>,
+    pub router: Option<RouterConfig>,
+    pub race: Option<RaceConfig>,
+    pub fallback: Option<FallbackConfig>,
+    pub retries: Option<u32>,
+    pub timeout: Option<u64>,
+    pub chatbots: Option<BTreeMap<String, ChatbotConfig>>,
+    pub compat: Option<BTreeMap<String, CompatConfig>>,
+    pub bedrock: Option<BedrockConfig>,
+    pub vertex: Option<VertexConfig>,
+    pub personas: Option<BTreeMap<String, PersonaConfig>>,
+    pub env: Option<BTreeMap<String, String>>,
+    pub otlp_endpoint: Option<String>,
+    pub update: Option<UpdateConfig>,
+    pub audit: Option<AuditConfig>,
+    pub prices: Option<BTreeMap<String, f64>>,
+    pub tasks: Option<TasksConfig>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    System(PathBuf),
+    User(PathBuf),
+    Project(PathBuf),
+    Env(String),
+    Flag,
+}
+
+impl Display for ConfigOrigin {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::System(ref path) => write!(f, "system ({})", path.display()),
+            Self::User(ref path) => write!(f, "user ({})", path.display()),
+            Self::Project(ref path) => {
+                write!(f, "project ({})", path.display())
+            }
+            Self::Env(ref var) => write!(f, "env ({var})"),
+            Self::Flag => write!(f, "flag (--set)"),
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Default)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub warnings: Vec<ConfigWarning>,
+    pub origins: BTreeMap<String, ConfigOrigin>,
+    pub effective: Table,
+}
+
+impl LoadedConfig {
+    #[inline]
+    #[must_use]
+    pub fn entries(&self) -> Vec<(String, String, Option<&ConfigOrigin>)> {
+        let mut values = Vec::new();
+        flatten(&self.effective, "", &mut values);
+
+        values
+            .into_iter()
+            .map(|(key, value)| {
+                let value = if is_secret(&key) {
+                    "\"********\"".to_owned()
+                } else {
+                    value.to_string()
+                };
+                let origin = self.origins.get(&key);
+                (key, value, origin)
+            })
+            .collect()
+    }
 }
 
 impl Config {
     #[inline]
-    pub fn load(cli_path: Option<PathBuf>) -> Result<Self, ConfigError> {
-        let config_path = match Self::get_file_path(cli_path) {
-            Ok(path) => path,
-            Err(ConfigError::NotFound) => {
-                return Ok(Self::default());
+    pub fn load(
+        cli_path: Option<PathBuf>,
+        overrides: &[String],
+        strict: bool,
+    ) -> Result<LoadedConfig, ConfigError> {
+        let mut loaded = LoadedConfig::default();
+        let mut sources = Vec::new();
+
+        for origin in Self::file_layers(cli_path)? {
+            let (ConfigOrigin::System(ref path)
+            | ConfigOrigin::User(ref path)
+            | ConfigOrigin::Project(ref path)) = origin
+            else {
+                continue;
+            };
+
+            if !path.exists() {
+                continue;
             }
-            Err(err) => {
-                return Err(err);
+
+            let config_str = fs::read_to_string(path)?;
+
+            match config_str.parse::<Table>() {
+                Ok(mut table) => {
+                    if matches!(origin, ConfigOrigin::Project(_)) {
+                        for key in restrict_to_project_keys(&mut table) {
+                            loaded.warnings.push(ConfigWarning {
+                                origin: Some(origin.clone()),
+                                line: config_str
+                                    .lines()
+                                    .position(|line| defines_key(line, &key))
+                                    .map(|idx| idx.saturating_add(1)),
+                                section: Some(key),
+                                message: "only the user config can set it"
+                                    .to_owned(),
+                            });
+                        }
+                    }
+                    merge(
+                        &mut loaded.effective,
+                        table,
+                        "",
+                        &origin,
+                        &mut loaded.origins,
+                    );
+                }
+                Err(err) if strict => return Err(err.into()),
+                Err(err) => loaded.warnings.push(ConfigWarning {
+                    origin: Some(origin.clone()),
+                    section: None,
+                    line: err
+                        .span()
+                        .map(|span| line_at(&config_str, span.start)),
+                    message: err.message().to_owned(),
+                }),
             }
-        };
 
-        if !config_path.exists() {
-            return Ok(Self::default());
+            sources.push((origin, config_str));
+        }
+
+        // Variables that are not valid UTF-8 cannot be ours, and reading
+        // them as strings would panic.
+        let mut env_vars: Vec<(String, String)> = env::vars_os()
+            .filter_map(|(var, value)| {
+                let var = var.into_string().ok().filter(|var| {
+                    var.starts_with(ENV_PREFIX) && var != "LLMCLI_CONFIG_PATH"
+                })?;
+                Some((var, value.into_string().ok()?))
+            })
+            .collect();
+        env_vars.sort();
+
+        for (var, value) in env_vars {
+            let key = var
+                .trim_start_matches(ENV_PREFIX)
+                .to_lowercase()
+                .replace("__", ".");
+            let mut layer = Table::new();
+            if insert_path(&mut layer, &key, parse_value(&value)) {
+                merge(
+                    &mut loaded.effective,
+                    layer,
+                    "",
+                    &ConfigOrigin::Env(var),
+                    &mut loaded.origins,
+                );
+            }
+        }
+
+        for entry in overrides {
+            let mut layer = Table::new();
+            let inserted = entry.split_once('=').is_some_and(|(key, value)| {
+                insert_path(&mut layer, key.trim(), parse_value(value.trim()))
+            });
+
+            if inserted {
+                merge(
+                    &mut loaded.effective,
+                    layer,
+                    "",
+                    &ConfigOrigin::Flag,
+                    &mut loaded.origins,
+                );
+            } else if strict {
+                return Err(ConfigError::InvalidOverride(entry.clone()));
+            } else {
+                loaded.warnings.push(ConfigWarning {
+                    origin: Some(ConfigOrigin::Flag),
+                    section: None,
+                    line: None,
+                    message: format!("expected KEY=VALUE, got `{entry}`"),
+                });
+            }
+        }
+
+        match Value::Table(loaded.effective.clone()).try_into::<Self>() {
+            Ok(config) => loaded.config = config,
+            Err(err) if strict => return Err(err.into()),
+            Err(_) => Self::parse_lenient(&mut loaded, &sources),
         }
 
-        let config_str = fs::read_to_string(config_path)?;
+        Ok(loaded)
+    }
 
-        if config_str.trim().is_empty() {
-            return Ok(Self::default());
+    #[inline]
+    #[must_use]
+    pub fn compat(&self, name: &str) -> Option<&CompatConfig> {
+        self.compat.as_ref()?.get(name)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn persona(&self, name: &str) -> Option<&PersonaConfig> {
+        self.personas.as_ref()?.get(name)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn chatbot(&self, name: &str) -> Option<&ChatbotConfig> {
+        self.chatbots.as_ref()?.get(name)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn api_key(&self, name: &str) -> Option<String> {
+        let api_key = self
+            .api_keys
+            .as_ref()
+            .and_then(|api_keys| api_keys.get(name).cloned());
+
+        api_key
+            .or_else(|| self.chatbot(name)?.api_keys.as_ref()?.first().cloned())
+    }
+
+    /// Every API key and header value in the config, for redaction.
+    #[inline]
+    #[must_use]
+    pub fn secrets(&self) -> Vec<String> {
+        let mut secrets: Vec<String> = self
+            .api_keys
+            .iter()
+            .flat_map(BTreeMap::values)
+            .cloned()
+            .collect();
+        secrets.extend(
+            self.compat
+                .iter()
+                .flat_map(BTreeMap::values)
+                .filter_map(|compat| compat.api_key.clone()),
+        );
+        secrets.extend(self.audit.as_ref().and_then(|audit| audit.key.clone()));
+        for chatbot in self.chatbots.iter().flat_map(BTreeMap::values) {
+            secrets.extend(chatbot.api_keys.iter().flatten().cloned());
+            secrets.extend(
+                chatbot.headers.iter().flat_map(BTreeMap::values).cloned(),
+            );
         }
+        secrets
+    }
 
-        Ok(toml::from_str(&config_str)?)
+    /// Sets the API key of the chatbot called `name`, returning whether it
+    /// is a registered chatbot that reads one from `[api_keys]`.
+    #[inline]
+    pub fn set_api_key(&mut self, name: &str, api_key: String) -> bool {
+        if !registry::global()
+            .get(name)
+            .is_some_and(|chatbot| chatbot.api_key)
+        {
+            return false;
+        }
+        self.api_keys
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.to_owned(), api_key);
+        true
+    }
+
+    /// Sets `key`, such as `api_keys.openai`, in the user config file and
+    /// leaves the rest of it, comments included, as it was.
+    #[inline]
+    pub fn persist_value(
+        cli_path: Option<PathBuf>,
+        key: &str,
+        value: String,
+    ) -> Result<(), ConfigError> {
+        let config_path = Self::get_file_path(cli_path)?;
+        let mut document =
+            fs::read_to_string(&config_path)?.parse::<DocumentMut>()?;
+
+        if !edit_path(document.as_table_mut(), key, value) {
+            return Err(ConfigError::InvalidOverride(key.to_owned()));
+        }
+
+        write_private(&config_path, &document.to_string())?;
+        Ok(())
     }
 
     #[inline]
     pub fn save(&self, cli_path: Option<PathBuf>) -> Result<(), ConfigError> {
         let config_path = Self::get_file_path(cli_path)?;
         let config_str = toml::to_string(self)?;
-        fs::write(config_path, config_str)?;
+        write_private(&config_path, &config_str)?;
         Ok(())
     }
 
+    fn parse_lenient(
+        loaded: &mut LoadedConfig,
+        sources: &[(ConfigOrigin, String)],
+    ) {
+        let mut table = loaded.effective.clone();
+        let keys: Vec<String> = table.keys().cloned().collect();
+
+        for key in keys {
+            let Some(value) = table.remove(&key) else {
+                continue;
+            };
+
+            let mut section = Table::new();
+            section.insert(key.clone(), value.clone());
+
+            match Value::Table(section).try_into::<Self>() {
+                Ok(_) => {
+                    table.insert(key, value);
+                }
+                Err(err) => {
+                    let prefix = format!("{key}.");
+                    let origin = loaded
+                        .origins
+                        .iter()
+                        .find(|&(path, _)| {
+                            *path == key || path.starts_with(&prefix)
+                        })
+                        .map(|(_, origin)| origin.clone());
+                    let line = sources
+                        .iter()
+                        .find(|source| Some(&source.0) == origin.as_ref())
+                        .and_then(|source| {
+                            source
+                                .1
+                                .lines()
+                                .position(|line| defines_key(line, &key))
+                        })
+                        .map(|idx| idx.saturating_add(1));
+
+                    loaded.warnings.push(ConfigWarning {
+                        origin,
+                        section: Some(key),
+                        line,
+                        message: err.message().to_owned(),
+                    });
+                }
+            }
+        }
+
+        loaded.config = Value::Table(table).try_into().unwrap_or_default();
+    }
+
+    fn file_layers(
+        cli_path: Option<PathBuf>,
+    ) -> Result<Vec<ConfigOrigin>, ConfigError> {
+        let mut layers = Vec::new();
+
+        if cfg!(unix) {
+            layers.push(ConfigOrigin::System(PathBuf::from(
+                "/etc/llmcli/config.toml",
+            )));
+        }
+
+        match Self::get_file_path(cli_path) {
+            Ok(path) => layers.push(ConfigOrigin::User(path)),
+            Err(ConfigError::NotFound) => {}
+            Err(err) => return Err(err),
+        }
+
+        if let Some(path) = Self::find_project_file() {
+            layers.push(ConfigOrigin::Project(path));
+        }
+
+        Ok(layers)
+    }
+
+    /// The directory of the nearest `.llmcli.toml`, or else the enclosing
+    /// git repository.
+    #[inline]
+    #[must_use]
+    pub fn project_root() -> Option<PathBuf> {
+        let current_dir = env::current_dir().ok()?;
+
+        current_dir
+            .ancestors()
+            .find(|dir| {
+                dir.join(PROJECT_FILE_NAME).is_file()
+                    || dir.join(".git").exists()
+            })
+            .map(Path::to_path_buf)
+    }
+
+    fn find_project_file() -> Option<PathBuf> {
+        let current_dir = env::current_dir().ok()?;
+
+        for dir in current_dir.ancestors() {
+            let candidate = dir.join(PROJECT_FILE_NAME);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+
+        None
+    }
+
     fn get_file_path(
         cli_path: Option<PathBuf>,
     ) -> Result<PathBuf, ConfigError> {
@@ -93,7 +756,7 @@ impl Config {
                 }
             }
             if !config_path.exists() {
-                File::create(&config_path)?;
+                write_private(&config_path, "")?;
             }
             return Ok(config_path);
         }
@@ -101,3 +764,300 @@ impl Config {
         Err(ConfigError::NotFound)
     }
 }
+
+fn line_at(text: &str, offset: usize) -> usize {
+    text.get(..offset)
+        .map_or(1, |prefix| prefix.matches('\n').count().saturating_add(1))
+}
+
+fn defines_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start();
+
+    line.strip_prefix('[').map_or_else(
+        || {
+            line.strip_prefix(key).is_some_and(|rest| {
+                rest.trim_start().starts_with('=') || rest.starts_with('.')
+            })
+        },
+        |header| {
+            header
+                .trim_start_matches('[')
+                .trim_start()
+                .strip_prefix(key)
+                .is_some_and(|rest| {
+                    rest.starts_with(']') || rest.starts_with('.')
+                })
+        },
+    )
+}
+
+/// Removes the settings a project config may not change from `table` and
+/// returns their keys.
+fn restrict_to_project_keys(table: &mut Table) -> Vec<String> {
+    let removed: Vec<String> = table
+        .keys()
+        .filter(|key| !PROJECT_KEYS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    for key in &removed {
+        table.remove(key);
+    }
+    removed
+}
+
+fn merge(
+    target: &mut Table,
+    source: Table,
+    prefix: &str,
+    origin: &ConfigOrigin,
+    origins: &mut BTreeMap<String, ConfigOrigin>,
+) {
+    for (key, value) in source {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match (value, target.get_mut(&key)) {
+            (
+                Value::Table(incoming),
+                Some(&mut Value::Table(ref mut existing)),
+            ) => {
+                merge(existing, incoming, &path, origin, origins);
+            }
+            (value, _) => {
+                let nested = format!("{path}.");
+                origins.retain(|existing, _| {
+                    *existing != path && !existing.starts_with(&nested)
+                });
+                record_origins(&path, &value, origin, origins);
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Whether the value at the dotted `path` may hold a credential: anything
+/// under `api_keys` or `headers`, and any key naming a key, token, secret
+/// or password.
+fn is_secret(path: &str) -> bool {
+    let mut segments = path.split('.');
+    let last = segments.next_back().unwrap_or_default();
+    segments.any(|segment| segment == "api_keys" || segment == "headers")
+        || last.split('_').any(|word| {
+            matches!(
+                word,
+                "key" | "keys" | "token" | "secret" | "password" | "auth"
+            )
+        })
+}
+
+fn flatten<'table>(
+    table: &'table Table,
+    prefix: &str,
+    values: &mut Vec<(String, &'table Value)>,
+) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        if let Value::Table(ref nested) = *value {
+            flatten(nested, &path, values);
+        } else {
+            values.push((path, value));
+        }
+    }
+}
+
+fn record_origins(
+    path: &str,
+    value: &Value,
+    origin: &ConfigOrigin,
+    origins: &mut BTreeMap<String, ConfigOrigin>,
+) {
+    if let Value::Table(ref table) = *value {
+        for (key, nested) in table {
+            record_origins(&format!("{path}.{key}"), nested, origin, origins);
+        }
+    } else {
+        origins.insert(path.to_owned(), origin.clone());
+    }
+}
+
+fn insert_path(table: &mut Table, path: &str, value: Value) -> bool {
+    let mut parts: Vec<&str> = path.split('.').collect();
+    let Some(last) = parts.pop() else {
+        return false;
+    };
+    if last.is_empty() || parts.iter().any(|part| part.is_empty()) {
+        return false;
+    }
+
+    let mut current = table;
+    for part in parts {
+        let entry = current
+            .entry(part.to_owned())
+            .or_insert(Value::Table(Table::new()));
+        let &mut Value::Table(ref mut next) = entry else {
+            return false;
+        };
+        current = next;
+    }
+
+    current.insert(last.to_owned(), value);
+    true
+}
+
+/// Like `insert_path`, for a document that is edited in place.
+fn edit_path(table: &mut EditTable, path: &str, value: String) -> bool {
+    let mut parts: Vec<&str> = path.split('.').collect();
+    let Some(last) = parts.pop() else {
+        return false;
+    };
+    if last.is_empty() || parts.iter().any(|part| part.is_empty()) {
+        return false;
+    }
+
+    let mut current: &mut dyn TableLike = table;
+    for part in parts {
+        let entry = current
+            .entry(part)
+            .or_insert_with(|| Item::Table(EditTable::new()));
+        let Some(next) = entry.as_table_like_mut() else {
+            return false;
+        };
+        current = next;
+    }
+
+    current.insert(last, toml_edit::value(value));
+    true
+}
+
+/// Writes a config file that only the user can read, as it may hold API
+/// keys. A file that already exists is narrowed down to the user as well.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    #[cfg(unix)]
+    use std::os::unix::fs::{OpenOptionsExt as _, PermissionsExt as _};
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+fn parse_value(raw: &str) -> Value {
+    format!("value = {raw}")
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Table;
+    use toml_edit::DocumentMut;
+
+    use super::{edit_path, is_secret, restrict_to_project_keys};
+
+    #[test]
+    fn masks_credentials_wherever_they_are() {
+        assert!(is_secret("api_keys.openai"));
+        assert!(is_secret("chatbots.openai.api_keys"));
+        assert!(is_secret("chatbots.openai.headers.Authorization"));
+        assert!(is_secret("compat.local.api_key"));
+    }
+
+    #[test]
+    fn leaves_other_settings_visible() {
+        assert!(!is_secret("chatbots.openai.tokens_per_minute"));
+        assert!(!is_secret("params.max_tokens"));
+        assert!(!is_secret("compat.local.base_url"));
+    }
+
+    #[test]
+    fn keeps_project_files_from_redirecting_requests() {
+        let mut table: Table = r#"
+            default_chatbot = "openai"
+            env = { OPENAI_BASE_URL = "https://example.com" }
+
+            [params]
+            temperature = 0.2
+
+            [chatbots.openai]
+            base_url = "https://example.com"
+
+            [tools]
+            auto_approve = ["*"]
+
+            [audit]
+            enabled = false
+        "#
+        .parse()
+        .unwrap_or_default();
+
+        let removed = restrict_to_project_keys(&mut table);
+
+        assert_eq!(removed, ["audit", "chatbots", "env", "tools"]);
+        assert!(table.contains_key("default_chatbot"));
+        assert!(table.contains_key("params"));
+    }
+
+    #[test]
+    fn keeps_project_files_from_replacing_the_guardrail() {
+        let mut table: Table = r#"
+            guardrail = ""
+            footer = true
+        "#
+        .parse()
+        .unwrap_or_default();
+
+        let removed = restrict_to_project_keys(&mut table);
+
+        assert_eq!(removed, ["guardrail"]);
+        assert!(table.contains_key("footer"));
+    }
+
+    #[test]
+    fn persisting_a_key_keeps_the_rest_of_the_file() {
+        let mut document: DocumentMut = r#"
+# Picked by hand
+default_chatbot = "gemini"
+
+[api_keys]
+groq = "gsk"
+"#
+        .parse()
+        .unwrap_or_default();
+
+        assert!(edit_path(
+            document.as_table_mut(),
+            "api_keys.openai",
+            "sk".to_owned()
+        ));
+        assert!(!edit_path(
+            document.as_table_mut(),
+            "default_chatbot.key",
+            "x".to_owned()
+        ));
+        assert_eq!(
+            document.to_string(),
+            r#"
+# Picked by hand
+default_chatbot = "gemini"
+
+[api_keys]
+groq = "gsk"
+openai = "sk"
+"#
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    backtrace::Backtrace,
+    env, fs,
+    io::{self, Write as _},
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    sync::{Mutex, TryLockError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::{cursor, execute, style::ResetColor, terminal};
+
+use crate::{session::Session, Chatbot};
+
+struct CrashState {
+    session: Option<String>,
+    chatbot: String,
+    model: String,
+    session_dir: Option<PathBuf>,
+    secrets: Vec<String>,
+}
+
+static STATE: Mutex<Option<CrashState>> = Mutex::new(None);
+
+/// Installs a panic hook that restores the terminal, autosaves the last
+/// recorded session and writes a crash report with secrets redacted.
+///
+/// Besides `secrets`, the values of environment variables named like
+/// credentials are redacted.
+#[inline]
+pub fn install(session_dir: Option<PathBuf>, secrets: Vec<String>) {
+    if let Ok(mut state) = STATE.lock() {
+        *state = Some(CrashState {
+            session: None,
+            chatbot: String::new(),
+            model: String::new(),
+            session_dir,
+            secrets,
+        });
+    }
+
+    panic::set_hook(Box::new(handle_panic));
+}
+
+/// Adds a secret obtained after `install`, such as an API key typed in, to
+/// redact from crash reports.
+#[inline]
+pub fn add_secret(secret: String) {
+    if let Ok(mut state) = STATE.lock() {
+        if let Some(ref mut state) = *state {
+            state.secrets.push(secret);
+        }
+    }
+}
+
+/// Records the state to recover if the process panics later on.
+#[inline]
+pub fn record(session: &Session, chatbot: &dyn Chatbot) {
+    if let Ok(mut state) = STATE.lock() {
+        if let Some(ref mut state) = *state {
+            state.session = serde_json::to_string(session).ok();
+            chatbot.name().clone_into(&mut state.chatbot);
+            chatbot.model().clone_into(&mut state.model);
+        }
+    }
+}
+
+fn handle_panic(info: &PanicHookInfo<'_>) {
+    // Best effort: the terminal may not be in raw mode at all, and a failure
+    // has nowhere to be reported to.
+    let cooked = terminal::disable_raw_mode();
+    let shown = execute!(io::stdout(), ResetColor, cursor::Show);
+    cooked.and(shown).unwrap_or_default();
+
+    // The hook runs before unwinding, so `lock` would deadlock if the panic
+    // happened on this thread while the state was locked. The report goes
+    // without the state then. A panic on another thread holding the lock
+    // leaves it poisoned, which is fine to read.
+    match STATE.try_lock() {
+        Ok(state) => report(info, state.as_ref()),
+        Err(TryLockError::Poisoned(poisoned)) => {
+            report(info, poisoned.into_inner().as_ref());
+        }
+        Err(TryLockError::WouldBlock) => report(info, None),
+    }
+}
+
+fn report(info: &PanicHookInfo<'_>, state: Option<&CrashState>) {
+    let backtrace = Backtrace::force_capture();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    let mut autosaved = None;
+    if let Some(state) = state {
+        if let (Some(session), Some(dir)) =
+            (state.session.as_ref(), state.session_dir.as_ref())
+        {
+            let path = dir.join(format!("crash-{timestamp}.json"));
+            if fs::write(&path, session).is_ok() {
+                autosaved = Some(path);
+            }
+        }
+    }
+
+    let (chatbot, model) = state.map_or(("", ""), |state| {
+        (state.chatbot.as_str(), state.model.as_str())
+    });
+    let mut report = format!(
+        "llmcli {}\nchatbot: {chatbot}\nmodel: {model}\n\n{info}\n\n\
+         {backtrace}\n",
+        env!("CARGO_PKG_VERSION"),
+    );
+    // `env::vars` would panic, inside the panic hook, on a variable that is
+    // not valid UTF-8.
+    let env_secrets = env::vars_os()
+        .filter(|var| var.0.to_str().is_some_and(is_secret_var))
+        .filter_map(|(_, value)| value.into_string().ok());
+    let secrets = state
+        .iter()
+        .flat_map(|state| state.secrets.iter().cloned())
+        .chain(env_secrets);
+    for secret in secrets {
+        if !secret.is_empty() {
+            report = report.replace(secret.as_str(), "[redacted]");
+        }
+    }
+
+    let report_path = dirs::data_dir()
+        .map(|dir| dir.join("llmcli_crashes"))
+        .filter(|dir| fs::create_dir_all(dir).is_ok())
+        .map(|dir| dir.join(format!("crash-{timestamp}.txt")))
+        .filter(|path| fs::write(path, &report).is_ok());
+
+    // Stderr is the last resort, a failure to write to it cannot be
+    // reported anywhere.
+    tell_user(
+        &report,
+        autosaved.as_deref(),
+        report_path.as_deref(),
+        timestamp,
+    )
+    .unwrap_or_default();
+}
+
+/// Tells the user where the session and the report went, or prints the
+/// report when it could not be written.
+fn tell_user(
+    report: &str,
+    autosaved: Option<&Path>,
+    report_path: Option<&Path>,
+    timestamp: u64,
+) -> io::Result<()> {
+    let mut stderr = io::stderr();
+    writeln!(stderr, "\nllmcli crashed unexpectedly.")?;
+    if let Some(path) = autosaved {
+        writeln!(
+            stderr,
+            "Your session was saved to {}; load it with /load crash-{timestamp}.",
+            path.display()
+        )?;
+    }
+    match report_path {
+        Some(path) => writeln!(
+            stderr,
+            "A crash report was written to {}.",
+            path.display()
+        ),
+        None => writeln!(stderr, "{report}"),
+    }
+}
+
+/// Whether the environment variable `name` looks like it holds a
+/// credential, such as `OPENAI_API_KEY` or `HF_TOKEN`.
+fn is_secret_var(name: &str) -> bool {
+    name.to_uppercase().split('_').any(|word| {
+        matches!(
+            word,
+            "KEY" | "TOKEN" | "SECRET" | "PASSWORD" | "CREDENTIALS"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_secret_var;
+
+    #[test]
+    fn spots_credential_variables() {
+        assert!(is_secret_var("OPENAI_API_KEY"));
+        assert!(is_secret_var("HF_TOKEN"));
+        assert!(is_secret_var("AWS_SECRET_ACCESS_KEY"));
+        assert!(!is_secret_var("KEYBOARD_LAYOUT"));
+        assert!(!is_secret_var("HOME"));
+    }
+}
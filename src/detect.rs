@@ -0,0 +1,180 @@
+use alloc::borrow::Cow;
+
+const CODE_LINE_ENDINGS: [&str; 4] = [";", "{", "}", ")"];
+
+const CODE_LINE_PREFIXES: [&str; 12] = [
+    "fn ",
+    "def ",
+    "class ",
+    "import ",
+    "from ",
+    "use ",
+    "let ",
+    "const ",
+    "function ",
+    "return ",
+    "#include",
+    "pub ",
+];
+
+const TRACE_MARKERS: [&str; 5] = [
+    "Traceback (most recent call last)",
+    "stack backtrace:",
+    "panicked at",
+    "Exception in thread",
+    "File \"",
+];
+
+#[inline]
+#[must_use]
+pub fn looks_like_stack_trace(text: &str) -> bool {
+    let trace_lines = text
+        .lines()
+        .map(str::trim_start)
+        .filter(|line| is_trace_line(line))
+        .count();
+
+    trace_lines >= 2
+}
+
+/// A line starting a trace, or a frame of one. Java and JavaScript frames
+/// start with `at ` like plenty of prose, so they also have to end in the
+/// parenthesized location.
+fn is_trace_line(line: &str) -> bool {
+    TRACE_MARKERS.iter().any(|marker| line.starts_with(marker))
+        || (line.starts_with("at ")
+            && line.ends_with(')')
+            && line.contains(':'))
+}
+
+/// Wraps the parts of `text` that are code or a stack trace in fenced
+/// blocks so the model sees where they start and end.
+///
+/// The prose around them is left as it is, and input that is already
+/// fenced is left untouched.
+#[inline]
+#[must_use]
+pub fn fence(text: &str) -> Cow<'_, str> {
+    if text.contains("```") {
+        return Cow::Borrowed(text);
+    }
+
+    // Paragraphs are fenced as a whole, and neighbouring ones share a fence
+    // so blank lines inside the code do not split it.
+    let paragraphs: Vec<&str> = text.trim_end().split("\n\n").collect();
+    let is_code: Vec<bool> = paragraphs
+        .iter()
+        .map(|paragraph| {
+            looks_like_stack_trace(paragraph) || looks_like_code(paragraph)
+        })
+        .collect();
+    if !is_code.contains(&true) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut fenced = String::with_capacity(text.len().saturating_add(16));
+    let mut fence_open = false;
+    for (idx, (paragraph, &code)) in paragraphs.iter().zip(&is_code).enumerate()
+    {
+        if idx > 0 {
+            fenced.push_str(if fence_open && code { "\n\n" } else { "\n" });
+        }
+        match (code, fence_open) {
+            (true, false) => {
+                if idx > 0 {
+                    fenced.push('\n');
+                }
+                fenced.push_str("```\n");
+            }
+            (false, true) => fenced.push_str("```\n\n"),
+            (true, true) | (false, false) => {}
+        }
+        fenced.push_str(paragraph);
+        fence_open = code;
+    }
+    if fence_open {
+        fenced.push_str("\n```");
+    }
+
+    Cow::Owned(fenced)
+}
+
+/// The contents of the last fenced code block in `text`, without the fences.
+#[inline]
+#[must_use]
+pub fn last_code_block(text: &str) -> Option<&str> {
+    let (before, _) = text.rsplit_once("```")?;
+    let (_, block) = before.rsplit_once("```")?;
+    // The rest of the opening fence line is the language tag.
+    let (_, code) = block.split_once('\n')?;
+    Some(code.trim_end_matches('\n'))
+}
+
+#[inline]
+#[must_use]
+pub fn looks_like_code(text: &str) -> bool {
+    if text.contains("```") {
+        return true;
+    }
+
+    let mut lines = 0_usize;
+    let mut code_lines = 0_usize;
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        lines = lines.saturating_add(1);
+        if CODE_LINE_ENDINGS
+            .iter()
+            .any(|ending| line.ends_with(ending))
+            || CODE_LINE_PREFIXES
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+        {
+            code_lines = code_lines.saturating_add(1);
+        }
+    }
+
+    // Treat the text as code when at least a third of a multi-line input
+    // looks like source.
+    lines >= 2 && code_lines.saturating_mul(3) >= lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fence, looks_like_stack_trace};
+
+    #[test]
+    fn fences_only_the_code() {
+        let input =
+            "Why does this fail?\n\nfn main() {\n    run();\n}\n\nThanks!";
+        assert_eq!(
+            fence(input),
+            "Why does this fail?\n\n```\nfn main() {\n    run();\n}\n```\n\n\
+             Thanks!"
+        );
+    }
+
+    #[test]
+    fn keeps_blank_lines_inside_one_fence() {
+        let input = "use std::io;\nuse std::fs;\n\nfn main() {\n}";
+        assert_eq!(fence(input), format!("```\n{input}\n```"));
+    }
+
+    #[test]
+    fn leaves_prose_and_fenced_input_alone() {
+        let prose = "Meet me at noon.\nWe can talk at length then.";
+        assert_eq!(fence(prose), prose);
+        let fenced = "```\nlet x = 1;\n```";
+        assert_eq!(fence(fenced), fenced);
+    }
+
+    #[test]
+    fn spots_stack_traces_without_matching_prose() {
+        assert!(looks_like_stack_trace(
+            "Exception in thread \"main\" java.lang.NullPointerException\n\
+             \tat com.example.App.main(App.java:5)"
+        ));
+        assert!(!looks_like_stack_trace(
+            "at first I thought so\nat least it works"
+        ));
+    }
+}
@@ -0,0 +1,129 @@
+/// One line of a line-based diff.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'text> {
+    Same(&'text str),
+    Removed(&'text str),
+    Added(&'text str),
+}
+
+/// Diffs that would need a larger table than this are shown as a full
+/// replacement instead.
+const MAX_TABLE_CELLS: usize = 4_000_000;
+
+/// Computes a line diff from `old` to `new` using the longest common
+/// subsequence of lines.
+#[inline]
+#[must_use]
+pub fn lines<'text>(old: &'text str, new: &'text str) -> Vec<DiffLine<'text>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let cols = new.len().saturating_add(1);
+    let cells = old.len().saturating_add(1).saturating_mul(cols);
+    if cells > MAX_TABLE_CELLS {
+        return old
+            .iter()
+            .map(|&line| DiffLine::Removed(line))
+            .chain(new.iter().map(|&line| DiffLine::Added(line)))
+            .collect();
+    }
+
+    // `table[row * cols + col]` holds the LCS length of `old[row..]` and
+    // `new[col..]`.
+    let mut table = vec![0_usize; cells];
+    let at =
+        |row: usize, col: usize| row.saturating_mul(cols).saturating_add(col);
+    for (row, old_line) in old.iter().enumerate().rev() {
+        for (col, new_line) in new.iter().enumerate().rev() {
+            let below = table.get(at(row.saturating_add(1), col)).copied();
+            let right = table.get(at(row, col.saturating_add(1))).copied();
+            let diagonal = table
+                .get(at(row.saturating_add(1), col.saturating_add(1)))
+                .copied();
+            let value = if old_line == new_line {
+                diagonal.unwrap_or_default().saturating_add(1)
+            } else {
+                below.max(right).unwrap_or_default()
+            };
+            if let Some(cell) = table.get_mut(at(row, col)) {
+                *cell = value;
+            }
+        }
+    }
+
+    let mut diff = Vec::with_capacity(old.len().saturating_add(new.len()));
+    let (mut row, mut col) = (0, 0);
+    loop {
+        match (old.get(row), new.get(col)) {
+            (Some(&old_line), Some(&new_line)) if old_line == new_line => {
+                diff.push(DiffLine::Same(old_line));
+                row = row.saturating_add(1);
+                col = col.saturating_add(1);
+            }
+            (Some(&old_line), Some(_))
+                if table.get(at(row.saturating_add(1), col))
+                    >= table.get(at(row, col.saturating_add(1))) =>
+            {
+                diff.push(DiffLine::Removed(old_line));
+                row = row.saturating_add(1);
+            }
+            (_, Some(&new_line)) => {
+                diff.push(DiffLine::Added(new_line));
+                col = col.saturating_add(1);
+            }
+            (Some(&old_line), None) => {
+                diff.push(DiffLine::Removed(old_line));
+                row = row.saturating_add(1);
+            }
+            (None, None) => break,
+        }
+    }
+
+    diff
+}
+
+/// Totals over a series of diffs, such as one per message of two sessions.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiffStats {
+    pub identical: usize,
+    pub changed: usize,
+    pub only_old: usize,
+    pub only_new: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+}
+
+impl DiffStats {
+    /// Diffs `old` against `new` and counts the result. A missing side
+    /// counts as a text that exists in only one of the series.
+    #[inline]
+    pub fn compare<'text>(
+        &mut self,
+        old: Option<&'text str>,
+        new: Option<&'text str>,
+    ) -> Vec<DiffLine<'text>> {
+        let diff = lines(old.unwrap_or_default(), new.unwrap_or_default());
+        let added = diff
+            .iter()
+            .filter(|line| matches!(**line, DiffLine::Added(_)))
+            .count();
+        let removed = diff
+            .iter()
+            .filter(|line| matches!(**line, DiffLine::Removed(_)))
+            .count();
+
+        let total = match (old, new) {
+            (Some(_), None) => &mut self.only_old,
+            (None, Some(_)) => &mut self.only_new,
+            _ if added == 0 && removed == 0 => &mut self.identical,
+            _ => &mut self.changed,
+        };
+        *total = total.saturating_add(1);
+        self.lines_added = self.lines_added.saturating_add(added);
+        self.lines_removed = self.lines_removed.saturating_add(removed);
+
+        diff
+    }
+}
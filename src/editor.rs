@@ -0,0 +1,415 @@
+use std::io::{self, Write as _};
+
+use futures::StreamExt as _;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::Config, detect, guardrail, params::GenerationParams,
+    personas::Persona, Chatbot, ChatbotChatError, Message, Role, StreamItem,
+};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const EDIT_PROMPT: &str = "Rewrite the selected lines according to the \
+                           instruction. Reply with the replacement for the \
+                           selection only, in a single fenced code block, \
+                           keeping the surrounding indentation.";
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum EditorError {
+    #[error("Failed to read request: {0}.")]
+    Read(io::Error),
+    #[error("Failed to write event: {0}.")]
+    Write(io::Error),
+}
+
+#[derive(Deserialize)]
+struct Buffer {
+    path: Option<String>,
+    language: Option<String>,
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Selection {
+    start_line: usize,
+    end_line: usize,
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Chat {
+        prompt: String,
+        buffer: Option<Buffer>,
+        selection: Option<Selection>,
+        persona: Option<String>,
+    },
+    Edit {
+        instruction: String,
+        buffer: Option<Buffer>,
+        selection: Selection,
+        persona: Option<String>,
+    },
+    Reset,
+    Info,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    id: Option<u64>,
+    #[serde(flatten)]
+    request: Request,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event<'text> {
+    Ready {
+        version: u32,
+        chatbot: &'text str,
+        model: &'text str,
+    },
+    Info {
+        id: Option<u64>,
+        chatbot: &'text str,
+        model: &'text str,
+        personas: Vec<&'text str>,
+    },
+    Delta {
+        id: Option<u64>,
+        text: &'text str,
+    },
+    Reasoning {
+        id: Option<u64>,
+        text: &'text str,
+    },
+    Sources {
+        id: Option<u64>,
+        urls: &'text [String],
+    },
+    Usage {
+        id: Option<u64>,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+    },
+    Image {
+        id: Option<u64>,
+        mime_type: &'text str,
+        data: &'text str,
+    },
+    Done {
+        id: Option<u64>,
+        text: &'text str,
+    },
+    Edit {
+        id: Option<u64>,
+        path: Option<&'text str>,
+        start_line: usize,
+        end_line: usize,
+        replacement: &'text str,
+    },
+    Error {
+        id: Option<u64>,
+        message: String,
+    },
+}
+
+/// Serves editor plugins over stdio with the configured chatbot, personas
+/// and keys, so a plugin does not have to implement providers itself.
+///
+/// Every line on stdin is one JSON request and every line on stdout one
+/// JSON event. A `ready` event with the protocol version is sent first.
+/// Requests carry an optional `id` that is echoed in the events they cause:
+///
+/// - `{"type": "chat", "prompt": "...", "buffer": {...}, "selection":
+///   {...}, "persona": "..."}` streams `reasoning` and `delta` events, and
+///   `sources` with the cited `urls` when the model searched the web, and
+///   `usage` with `prompt_tokens` and `completion_tokens` when the provider
+///   reports them, then `done` with the whole answer. `buffer` (`path`,
+///   `language`, `content`), `selection` (`start_line`, `end_line`, `text`)
+///   and `persona` are optional.
+/// - `{"type": "edit", "instruction": "...", "selection": {...}}` streams
+///   like `chat` and then sends an `edit` event with the `replacement` for
+///   lines `start_line` to `end_line`.
+/// - `{"type": "reset"}` forgets the conversation.
+/// - `{"type": "info"}` reports the chatbot, model and personas.
+///
+/// Failures are reported as `error` events and the session continues until
+/// stdin is closed.
+#[inline]
+pub async fn serve(
+    chatbot: &dyn Chatbot,
+    params: GenerationParams,
+    config: &Config,
+) -> Result<(), EditorError> {
+    emit(&Event::Ready {
+        version: PROTOCOL_VERSION,
+        chatbot: chatbot.name(),
+        model: chatbot.model(),
+    })?;
+
+    let mut history: Vec<Message> = Vec::new();
+    for line in io::stdin().lines() {
+        let line = line.map_err(EditorError::Read)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Envelope { id, request } = match serde_json::from_str(&line) {
+            Ok(envelope) => envelope,
+            Err(err) => {
+                emit(&Event::Error {
+                    id: None,
+                    message: format!("Invalid request: {err}"),
+                })?;
+                continue;
+            }
+        };
+
+        match request {
+            Request::Chat {
+                prompt,
+                buffer,
+                selection,
+                persona,
+            } => {
+                let content =
+                    with_context(&prompt, buffer.as_ref(), selection.as_ref());
+                respond(
+                    id,
+                    chatbot,
+                    params,
+                    config,
+                    &mut history,
+                    content,
+                    persona,
+                )
+                .await?;
+            }
+            Request::Edit {
+                instruction,
+                buffer,
+                selection,
+                persona,
+            } => {
+                let content = format!(
+                    "{EDIT_PROMPT}\n\n{}",
+                    with_context(
+                        &format!("Instruction: {instruction}"),
+                        buffer.as_ref(),
+                        Some(&selection),
+                    )
+                );
+                let Some(answer) = respond(
+                    id,
+                    chatbot,
+                    params,
+                    config,
+                    &mut history,
+                    content,
+                    persona,
+                )
+                .await?
+                else {
+                    continue;
+                };
+                emit(&Event::Edit {
+                    id,
+                    path: buffer
+                        .as_ref()
+                        .and_then(|buffer| buffer.path.as_deref()),
+                    start_line: selection.start_line,
+                    end_line: selection.end_line,
+                    replacement: detect::last_code_block(&answer)
+                        .unwrap_or(&answer),
+                })?;
+            }
+            Request::Reset => {
+                history.clear();
+                emit(&Event::Done { id, text: "" })?;
+            }
+            Request::Info => {
+                emit(&Event::Info {
+                    id,
+                    chatbot: chatbot.name(),
+                    model: chatbot.model(),
+                    personas: config
+                        .personas
+                        .iter()
+                        .flat_map(|personas| personas.keys())
+                        .map(String::as_str)
+                        .collect(),
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends `content` with the conversation so far and streams the answer as
+/// events. Returns the answer, or `None` after reporting a failure.
+async fn respond(
+    id: Option<u64>,
+    chatbot: &dyn Chatbot,
+    params: GenerationParams,
+    config: &Config,
+    history: &mut Vec<Message>,
+    content: String,
+    persona: Option<String>,
+) -> Result<Option<String>, EditorError> {
+    let persona = match persona {
+        Some(name) => {
+            if let Some(persona) = Persona::from_name(&name, config) {
+                Some(persona)
+            } else {
+                emit(&Event::Error {
+                    id,
+                    message: format!("Unknown persona {name}."),
+                })?;
+                return Ok(None);
+            }
+        }
+        None => None,
+    };
+    let persona_chatbot =
+        match persona.as_ref().map(|persona| persona.chatbot(config)) {
+            Some(Ok(persona_chatbot)) => Some(persona_chatbot),
+            Some(Err(err)) => {
+                emit(&Event::Error {
+                    id,
+                    message: err.to_string(),
+                })?;
+                return Ok(None);
+            }
+            None => None,
+        };
+    let turn_chatbot = persona_chatbot.as_deref().unwrap_or(chatbot);
+
+    history.push(Message::new(Role::User, content));
+    let messages = persona
+        .as_ref()
+        .map_or_else(|| history.clone(), |persona| persona.messages(history));
+    let messages = guardrail::apply(&messages, config.guardrail.as_deref());
+
+    match stream_answer(id, turn_chatbot, &messages, params).await {
+        Ok(Ok(answer)) => {
+            emit(&Event::Done { id, text: &answer })?;
+            history.push(
+                Message::new(Role::Assistant, answer.clone())
+                    .with_provenance(turn_chatbot.provenance())
+                    .with_speaker(
+                        persona
+                            .as_ref()
+                            .map(|persona| persona.name().to_owned()),
+                    ),
+            );
+            Ok(Some(answer))
+        }
+        Ok(Err(err)) => {
+            history.pop();
+            emit(&Event::Error {
+                id,
+                message: err.to_string(),
+            })?;
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Streams one answer. Chatbot failures are returned inside, so they can be
+/// reported to the editor while write failures end the session.
+async fn stream_answer(
+    id: Option<u64>,
+    chatbot: &dyn Chatbot,
+    messages: &[Message],
+    params: GenerationParams,
+) -> Result<Result<String, ChatbotChatError>, EditorError> {
+    let mut stream = match chatbot.send_message(messages, params).await {
+        Ok(stream) => stream,
+        Err(err) => return Ok(Err(err)),
+    };
+
+    let mut answer = String::new();
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(StreamItem::Text(text)) => {
+                emit(&Event::Delta { id, text: &text })?;
+                answer.push_str(&text);
+            }
+            Ok(StreamItem::Reasoning(text)) => {
+                emit(&Event::Reasoning { id, text: &text })?;
+            }
+            Ok(StreamItem::Citations(urls)) => {
+                emit(&Event::Sources { id, urls: &urls })?;
+            }
+            Ok(StreamItem::Usage(usage)) => {
+                emit(&Event::Usage {
+                    id,
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                })?;
+            }
+            Ok(StreamItem::Image { mime_type, data }) => {
+                emit(&Event::Image {
+                    id,
+                    mime_type: &mime_type,
+                    data: &data,
+                })?;
+            }
+            Err(err) => return Ok(Err(err)),
+        }
+    }
+
+    Ok(Ok(answer))
+}
+
+fn with_context(
+    prompt: &str,
+    buffer: Option<&Buffer>,
+    selection: Option<&Selection>,
+) -> String {
+    let mut content = prompt.to_owned();
+    let language = buffer
+        .and_then(|buffer| buffer.language.as_deref())
+        .unwrap_or_default();
+
+    if let Some(buffer) = buffer {
+        if let Some(ref path) = buffer.path {
+            content.push_str("\n\nFile: ");
+            content.push_str(path);
+        }
+        if let Some(ref text) = buffer.content {
+            content.push_str("\n```");
+            content.push_str(language);
+            content.push('\n');
+            content.push_str(text.trim_end());
+            content.push_str("\n```");
+        }
+    }
+    if let Some(selection) = selection {
+        let header = format!(
+            "\n\nSelected lines {}-{}:\n```{language}\n",
+            selection.start_line, selection.end_line
+        );
+        content.push_str(&header);
+        content.push_str(selection.text.trim_end());
+        content.push_str("\n```");
+    }
+
+    content
+}
+
+fn emit(event: &Event<'_>) -> Result<(), EditorError> {
+    let line = serde_json::to_string(event)
+        .map_err(|err| EditorError::Write(err.into()))?;
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{line}")
+        .and_then(|()| stdout.flush())
+        .map_err(EditorError::Write)
+}
@@ -0,0 +1,271 @@
+use std::env;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{chatbots, config::Config, ChatbotChatError, ChatbotCreationError};
+
+pub const DEFAULT_MODEL: &str = "text-embedding-004";
+
+const GEMINI_BASE_URL: &str =
+    "https://generativelanguage.googleapis.com/v1beta/models/";
+
+const GEMINI_MODELS: [&str; 2] = ["text-embedding-004", "gemini-embedding-exp"];
+
+const OPENAI_URL: &str = "https://api.openai.com/v1/embeddings";
+
+const OPENAI_MODELS: [&str; 3] = [
+    "text-embedding-3-small",
+    "text-embedding-3-large",
+    "text-embedding-ada-002",
+];
+
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Embeds each of `texts`, returning the vectors in the same order.
+    async fn embed(
+        &self,
+        texts: &[String],
+    ) -> Result<Vec<Vec<f32>>, ChatbotChatError>;
+}
+
+/// Picks the embedding backend for `model`, reusing the keys and headers
+/// configured for the matching chatbot.
+#[inline]
+pub fn create(
+    model: &str,
+    config: &Config,
+) -> Result<Box<dyn Embedder>, ChatbotCreationError> {
+    if GEMINI_MODELS.contains(&model) {
+        let (api_key, client) =
+            credentials("gemini", "GEMINI_API_KEY", config)?;
+        return Ok(Box::new(GeminiEmbedder {
+            model: model.to_owned(),
+            api_key,
+            client,
+        }));
+    }
+    if OPENAI_MODELS.contains(&model) {
+        let (api_key, client) =
+            credentials("openai", "OPENAI_API_KEY", config)?;
+        return Ok(Box::new(OpenAIEmbedder {
+            model: model.to_owned(),
+            api_key,
+            client,
+        }));
+    }
+
+    Err(ChatbotCreationError::UnknownModel)
+}
+
+fn credentials(
+    chatbot: &str,
+    env_var: &str,
+    config: &Config,
+) -> Result<(String, Client), ChatbotCreationError> {
+    let api_key = if let Some(api_key) = config.api_key(chatbot) {
+        api_key
+    } else {
+        env::var(env_var)?
+    };
+
+    let client = match config
+        .chatbot(chatbot)
+        .and_then(|chatbot_config| chatbot_config.headers.as_ref())
+    {
+        Some(headers) => chatbots::client_with_headers(headers)?,
+        None => Client::new(),
+    };
+
+    Ok((api_key, client))
+}
+
+fn send_error(err: reqwest::Error) -> ChatbotChatError {
+    if err.is_timeout() {
+        ChatbotChatError::Timeout
+    } else {
+        ChatbotChatError::NetworkError(err)
+    }
+}
+
+#[derive(Serialize)]
+struct GeminiPart<'text> {
+    text: &'text str,
+}
+
+#[derive(Serialize)]
+struct GeminiContent<'text> {
+    parts: [GeminiPart<'text>; 1],
+}
+
+#[derive(Serialize)]
+struct GeminiEmbedRequest<'text> {
+    model: String,
+    content: GeminiContent<'text>,
+}
+
+#[derive(Serialize)]
+struct GeminiBatchRequest<'text> {
+    requests: Vec<GeminiEmbedRequest<'text>>,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbedding {
+    values: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct GeminiBatchResponse {
+    #[serde(default)]
+    embeddings: Vec<GeminiEmbedding>,
+}
+
+pub struct GeminiEmbedder {
+    model: String,
+    api_key: String,
+    client: Client,
+}
+
+#[async_trait]
+impl Embedder for GeminiEmbedder {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Gemini"
+    }
+
+    #[inline]
+    async fn embed(
+        &self,
+        texts: &[String],
+    ) -> Result<Vec<Vec<f32>>, ChatbotChatError> {
+        let body = GeminiBatchRequest {
+            requests: texts
+                .iter()
+                .map(|text| GeminiEmbedRequest {
+                    model: format!("models/{}", self.model),
+                    content: GeminiContent {
+                        parts: [GeminiPart { text }],
+                    },
+                })
+                .collect(),
+        };
+
+        let resp = self
+            .client
+            .post(format!(
+                "{GEMINI_BASE_URL}{}:batchEmbedContents?key={}",
+                self.model, self.api_key
+            ))
+            .json(&body)
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                Invalid JSON from the API indicates a critical error so we
+                hide that detail from the end user, as they cannot address
+                this issue.
+            "#
+        )]
+        let gemini_resp: GeminiBatchResponse = resp
+            .json()
+            .await
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+        if gemini_resp.embeddings.len() != texts.len() {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        }
+
+        Ok(gemini_resp
+            .embeddings
+            .into_iter()
+            .map(|embedding| embedding.values)
+            .collect())
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIRequest<'req> {
+    model: &'req str,
+    input: &'req [String],
+}
+
+#[derive(Deserialize)]
+struct OpenAIEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIResponse {
+    #[serde(default)]
+    data: Vec<OpenAIEmbedding>,
+}
+
+pub struct OpenAIEmbedder {
+    model: String,
+    api_key: String,
+    client: Client,
+}
+
+#[async_trait]
+impl Embedder for OpenAIEmbedder {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    #[inline]
+    async fn embed(
+        &self,
+        texts: &[String],
+    ) -> Result<Vec<Vec<f32>>, ChatbotChatError> {
+        let resp = self
+            .client
+            .post(OPENAI_URL)
+            .bearer_auth(&self.api_key)
+            .json(&OpenAIRequest {
+                model: &self.model,
+                input: texts,
+            })
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                Invalid JSON from the API indicates a critical error so we
+                hide that detail from the end user, as they cannot address
+                this issue.
+            "#
+        )]
+        let mut openai_resp: OpenAIResponse = resp
+            .json()
+            .await
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+        if openai_resp.data.len() != texts.len() {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        }
+
+        // The API documents no ordering guarantee, only the index.
+        openai_resp.data.sort_by_key(|embedding| embedding.index);
+        Ok(openai_resp
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+}
@@ -0,0 +1,50 @@
+use alloc::collections::BTreeMap;
+use std::process::Command;
+
+#[derive(Default)]
+pub struct SessionEnv {
+    vars: BTreeMap<String, String>,
+}
+
+impl SessionEnv {
+    #[inline]
+    #[must_use]
+    pub fn new(vars: Option<&BTreeMap<String, String>>) -> Self {
+        Self {
+            vars: vars.cloned().unwrap_or_default(),
+        }
+    }
+
+    #[inline]
+    pub fn set(&mut self, key: String, value: String) {
+        self.vars.insert(key, value);
+    }
+
+    #[inline]
+    pub fn unset(&mut self, key: &str) -> bool {
+        self.vars.remove(key).is_some()
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.vars.keys().map(String::as_str)
+    }
+
+    /// Injects the session variables into a child process spawned by `/run`,
+    /// leaving the environment of llmcli itself untouched.
+    #[inline]
+    pub fn apply(&self, command: &mut Command) {
+        command.envs(&self.vars);
+    }
+}
+
+#[inline]
+#[must_use]
+pub fn parse_assignment(assignment: &str) -> Option<(String, String)> {
+    let (key, value) = assignment.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty() {
+        return None;
+    }
+    Some((key.to_owned(), value.to_owned()))
+}
@@ -0,0 +1,46 @@
+/// Exit codes by error class, so scripts can branch on the kind of failure
+/// without parsing messages. Usage errors exit with 2, like the ones clap
+/// reports itself.
+pub const FAILURE: i32 = 1;
+pub const USAGE: i32 = 2;
+pub const CONFIG: i32 = 3;
+pub const AUTH: i32 = 4;
+pub const RATE_LIMIT: i32 = 5;
+pub const NETWORK: i32 = 6;
+pub const REFUSAL: i32 = 7;
+pub const VALIDATION: i32 = 8;
+pub const QUOTA: i32 = 9;
+/// 128 plus SIGINT, what a shell reports for a program stopped by Ctrl+C.
+pub const INTERRUPTED: i32 = 130;
+
+pub const HELP: &str = "Exit codes:
+    0  Success
+    1  Other failure
+    2  Invalid usage
+    3  Configuration error
+    4  Missing or rejected API key
+    5  Rate limit exceeded
+    6  Network error or timeout
+    7  The model refused to answer
+    8  Validation failed, e.g. quotes not found by --verify-quotes
+    9  Quota or credits used up
+  130  Cancelled with Ctrl+C or the cancel key";
+
+/// The exit code for an error class, as returned by the `class` methods of
+/// the error types.
+#[inline]
+#[must_use]
+pub fn code(class: &str) -> i32 {
+    match class {
+        "usage" => USAGE,
+        "config" => CONFIG,
+        "auth" => AUTH,
+        "rate_limit" => RATE_LIMIT,
+        "quota" => QUOTA,
+        "network" | "timeout" => NETWORK,
+        "refusal" => REFUSAL,
+        "validation" => VALIDATION,
+        "cancelled" => INTERRUPTED,
+        _ => FAILURE,
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    chatbots, config::Config, params::GenerationParams, refine, Chatbot,
+    ChatbotChatError, ChatbotCreationError, Message, Role,
+};
+
+pub const DEFAULT_COUNT: usize = 3;
+
+const FOLLOW_UP_PROMPT: &str = "Suggest short follow-up questions the user \
+                                might ask next about this conversation. \
+                                Reply with one question per line and \
+                                nothing else.";
+
+/// Numbered follow-up suggestions shown after each response. Typing one of
+/// the numbers at the prompt sends that suggestion.
+pub struct FollowUps {
+    chatbot: Option<Box<dyn Chatbot>>,
+    count: usize,
+    current: Vec<String>,
+}
+
+impl FollowUps {
+    #[inline]
+    pub fn from_config(
+        config: &Config,
+    ) -> Result<Option<Self>, ChatbotCreationError> {
+        let Some(follow_ups) = config
+            .follow_ups
+            .as_ref()
+            .filter(|follow_ups| follow_ups.enabled.unwrap_or(false))
+        else {
+            return Ok(None);
+        };
+
+        let chatbot = follow_ups
+            .chatbot
+            .as_deref()
+            .map(|name| {
+                chatbots::create(name, follow_ups.model.clone(), config)
+            })
+            .transpose()?;
+
+        Ok(Some(Self {
+            chatbot,
+            count: follow_ups.count.unwrap_or(DEFAULT_COUNT),
+            current: Vec::new(),
+        }))
+    }
+
+    /// Asks the configured chatbot, or `fallback` when none is configured,
+    /// for suggestions based on `history`.
+    #[inline]
+    pub async fn suggest(
+        &mut self,
+        fallback: &dyn Chatbot,
+        history: &[Message],
+    ) -> Result<&[String], ChatbotChatError> {
+        let mut messages = history.to_vec();
+        messages.push(Message::new(
+            Role::User,
+            format!("{FOLLOW_UP_PROMPT} Give at most {}.", self.count),
+        ));
+
+        let response = refine::complete(
+            self.chatbot.as_deref().unwrap_or(fallback),
+            &messages,
+            GenerationParams::default(),
+        )
+        .await?;
+
+        self.current = response
+            .lines()
+            .map(|line| {
+                line.trim()
+                    .trim_start_matches(|ch: char| {
+                        ch.is_ascii_digit()
+                            || matches!(ch, '.' | ')' | '-' | '*')
+                    })
+                    .trim()
+                    .to_owned()
+            })
+            .filter(|line| !line.is_empty())
+            .take(self.count)
+            .collect();
+
+        Ok(&self.current)
+    }
+
+    /// Returns the suggestion selected by typing its number, if any.
+    #[inline]
+    pub fn select(&mut self, input: &str) -> Option<String> {
+        let idx = input.trim().parse::<usize>().ok()?.checked_sub(1)?;
+        let selected = self.current.get(idx).cloned();
+        self.current.clear();
+        selected
+    }
+}
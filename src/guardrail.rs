@@ -0,0 +1,93 @@
+use alloc::borrow::Cow;
+
+use crate::{Message, Role};
+
+#[inline]
+#[must_use]
+pub fn apply<'messages>(
+    messages: &'messages [Message],
+    guardrail: Option<&str>,
+) -> Cow<'messages, [Message]> {
+    let Some(guardrail) = guardrail else {
+        return Cow::Borrowed(messages);
+    };
+
+    let mut guarded = messages.to_vec();
+
+    if let Some(system) =
+        guarded.iter_mut().find(|msg| msg.role == Role::System)
+    {
+        system.content = format!("{guardrail}\n\n{}", system.content);
+    } else {
+        guarded.insert(0, Message::new(Role::System, guardrail.to_owned()));
+    }
+
+    Cow::Owned(guarded)
+}
+
+/// Adds `extra` to the end of the system prompt, after anything already
+/// there. Context such as tool descriptions goes here so that the guardrail,
+/// applied last, still opens the prompt.
+#[inline]
+#[must_use]
+pub fn append<'messages>(
+    messages: &'messages [Message],
+    extra: Option<&str>,
+) -> Cow<'messages, [Message]> {
+    let Some(extra) = extra else {
+        return Cow::Borrowed(messages);
+    };
+
+    let mut extended = messages.to_vec();
+
+    if let Some(system) =
+        extended.iter_mut().find(|msg| msg.role == Role::System)
+    {
+        system.content = format!("{}\n\n{extra}", system.content);
+    } else {
+        extended.insert(0, Message::new(Role::System, extra.to_owned()));
+    }
+
+    Cow::Owned(extended)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append, apply};
+    use crate::{Message, Role};
+
+    #[test]
+    fn guardrail_stays_first_after_appending_context() {
+        let messages = vec![
+            Message::new(Role::System, "Be brief.".to_owned()),
+            Message::new(Role::User, "hi".to_owned()),
+        ];
+
+        let messages = append(&messages, Some("Memories: none"));
+        let messages = append(&messages, Some("Tools: calculator"));
+        let messages = apply(&messages, Some("Never share secrets."));
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages.first().map(|msg| msg.content.as_str()),
+            Some(
+                "Never share secrets.\n\nBe brief.\n\nMemories: none\n\n\
+                 Tools: calculator"
+            )
+        );
+    }
+
+    #[test]
+    fn append_adds_a_system_message_when_there_is_none() {
+        let messages = vec![Message::new(Role::User, "hi".to_owned())];
+
+        let messages = append(&messages, Some("Tools: calculator"));
+
+        assert_eq!(messages.first().map(|msg| msg.role), Some(Role::System));
+        assert_eq!(
+            messages.first().map(|msg| msg.content.as_str()),
+            Some("Tools: calculator")
+        );
+        assert_eq!(messages.len(), 2);
+    }
+}
@@ -0,0 +1,97 @@
+use alloc::borrow::Cow;
+
+use crossterm::style::{Attribute, SetAttribute};
+use rustyline::{
+    completion::Completer,
+    highlight::Highlighter,
+    hint::{Hinter, HistoryHinter},
+    validate::Validator,
+    Context, Helper,
+};
+
+use crate::commands::COMMANDS;
+
+/// Rustyline helper that shows the rest of a matching history entry as a
+/// dimmed, fish-style suggestion (accepted with Right-arrow) and completes
+/// command names on Tab.
+pub struct InputHelper {
+    history: HistoryHinter,
+    hints: bool,
+    no_color: bool,
+}
+
+impl InputHelper {
+    #[inline]
+    #[must_use]
+    pub fn new(hints: bool, no_color: bool) -> Self {
+        Self {
+            history: HistoryHinter::new(),
+            hints,
+            no_color,
+        }
+    }
+}
+
+impl Completer for InputHelper {
+    type Candidate = String;
+
+    #[inline]
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let Some(prefix) = line.get(..pos).filter(|prefix| {
+            prefix.starts_with('/') && !prefix.contains(char::is_whitespace)
+        }) else {
+            return Ok((0, Vec::new()));
+        };
+
+        let candidates = COMMANDS
+            .iter()
+            .map(|spec| spec.name)
+            .filter(|name| name.starts_with(prefix))
+            .map(ToOwned::to_owned)
+            .collect();
+
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for InputHelper {
+    type Hint = String;
+
+    #[inline]
+    fn hint(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Option<String> {
+        if self.hints {
+            self.history.hint(line, pos, ctx)
+        } else {
+            None
+        }
+    }
+}
+
+impl Highlighter for InputHelper {
+    #[inline]
+    fn highlight_hint<'hint>(&self, hint: &'hint str) -> Cow<'hint, str> {
+        if self.no_color {
+            Cow::Borrowed(hint)
+        } else {
+            Cow::Owned(format!(
+                "{}{hint}{}",
+                SetAttribute(Attribute::Dim),
+                SetAttribute(Attribute::Reset)
+            ))
+        }
+    }
+}
+
+impl Validator for InputHelper {}
+
+impl Helper for InputHelper {}
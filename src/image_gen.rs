@@ -0,0 +1,367 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{chatbots, config::Config, ChatbotChatError, ChatbotCreationError};
+
+const IMAGEN_BASE_URL: &str =
+    "https://generativelanguage.googleapis.com/v1beta/models/";
+
+const IMAGEN_MODELS: [(&str, &str); 2] = [
+    ("imagen-3", "imagen-3.0-generate-002"),
+    ("imagen-3-fast", "imagen-3.0-fast-generate-001"),
+];
+
+const OPENAI_URL: &str = "https://api.openai.com/v1/images/generations";
+
+const OPENAI_MODELS: [&str; 3] = ["gpt-image-1", "dall-e-3", "dall-e-2"];
+
+#[non_exhaustive]
+pub struct ImageRequest {
+    pub prompt: String,
+    pub count: u8,
+    pub size: Option<String>,
+}
+
+impl ImageRequest {
+    #[inline]
+    #[must_use]
+    pub const fn new(prompt: String, count: u8, size: Option<String>) -> Self {
+        Self {
+            prompt,
+            count,
+            size,
+        }
+    }
+}
+
+#[non_exhaustive]
+pub struct GeneratedImage {
+    pub mime_type: String,
+    pub data: String,
+}
+
+#[async_trait]
+pub trait ImageGenerator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn generate(
+        &self,
+        request: &ImageRequest,
+    ) -> Result<Vec<GeneratedImage>, ChatbotChatError>;
+}
+
+/// Picks the image generation backend for `model`, reusing the keys and
+/// headers configured for the matching chatbot.
+#[inline]
+pub fn create(
+    model: &str,
+    config: &Config,
+) -> Result<Box<dyn ImageGenerator>, ChatbotCreationError> {
+    if let Some(&(_, model)) = IMAGEN_MODELS
+        .iter()
+        .find(|&&(alias, full)| alias == model || full == model)
+    {
+        return ImagenGenerator::create(model.to_owned(), config);
+    }
+    if OPENAI_MODELS.contains(&model) {
+        return OpenAIImageGenerator::create(model.to_owned(), config);
+    }
+
+    Err(ChatbotCreationError::UnknownModel)
+}
+
+#[derive(Serialize)]
+struct ImagenInstance<'prompt> {
+    prompt: &'prompt str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImagenParameters<'size> {
+    sample_count: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aspect_ratio: Option<&'size str>,
+}
+
+#[derive(Serialize)]
+struct ImagenRequest<'prompt, 'size> {
+    instances: Vec<ImagenInstance<'prompt>>,
+    parameters: ImagenParameters<'size>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImagenPrediction {
+    bytes_base64_encoded: String,
+    mime_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ImagenResponse {
+    #[serde(default)]
+    predictions: Vec<ImagenPrediction>,
+}
+
+pub struct ImagenGenerator {
+    model: String,
+    api_key: String,
+    client: Client,
+}
+
+impl ImagenGenerator {
+    fn create(
+        model: String,
+        config: &Config,
+    ) -> Result<Box<dyn ImageGenerator>, ChatbotCreationError> {
+        let api_key = if let Some(api_key) = config.api_key("gemini") {
+            api_key
+        } else {
+            env::var("GEMINI_API_KEY")?
+        };
+
+        let client = match config
+            .chatbot("gemini")
+            .and_then(|chatbot| chatbot.headers.as_ref())
+        {
+            Some(headers) => chatbots::client_with_headers(headers)?,
+            None => Client::new(),
+        };
+
+        Ok(Box::new(Self {
+            model,
+            api_key,
+            client,
+        }))
+    }
+}
+
+#[async_trait]
+impl ImageGenerator for ImagenGenerator {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Imagen"
+    }
+
+    #[inline]
+    async fn generate(
+        &self,
+        request: &ImageRequest,
+    ) -> Result<Vec<GeneratedImage>, ChatbotChatError> {
+        let body = ImagenRequest {
+            instances: vec![ImagenInstance {
+                prompt: &request.prompt,
+            }],
+            parameters: ImagenParameters {
+                sample_count: request.count,
+                aspect_ratio: request.size.as_deref(),
+            },
+        };
+
+        let resp = self
+            .client
+            .post(format!(
+                "{IMAGEN_BASE_URL}{}:predict?key={}",
+                self.model, self.api_key
+            ))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(ChatbotChatError::RateLimited);
+        }
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        }
+
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                Invalid JSON from the API indicates a critical error so we
+                hide that detail from the end user, as they cannot address
+                this issue.
+            "#
+        )]
+        let imagen_resp: ImagenResponse = resp
+            .json()
+            .await
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+
+        Ok(imagen_resp
+            .predictions
+            .into_iter()
+            .map(|prediction| GeneratedImage {
+                mime_type: prediction
+                    .mime_type
+                    .unwrap_or_else(|| "image/png".to_owned()),
+                data: prediction.bytes_base64_encoded,
+            })
+            .collect())
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIRequest<'req> {
+    model: &'req str,
+    prompt: &'req str,
+    n: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<&'req str>,
+    /// Only the DALL-E models take this; GPT Image always returns base64.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<&'req str>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIImage {
+    b64_json: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAIResponse {
+    #[serde(default)]
+    data: Vec<OpenAIImage>,
+}
+
+pub struct OpenAIImageGenerator {
+    model: String,
+    api_key: String,
+    client: Client,
+}
+
+impl OpenAIImageGenerator {
+    fn create(
+        model: String,
+        config: &Config,
+    ) -> Result<Box<dyn ImageGenerator>, ChatbotCreationError> {
+        let api_key = if let Some(api_key) = config.api_key("openai") {
+            api_key
+        } else {
+            env::var("OPENAI_API_KEY")?
+        };
+
+        let client = match config
+            .chatbot("openai")
+            .and_then(|chatbot| chatbot.headers.as_ref())
+        {
+            Some(headers) => chatbots::client_with_headers(headers)?,
+            None => Client::new(),
+        };
+
+        Ok(Box::new(Self {
+            model,
+            api_key,
+            client,
+        }))
+    }
+
+    /// The images API takes sizes in pixels, so the aspect ratios Imagen
+    /// takes are mapped to the closest size the model offers.
+    fn size<'size>(&self, size: &'size str) -> &'size str {
+        let dall_e_3 = self.model == "dall-e-3";
+        match size {
+            "1:1" => "1024x1024",
+            "16:9" | "3:2" | "4:3" if dall_e_3 => "1792x1024",
+            "9:16" | "2:3" | "3:4" if dall_e_3 => "1024x1792",
+            "16:9" | "3:2" | "4:3" => "1536x1024",
+            "9:16" | "2:3" | "3:4" => "1024x1536",
+            pixels => pixels,
+        }
+    }
+}
+
+#[async_trait]
+impl ImageGenerator for OpenAIImageGenerator {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "OpenAI Images"
+    }
+
+    #[inline]
+    async fn generate(
+        &self,
+        request: &ImageRequest,
+    ) -> Result<Vec<GeneratedImage>, ChatbotChatError> {
+        let body = OpenAIRequest {
+            model: &self.model,
+            prompt: &request.prompt,
+            n: request.count,
+            size: request.size.as_deref().map(|size| self.size(size)),
+            response_format: self
+                .model
+                .starts_with("dall-e")
+                .then_some("b64_json"),
+        };
+
+        let resp = self
+            .client
+            .post(OPENAI_URL)
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                Invalid JSON from the API indicates a critical error so we
+                hide that detail from the end user, as they cannot address
+                this issue.
+            "#
+        )]
+        let openai_resp: OpenAIResponse = resp
+            .json()
+            .await
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+
+        Ok(openai_resp
+            .data
+            .into_iter()
+            .map(|image| GeneratedImage {
+                mime_type: "image/png".to_owned(),
+                data: image.b64_json,
+            })
+            .collect())
+    }
+}
+
+/// Numbers the output files when more than one image is generated, so
+/// `fox.png` becomes `fox-1.png`, `fox-2.png` and so on.
+#[inline]
+#[must_use]
+pub fn output_path(out: &Path, idx: usize, count: usize) -> PathBuf {
+    if count <= 1 {
+        return out.to_path_buf();
+    }
+
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    let numbered = out.extension().map_or_else(
+        || format!("{stem}-{idx}"),
+        |ext| format!("{stem}-{idx}.{}", ext.to_string_lossy()),
+    );
+    out.with_file_name(numbered)
+}
@@ -0,0 +1,109 @@
+use std::{
+    env, fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::base64;
+
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+    None,
+}
+
+impl Protocol {
+    /// Guesses the graphics protocol of the running terminal from its
+    /// environment. Sixel terminals cannot be told apart reliably this way
+    /// and fall back to printing the path.
+    #[inline]
+    #[must_use]
+    pub fn detect() -> Self {
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" {
+            Self::Kitty
+        } else if matches!(program.as_str(), "iTerm.app" | "WezTerm") {
+            Self::Iterm
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// Saves a base64 encoded image from a response to a temporary file and
+/// returns its path.
+#[inline]
+pub fn save(mime_type: &str, data: &str) -> io::Result<PathBuf> {
+    let extension = mime_type.strip_prefix("image/").unwrap_or("png");
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let path =
+        env::temp_dir().join(format!("llmcli-image-{timestamp}.{extension}"));
+
+    write(&path, data)?;
+
+    Ok(path)
+}
+
+/// Decodes a base64 encoded image and writes it to `path`.
+#[inline]
+pub fn write(path: &Path, data: &str) -> io::Result<()> {
+    let bytes = base64::decode(data).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "invalid base64 image")
+    })?;
+
+    fs::write(path, bytes)
+}
+
+/// Draws the image inline when the terminal supports it, otherwise prints
+/// its path.
+#[inline]
+pub fn render(path: &Path, protocol: Protocol) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let encoded = match protocol {
+        Protocol::None => {
+            return writeln!(stdout, "[image: {}]", path.display());
+        }
+        Protocol::Kitty | Protocol::Iterm => base64::encode(&fs::read(path)?),
+    };
+
+    if protocol == Protocol::Iterm {
+        writeln!(
+            stdout,
+            "\x1b]1337;File=inline=1;size={}:{encoded}\x07",
+            encoded.len()
+        )?;
+    } else {
+        // Kitty only takes PNG data directly and wants it in chunks.
+        let is_png = path.extension().is_some_and(|ext| ext == "png");
+        if !is_png {
+            return writeln!(stdout, "[image: {}]", path.display());
+        }
+
+        let chunks: Vec<&[u8]> =
+            encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+        let last = chunks.len().saturating_sub(1);
+        for (idx, chunk) in chunks.into_iter().enumerate() {
+            let more = u8::from(idx != last);
+            let control = if idx == 0 {
+                format!("f=100,a=T,m={more}")
+            } else {
+                format!("m={more}")
+            };
+            write!(stdout, "\x1b_G{control};")?;
+            stdout.write_all(chunk)?;
+            write!(stdout, "\x1b\\")?;
+        }
+        writeln!(stdout)?;
+    }
+
+    stdout.flush()
+}
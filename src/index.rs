@@ -0,0 +1,485 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::time::Duration;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+    thread,
+    time::{Instant, SystemTime},
+};
+
+use thiserror::Error;
+
+use crate::config::Config;
+
+pub const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 256 * 1024;
+
+pub const DEFAULT_RESULTS: usize = 4;
+
+const CHUNK_LINES: usize = 40;
+
+const DIMENSIONS: u64 = 1_024;
+
+const MIN_SCORE: f32 = 0.2;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("Not inside a git repository.")]
+    NotARepo,
+    #[error("Failed to start the indexer: {0}.")]
+    Spawn(#[from] io::Error),
+}
+
+struct Chunk {
+    start_line: usize,
+    text: String,
+    vector: Vec<f32>,
+}
+
+struct FileEntry {
+    modified: SystemTime,
+    chunks: Vec<Chunk>,
+}
+
+struct IndexState {
+    root: PathBuf,
+    files: BTreeMap<String, FileEntry>,
+    pending: usize,
+    last_scan: Option<Instant>,
+    error: Option<String>,
+}
+
+static STATE: Mutex<Option<IndexState>> = Mutex::new(None);
+
+#[non_exhaustive]
+pub struct IndexStatus {
+    pub root: PathBuf,
+    pub files: usize,
+    pub chunks: usize,
+    pub pending: usize,
+    pub last_scan: Option<Duration>,
+    pub error: Option<String>,
+}
+
+/// A chunk of a file matching a query, with its cosine similarity.
+#[non_exhaustive]
+pub struct Hit {
+    pub path: String,
+    pub start_line: usize,
+    pub text: String,
+    pub score: f32,
+}
+
+/// Starts indexing the git repository containing the current directory in a
+/// background thread, if `[index]` is enabled.
+///
+/// Files are rescanned every `interval_secs` and only the ones modified since
+/// are embedded again.
+#[inline]
+pub fn start(config: &Config) -> Result<bool, IndexError> {
+    let Some(index_config) = config
+        .index
+        .as_ref()
+        .filter(|index| index.enabled.unwrap_or(false))
+    else {
+        return Ok(false);
+    };
+
+    let root = git(None, &["rev-parse", "--show-toplevel"])
+        .and_then(|output| output.lines().next().map(PathBuf::from))
+        .ok_or(IndexError::NotARepo)?;
+    let interval = Duration::from_secs(
+        index_config.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS),
+    );
+    let max_file_bytes = index_config
+        .max_file_bytes
+        .unwrap_or(DEFAULT_MAX_FILE_BYTES);
+
+    if let Ok(mut state) = STATE.lock() {
+        *state = Some(IndexState {
+            root: root.clone(),
+            files: BTreeMap::new(),
+            pending: 0,
+            last_scan: None,
+            error: None,
+        });
+    }
+
+    thread::Builder::new()
+        .name("llmcli-index".to_owned())
+        .spawn(move || rescan(&root, max_file_bytes, interval))?;
+
+    Ok(true)
+}
+
+#[inline]
+#[must_use]
+pub fn status() -> Option<IndexStatus> {
+    STATE.lock().ok()?.as_ref().map(|state| IndexStatus {
+        root: state.root.clone(),
+        files: state.files.len(),
+        chunks: state.files.values().map(|file| file.chunks.len()).sum(),
+        pending: state.pending,
+        last_scan: state.last_scan.map(|scanned| scanned.elapsed()),
+        error: state.error.clone(),
+    })
+}
+
+/// The `limit` chunks most similar to `query`.
+#[inline]
+#[must_use]
+pub fn search(query: &str, limit: usize) -> Vec<Hit> {
+    let query = embed(query);
+    let Ok(state) = STATE.lock() else {
+        return Vec::new();
+    };
+    let Some(ref state) = *state else {
+        return Vec::new();
+    };
+
+    let mut hits: Vec<Hit> = state
+        .files
+        .iter()
+        .flat_map(|(path, file)| {
+            file.chunks.iter().map(|chunk| Hit {
+                path: path.clone(),
+                start_line: chunk.start_line,
+                text: chunk.text.clone(),
+                score: similarity(&query, &chunk.vector),
+            })
+        })
+        .filter(|hit| hit.score >= MIN_SCORE)
+        .collect();
+    hits.sort_by(|lhs, rhs| rhs.score.total_cmp(&lhs.score));
+    hits.truncate(limit);
+
+    hits
+}
+
+/// Indexed files whose path ends with `partial`, for resolving the
+/// incomplete paths models pass to `read_file`.
+#[inline]
+#[must_use]
+pub fn complete_path(partial: &str) -> Vec<PathBuf> {
+    let partial = partial.trim().trim_start_matches("./");
+    let Ok(state) = STATE.lock() else {
+        return Vec::new();
+    };
+    let Some(ref state) = *state else {
+        return Vec::new();
+    };
+    if partial.is_empty() {
+        return Vec::new();
+    }
+
+    state
+        .files
+        .keys()
+        .filter(|path| {
+            path.as_str() == partial || path.ends_with(&format!("/{partial}"))
+        })
+        .map(|path| state.root.join(path))
+        .collect()
+}
+
+/// Formats `hits` as context for the system prompt.
+#[inline]
+#[must_use]
+pub fn prompt(hits: &[Hit]) -> Option<String> {
+    if hits.is_empty() {
+        return None;
+    }
+
+    let excerpts = hits
+        .iter()
+        .map(|hit| {
+            format!(
+                "{}:{}\n```\n{}\n```",
+                hit.path,
+                hit.start_line,
+                hit.text.trim_end()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    Some(format!(
+        "These excerpts from the current repository may be relevant to the \
+         request:\n\n{excerpts}"
+    ))
+}
+
+fn git(dir: Option<&Path>, args: &[&str]) -> Option<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command.args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Brings the index up to date with the files git tracks or would track,
+/// which skips everything matched by `.gitignore`.
+/// Scans `root` every `interval` for as long as the process runs.
+fn rescan(root: &Path, max_file_bytes: u64, interval: Duration) -> ! {
+    loop {
+        scan(root, max_file_bytes);
+        thread::sleep(interval);
+    }
+}
+
+fn scan(root: &Path, max_file_bytes: u64) {
+    let Some(listing) = git(
+        Some(root),
+        &[
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ],
+    ) else {
+        update(|state| state.error = Some("git ls-files failed".to_owned()));
+        return;
+    };
+    let paths: BTreeSet<&str> = listing
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .collect();
+    let candidates: Vec<(&str, SystemTime)> = paths
+        .iter()
+        .filter_map(|&path| {
+            let metadata = fs::metadata(root.join(path)).ok()?;
+            (metadata.is_file() && metadata.len() <= max_file_bytes)
+                .then_some((path, metadata.modified().ok()?))
+        })
+        .collect();
+
+    let mut changed = Vec::new();
+    update(|state| {
+        state.files.retain(|path, _| paths.contains(path.as_str()));
+        changed = candidates
+            .into_iter()
+            .filter(|&(path, modified)| {
+                state
+                    .files
+                    .get(path)
+                    .is_none_or(|file| file.modified != modified)
+            })
+            .collect();
+        state.pending = changed.len();
+    });
+
+    for (path, modified) in changed {
+        // Binary and non-UTF-8 files are indexed without chunks, so they
+        // are not read again until they change.
+        let chunks = fs::read_to_string(root.join(path))
+            .map(|content| chunk(&content))
+            .unwrap_or_default();
+        update(|state| {
+            state
+                .files
+                .insert(path.to_owned(), FileEntry { modified, chunks });
+            state.pending = state.pending.saturating_sub(1);
+        });
+    }
+
+    update(|state| {
+        state.last_scan = Some(Instant::now());
+        state.error = None;
+    });
+}
+
+fn update(apply: impl FnOnce(&mut IndexState)) {
+    if let Ok(mut state) = STATE.lock() {
+        if let Some(ref mut state) = *state {
+            apply(state);
+        }
+    }
+}
+
+fn chunk(content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    lines
+        .chunks(CHUNK_LINES)
+        .enumerate()
+        .filter_map(|(idx, lines)| {
+            let text = lines.join("\n");
+            (!text.trim().is_empty()).then(|| Chunk {
+                start_line: idx.saturating_mul(CHUNK_LINES).saturating_add(1),
+                vector: embed(&text),
+                text,
+            })
+        })
+        .collect()
+}
+
+/// A local embedding: identifiers and words, plus the parts of `snake_case`
+/// and `camelCase` identifiers, hashed into a fixed number of buckets and
+/// normalized. Cheap enough to index a whole repository without a network
+/// round trip.
+#[expect(
+    clippy::float_arithmetic,
+    reason = r#"
+        Vector math needs floats.
+    "#
+)]
+fn embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0_f32; usize::try_from(DIMENSIONS).unwrap_or(0)];
+
+    for word in text
+        .split(|ch: char| !ch.is_alphanumeric() && ch != '_')
+        .filter(|word| word.len() > 1)
+    {
+        let mut tokens = split_identifier(word);
+        if tokens.len() > 1 {
+            tokens.push(word.to_lowercase());
+        }
+        for token in &tokens {
+            let bucket = fnv1a(token)
+                .checked_rem(DIMENSIONS)
+                .and_then(|bucket| usize::try_from(bucket).ok());
+            if let Some(value) =
+                bucket.and_then(|bucket| vector.get_mut(bucket))
+            {
+                *value += 1.0;
+            }
+        }
+    }
+
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for value in &mut vector {
+            *value /= norm;
+        }
+    }
+
+    vector
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+
+    for ch in word.chars() {
+        if ch == '_' || (ch.is_uppercase() && prev_lower) {
+            if current.len() > 1 {
+                parts.push(current.clone());
+            }
+            current.clear();
+        }
+        if ch != '_' {
+            current.extend(ch.to_lowercase());
+        }
+        prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+    }
+    if current.len() > 1 {
+        parts.push(current);
+    }
+
+    parts
+}
+
+fn fnv1a(token: &str) -> u64 {
+    token.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[expect(
+    clippy::float_arithmetic,
+    reason = r#"
+        Vector math needs floats.
+    "#
+)]
+fn similarity(lhs: &[f32], rhs: &[f32]) -> f32 {
+    lhs.iter().zip(rhs).map(|(left, right)| left * right).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+    use std::{path::PathBuf, time::SystemTime};
+
+    use super::{
+        chunk, complete_path, embed, search, similarity, split_identifier,
+        FileEntry, IndexState, STATE,
+    };
+
+    #[test]
+    fn splits_snake_and_camel_case_identifiers() {
+        assert_eq!(
+            split_identifier("parse_HttpHeader2"),
+            ["parse", "http", "header2"]
+        );
+        assert_eq!(split_identifier("x_y"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn chunks_number_their_first_line_and_skip_blank_ones() {
+        let content = format!("{}fn main() {{}}\n", "\n".repeat(45));
+        let chunks = chunk(&content);
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks.first().map(|chunk| chunk.start_line), Some(41));
+    }
+
+    #[test]
+    fn identifier_parts_match_their_words() {
+        let query = embed("where is the http header parsed");
+
+        assert!(
+            similarity(&query, &embed("fn parse_http_header(raw: &str)"))
+                > similarity(&query, &embed("fn render_menu(items: &[Item])"))
+        );
+    }
+
+    #[test]
+    fn searches_and_completes_indexed_files() {
+        let files = BTreeMap::from([
+            (
+                "src/http.rs".to_owned(),
+                FileEntry {
+                    modified: SystemTime::UNIX_EPOCH,
+                    chunks: chunk("fn parse_http_header(raw: &str) {}\n"),
+                },
+            ),
+            (
+                "src/menu.rs".to_owned(),
+                FileEntry {
+                    modified: SystemTime::UNIX_EPOCH,
+                    chunks: chunk("fn render_menu(items: &[Item]) {}\n"),
+                },
+            ),
+        ]);
+        if let Ok(mut state) = STATE.lock() {
+            *state = Some(IndexState {
+                root: PathBuf::from("/repo"),
+                files,
+                pending: 0,
+                last_scan: None,
+                error: None,
+            });
+        }
+
+        let hits: Vec<String> = search("http header parsing", 4)
+            .into_iter()
+            .map(|hit| hit.path)
+            .collect();
+        assert_eq!(hits, ["src/http.rs"]);
+        assert_eq!(
+            complete_path("./menu.rs"),
+            [PathBuf::from("/repo/src/menu.rs")]
+        );
+        assert!(complete_path("enu.rs").is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use tokio::task::JoinHandle;
+
+use crate::{
+    params::GenerationParams, refine, Chatbot, ChatbotChatError, Message,
+};
+
+#[non_exhaustive]
+pub enum JobStatus {
+    Running,
+    Done(String),
+    Failed(String),
+}
+
+#[non_exhaustive]
+#[expect(
+    clippy::partial_pub_fields,
+    reason = r#"
+        Only `Jobs` may take the handle, when it records the outcome in
+        `status`.
+    "#
+)]
+pub struct Job {
+    pub id: usize,
+    pub label: String,
+    pub status: JobStatus,
+    handle: Option<JoinHandle<Result<String, ChatbotChatError>>>,
+}
+
+/// Requests running in the background while the REPL stays usable.
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    next_id: usize,
+}
+
+impl Jobs {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            jobs: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Sends `messages` with the `params` current when the job starts, so
+    /// later changes in the REPL do not affect it.
+    #[inline]
+    pub fn spawn(
+        &mut self,
+        label: String,
+        chatbot: Box<dyn Chatbot>,
+        messages: Vec<Message>,
+        params: GenerationParams,
+    ) -> usize {
+        self.next_id = self.next_id.saturating_add(1);
+        let handle = tokio::spawn(async move {
+            refine::complete(&*chatbot, &messages, params).await
+        });
+
+        self.jobs.push(Job {
+            id: self.next_id,
+            label,
+            status: JobStatus::Running,
+            handle: Some(handle),
+        });
+
+        self.next_id
+    }
+
+    /// Collects the jobs that finished since the last call and returns
+    /// their ids so the caller can notify the user.
+    #[inline]
+    pub async fn poll(&mut self) -> Vec<usize> {
+        let mut finished = Vec::new();
+
+        for job in &mut self.jobs {
+            let Some(handle) =
+                job.handle.take_if(|handle| handle.is_finished())
+            else {
+                continue;
+            };
+
+            job.status = match handle.await {
+                Ok(Ok(response)) => JobStatus::Done(response),
+                Ok(Err(err)) => JobStatus::Failed(err.to_string()),
+                Err(err) => JobStatus::Failed(err.to_string()),
+            };
+            finished.push(job.id);
+        }
+
+        finished
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+}
@@ -0,0 +1,162 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::ChatbotConfig;
+
+#[non_exhaustive]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyRotation {
+    RoundRobin,
+    #[default]
+    Failover,
+}
+
+pub struct KeyPool {
+    keys: Vec<String>,
+    rotation: KeyRotation,
+    next: AtomicUsize,
+    last: AtomicUsize,
+}
+
+impl KeyPool {
+    #[inline]
+    #[must_use]
+    pub const fn new(keys: Vec<String>, rotation: KeyRotation) -> Self {
+        Self {
+            keys,
+            rotation,
+            next: AtomicUsize::new(0),
+            last: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn single(key: String) -> Self {
+        Self::new(vec![key], KeyRotation::default())
+    }
+
+    /// Switches to the `api_keys` of `chatbot_config`, if it lists any.
+    #[inline]
+    pub fn configure(&mut self, chatbot_config: &ChatbotConfig) {
+        if let Some(ref api_keys) = chatbot_config.api_keys {
+            if !api_keys.is_empty() {
+                *self = Self::new(
+                    api_keys.clone(),
+                    chatbot_config.key_rotation.unwrap_or_default(),
+                );
+            }
+        }
+    }
+
+    /// Returns the keys to try for one request, in order. Round-robin
+    /// rotation hands out a single key per request, while failover offers
+    /// every key starting from the last one that worked.
+    #[inline]
+    #[must_use]
+    pub fn candidates(&self) -> Vec<(usize, &str)> {
+        let len = self.keys.len();
+
+        match self.rotation {
+            KeyRotation::RoundRobin => {
+                let idx = self
+                    .next
+                    .fetch_add(1, Ordering::Relaxed)
+                    .checked_rem(len)
+                    .unwrap_or_default();
+                self.keys
+                    .get(idx)
+                    .map(|key| vec![(idx, key.as_str())])
+                    .unwrap_or_default()
+            }
+            KeyRotation::Failover => {
+                let start = self.next.load(Ordering::Relaxed);
+                (0..len)
+                    .filter_map(|offset| {
+                        let idx =
+                            start.wrapping_add(offset).checked_rem(len)?;
+                        self.keys.get(idx).map(|key| (idx, key.as_str()))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    #[inline]
+    pub fn mark_working(&self, idx: usize) {
+        self.last.store(idx, Ordering::Relaxed);
+        if matches!(self.rotation, KeyRotation::Failover) {
+            self.next.store(idx, Ordering::Relaxed);
+        }
+    }
+
+    /// The position in the config of the key that served the last request,
+    /// when there is more than one key to tell apart.
+    #[inline]
+    #[must_use]
+    pub fn last_used(&self) -> Option<usize> {
+        (self.keys.len() > 1).then(|| self.last.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::iter;
+
+    use super::{KeyPool, KeyRotation};
+
+    fn pool(rotation: KeyRotation) -> KeyPool {
+        KeyPool::new(
+            vec!["first".to_owned(), "second".to_owned(), "third".to_owned()],
+            rotation,
+        )
+    }
+
+    #[test]
+    fn round_robin_hands_out_one_key_per_request_in_turn() {
+        let keys = pool(KeyRotation::RoundRobin);
+        let picked: Vec<_> =
+            iter::repeat_with(|| keys.candidates()).take(4).collect();
+        assert_eq!(
+            picked,
+            vec![
+                vec![(0, "first")],
+                vec![(1, "second")],
+                vec![(2, "third")],
+                vec![(0, "first")],
+            ]
+        );
+    }
+
+    #[test]
+    fn failover_starts_from_the_last_working_key() {
+        let keys = pool(KeyRotation::Failover);
+        assert_eq!(
+            keys.candidates(),
+            vec![(0, "first"), (1, "second"), (2, "third")]
+        );
+
+        keys.mark_working(2);
+        assert_eq!(
+            keys.candidates(),
+            vec![(2, "third"), (0, "first"), (1, "second")]
+        );
+        assert_eq!(keys.last_used(), Some(2));
+    }
+
+    #[test]
+    fn a_single_key_is_not_told_apart() {
+        let keys = KeyPool::single("only".to_owned());
+        keys.mark_working(0);
+        assert_eq!(keys.candidates(), vec![(0, "only")]);
+        assert_eq!(keys.last_used(), None);
+    }
+
+    #[test]
+    fn an_empty_pool_offers_nothing() {
+        let keys = KeyPool::new(Vec::new(), KeyRotation::RoundRobin);
+        assert!(keys.candidates().is_empty());
+    }
+}
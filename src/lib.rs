@@ -1,24 +1,133 @@
 extern crate alloc;
 
 use alloc::boxed::Box;
-use core::pin::Pin;
-use std::env::VarError;
+use core::{fmt, pin::Pin, time::Duration};
+use std::{
+    env::VarError,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use async_trait::async_trait;
-use futures::stream::Stream;
+use futures::stream::{Stream, StreamExt as _};
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
 
+use crate::{
+    audit::AuditError, config::ChatbotConfig, params::GenerationParams,
+    tools::ToolCall,
+};
+
+pub mod agents;
+pub mod analysis;
+pub mod audit;
+pub mod auth;
+pub mod base64;
+pub mod bindings;
+pub mod capabilities;
 pub mod chatbots;
 pub mod cli;
 pub mod commands;
 pub mod config;
+pub mod crash;
+pub mod detect;
+pub mod diff;
+pub mod editor;
+pub mod embeddings;
+pub mod env;
+pub mod exit;
+pub mod followup;
+pub mod guardrail;
+pub mod hints;
 pub mod history;
+pub mod image_gen;
+pub mod images;
+pub mod index;
+pub mod jobs;
+pub mod keys;
+pub mod listen;
+pub mod map_reduce;
+pub mod memory;
+pub mod params;
+pub mod personas;
+pub mod pricing;
+pub mod quotes;
+pub mod refine;
+pub mod repetition;
+pub mod routing;
+pub mod scrub;
 pub mod session;
+pub mod speech;
+pub mod tasks;
+pub mod tee;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod temp;
+pub mod tmux;
+pub mod tools;
+pub mod transport;
 pub mod ui;
+#[cfg(feature = "update-check")]
+pub mod update;
+
+/// A piece of a streamed response.
+///
+/// Models that think before answering stream their reasoning separately, so
+/// it can be shown without becoming part of the answer. Models that search
+/// the web send the URLs they used as citations; each citation item holds the
+/// full list so far and replaces any earlier one. Backends that report token
+/// counts send them as usage once generation is done. Generated images come
+/// as their own items, with the data base64 encoded, and are never part of
+/// the text.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamItem {
+    Text(String),
+    Reasoning(String),
+    Citations(Vec<String>),
+    Usage(TokenUsage),
+    Image { mime_type: String, data: String },
+}
+
+/// Tokens billed for a request, as reported by the provider.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    #[inline]
+    #[must_use]
+    pub const fn new(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn add(self, other: Self) -> Self {
+        Self {
+            prompt_tokens: self
+                .prompt_tokens
+                .saturating_add(other.prompt_tokens),
+            completion_tokens: self
+                .completion_tokens
+                .saturating_add(other.completion_tokens),
+        }
+    }
+}
 
 type ResponseStream = Pin<
-    Box<dyn Stream<Item = Result<String, ChatbotChatError>> + Send + 'static>,
+    Box<
+        dyn Stream<Item = Result<StreamItem, ChatbotChatError>>
+            + Send
+            + 'static,
+    >,
 >;
 
 #[non_exhaustive]
@@ -29,20 +138,119 @@ pub enum Role {
     User,
     #[serde(alias = "model")]
     Assistant,
+    Note,
+}
+
+/// The chatbot and model that produced an assistant message.
+#[non_exhaustive]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub chatbot: String,
+    pub model: String,
+    /// Position of the configured API key that was used, when the chatbot
+    /// has several.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<usize>,
+}
+
+impl Provenance {
+    #[inline]
+    #[must_use]
+    pub fn new(chatbot: &str, model: &str) -> Self {
+        Self {
+            chatbot: chatbot.to_owned(),
+            model: model.to_owned(),
+            key: None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_key(mut self, key: Option<usize>) -> Self {
+        self.key = key;
+        self
+    }
+}
+
+impl fmt::Display for Provenance {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.chatbot, self.model)
+    }
 }
 
 #[non_exhaustive]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Message {
     pub role: Role,
     pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Unix time in seconds when the message was written. Messages saved
+    /// before timestamps were recorded have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    /// An identifier chosen by the caller, kept as is in saved sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
 }
 
 impl Message {
+    /// Creates a message stamped with the current time.
+    #[inline]
+    #[must_use]
+    pub fn new(role: Role, content: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Self {
+            role,
+            content,
+            provenance: None,
+            tool_calls: Vec::new(),
+            speaker: None,
+            timestamp: Some(timestamp),
+            id: None,
+        }
+    }
+
     #[inline]
     #[must_use]
-    pub const fn new(role: Role, content: String) -> Self {
-        Self { role, content }
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_timestamp(mut self, timestamp: Option<u64>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn with_tool_calls(mut self, tool_calls: Vec<ToolCall>) -> Self {
+        self.tool_calls = tool_calls;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn with_speaker(mut self, speaker: Option<String>) -> Self {
+        self.speaker = speaker;
+        self
     }
 }
 
@@ -53,8 +261,244 @@ pub enum ChatbotChatError {
     Timeout,
     #[error("Network error: {0}.")]
     NetworkError(#[from] reqwest::Error),
+    #[error("WebSocket error: {0}.")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    #[cfg(feature = "grpc")]
+    #[error("gRPC error: {0}.")]
+    Grpc(Box<tonic::Status>),
     #[error("Unexpected response.")]
     UnexpectedResponse,
+    #[error("Rate limit exceeded.")]
+    RateLimited,
+    #[error("{0}")]
+    Api(ApiError),
+    #[error("The model refused to answer.")]
+    Refused,
+    #[error("{0}")]
+    Audit(AuditError),
+}
+
+impl ChatbotChatError {
+    #[inline]
+    #[must_use]
+    pub fn class(&self) -> &'static str {
+        match *self {
+            Self::Timeout => "timeout",
+            Self::NetworkError(_) | Self::WebSocket(_) => "network",
+            #[cfg(feature = "grpc")]
+            Self::Grpc(_) => "network",
+            Self::UnexpectedResponse => "unexpected_response",
+            Self::RateLimited => "rate_limit",
+            Self::Api(ref err) => err.class(),
+            Self::Refused => "refusal",
+            Self::Audit(_) => "audit",
+        }
+    }
+
+    /// Whether sending the same request again later may succeed.
+    #[inline]
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Self::Timeout | Self::RateLimited => true,
+            Self::Api(ref err) => err.is_retryable(),
+            Self::NetworkError(_)
+            | Self::WebSocket(_)
+            | Self::UnexpectedResponse
+            | Self::Refused
+            | Self::Audit(_) => false,
+            #[cfg(feature = "grpc")]
+            Self::Grpc(_) => false,
+        }
+    }
+
+    /// How long the provider asked to wait before trying again.
+    #[inline]
+    #[must_use]
+    pub const fn retry_after(&self) -> Option<Duration> {
+        match *self {
+            Self::Api(ref err) => err.retry_after,
+            Self::Timeout
+            | Self::NetworkError(_)
+            | Self::WebSocket(_)
+            | Self::UnexpectedResponse
+            | Self::RateLimited
+            | Self::Refused
+            | Self::Audit(_) => None,
+            #[cfg(feature = "grpc")]
+            Self::Grpc(_) => None,
+        }
+    }
+
+    /// Maps a failed HTTP status to an error, for when the response body is
+    /// not at hand.
+    #[inline]
+    #[must_use]
+    pub const fn from_status(status: StatusCode) -> Self {
+        Self::Api(ApiError::new(status))
+    }
+
+    /// Reads the status, `Retry-After` header and provider error details of
+    /// a failed response.
+    #[inline]
+    pub async fn from_response(resp: Response) -> Self {
+        Self::Api(ApiError::from_response(resp).await)
+    }
+}
+
+/// Provider error codes for accounts out of credits or quota, which waiting
+/// does not fix.
+const QUOTA_CODES: [&str; 4] = [
+    "insufficient_quota",
+    "quota_exceeded",
+    "billing_hard_limit_reached",
+    "insufficient_balance",
+];
+
+/// A request the provider answered with an error status.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// The provider's own error code, such as `insufficient_quota` or
+    /// `RESOURCE_EXHAUSTED`.
+    pub code: Option<String>,
+    pub message: Option<String>,
+    pub retry_after: Option<Duration>,
+}
+
+impl ApiError {
+    #[inline]
+    #[must_use]
+    pub const fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            code: None,
+            message: None,
+            retry_after: None,
+        }
+    }
+
+    /// Reads the error details from the `Retry-After` header and the body.
+    /// Bodies that are not JSON only leave the status.
+    #[inline]
+    pub async fn from_response(resp: Response) -> Self {
+        let mut err = Self::new(resp.status());
+        err.retry_after = resp
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        if let Ok(body) = resp.json::<Value>().await {
+            err.read_body(&body);
+        }
+        err
+    }
+
+    /// Whether the account is out of credits or quota.
+    #[inline]
+    #[must_use]
+    pub fn is_quota_exhausted(&self) -> bool {
+        self.status == StatusCode::PAYMENT_REQUIRED
+            || self
+                .code
+                .as_deref()
+                .is_some_and(|code| QUOTA_CODES.contains(&code))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        !self.is_quota_exhausted()
+            && (matches!(
+                self.status,
+                StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+            ) || self.status.is_server_error())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn class(&self) -> &'static str {
+        if self.is_quota_exhausted() {
+            return "quota";
+        }
+        match self.status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => "auth",
+            StatusCode::TOO_MANY_REQUESTS => "rate_limit",
+            StatusCode::REQUEST_TIMEOUT => "timeout",
+            status if status.is_server_error() => "server",
+            _ => "unexpected_response",
+        }
+    }
+
+    /// Most providers wrap the details in an `error` object, others put
+    /// them at the top level or send the message as `error` or `detail`.
+    /// Gemini gives the delay in the details, e.g. `"retryDelay": "17s"`.
+    fn read_body(&mut self, body: &Value) {
+        let details = body
+            .get("error")
+            .filter(|error| error.is_object())
+            .unwrap_or(body);
+        self.message = details
+            .get("message")
+            .or_else(|| body.get("detail"))
+            .or_else(|| body.get("error"))
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+        self.code = ["code", "status", "type"]
+            .into_iter()
+            .find_map(|key| details.get(key)?.as_str())
+            .map(str::to_owned);
+        if self.retry_after.is_none() {
+            self.retry_after = details
+                .get("details")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .find_map(|detail| detail.get("retryDelay")?.as_str())
+                .and_then(parse_retry_delay);
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let summary = match self.class() {
+            "quota" => "Quota or credits used up",
+            "auth" => "The API key was rejected",
+            "rate_limit" => "Rate limit exceeded",
+            "server" => "Server error",
+            _ => "Request failed",
+        };
+        write!(f, "{summary} ({})", self.status)?;
+        if let Some(ref message) = self.message {
+            write!(f, ": {}", message.trim_end_matches('.'))?;
+        }
+        if let Some(ref code) = self.code {
+            write!(f, " [{code}]")?;
+        }
+        if let Some(delay) = self.retry_after {
+            write!(f, ", retry in {}s", delay.as_secs())?;
+        }
+        write!(f, ".")
+    }
+}
+
+/// Parses a protobuf duration such as `17s` or `0.5s`, rounded up to whole
+/// seconds.
+fn parse_retry_delay(delay: &str) -> Option<Duration> {
+    let seconds = delay.strip_suffix('s')?;
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let whole: u64 = whole.parse().ok()?;
+    Some(Duration::from_secs(
+        if fraction.trim_matches('0').is_empty() {
+            whole
+        } else {
+            whole.saturating_add(1)
+        },
+    ))
 }
 
 #[non_exhaustive]
@@ -66,6 +510,32 @@ pub enum ChatbotCreationError {
     UnknownChatbot,
     #[error("Unknown model.")]
     UnknownModel,
+    #[error("Chatbot configuration missing.")]
+    MissingConfig,
+    #[error("No cloud credentials found.")]
+    MissingCredentials,
+    #[error("Invalid HTTP header: {0}.")]
+    InvalidHeader(String),
+    #[error("Built without the `{0}` feature.")]
+    MissingFeature(&'static str),
+    #[error("Failed to create HTTP client: {0}.")]
+    Client(#[from] reqwest::Error),
+}
+
+impl ChatbotCreationError {
+    #[inline]
+    #[must_use]
+    pub const fn class(&self) -> &'static str {
+        match *self {
+            Self::ApiKeyMissing(_) | Self::MissingCredentials => "auth",
+            Self::UnknownChatbot
+            | Self::UnknownModel
+            | Self::MissingConfig
+            | Self::InvalidHeader(_)
+            | Self::MissingFeature(_) => "config",
+            Self::Client(_) => "client",
+        }
+    }
 }
 
 #[non_exhaustive]
@@ -74,7 +544,7 @@ pub enum ChatbotCreationError {
 pub struct InvalidModelError;
 
 #[async_trait]
-pub trait Chatbot {
+pub trait Chatbot: Send + Sync {
     fn create(
         model: String,
         api_key: Option<String>,
@@ -82,19 +552,104 @@ pub trait Chatbot {
     where
         Self: Sized;
 
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
 
-    fn model(&self) -> &'static str;
+    fn model(&self) -> &str;
 
-    fn available_models(&self) -> &[&str];
+    /// The id the provider knows the current model by, as in
+    /// `available_models`. `model` may be a display name instead.
+    #[inline]
+    fn model_id(&self) -> &str {
+        self.model()
+    }
+
+    fn available_models(&self) -> Vec<&str>;
 
     fn change_model(
         &mut self,
         new_model: String,
     ) -> Result<(), InvalidModelError>;
 
+    /// Sends `messages` with the sampling parameters of this request.
+    /// Backends leave out the parameters they cannot send.
     async fn send_message(
         &self,
         messages: &[Message],
+        params: GenerationParams,
     ) -> Result<ResponseStream, ChatbotChatError>;
+
+    #[inline]
+    /// Sends `messages` and waits for the whole response. Reasoning and
+    /// citations are dropped, only the answer text is kept.
+    async fn send_message_once(
+        &self,
+        messages: &[Message],
+        params: GenerationParams,
+    ) -> Result<Message, ChatbotChatError> {
+        let mut content = String::new();
+        let mut stream = self.send_message(messages, params).await?;
+        while let Some(item) = stream.next().await {
+            if let StreamItem::Text(text) = item? {
+                content.push_str(&text);
+            }
+        }
+
+        Ok(Message::new(Role::Assistant, content)
+            .with_provenance(self.provenance()))
+    }
+
+    #[inline]
+    /// Asks the provider which models it currently serves. Providers
+    /// without a models endpoint report `available_models`.
+    async fn list_models(&self) -> Result<Vec<String>, ChatbotChatError> {
+        Ok(self
+            .available_models()
+            .iter()
+            .map(|&model| model.to_owned())
+            .collect())
+    }
+
+    #[inline]
+    async fn warm_up(&self) -> Result<(), ChatbotChatError> {
+        Ok(())
+    }
+
+    #[inline]
+    async fn status(&self) -> Result<Vec<String>, ChatbotChatError> {
+        Ok(Vec::new())
+    }
+
+    #[inline]
+    fn escalate(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    /// Reports which chatbot and model produced the last response. Wrappers
+    /// that delegate to other chatbots should name the one that answered.
+    fn provenance(&self) -> Provenance {
+        Provenance::new(self.name(), self.model_id()).with_key(self.key_index())
+    }
+
+    #[inline]
+    /// Position of the configured API key that served the last request, for
+    /// chatbots rotating between several.
+    fn key_index(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn configure(
+        &mut self,
+        _chatbot_config: &ChatbotConfig,
+    ) -> Result<(), ChatbotCreationError> {
+        Ok(())
+    }
+
+    #[inline]
+    /// Names of the sampling parameters, from `params::NAMES`, that this
+    /// backend sends with its requests.
+    fn supported_params(&self) -> &[&str] {
+        &[]
+    }
 }
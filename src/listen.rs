@@ -0,0 +1,154 @@
+use core::mem;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead as _, BufReader, Write as _},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use thiserror::Error;
+
+use crate::{
+    config::Config, guardrail, params::GenerationParams, ui::Printer, Chatbot,
+    Message, Role,
+};
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ListenError {
+    #[error("Failed to create FIFO {}: {}.", .0.display(), .1)]
+    Create(PathBuf, io::Error),
+    #[error("Failed to print message: {0}.")]
+    Print(io::Error),
+    #[error("Failed to read from {}: {}.", .0.display(), .1)]
+    Read(PathBuf, io::Error),
+    #[error("Failed to write response: {0}.")]
+    Write(io::Error),
+}
+
+/// Where prompts come from and responses go.
+#[non_exhaustive]
+pub struct ListenOptions {
+    pub fifo: PathBuf,
+    pub output: Option<PathBuf>,
+    pub delimiter: Option<String>,
+}
+
+impl ListenOptions {
+    #[inline]
+    #[must_use]
+    pub const fn new(
+        fifo: PathBuf,
+        output: Option<PathBuf>,
+        delimiter: Option<String>,
+    ) -> Self {
+        Self {
+            fifo,
+            output,
+            delimiter,
+        }
+    }
+}
+
+/// Reads prompts from a FIFO, creating it when missing, and answers each in
+/// one ongoing conversation that starts from `history`.
+///
+/// Every line is a prompt, or with a `delimiter` every block of lines
+/// ending with a line equal to it. Responses are appended to `output`,
+/// which may be another FIFO, or printed to stdout, and end with the
+/// delimiter too so readers can split them. When the last writer closes the
+/// FIFO it is reopened, so scripts can write to it one at a time. Failed
+/// requests are reported on stderr with `printer` and do not end the
+/// session.
+#[inline]
+pub async fn serve(
+    chatbot: &dyn Chatbot,
+    params: GenerationParams,
+    config: &Config,
+    mut history: Vec<Message>,
+    options: &ListenOptions,
+    printer: Printer,
+) -> Result<(), ListenError> {
+    if !options.fifo.exists() {
+        create_fifo(&options.fifo)?;
+    }
+
+    loop {
+        let fifo = File::open(&options.fifo)
+            .map_err(|err| ListenError::Read(options.fifo.clone(), err))?;
+        let mut block = String::new();
+        for line in BufReader::new(fifo).lines() {
+            let line = line
+                .map_err(|err| ListenError::Read(options.fifo.clone(), err))?;
+            let prompt = match options.delimiter {
+                Some(ref delimiter) if line.trim_end() != delimiter => {
+                    block.push_str(&line);
+                    block.push('\n');
+                    continue;
+                }
+                Some(_) => mem::take(&mut block),
+                None => line,
+            };
+            if prompt.trim().is_empty() {
+                continue;
+            }
+
+            history
+                .push(Message::new(Role::User, prompt.trim_end().to_owned()));
+            let messages =
+                guardrail::apply(&history, config.guardrail.as_deref());
+            match chatbot.send_message_once(&messages, params).await {
+                Ok(answer) => {
+                    write_response(options, &answer.content)?;
+                    history.push(answer);
+                }
+                Err(err) => {
+                    history.pop();
+                    printer
+                        .print_error_notice(err.class(), &err.to_string())
+                        .map_err(ListenError::Print)?;
+                }
+            }
+        }
+    }
+}
+
+/// FIFOs cannot be created with the standard library, so this defers to
+/// `mkfifo`.
+fn create_fifo(path: &Path) -> Result<(), ListenError> {
+    let status = Command::new("mkfifo")
+        .arg(path)
+        .status()
+        .map_err(|err| ListenError::Create(path.to_owned(), err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ListenError::Create(
+            path.to_owned(),
+            io::Error::other(format!("mkfifo exited with {status}")),
+        ))
+    }
+}
+
+/// Opens the output for each response, as opening a FIFO for writing waits
+/// for a reader and readers may come and go.
+fn write_response(
+    options: &ListenOptions,
+    response: &str,
+) -> Result<(), ListenError> {
+    let mut out: Box<dyn io::Write> = match options.output {
+        Some(ref path) => Box::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(ListenError::Write)?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+    writeln!(out, "{}", response.trim_end()).map_err(ListenError::Write)?;
+    if let Some(ref delimiter) = options.delimiter {
+        writeln!(out, "{delimiter}").map_err(ListenError::Write)?;
+    }
+    out.flush().map_err(ListenError::Write)
+}
@@ -1,38 +1,432 @@
+extern crate alloc;
+
+use alloc::{borrow::Cow, collections::BTreeMap, sync::Arc};
+use core::{mem, time::Duration};
 use std::{
-    io::{self, IsTerminal as _, Read as _},
+    fs,
+    io::{self, IsTerminal as _, Read as _, Write as _},
+    panic,
+    path::{Path, PathBuf},
     process,
+    time::Instant,
 };
 
-use clap::Parser as _;
 use futures::StreamExt as _;
+#[cfg(feature = "otel")]
+use llmcli::telemetry::{RequestSpan, Telemetry};
+#[cfg(feature = "update-check")]
+use llmcli::update;
 use llmcli::{
-    chatbots::{dummy::DummyChatbot, gemini::GeminiChatbot},
-    cli::{Args, ChatbotArg},
+    agents::{self, AgentsConfig, AgentsError, Conversation},
+    analysis::{self, SessionAnalysis},
+    audit::{self, AuditError, AuditLog},
+    bindings::{
+        self, ActionHandler, CancelWatch, InterruptWatch, KeyAction,
+        LastResponse,
+    },
+    capabilities,
+    chatbots::{self, registry},
+    cli::{
+        Args, AuditAction, ChatSelection, ChatbotArg, ConfigAction,
+        MemoryAction, SessionsAction,
+    },
     commands::{Command, CommandContext, CommandExecuteError},
-    config::Config,
+    config::{Config, ConfigError},
+    crash, detect,
+    diff::{DiffLine, DiffStats},
+    editor, embeddings,
+    env::SessionEnv,
+    exit,
+    followup::FollowUps,
+    guardrail,
+    hints::InputHelper,
     history::{self, HistoryError},
-    session::Session,
-    ui::Printer,
-    Chatbot, ChatbotChatError, ChatbotCreationError, Role,
+    image_gen::{self, ImageRequest},
+    images::{self, Protocol},
+    index,
+    jobs::{JobStatus, Jobs},
+    listen::{self, ListenOptions},
+    map_reduce::{self, Usage},
+    memory::{self, MemoryError, MemoryStore},
+    params::{self, GenerationParams},
+    personas::{self, Persona},
+    pricing,
+    quotes::{self, QuoteCheck},
+    refine,
+    repetition::RepetitionDetector,
+    routing,
+    session::{Session, SessionError},
+    speech::{self, SpeechSynthesizer},
+    tasks::{self, TaskError},
+    tee::Tee,
+    temp,
+    tools::Toolbox,
+    ui::{self, Printer, Progress, TerminalIntegration},
+    Chatbot, ChatbotChatError, ChatbotCreationError, Message, Role, StreamItem,
+    TokenUsage,
+};
+use rustyline::{
+    error::ReadlineError, history::DefaultHistory, DefaultEditor, Editor,
+    EventHandler,
 };
-use rustyline::{error::ReadlineError, DefaultEditor};
 use thiserror::Error;
+use tokio::{runtime::Handle, task};
 
 #[tokio::main]
+#[expect(
+    clippy::integer_division_remainder_used,
+    reason = r#"
+        `tokio::select!` picks its first branch with `%`.
+    "#
+)]
 async fn main() {
-    let args = Args::parse();
-    let printer = Printer::new(args.no_color);
-    let config = Config::load(args.config).unwrap_or_else(|err| {
-        if let Err(err) = printer.print_error_message(&err.to_string()) {
+    // The app runs on a thread of its own so that Ctrl+C ends the process
+    // here even while the app is blocked on a read.
+    let interrupted = bindings::watch_interrupt();
+    let runtime = Handle::current();
+    let app = task::spawn_blocking(move || runtime.block_on(run()));
+    tokio::select! {
+        res = app => {
+            if let Err(err) = res {
+                if let Ok(payload) = err.try_into_panic() {
+                    panic::resume_unwind(payload);
+                }
+            }
+        }
+        _ = interrupted => process::exit(exit::INTERRUPTED),
+    }
+}
+
+/// The body of `main`, on a thread of its own. It dispatches every command
+/// and ends the process with the exit code of the outcome, as `main` would.
+#[expect(
+    clippy::exit,
+    clippy::cognitive_complexity,
+    reason = r#"
+        This is `main` moved off the main thread, so that Ctrl+C can end the
+        process while it blocks on a read.
+    "#
+)]
+async fn run() {
+    let args = Args::parse_with(registry::global());
+    let printer = Printer::new(args.no_color)
+        .strict(args.non_interactive)
+        .assume_yes(args.yes);
+    ui::install(printer);
+    ui::watch_resize();
+    let loaded =
+        Config::load(args.config.clone(), &args.overrides, args.strict_config)
+            .unwrap_or_else(|err| {
+                if let Err(err) =
+                    printer.print_error("config", &err.to_string())
+                {
+                    eprintln!("Error: {err}");
+                }
+                process::exit(exit::CONFIG);
+            });
+    for warning in &loaded.warnings {
+        if let Err(err) = printer.print_warning_message(&warning.to_string()) {
             eprintln!("Error: {err}");
         }
-        process::exit(1);
-    });
-    let chatbot = create_chatbot(args.command, &config).unwrap_or_else(|err| {
-        if let Err(err) = printer.print_error_message(&err.to_string()) {
+    }
+
+    if let Some(ChatbotArg::Config {
+        action: ConfigAction::Show { origin },
+    }) = args.command
+    {
+        for (key, value, value_origin) in loaded.entries() {
+            if origin {
+                let value_origin = value_origin
+                    .map_or_else(|| "default".to_owned(), ToString::to_string);
+                println!("{key} = {value}  # {value_origin}");
+            } else {
+                println!("{key} = {value}");
+            }
+        }
+        return;
+    }
+
+    let mut config = loaded.config;
+    if args.global_memory {
+        config.memory.get_or_insert_with(Default::default).global = Some(true);
+    }
+    if let Some(retries) = args.retries {
+        config.retries = Some(retries);
+    }
+    if let Some(timeout) = args.timeout {
+        config.timeout = Some(timeout);
+    }
+
+    crash::install(Session::dir(&config).ok(), config.secrets());
+
+    // A log that is enabled but cannot be opened must stop here, as
+    // requests would otherwise go unrecorded. The `[audit]` settings are
+    // what needs fixing, so it exits as a configuration error.
+    if let Err(err) = audit::install(&config) {
+        if let Err(err) = printer.print_error("audit", &err.to_string()) {
             eprintln!("Error: {err}");
         }
-        process::exit(1);
+        process::exit(exit::CONFIG);
+    }
+
+    if let Some(ChatbotArg::Audit {
+        action: AuditAction::Verify { ref path },
+    }) = args.command
+    {
+        let path = path.clone().map_or_else(|| AuditLog::path(&config), Ok);
+        match path
+            .and_then(|path| audit::verify(&path, &AuditLog::key(&config)?))
+        {
+            Ok(count) => println!("Audit log intact, {count} entries."),
+            Err(err) => {
+                if let Err(err) = printer.print_error_message(&err.to_string())
+                {
+                    eprintln!("Error: {err}");
+                }
+                process::exit(exit::VALIDATION);
+            }
+        }
+        return;
+    }
+
+    if let Some(ChatbotArg::Memory { ref action }) = args.command {
+        if let Err(err) = run_memory(action, &config) {
+            if let Err(err) = printer.print_error_message(&err.to_string()) {
+                eprintln!("Error: {err}");
+            }
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(ChatbotArg::Sessions {
+        action: SessionsAction::Gc { dry_run },
+    }) = args.command
+    {
+        match Session::gc(&config, dry_run) {
+            Ok(expired) => {
+                let verb = if dry_run {
+                    "Would remove"
+                } else if config
+                    .sessions
+                    .as_ref()
+                    .is_some_and(|sessions| sessions.archive_path.is_some())
+                {
+                    "Archived"
+                } else {
+                    "Removed"
+                };
+                for name in expired {
+                    println!("{verb} {name}");
+                }
+            }
+            Err(err) => {
+                if let Err(err) = printer.print_error_message(&err.to_string())
+                {
+                    eprintln!("Error: {err}");
+                }
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    #[cfg(feature = "update-check")]
+    if matches!(args.command, Some(ChatbotArg::SelfUpdate)) {
+        match update::self_update().await {
+            Ok(Some(version)) => println!("Updated llmcli to {version}."),
+            Ok(None) => println!("llmcli is already up to date."),
+            Err(err) => {
+                if let Err(err) = printer.print_error_message(&err.to_string())
+                {
+                    eprintln!("Error: {err}");
+                }
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(ChatbotArg::Sessions {
+        action: SessionsAction::Diff { ref old, ref new },
+    }) = args.command
+    {
+        if let Err(err) = run_sessions_diff(old, new, &config, printer) {
+            if let Err(err) = printer.print_error(err.class(), &err.to_string())
+            {
+                eprintln!("Error: {err}");
+            }
+            process::exit(exit::code(err.class()));
+        }
+        return;
+    }
+
+    if let Some(ChatbotArg::Sessions {
+        action:
+            SessionsAction::Analyze {
+                ref name,
+                json,
+                top,
+            },
+    }) = args.command
+    {
+        if let Err(err) =
+            run_sessions_analyze(name, json, top, &config, printer)
+        {
+            if let Err(err) = printer.print_error(err.class(), &err.to_string())
+            {
+                eprintln!("Error: {err}");
+            }
+            process::exit(exit::code(err.class()));
+        }
+        return;
+    }
+
+    if config
+        .sessions
+        .as_ref()
+        .and_then(|sessions| sessions.gc_on_startup)
+        .unwrap_or(false)
+    {
+        if let Err(err) = Session::gc(&config, false) {
+            if let Err(err) = printer.print_warning_message(&format!(
+                "Session cleanup failed: {err}"
+            )) {
+                eprintln!("Error: {err}");
+            }
+        }
+    }
+
+    #[cfg(feature = "otel")]
+    let telemetry = config
+        .otlp_endpoint
+        .as_deref()
+        .map(Telemetry::init)
+        .transpose()
+        .unwrap_or_else(|err| {
+            if let Err(err) = printer.print_error_message(&err.to_string()) {
+                eprintln!("Error: {err}");
+            }
+            process::exit(1);
+        });
+
+    if let Some(ChatbotArg::Image {
+        prompt,
+        model,
+        out,
+        count,
+        size,
+    }) = args.command
+    {
+        let request = ImageRequest::new(prompt, count, size);
+        if let Err(err) =
+            run_image(&request, &model, out.as_deref(), &config).await
+        {
+            if let Err(err) = printer.print_error(err.class(), &err.to_string())
+            {
+                eprintln!("Error printing message: {err}");
+            }
+            process::exit(exit::code(err.class()));
+        }
+        return;
+    }
+
+    if let Some(ChatbotArg::Embed {
+        ref files,
+        ref model,
+    }) = args.command
+    {
+        if let Err(err) = run_embed(files, model, &config).await {
+            if let Err(err) = printer.print_error(err.class(), &err.to_string())
+            {
+                eprintln!("Error printing message: {err}");
+            }
+            process::exit(exit::code(err.class()));
+        }
+        return;
+    }
+
+    let mut listen = None;
+    match args.command {
+        Some(ChatbotArg::Listen {
+            fifo,
+            output,
+            delimiter,
+        }) => {
+            listen = Some(ListenOptions::new(fifo, output, delimiter));
+        }
+        Some(ChatbotArg::ListModels { chatbot }) => {
+            if let Err(err) = run_list_models(chatbot.as_deref(), &config).await
+            {
+                if let Err(err) =
+                    printer.print_error(err.class(), &err.to_string())
+                {
+                    eprintln!("Error printing message: {err}");
+                }
+                process::exit(exit::code(err.class()));
+            }
+            return;
+        }
+        Some(ChatbotArg::Agents {
+            config: agents_path,
+            turns,
+        }) => {
+            if let Err(err) =
+                run_agents(agents_path, turns, &config, &printer).await
+            {
+                if let Err(err) =
+                    printer.print_error(err.class(), &err.to_string())
+                {
+                    eprintln!("Error printing message: {err}");
+                }
+                if !matches!(
+                    err,
+                    ChatError::Readline(ReadlineError::Interrupted)
+                ) {
+                    process::exit(exit::code(err.class()));
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+    let interactive = !args.non_interactive
+        && args.prompt.is_none()
+        && io::stdin().is_terminal();
+    let chatbot = match create_chatbot(args.chat.as_ref(), &config) {
+        Err(ChatbotCreationError::ApiKeyMissing(err)) if interactive => {
+            let backend = match args.chat {
+                Some(ref chat) => Some(chat.name.clone()),
+                None => config.default_chatbot.clone(),
+            }
+            .filter(|name| {
+                registry::global()
+                    .get(name)
+                    .is_some_and(|chatbot| chatbot.api_key)
+            });
+            match backend {
+                Some(backend) => {
+                    prompt_api_key(&backend, &mut config, args.config, printer)
+                        .unwrap_or_else(|err| {
+                            if let Err(err) = printer
+                                .print_error(err.class(), &err.to_string())
+                            {
+                                eprintln!("Error: {err}");
+                            }
+                            process::exit(exit::code(err.class()));
+                        });
+                    create_chatbot(args.chat.as_ref(), &config)
+                }
+                None => Err(ChatbotCreationError::ApiKeyMissing(err)),
+            }
+        }
+        res => res,
+    }
+    .unwrap_or_else(|err| {
+        if let Err(err) = printer.print_error(err.class(), &err.to_string()) {
+            eprintln!("Error: {err}");
+        }
+        process::exit(exit::code(err.class()));
     });
 
     let mut session = Session::new();
@@ -40,64 +434,614 @@ async fn main() {
     if let Some(system_prompt) = args.system_prompt {
         session.add_message(Role::System, system_prompt);
     }
+    session.prefill = args.prefill.filter(|prefill| !prefill.is_empty());
+    session.footer = config.footer.unwrap_or(false);
 
-    let mut app = App::new(chatbot, &printer, session);
+    // Flags take precedence over the preset, and both over the `[params]`
+    // defaults from the config.
+    let mut params = GenerationParams::default();
+    params.temperature = args.temperature;
+    params.top_p = args.top_p;
+    params.top_k = args.top_k;
+    params.max_tokens = args.max_tokens;
+    params.presence_penalty = args.presence_penalty;
+    params.frequency_penalty = args.frequency_penalty;
+    params.repetition_penalty = args.repetition_penalty;
+    if let Some(preset) = args.preset.as_ref().or(config.preset.as_ref()) {
+        let Some(preset_params) = params::preset(preset, &config) else {
+            if let Err(err) = printer
+                .print_error("config", &format!("Unknown preset {preset}."))
+            {
+                eprintln!("Error: {err}");
+            }
+            process::exit(exit::CONFIG);
+        };
+        params = params.or(preset_params);
+    }
+    let params = params.or(config.params.unwrap_or_default());
+    if params != GenerationParams::default() {
+        session.params = Some(params);
+        if let Some(note) = params.ignored_note(&*chatbot) {
+            if let Err(err) = printer.print_warning_message(&note) {
+                eprintln!("Error: {err}");
+            }
+        }
+    }
+
+    let tee = args
+        .tee
+        .map(Tee::open)
+        .or_else(|| args.live_md.map(Tee::open_markdown))
+        .transpose()
+        .unwrap_or_else(|err| {
+            if let Err(err) = printer
+                .print_error_message(&format!("Failed to open tee file: {err}"))
+            {
+                eprintln!("Error: {err}");
+            }
+            process::exit(1);
+        });
+
+    let mut app = App::new(chatbot, &printer, session, tee, &config);
+
+    match index::start(&config) {
+        Ok(true) => {
+            app.index_results = Some(
+                config
+                    .index
+                    .as_ref()
+                    .and_then(|index| index.results)
+                    .unwrap_or(index::DEFAULT_RESULTS),
+            );
+        }
+        Ok(false) => {}
+        Err(err) => {
+            if let Err(err) = printer
+                .print_warning_message(&format!("Indexing disabled: {err}"))
+            {
+                eprintln!("Error: {err}");
+            }
+        }
+    }
+
+    if args.warm_up {
+        if let Err(err) = app.warm_up().await {
+            if let Err(err) = printer.print_error(err.class(), &err.to_string())
+            {
+                eprintln!("Error printing message: {err}");
+            }
+            process::exit(exit::code(err.class()));
+        }
+    }
+
+    if args.editor_protocol {
+        if let Err(err) = editor::serve(
+            &*app.chatbot,
+            app.session.params.unwrap_or_default(),
+            &config,
+        )
+        .await
+        {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+        return;
+    }
 
-    let res = if let Some(prompt) = args.prompt {
-        app.run_single_prompt(prompt).await
+    if let Some(ref options) = listen {
+        let history = app.session.request_messages();
+        if let Err(err) = listen::serve(
+            &*app.chatbot,
+            app.session.params.unwrap_or_default(),
+            &config,
+            history,
+            options,
+            printer,
+        )
+        .await
+        {
+            if let Err(err) = printer.print_error_message(&err.to_string()) {
+                eprintln!("Error: {err}");
+            }
+            process::exit(1);
+        }
+        return;
+    }
+
+    let res = if let Some(ref source) = args.messages_json {
+        app.run_messages_json(source).await
+    } else if let Some(prompt) = args.prompt {
+        if args.estimate {
+            app.estimate(prompt, &config)
+        } else if args.map_reduce || args.verify_quotes {
+            let chunk_chars = args.map_reduce.then(|| {
+                config
+                    .map_reduce
+                    .as_ref()
+                    .and_then(|map_reduce| map_reduce.chunk_chars)
+                    .unwrap_or(map_reduce::DEFAULT_CHUNK_CHARS)
+            });
+            app.run_with_input(prompt, chunk_chars, args.verify_quotes)
+                .await
+        } else {
+            app.run_single_prompt(prompt, args.audio_out.as_deref())
+                .await
+        }
+    } else if args.non_interactive {
+        Err(ChatError::PromptRequired)
     } else {
+        #[cfg(feature = "update-check")]
+        if let Some(notice) = update::check(&config).await {
+            if let Err(err) = printer.print_app_message(&notice.to_string()) {
+                eprintln!("Error printing message: {err}");
+            }
+        }
         app.run_repl(config).await
     };
 
+    let res = match args.ics {
+        Some(ref ics)
+            if !args.estimate
+                && matches!(res, Ok(()) | Err(ChatError::Quit)) =>
+        {
+            app.export_ics(ics).await.and(res)
+        }
+        _ => res,
+    };
+
+    #[cfg(feature = "otel")]
+    if let Some(telemetry) = telemetry {
+        if let Err(err) = telemetry.shutdown() {
+            eprintln!("Error: {err}");
+        }
+    }
+
     if let Err(err) = res {
-        if let Err(err) = printer.print_error_message(&err.to_string()) {
-            eprintln!("Error printing message: {err}");
+        // A cancelled response has already been reported where it stopped.
+        if !matches!(err, ChatError::Cancelled) {
+            if let Err(err) = printer.print_error(err.class(), &err.to_string())
+            {
+                eprintln!("Error printing message: {err}");
+            }
         }
         if !matches!(err, ChatError::Quit)
             && !matches!(err, ChatError::Readline(ReadlineError::Interrupted))
         {
-            process::exit(1);
+            process::exit(exit::code(err.class()));
         }
     }
 }
 
 fn create_chatbot(
-    chatbot: Option<ChatbotArg>,
+    chat: Option<&ChatSelection>,
     config: &Config,
 ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
-    match chatbot {
-        Some(ChatbotArg::Gemini { model }) => {
-            let api_key = config
-                .api_keys
-                .as_ref()
-                .and_then(|api_keys| api_keys.gemini.clone());
+    let Some(chat) = chat else {
+        let default_chatbot = config
+            .default_chatbot
+            .as_ref()
+            .ok_or(ChatbotCreationError::UnknownChatbot)?;
+
+        return chatbots::create(default_chatbot, None, config);
+    };
+    if chat.compat && config.compat(&chat.name).is_none() {
+        return Err(ChatbotCreationError::UnknownChatbot);
+    }
+    chatbots::create(&chat.name, chat.model.clone(), config)
+}
+
+fn prompt_api_key(
+    backend: &str,
+    config: &mut Config,
+    config_path: Option<PathBuf>,
+    printer: Printer,
+) -> Result<(), ChatError> {
+    let api_key = rpassword::prompt_password(format!("{backend} API key: "))
+        .map_err(ChatError::Read)?
+        .trim()
+        .to_owned();
+
+    if api_key.is_empty() || !config.set_api_key(backend, api_key.clone()) {
+        return Err(ChatError::ApiKeyMissing);
+    }
+    crash::add_secret(api_key.clone());
+
+    let mut rl = DefaultEditor::new()?;
+    let answer = rl.readline("Save the API key to the config file? [y/N] ")?;
+    if answer.trim().eq_ignore_ascii_case("y") {
+        Config::persist_value(
+            config_path,
+            &format!("api_keys.{backend}"),
+            api_key,
+        )?;
+        printer
+            .print_app_message("API key saved.")
+            .map_err(ChatError::Print)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the models that the provider of `name`, or of the default
+/// chatbot, currently serves, one per line.
+async fn run_list_models(
+    name: Option<&str>,
+    config: &Config,
+) -> Result<(), ChatError> {
+    let name = name
+        .or(config.default_chatbot.as_deref())
+        .ok_or(ChatError::Setup(ChatbotCreationError::UnknownChatbot))?;
+    let chatbot =
+        chatbots::create(name, None, config).map_err(ChatError::Setup)?;
+    for model in chatbot.list_models().await? {
+        println!("{model}");
+    }
+    Ok(())
+}
+
+/// Pairs the messages of two sessions in order, skipping notes, and shows
+/// how each pair differs, which models wrote the replies and how much
+/// changed overall.
+fn run_sessions_diff(
+    old: &str,
+    new: &str,
+    config: &Config,
+    printer: Printer,
+) -> Result<(), ChatError> {
+    let old_session = Session::load(old, config)?;
+    let new_session = Session::load(new, config)?;
+    let sent = |session: &Session| {
+        session
+            .messages
+            .iter()
+            .filter(|msg| msg.role != Role::Note)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let old_messages = sent(&old_session);
+    let new_messages = sent(&new_session);
 
-            GeminiChatbot::create(model.to_string(), api_key)
+    let mut stats = DiffStats::default();
+    let count = old_messages.len().max(new_messages.len());
+    for idx in 0..count {
+        let old_msg = old_messages.get(idx);
+        let new_msg = new_messages.get(idx);
+        let diff = stats.compare(
+            old_msg.map(|msg| msg.content.as_str()),
+            new_msg.map(|msg| msg.content.as_str()),
+        );
+        #[expect(
+            clippy::wildcard_enum_match_arm,
+            reason = r#"
+                `Role` is non-exhaustive outside the library, and notes are
+                filtered out above.
+            "#
+        )]
+        let role = old_msg.or(new_msg).map_or("", |msg| match msg.role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            _ => "note",
+        });
+
+        let unchanged =
+            diff.iter().all(|line| matches!(*line, DiffLine::Same(_)));
+        let verdict = match (old_msg, new_msg) {
+            (Some(_), None) => format!("only in {old}"),
+            (None, Some(_)) => format!("only in {new}"),
+            _ if unchanged => "identical".to_owned(),
+            _ => "changed".to_owned(),
+        };
+        printer
+            .print_app_message(&format!(
+                "#{} {role}: {verdict}",
+                idx.saturating_add(1)
+            ))
+            .map_err(ChatError::Print)?;
+        for (name, msg) in [(old, old_msg), (new, new_msg)] {
+            if let Some(provenance) =
+                msg.and_then(|msg| msg.provenance.as_ref())
+            {
+                println!("  {name}: {provenance}");
+            }
         }
-        Some(ChatbotArg::Dummy) => DummyChatbot::create(String::new(), None),
-        Some(_) => Err(ChatbotCreationError::UnknownChatbot),
-        None => {
-            let default_chatbot = config
-                .default_chatbot
+        if !unchanged {
+            printer.print_diff(&diff).map_err(ChatError::Print)?;
+        }
+    }
+
+    let length = |messages: &[Message]| {
+        messages
+            .iter()
+            .filter(|msg| msg.role == Role::Assistant)
+            .map(|msg| msg.content.chars().count())
+            .sum::<usize>()
+    };
+    println!();
+    printer
+        .print_app_message(&format!(
+            "{count} messages: {} identical, {} changed, {} only in {old}, {} \
+             only in {new}; +{} -{} lines; responses {} chars in {old}, {} \
+             chars in {new}",
+            stats.identical,
+            stats.changed,
+            stats.only_old,
+            stats.only_new,
+            stats.lines_added,
+            stats.lines_removed,
+            length(&old_messages),
+            length(&new_messages),
+        ))
+        .map_err(ChatError::Print)
+}
+
+/// Reports where the tokens of a saved session go, as a table or as JSON.
+fn run_sessions_analyze(
+    name: &str,
+    json: bool,
+    top: usize,
+    config: &Config,
+    printer: Printer,
+) -> Result<(), ChatError> {
+    let session = Session::load(name, config)?;
+    let analysis = SessionAnalysis::new(&session, top, config);
+    if json {
+        let report = serde_json::to_string_pretty(&analysis)
+            .map_err(SessionError::Serialize)?;
+        println!("{report}");
+        return Ok(());
+    }
+
+    printer
+        .print_app_message(&format!(
+            "{name}: about {} tokens, {} responses{}",
+            analysis.total_tokens,
+            analysis.growth.len(),
+            analysis
+                .model
                 .as_ref()
-                .ok_or(ChatbotCreationError::UnknownChatbot)?;
+                .map_or_else(String::new, |model| format!(", last by {model}"))
+        ))
+        .map_err(ChatError::Print)?;
+
+    println!();
+    println!(
+        "{:>5}  {:<20}  {:>8}  {:>5}",
+        "#", "Speaker", "Tokens", "Share"
+    );
+    for msg in &analysis.heaviest {
+        println!(
+            "{:>5}  {:<20}  {:>8}  {:>4}%  {}",
+            msg.number,
+            msg.speaker,
+            msg.tokens,
+            msg.percent,
+            "#".repeat(msg.percent.div_ceil(5))
+        );
+    }
 
-            let api_keys = config.api_keys.as_ref();
+    if !analysis.growth.is_empty() {
+        println!();
+        println!(
+            "Context per response: {}",
+            analysis
+                .growth
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" > ")
+        );
+    }
 
-            match default_chatbot.as_str() {
-                "gemini" => GeminiChatbot::create(
-                    config
-                        .default_models
-                        .as_ref()
-                        .and_then(|models| models.gemini.clone())
-                        .ok_or(ChatbotCreationError::UnknownModel)?,
-                    api_keys.and_then(|api_keys| api_keys.gemini.clone()),
-                ),
-                "dummy" => DummyChatbot::create(String::new(), None),
-                _ => Err(ChatbotCreationError::UnknownChatbot),
+    println!();
+    let cost = analysis.projected_cost.map_or_else(
+        || "unknown cost".to_owned(),
+        |cost| format!("${cost:.4}"),
+    );
+    printer
+        .print_app_message(&format!(
+            "The next {} turns would send about {} prompt tokens, {cost}.",
+            analysis::PROJECTED_TURNS,
+            analysis.projected_tokens
+        ))
+        .map_err(ChatError::Print)
+}
+
+fn run_memory(
+    action: &MemoryAction,
+    config: &Config,
+) -> Result<(), MemoryError> {
+    let mut store = MemoryStore::open(config)?;
+
+    match *action {
+        MemoryAction::List { all } => {
+            let memories: Vec<&memory::Memory> = if all {
+                store.all_memories().iter().collect()
+            } else {
+                store.memories()
+            };
+            for memory in memories {
+                match memory.project {
+                    Some(ref project) if all => println!(
+                        "#{} {}  ({})",
+                        memory.id,
+                        memory.fact,
+                        project.display()
+                    ),
+                    _ => println!("#{} {}", memory.id, memory.fact),
+                }
+            }
+            return Ok(());
+        }
+        MemoryAction::Add { ref fact } => {
+            let id = store.add(fact.clone());
+            match store.project() {
+                Some(project) => {
+                    println!("Remembered as #{id} for {}.", project.display());
+                }
+                None => println!("Remembered as #{id}."),
             }
         }
+        MemoryAction::Edit { id, ref fact } => {
+            store.edit(id, fact.clone())?;
+            println!("Updated #{id}.");
+        }
+        MemoryAction::Delete { id } => {
+            store.remove(id)?;
+            println!("Forgot #{id}.");
+        }
+        _ => return Ok(()),
     }
+
+    store.save()
+}
+
+async fn run_image(
+    request: &ImageRequest,
+    model: &str,
+    out: Option<&Path>,
+    config: &Config,
+) -> Result<(), ChatError> {
+    let generator =
+        image_gen::create(model, config).map_err(ChatError::ImageSetup)?;
+    let images = generator.generate(request).await?;
+    let count = images.len();
+
+    for (idx, image) in images.into_iter().enumerate() {
+        let path = match out {
+            Some(out) => {
+                let path =
+                    image_gen::output_path(out, idx.saturating_add(1), count);
+                images::write(&path, &image.data)
+                    .map_err(ChatError::ImageWrite)?;
+                path
+            }
+            None => images::save(&image.mime_type, &image.data)
+                .map_err(ChatError::ImageWrite)?,
+        };
+
+        if out.is_none() && io::stdout().is_terminal() {
+            images::render(&path, Protocol::detect())
+                .map_err(ChatError::Print)?;
+        } else {
+            println!("{}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints one JSON object per input with its source, the file name or `-`
+/// for stdin, and its embedding.
+async fn run_embed(
+    files: &[PathBuf],
+    model: &str,
+    config: &Config,
+) -> Result<(), ChatError> {
+    let embedder =
+        embeddings::create(model, config).map_err(ChatError::EmbedSetup)?;
+    let sources: Vec<String> = if files.is_empty() {
+        vec!["-".to_owned()]
+    } else {
+        files
+            .iter()
+            .map(|file| file.to_string_lossy().into_owned())
+            .collect()
+    };
+    let texts = sources
+        .iter()
+        .map(|source| {
+            if source == "-" {
+                read_prompt(source.clone())
+            } else {
+                fs::read_to_string(source).map_err(ChatError::Read)
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let vectors = embedder.embed(&texts).await?;
+    audit::record(
+        embedder.name(),
+        model,
+        &serde_json::to_string(&texts).unwrap_or_default(),
+        &serde_json::to_string(&vectors).unwrap_or_default(),
+    )?;
+    for (source, vector) in sources.iter().zip(vectors) {
+        println!(
+            "{}",
+            serde_json::json!({ "source": source, "embedding": vector })
+        );
+    }
+
+    Ok(())
+}
+
+async fn run_agents(
+    path: PathBuf,
+    turns: Option<usize>,
+    config: &Config,
+    printer: &Printer,
+) -> Result<(), ChatError> {
+    let agents_config = AgentsConfig::load(&path)?;
+    let max_turns = turns
+        .or(agents_config.max_turns)
+        .unwrap_or(agents::DEFAULT_MAX_TURNS);
+    let mut conversation = Conversation::new(agents_config, config)?;
+    let agent_count = conversation.agents().len();
+    let last_agent = agent_count.saturating_sub(1);
+
+    let mut rl = DefaultEditor::new()?;
+    let user_prefix = printer.get_user_prefix();
+
+    printer
+        .print_app_message(
+            "After each round, press Enter to continue, type a message to \
+             interject, or /quit to stop.",
+        )
+        .map_err(ChatError::Print)?;
+
+    for (turn, idx) in (0..agent_count).cycle().take(max_turns).enumerate() {
+        let messages = conversation.messages_for(idx);
+        let messages = guardrail::apply(&messages, config.guardrail.as_deref());
+        let Some(agent) = conversation.agents().get(idx) else {
+            break;
+        };
+
+        printer
+            .print_chatbot_prefix(agent.name())
+            .map_err(ChatError::Print)?;
+
+        let mut full_resp = String::new();
+        let mut stream = agent
+            .chatbot()
+            .send_message(&messages, GenerationParams::default())
+            .await?;
+        while let Some(result) = stream.next().await {
+            if let StreamItem::Text(text) = result? {
+                print!("{text}");
+                full_resp.push_str(&text);
+            }
+        }
+        println!();
+
+        conversation.add_agent_turn(idx, full_resp);
+
+        if idx == last_agent
+            && turn.saturating_add(1) < max_turns
+            && !printer.is_strict()
+        {
+            let input = rl.readline(&user_prefix)?;
+            let input = input.trim();
+            if input == "/quit" || input == "/q" {
+                break;
+            }
+            if !input.is_empty() {
+                conversation.add_user_turn(input.to_owned());
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Error)]
@@ -112,61 +1056,594 @@ enum ChatError {
     Chatbot(#[from] ChatbotChatError),
     #[error("{0}")]
     History(#[from] HistoryError),
+    #[error("{0}")]
+    Agents(#[from] AgentsError),
+    #[error("{0}")]
+    Config(#[from] ConfigError),
+    #[error("{0}")]
+    Session(#[from] SessionError),
+    #[error("API key missing.")]
+    ApiKeyMissing,
+    #[error("Failed to write to tee file: {0}.")]
+    Tee(io::Error),
+    #[error("{0}")]
+    Setup(ChatbotCreationError),
+    #[error("{0}")]
+    ImageSetup(ChatbotCreationError),
+    #[error("{0}")]
+    EmbedSetup(ChatbotCreationError),
+    #[error("Failed to write image: {0}.")]
+    ImageWrite(io::Error),
+    #[error("Audio output needs an API key for {0}.")]
+    AudioUnavailable(&'static str),
+    #[error("Failed to save or play audio: {0}.")]
+    Audio(io::Error),
+    #[error("{0}")]
+    Audit(#[from] AuditError),
+    #[error("{0}")]
+    Tasks(#[from] TaskError),
     #[error("User quit.")]
     Quit,
+    #[error("Response cancelled.")]
+    Cancelled,
+    #[error("The response kept repeating itself.")]
+    Repetition,
+    #[error("Quotes not found in the source: {0}.")]
+    UnverifiedQuotes(usize),
+    #[error(
+        "--non-interactive needs a prompt, pass `-` to read it from stdin."
+    )]
+    PromptRequired,
+    #[error("Invalid --messages-json input: {0}.")]
+    MessagesJson(serde_json::Error),
+    #[error("--messages-json needs at least one message.")]
+    NoMessages,
+}
+
+impl ChatError {
+    /// The stable code reported in strict mode and on telemetry spans.
+    fn class(&self) -> &'static str {
+        match *self {
+            Self::Chatbot(ref err)
+            | Self::Tasks(TaskError::Chatbot(ref err)) => err.class(),
+            Self::Setup(ref err)
+            | Self::ImageSetup(ref err)
+            | Self::EmbedSetup(ref err) => err.class(),
+            Self::Config(_) => "config",
+            Self::ApiKeyMissing | Self::AudioUnavailable(_) => "auth",
+            Self::PromptRequired | Self::MessagesJson(_) | Self::NoMessages => {
+                "usage"
+            }
+            Self::UnverifiedQuotes(_) => "validation",
+            Self::Read(_)
+            | Self::Print(_)
+            | Self::Readline(_)
+            | Self::History(_)
+            | Self::Agents(_)
+            | Self::Tee(_)
+            | Self::ImageWrite(_)
+            | Self::Audio(_)
+            | Self::Session(_)
+            | Self::Audit(_)
+            | Self::Tasks(_)
+            | Self::Quit
+            | Self::Repetition => "client",
+            Self::Cancelled => "cancelled",
+        }
+    }
 }
 
 struct App<'printer> {
     chatbot: Box<dyn Chatbot>,
     printer: &'printer Printer,
     session: Session,
+    tee: Option<Tee>,
+    guardrail: Option<String>,
+    env: SessionEnv,
+    fence_code: bool,
+    detect_repetition: bool,
+    model_advice: bool,
+    prices: Option<BTreeMap<String, f64>>,
+    jobs: Jobs,
+    last_response: LastResponse,
+    terminal: TerminalIntegration,
+    audio_player: Option<String>,
+    speech: Result<Box<dyn SpeechSynthesizer>, &'static str>,
+    follow_ups: Option<FollowUps>,
+    toolbox: Option<Toolbox>,
+    memory: Option<MemoryStore>,
+    index_results: Option<usize>,
 }
 
 impl<'printer> App<'printer> {
-    const fn new(
+    fn new(
         chatbot: Box<dyn Chatbot>,
         printer: &'printer Printer,
         session: Session,
+        tee: Option<Tee>,
+        config: &Config,
     ) -> Self {
         Self {
             chatbot,
             printer,
             session,
+            tee,
+            guardrail: config.guardrail.clone(),
+            env: SessionEnv::new(config.env.as_ref()),
+            fence_code: config.fence_code.unwrap_or(false),
+            detect_repetition: config.detect_repetition.unwrap_or(true),
+            model_advice: config.model_advice.unwrap_or(true),
+            prices: config.prices.clone(),
+            jobs: Jobs::new(),
+            last_response: LastResponse::default(),
+            terminal: TerminalIntegration::new(
+                config.terminal.as_ref(),
+                printer,
+            ),
+            audio_player: config
+                .audio
+                .as_ref()
+                .and_then(|audio| audio.player.clone()),
+            speech: speech::create(config)
+                .map_err(|_err| speech::provider(config)),
+            follow_ups: None,
+            toolbox: Toolbox::from_config(config, *printer),
+            memory: MemoryStore::open(config).ok(),
+            index_results: None,
         }
     }
 
+    async fn warm_up(&self) -> Result<(), ChatError> {
+        self.printer
+            .print_app_message(&format!(
+                "Warming up {}...",
+                self.chatbot.model()
+            ))
+            .map_err(ChatError::Print)?;
+        self.chatbot.warm_up().await?;
+        self.printer
+            .print_app_message(&format!("{} is ready.", self.chatbot.model()))
+            .map_err(ChatError::Print)?;
+
+        Ok(())
+    }
+
     async fn run_single_prompt(
         &mut self,
         prompt: String,
+        audio_out: Option<&Path>,
     ) -> Result<(), ChatError> {
-        let input = if prompt == "-" {
-            let mut input = String::new();
-            io::stdin()
-                .read_to_string(&mut input)
-                .map_err(ChatError::Read)?;
-            input
+        let input = read_prompt(prompt)?;
+        self.add_user_message(input)?;
+
+        if self.handle_chat_message(None, None).await? {
+            return Err(ChatError::Cancelled);
+        }
+
+        if let Some(out) = audio_out {
+            let text = self.last_response.lock().map_or_else(
+                |_| String::new(),
+                |last_response| last_response.clone(),
+            );
+            self.speak(&text, Some(out)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a conversation kept by another program, read as a JSON array of
+    /// messages from the file `source` or from stdin for `-`, as given apart
+    /// from the guardrail, and prints the reply. Nothing is added to the
+    /// session.
+    async fn run_messages_json(
+        &mut self,
+        source: &str,
+    ) -> Result<(), ChatError> {
+        let json = if source == "-" {
+            read_prompt(source.to_owned())?
         } else {
-            prompt
+            fs::read_to_string(source).map_err(ChatError::Read)?
         };
+        let messages: Vec<Message> =
+            serde_json::from_str(&json).map_err(ChatError::MessagesJson)?;
+        if messages.is_empty() {
+            return Err(ChatError::NoMessages);
+        }
+        let messages = guardrail::apply(&messages, self.guardrail.as_deref());
+
+        let mut full_resp = String::new();
+        let res = stream_response(
+            self.printer,
+            &*self.chatbot,
+            &messages,
+            self.session.params.unwrap_or_default(),
+            &mut self.tee,
+            &mut full_resp,
+            self.detect_repetition,
+        )
+        .await;
+        println!();
+        if matches!(res, Err(ChatError::Cancelled)) {
+            self.printer
+                .print_app_message("Response cancelled.")
+                .map_err(ChatError::Print)?;
+        }
+        res.map(|_| ())
+    }
+
+    /// Prints the input tokens and cost of the request `prompt` would send,
+    /// with the system prompt, memories and other injected context, without
+    /// sending it.
+    fn estimate(
+        &mut self,
+        prompt: String,
+        config: &Config,
+    ) -> Result<(), ChatError> {
+        let input = read_prompt(prompt)?;
+        self.add_user_message(input)?;
+
+        let messages = self.request_messages(None);
+        let tokens = Usage::estimate(&messages, "").input_tokens;
+        self.printer
+            .print_app_message(&estimate_message(
+                tokens,
+                self.chatbot.model_id(),
+                config,
+            ))
+            .map_err(ChatError::Print)
+    }
+
+    /// Extracts the action items of the conversation and writes the dated
+    /// ones to `path` as calendar events.
+    async fn export_ics(&self, path: &Path) -> Result<(), ChatError> {
+        let history = self.session.request_messages();
+        if !history.iter().any(|msg| msg.role == Role::User) {
+            return Ok(());
+        }
+        let history = guardrail::apply(&history, self.guardrail.as_deref());
+
+        let items = tasks::extract(&*self.chatbot, &history).await?;
+        let count = tasks::write_ics(path, &items)?;
+        self.printer
+            .print_app_message(&format!(
+                "Wrote {count} events to {}.",
+                path.display()
+            ))
+            .map_err(ChatError::Print)
+    }
+
+    /// Applies `prompt` to the source text on stdin. With `chunk_chars`,
+    /// longer input is answered part by part. With `verify_quotes`, quotes in
+    /// the answer are checked against the source.
+    async fn run_with_input(
+        &mut self,
+        prompt: String,
+        chunk_chars: Option<usize>,
+        verify_quotes: bool,
+    ) -> Result<(), ChatError> {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(ChatError::Read)?;
+
+        let chunks = chunk_chars
+            .map(|chunk_chars| map_reduce::split(&input, chunk_chars))
+            .filter(|chunks| chunks.len() > 1);
+        if let Some(chunks) = chunks {
+            self.answer_in_parts(&prompt, chunks).await?;
+        } else {
+            self.add_user_message(format!("{prompt}\n\n{input}"))?;
+            if self.handle_chat_message(None, None).await? {
+                return Err(ChatError::Cancelled);
+            }
+        }
+
+        if verify_quotes {
+            self.verify_quotes(&input)?;
+        }
 
-        self.session.add_message(Role::User, input);
+        Ok(())
+    }
+
+    /// Answers `prompt` for each chunk separately, then combines the partial
+    /// answers in a final request.
+    async fn answer_in_parts(
+        &mut self,
+        prompt: &str,
+        chunks: Vec<&str>,
+    ) -> Result<(), ChatError> {
+        let history = self.session.request_messages();
+        let history = guardrail::apply(&history, self.guardrail.as_deref());
+        let total = chunks.len();
+        let mut usage = Usage::default();
+        let mut partials = Vec::with_capacity(total);
+        for (idx, chunk) in chunks.into_iter().enumerate() {
+            self.printer
+                .print_app_message(&format!(
+                    "Processing part {}/{total}...",
+                    idx.saturating_add(1)
+                ))
+                .map_err(ChatError::Print)?;
 
+            let messages =
+                map_reduce::map_messages(&history, prompt, chunk, idx, total);
+            self.terminal.start_progress().map_err(ChatError::Print)?;
+            let res = refine::complete(
+                &*self.chatbot,
+                &messages,
+                self.session.params.unwrap_or_default(),
+            )
+            .await;
+            self.terminal
+                .finish_progress(res.is_ok(), self.chatbot.name())
+                .map_err(ChatError::Print)?;
+            let partial = res?;
+
+            let chunk_usage = Usage::estimate(&messages, &partial);
+            self.printer
+                .print_app_message(&format!(
+                    "Part {}/{total}: ~{} tokens in, ~{} tokens out.",
+                    idx.saturating_add(1),
+                    chunk_usage.input_tokens,
+                    chunk_usage.output_tokens
+                ))
+                .map_err(ChatError::Print)?;
+            usage = usage.add(chunk_usage);
+            partials.push(partial);
+        }
+
+        self.session.add_message(
+            Role::User,
+            map_reduce::reduce_prompt(prompt, &partials),
+        );
+        if self.handle_chat_message(None, None).await? {
+            return Err(ChatError::Cancelled);
+        }
+
+        let messages = self.session.request_messages();
+        if let Some((answer, request)) = messages.split_last() {
+            usage = usage.add(Usage::estimate(request, &answer.content));
+        }
         self.printer
-            .print_chatbot_prefix(self.chatbot.name())
+            .print_app_message(&format!(
+                "Total: ~{} tokens in, ~{} tokens out over {} requests.",
+                usage.input_tokens,
+                usage.output_tokens,
+                total.saturating_add(1)
+            ))
             .map_err(ChatError::Print)?;
 
-        self.handle_chat_message().await?;
+        Ok(())
+    }
+
+    /// Flags quotes in the last response that do not occur in `source`, and
+    /// fails if there are any so scripts can tell.
+    fn verify_quotes(&self, source: &str) -> Result<(), ChatError> {
+        let response = self.last_response.lock().map_or_else(
+            |_| String::new(),
+            |last_response| last_response.clone(),
+        );
+        let checks = quotes::verify(&response, source);
+        let fabricated: Vec<&QuoteCheck> =
+            checks.iter().filter(|check| !check.found).collect();
+
+        for check in &fabricated {
+            self.printer
+                .print_warning_message(&format!(
+                    "Quote not found in the source: \"{}\"",
+                    check.quote
+                ))
+                .map_err(ChatError::Print)?;
+        }
+        self.printer
+            .print_app_message(&format!(
+                "{} of {} quotes verified against the source.",
+                checks.len().saturating_sub(fabricated.len()),
+                checks.len()
+            ))
+            .map_err(ChatError::Print)?;
+
+        if fabricated.is_empty() {
+            Ok(())
+        } else {
+            Err(ChatError::UnverifiedQuotes(fabricated.len()))
+        }
+    }
+
+    /// Synthesizes `text` and writes it to `out`, or to a temporary file
+    /// that is played with the configured player.
+    async fn speak(
+        &self,
+        text: &str,
+        out: Option<&Path>,
+    ) -> Result<(), ChatError> {
+        let synthesizer = self
+            .speech
+            .as_ref()
+            .map_err(|&provider| ChatError::AudioUnavailable(provider))?;
+        let wav = synthesizer.synthesize(text).await?;
+
+        let path = if let Some(out) = out {
+            fs::write(out, wav).map_err(ChatError::Audio)?;
+            out.to_path_buf()
+        } else {
+            let (path, mut file) = temp::private_file("llmcli-audio", "wav")
+                .map_err(ChatError::Audio)?;
+            file.write_all(&wav).map_err(ChatError::Audio)?;
+            path
+        };
+
+        match self.audio_player {
+            Some(ref player) if out.is_none() => {
+                process::Command::new(player)
+                    .arg(&path)
+                    .status()
+                    .map_err(ChatError::Audio)?;
+            }
+            _ => self
+                .printer
+                .print_app_message(&format!(
+                    "Audio saved to {}",
+                    path.display()
+                ))
+                .map_err(ChatError::Print)?,
+        }
+
+        Ok(())
+    }
+
+    /// Looks up remembered facts relevant to the latest prompt. The store is
+    /// reread first, as `/remember` and `/memories` write to it directly.
+    fn recall_memories(&mut self, messages: &[Message]) -> Option<String> {
+        let store = self.memory.as_mut()?;
+        if let Err(err) = store.reload() {
+            if let Err(err) =
+                self.printer.print_warning_message(&err.to_string())
+            {
+                eprintln!("Error: {err}");
+            }
+            return None;
+        }
+        let prompt =
+            messages.iter().rev().find(|msg| msg.role == Role::User)?;
+        memory::prompt(&store.relevant(&prompt.content))
+    }
+
+    /// Looks up repository excerpts relevant to the latest prompt.
+    fn retrieve_context(&self, messages: &[Message]) -> Option<String> {
+        let results = self.index_results?;
+        let prompt =
+            messages.iter().rev().find(|msg| msg.role == Role::User)?;
+        index::prompt(&index::search(&prompt.content, results))
+    }
+
+    async fn suggest_follow_ups(&mut self) -> Result<(), ChatError> {
+        let Some(ref mut follow_ups) = self.follow_ups else {
+            return Ok(());
+        };
+
+        let messages = self.session.request_messages();
+        let messages = guardrail::apply(&messages, self.guardrail.as_deref());
+        match follow_ups.suggest(&*self.chatbot, &messages).await {
+            Ok(suggestions) => {
+                for (idx, suggestion) in suggestions.iter().enumerate() {
+                    self.printer
+                        .print_app_message(&format!(
+                            "{}. {suggestion}",
+                            idx.saturating_add(1)
+                        ))
+                        .map_err(ChatError::Print)?;
+                }
+            }
+            Err(err) => self
+                .printer
+                .print_warning_message(&format!(
+                    "Could not suggest follow-ups: {err}"
+                ))
+                .map_err(ChatError::Print)?,
+        }
+
+        Ok(())
+    }
+
+    async fn notify_finished_jobs(&mut self) -> Result<(), ChatError> {
+        for id in self.jobs.poll().await {
+            let failed = self
+                .jobs
+                .get(id)
+                .is_some_and(|job| matches!(job.status, JobStatus::Failed(_)));
+            let message = if failed {
+                format!("Job {id} failed, see /jobs {id}.")
+            } else {
+                format!("Job {id} finished, see /jobs {id}.")
+            };
+            self.printer
+                .print_app_message(&message)
+                .map_err(ChatError::Print)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_user_message(&mut self, input: String) -> Result<(), ChatError> {
+        if !self.fence_code {
+            self.session.add_message(Role::User, input);
+            return Ok(());
+        }
+
+        let fenced = detect::fence(&input);
+        if let Cow::Owned(fenced) = fenced {
+            self.printer
+                .print_code_block(&fenced)
+                .map_err(ChatError::Print)?;
+            self.session.add_message(Role::User, fenced);
+        } else {
+            self.session.add_message(Role::User, input);
+        }
 
         Ok(())
     }
 
     async fn run_repl(&mut self, config: Config) -> Result<(), ChatError> {
-        let mut rl = DefaultEditor::new()?;
+        let suggestions = config.suggestions.unwrap_or(false);
+        let editor_config = rustyline::Config::builder()
+            .edit_mode(config.edit_mode.unwrap_or_default().into())
+            .build();
+        let mut rl =
+            Editor::<InputHelper, DefaultHistory>::with_config(editor_config)?;
+        rl.set_helper(Some(InputHelper::new(
+            suggestions,
+            self.printer.no_color(),
+        )));
+        for (key, &action) in config.keybindings.iter().flatten() {
+            if matches!(action, KeyAction::CancelStream) {
+                // Streams are not read through rustyline, so the key is
+                // watched for separately.
+                if !bindings::set_cancel_key(key) {
+                    self.printer
+                        .print_warning_message(&format!(
+                            "Unknown key `{key}` in keybindings."
+                        ))
+                        .map_err(ChatError::Print)?;
+                }
+                continue;
+            }
+            let Some(key_event) = bindings::parse_key(key) else {
+                self.printer
+                    .print_warning_message(&format!(
+                        "Unknown key `{key}` in keybindings."
+                    ))
+                    .map_err(ChatError::Print)?;
+                continue;
+            };
+            rl.bind_sequence(
+                key_event,
+                EventHandler::Conditional(Box::new(ActionHandler::new(
+                    action,
+                    Arc::clone(&self.last_response),
+                ))),
+            );
+        }
         let history_file = history::locate_file(&config)?;
         rl.load_history(&*history_file)?;
         let user_prefix = self.printer.get_user_prefix();
+        match FollowUps::from_config(&config) {
+            Ok(follow_ups) => self.follow_ups = follow_ups,
+            Err(err) => self
+                .printer
+                .print_warning_message(&format!(
+                    "Follow-up suggestions disabled: {err}"
+                ))
+                .map_err(ChatError::Print)?,
+        }
 
         loop {
+            crash::record(&self.session, &*self.chatbot);
+            self.terminal
+                .set_title(self.session.name.as_deref(), &*self.chatbot)
+                .map_err(ChatError::Print)?;
+            self.notify_finished_jobs().await?;
+
             let input = match rl.readline(&user_prefix) {
                 Ok(line) => Ok(line),
                 Err(err) => {
@@ -176,6 +1653,11 @@ impl<'printer> App<'printer> {
                     Err(err)
                 }
             }?;
+            let input = self
+                .follow_ups
+                .as_mut()
+                .and_then(|follow_ups| follow_ups.select(&input))
+                .unwrap_or(input);
 
             if input.trim().is_empty() {
                 continue;
@@ -196,9 +1678,12 @@ impl<'printer> App<'printer> {
                             &mut self.chatbot,
                             self.printer,
                             &config,
+                            &mut self.tee,
+                            &mut self.env,
+                            &mut self.jobs,
                         );
 
-                        if let Err(err) = command.execute(&mut context) {
+                        if let Err(err) = command.execute(&mut context).await {
                             match err {
                                 CommandExecuteError::Quit => {
                                     rl.save_history(&&*history_file)?;
@@ -206,7 +1691,14 @@ impl<'printer> App<'printer> {
                                 }
                                 CommandExecuteError::Print(_)
                                 | CommandExecuteError::ChatbotSwitch(_)
+                                | CommandExecuteError::Chatbot(_)
                                 | CommandExecuteError::Session(_)
+                                | CommandExecuteError::Tee(_)
+                                | CommandExecuteError::Export(_)
+                                | CommandExecuteError::Tmux(_)
+                                | CommandExecuteError::Usage(_)
+                                | CommandExecuteError::Memory(_)
+                                | CommandExecuteError::Tasks(_)
                                 | _ => self
                                     .printer
                                     .print_error_message(&err.to_string())
@@ -222,13 +1714,39 @@ impl<'printer> App<'printer> {
                 continue;
             }
 
-            self.session.add_message(Role::User, input);
+            if suggestions {
+                rl.add_history_entry(&input)?;
+            }
 
-            self.printer
-                .print_chatbot_prefix(self.chatbot.name())
-                .map_err(ChatError::Print)?;
+            let mentioned = Persona::from_mention(&input, &config)
+                .map(|(persona, prompt)| (persona, prompt.to_owned()));
+            let routed = routing::parse_model_override(&input)
+                .map(|(model, prompt)| (model.to_owned(), prompt.to_owned()));
+            let turn = if let Some((persona, prompt)) = mentioned {
+                persona
+                    .chatbot(&config)
+                    .map(|chatbot| (Some(chatbot), Some(persona), prompt))
+            } else if let Some((model, prompt)) = routed {
+                chatbots::create_for_model(&model, &config)
+                    .map(|chatbot| (Some(chatbot), None, prompt))
+            } else {
+                Ok((None, None, input))
+            };
+            let (turn_chatbot, persona, input) = match turn {
+                Ok(turn) => turn,
+                Err(err) => {
+                    self.printer
+                        .print_error_message(&err.to_string())
+                        .map_err(ChatError::Print)?;
+                    continue;
+                }
+            };
+
+            self.add_user_message(input)?;
 
-            self.handle_chat_message().await?;
+            self.handle_chat_message(turn_chatbot, persona.as_ref())
+                .await?;
+            self.suggest_follow_ups().await?;
 
             if !io::stdin().is_terminal() {
                 break Ok(());
@@ -236,26 +1754,621 @@ impl<'printer> App<'printer> {
         }
     }
 
-    async fn handle_chat_message(&mut self) -> Result<(), ChatError> {
+    /// The messages sent for the next response: the session with the
+    /// guardrail, recalled memories, repository context and tool
+    /// instructions added.
+    fn request_messages(&mut self, persona: Option<&Persona>) -> Vec<Message> {
+        let messages = self.session.request_messages();
+        let messages = persona.map_or_else(
+            || personas::label_speakers(&messages, None),
+            |persona| persona.messages(&messages),
+        );
+        let memory_prompt = self.recall_memories(&messages);
+        let messages = guardrail::append(&messages, memory_prompt.as_deref());
+        let index_prompt = self.retrieve_context(&messages);
+        let messages = guardrail::append(&messages, index_prompt.as_deref());
+        let tools_prompt = self.toolbox.as_ref().map(Toolbox::prompt);
+        let messages = guardrail::append(&messages, tools_prompt.as_deref());
+        guardrail::apply(&messages, self.guardrail.as_deref()).into_owned()
+    }
+
+    /// Points out a better suited model for `messages`, if there is one.
+    fn print_model_advice(
+        &self,
+        chatbot: &dyn Chatbot,
+        messages: &[Message],
+    ) -> Result<(), ChatError> {
+        // Advice is for a person at the terminal, not for scripts reading
+        // the answer.
+        if !self.model_advice
+            || !io::stdout().is_terminal()
+            || self.printer.is_strict()
+        {
+            return Ok(());
+        }
+        for note in capabilities::advise(
+            chatbot.model_id(),
+            &chatbot.available_models(),
+            messages,
+        ) {
+            self.printer.print_advice(&note).map_err(ChatError::Print)?;
+        }
+        Ok(())
+    }
+
+    /// Sends the session and adds the reply to it. Returns whether the
+    /// response was cancelled, which only ends the turn in the REPL but
+    /// fails a single prompt.
+    async fn handle_chat_message(
+        &mut self,
+        turn_chatbot: Option<Box<dyn Chatbot>>,
+        persona: Option<&Persona>,
+    ) -> Result<bool, ChatError> {
+        let started = Instant::now();
+        let mut messages = self.request_messages(persona);
+        let chatbot = turn_chatbot.as_deref().unwrap_or(&*self.chatbot);
+        let params = self.session.params.unwrap_or_default();
+        let speaker = persona.map_or_else(|| chatbot.name(), Persona::name);
+
+        self.print_model_advice(chatbot, &messages)?;
+        self.printer
+            .print_chatbot_prefix(speaker)
+            .map_err(ChatError::Print)?;
+        if let Some(ref mut tee) = self.tee {
+            let prompt = self
+                .session
+                .messages
+                .last()
+                .filter(|msg| msg.role == Role::User)
+                .map(|msg| msg.content.as_str());
+            tee.start_response(prompt, speaker)
+                .map_err(ChatError::Tee)?;
+        }
+
+        // The prefill is sent as the start of the reply for the model to
+        // continue, and is part of the answer as far as the user sees it.
         let mut full_resp = String::new();
+        let mut prefilled = self.session.prefill.is_some();
+        if let Some(ref prefill) = self.session.prefill {
+            messages.push(Message::new(Role::Assistant, prefill.clone()));
+            print!("{prefill}");
+            if let Some(ref mut tee) = self.tee {
+                tee.write_chunk(prefill).map_err(ChatError::Tee)?;
+            }
+            full_resp.push_str(prefill);
+        }
+        let mut tool_calls = Vec::new();
+        // Where the current tool round starts in the answer, which keeps
+        // the text of every round.
+        let mut round_start = 0_usize;
+        let mut rounds = 0_usize;
+        let mut stopped = None;
+        let mut turn_usage: Option<TokenUsage> = None;
+        loop {
+            #[cfg(feature = "otel")]
+            let span = RequestSpan::start(chatbot.name(), chatbot.model());
 
-        let mut stream =
-            self.chatbot.send_message(&self.session.messages).await?;
+            // What the answer holds before this request, such as the prefill
+            // or earlier tool rounds, which a retry has to keep.
+            let kept = full_resp.len();
+            self.terminal.start_progress().map_err(ChatError::Print)?;
+            let res = stream_response(
+                self.printer,
+                chatbot,
+                &messages,
+                params,
+                &mut self.tee,
+                &mut full_resp,
+                self.detect_repetition,
+            )
+            .await;
+            self.terminal
+                .finish_progress(res.is_ok(), chatbot.name())
+                .map_err(ChatError::Print)?;
 
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(text) => {
-                    print!("{text}");
-                    full_resp.push_str(&text);
+            #[cfg(feature = "otel")]
+            span.finish(
+                full_resp.chars().count(),
+                res.as_ref().ok().copied().flatten(),
+                res.as_ref().err().map(ChatError::class),
+            );
+
+            let usage = match res {
+                Ok(usage) => usage,
+                Err(err @ (ChatError::Cancelled | ChatError::Repetition)) => {
+                    stopped = Some(err);
+                    None
                 }
                 Err(err) => {
-                    return Err(err.into());
+                    if full_resp.len() == kept
+                        || !is_dropped_stream(&err)
+                        || !io::stdin().is_terminal()
+                        || self.printer.is_strict()
+                    {
+                        return Err(err);
+                    }
+                    match recover_dropped_stream(
+                        self.printer,
+                        chatbot,
+                        &messages,
+                        params,
+                        &mut self.tee,
+                        &mut full_resp,
+                        kept,
+                        err,
+                        self.detect_repetition,
+                    )
+                    .await
+                    {
+                        Err(
+                            err
+                            @ (ChatError::Cancelled | ChatError::Repetition),
+                        ) => {
+                            stopped = Some(err);
+                            None
+                        }
+                        res => res?,
+                    }
                 }
+            };
+            if let Some(usage) = usage {
+                self.session.record_usage(usage, &chatbot.provenance());
+                turn_usage =
+                    Some(turn_usage.map_or(usage, |total| total.add(usage)));
+            }
+
+            if stopped.is_some() {
+                break;
+            }
+            let Some(ref toolbox) = self.toolbox else {
+                break;
+            };
+            let round = full_resp.get(round_start..).unwrap_or_default();
+            let calls = toolbox.run(round);
+            if calls.is_empty() || rounds >= toolbox.max_rounds() {
+                break;
             }
+            rounds = rounds.saturating_add(1);
+            if mem::take(&mut prefilled) {
+                messages.pop();
+            }
+
+            println!();
+            for call in &calls {
+                self.printer
+                    .print_app_message(&format!(
+                        "[{}] {} => {}",
+                        call.tool, call.input, call.output
+                    ))
+                    .map_err(ChatError::Print)?;
+            }
+            messages.push(Message::new(Role::Assistant, round.to_owned()));
+            if !full_resp.ends_with('\n') {
+                full_resp.push('\n');
+            }
+            round_start = full_resp.len();
+            messages.push(Message::new(
+                Role::User,
+                Toolbox::results_message(&calls),
+            ));
+            tool_calls.extend(calls);
+            self.printer
+                .print_chatbot_prefix(speaker)
+                .map_err(ChatError::Print)?;
         }
 
-        self.session.add_message(Role::Assistant, full_resp);
+        if let Some(ref mut tee) = self.tee {
+            tee.finish_response().map_err(ChatError::Tee)?;
+        }
 
-        Ok(())
+        let ends_line = stopped.is_some() || full_resp.ends_with('\n');
+        let cancelled = matches!(stopped, Some(ChatError::Cancelled));
+        if stopped.is_some() {
+            println!();
+            let message = if cancelled {
+                "Response cancelled."
+            } else {
+                "Stopped the response as it kept repeating itself. Try /retry \
+                 after raising the penalties, e.g. /set frequency_penalty 0.5."
+            };
+            self.printer
+                .print_app_message(message)
+                .map_err(ChatError::Print)?;
+            // With nothing to keep, the prompt is dropped as well so the
+            // history does not end with an unanswered message.
+            if full_resp.is_empty() {
+                if self
+                    .session
+                    .messages
+                    .last()
+                    .is_some_and(|msg| msg.role == Role::User)
+                {
+                    self.session.messages.pop();
+                }
+                return Ok(cancelled);
+            }
+        }
+
+        if let Ok(mut last_response) = self.last_response.lock() {
+            last_response.clone_from(&full_resp);
+        }
+
+        let provenance = chatbot.provenance();
+        if self.session.footer
+            && io::stdout().is_terminal()
+            && !self.printer.is_strict()
+        {
+            if !ends_line {
+                println!();
+            }
+            let price =
+                pricing::listed_price(&provenance.model, self.prices.as_ref());
+            self.printer
+                .print_footer(&footer(
+                    &provenance.model,
+                    started.elapsed(),
+                    turn_usage,
+                    price,
+                ))
+                .map_err(ChatError::Print)?;
+        }
+
+        let spoken = self.session.audio.then(|| full_resp.clone());
+
+        self.session.messages.push(
+            Message::new(Role::Assistant, full_resp)
+                .with_provenance(provenance)
+                .with_tool_calls(tool_calls)
+                .with_speaker(persona.map(|persona| persona.name().to_owned())),
+        );
+
+        if let Some(text) = spoken {
+            if let Err(err) = self.speak(&text, None).await {
+                self.printer
+                    .print_error_message(&err.to_string())
+                    .map_err(ChatError::Print)?;
+            }
+        }
+
+        Ok(cancelled)
+    }
+}
+
+/// What `--estimate` prints for `tokens` input tokens sent to `model`, an
+/// id as priced in `pricing` and under `[prices]`.
+fn estimate_message(tokens: usize, model: &str, config: &Config) -> String {
+    pricing::input_price(model, config).map_or_else(
+        || {
+            format!(
+                "~{tokens} input tokens, no price known for {model}, set it \
+                 under [prices] in the config."
+            )
+        },
+        |price| {
+            format!(
+                "~{tokens} input tokens, ~${:.4} with {model}.",
+                pricing::cost(tokens, price)
+            )
+        },
+    )
+}
+
+/// The line under a response, e.g. `gemini-1.5-flash · 2.4s · 812 tok ·
+/// $0.0007 input`. Tokens are left out when the provider reported no usage,
+/// and the cost when the model has no known price. Only input prices are
+/// known, so the cost is that of the prompt tokens and labelled as such.
+fn footer(
+    model: &str,
+    elapsed: Duration,
+    usage: Option<TokenUsage>,
+    price: Option<f64>,
+) -> String {
+    let mut parts =
+        vec![model.to_owned(), format!("{:.1}s", elapsed.as_secs_f64())];
+    if let Some(usage) = usage {
+        parts.push(format!(
+            "{} tok",
+            usage.prompt_tokens.saturating_add(usage.completion_tokens)
+        ));
+        if let Some(price) = price {
+            let prompt_tokens =
+                usize::try_from(usage.prompt_tokens).unwrap_or(usize::MAX);
+            parts.push(format!(
+                "${:.4} input",
+                pricing::cost(prompt_tokens, price)
+            ));
+        }
+    }
+    parts.join(" \u{b7} ")
+}
+
+const CONTINUE_PROMPT: &str = "Your previous answer was cut off. Continue \
+                               exactly where it stopped, without repeating \
+                               anything.";
+
+/// Streams a response to stdout and returns the token usage, if the
+/// provider reported it. Reasoning is only shown on a terminal and never
+/// becomes part of the answer, the tee file or the session. Images are
+/// saved and shown, or their path printed, and stay out of all three too.
+///
+/// Ctrl+C drops the request and returns `ChatError::Cancelled`, leaving what
+/// arrived so far in `full_resp`. With `detect_repetition`, a response that
+/// keeps repeating itself is dropped the same way with
+/// `ChatError::Repetition`.
+#[expect(
+    clippy::integer_division_remainder_used,
+    reason = r#"
+        `tokio::select!` picks its first branch with `%`.
+    "#
+)]
+async fn stream_response(
+    printer: &Printer,
+    chatbot: &dyn Chatbot,
+    messages: &[Message],
+    params: GenerationParams,
+    tee: &mut Option<Tee>,
+    full_resp: &mut String,
+    detect_repetition: bool,
+) -> Result<Option<TokenUsage>, ChatError> {
+    let mut interrupt = InterruptWatch::start();
+    let mut cancel_key = CancelWatch::start();
+    let mut repetition = detect_repetition.then(RepetitionDetector::new);
+    let mut progress = Progress::start(printer);
+    let mut stream = tokio::select! {
+        stream = chatbot.send_message(messages, params) => stream?,
+        () = bindings::interrupted(&mut interrupt) => {
+            progress.finish().map_err(ChatError::Print)?;
+            return Err(ChatError::Cancelled);
+        }
+        () = bindings::cancelled(&mut cancel_key) => {
+            progress.finish().map_err(ChatError::Print)?;
+            return Err(ChatError::Cancelled);
+        }
+    };
+    let show_reasoning = io::stdout().is_terminal();
+    let mut reasoning = false;
+    let mut sources = Vec::new();
+    let mut usage = None;
+
+    loop {
+        let next = tokio::select! {
+            next = stream.next() => next,
+            () = bindings::interrupted(&mut interrupt) => {
+                progress.finish().map_err(ChatError::Print)?;
+                return Err(ChatError::Cancelled);
+            }
+            () = bindings::cancelled(&mut cancel_key) => {
+                progress.finish().map_err(ChatError::Print)?;
+                return Err(ChatError::Cancelled);
+            }
+        };
+        let Some(result) = next else {
+            break;
+        };
+        let text = match result? {
+            StreamItem::Reasoning(text) => {
+                if show_reasoning {
+                    printer.print_reasoning(&text).map_err(ChatError::Print)?;
+                    reasoning = true;
+                }
+                continue;
+            }
+            StreamItem::Text(text) => text,
+            StreamItem::Citations(urls) => {
+                sources = urls;
+                continue;
+            }
+            StreamItem::Usage(reported) => {
+                usage = Some(reported);
+                continue;
+            }
+            StreamItem::Image { mime_type, data } => {
+                let path = images::save(&mime_type, &data)
+                    .map_err(ChatError::ImageWrite)?;
+                if io::stdout().is_terminal() && !printer.is_strict() {
+                    images::render(&path, Protocol::detect())
+                        .map_err(ChatError::Print)?;
+                } else {
+                    println!("[image: {}]", path.display());
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if reasoning {
+            println!("\n");
+            reasoning = false;
+        }
+        progress.update(&text).map_err(ChatError::Print)?;
+        print!("{text}");
+        if let Some(ref mut tee) = *tee {
+            tee.write_chunk(&text).map_err(ChatError::Tee)?;
+        }
+        full_resp.push_str(&text);
+        if repetition
+            .as_mut()
+            .is_some_and(|detector| detector.push(&text))
+        {
+            progress.finish().map_err(ChatError::Print)?;
+            return Err(ChatError::Repetition);
+        }
+    }
+    progress.finish().map_err(ChatError::Print)?;
+    if !sources.is_empty() {
+        println!("\n");
+        printer.print_sources(&sources).map_err(ChatError::Print)?;
+    }
+
+    Ok(usage)
+}
+
+/// Reads the prompt from stdin if it is `-`.
+fn read_prompt(prompt: String) -> Result<String, ChatError> {
+    if prompt != "-" {
+        return Ok(prompt);
+    }
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(ChatError::Read)?;
+    Ok(input)
+}
+
+const fn is_dropped_stream(err: &ChatError) -> bool {
+    matches!(
+        *err,
+        ChatError::Chatbot(
+            ChatbotChatError::Timeout | ChatbotChatError::NetworkError(_)
+        )
+    )
+}
+
+/// Lets the user decide what to do with a response that was cut off by a
+/// network error instead of throwing the partial answer away.
+#[expect(
+    clippy::too_many_arguments,
+    reason = r#"
+        Recovering continues the same stream, so it takes everything
+        `stream_response` does plus what to keep of the answer.
+    "#
+)]
+async fn recover_dropped_stream(
+    printer: &Printer,
+    chatbot: &dyn Chatbot,
+    messages: &[Message],
+    params: GenerationParams,
+    tee: &mut Option<Tee>,
+    full_resp: &mut String,
+    kept: usize,
+    mut error: ChatError,
+    detect_repetition: bool,
+) -> Result<Option<TokenUsage>, ChatError> {
+    loop {
+        println!();
+        printer
+            .print_error_message(&error.to_string())
+            .map_err(ChatError::Print)?;
+        printer
+            .print_app_message(
+                "The response was cut off. [r]etry, [c]ontinue from the \
+                 partial answer, [k]eep it as is or [d]iscard it?",
+            )
+            .map_err(ChatError::Print)?;
+
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .map_err(ChatError::Read)?;
+
+        let res = match answer.trim() {
+            "r" | "retry" => {
+                // Only the cut off part is asked for again, in the answer
+                // and in the tee.
+                if let Some(ref mut tee) = *tee {
+                    let partial = full_resp.len().saturating_sub(kept);
+                    tee.discard(u64::try_from(partial).unwrap_or(u64::MAX))
+                        .map_err(ChatError::Tee)?;
+                }
+                full_resp.truncate(kept);
+                printer
+                    .print_chatbot_prefix(chatbot.name())
+                    .map_err(ChatError::Print)?;
+                print!("{full_resp}");
+                stream_response(
+                    printer,
+                    chatbot,
+                    messages,
+                    params,
+                    tee,
+                    full_resp,
+                    detect_repetition,
+                )
+                .await
+            }
+            "c" | "continue" => {
+                let mut continued = messages.to_vec();
+                continued
+                    .push(Message::new(Role::Assistant, full_resp.clone()));
+                continued
+                    .push(Message::new(Role::User, CONTINUE_PROMPT.to_owned()));
+                printer
+                    .print_chatbot_prefix(chatbot.name())
+                    .map_err(ChatError::Print)?;
+                print!("{full_resp}");
+                stream_response(
+                    printer,
+                    chatbot,
+                    &continued,
+                    params,
+                    tee,
+                    full_resp,
+                    detect_repetition,
+                )
+                .await
+            }
+            "k" | "keep" => return Ok(None),
+            _ => return Err(error),
+        };
+
+        match res {
+            Ok(usage) => return Ok(usage),
+            Err(err) if is_dropped_stream(&err) => error = err,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use llmcli::{
+        chatbots::gemini::GeminiChatbot, config::Config, pricing, Chatbot as _,
+        TokenUsage,
+    };
+
+    use super::{estimate_message, footer};
+
+    #[test]
+    fn estimates_price_gemini_models_by_id() {
+        let message = GeminiChatbot::create(
+            "gemini-1.5-flash".to_owned(),
+            Some("key".to_owned()),
+        )
+        .ok()
+        .map(|chatbot| {
+            estimate_message(1_000, chatbot.model_id(), &Config::default())
+        });
+
+        assert_eq!(
+            message.as_deref(),
+            Some("~1000 input tokens, ~$0.0001 with gemini-1.5-flash.")
+        );
+    }
+
+    #[test]
+    fn footer_shows_tokens_and_cost_of_gemini_answers() {
+        let line = GeminiChatbot::create(
+            "gemini-1.5-flash".to_owned(),
+            Some("key".to_owned()),
+        )
+        .ok()
+        .map(|chatbot| {
+            let model = chatbot.provenance().model;
+            footer(
+                &model,
+                Duration::from_millis(2_400),
+                Some(TokenUsage::new(800, 12)),
+                pricing::listed_price(&model, None),
+            )
+        });
+
+        assert_eq!(
+            line.as_deref(),
+            Some(
+                "gemini-1.5-flash \u{b7} 2.4s \u{b7} 812 tok \u{b7} $0.0001 \
+                 input"
+            )
+        );
     }
 }
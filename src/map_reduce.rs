@@ -0,0 +1,157 @@
+use crate::{Message, Role};
+
+pub const DEFAULT_CHUNK_CHARS: usize = 12_000;
+
+/// Rough characters per token, close enough for English text and code with
+/// every supported tokenizer.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimated tokens sent and received for one request.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+}
+
+impl Usage {
+    #[inline]
+    #[must_use]
+    pub fn estimate(messages: &[Message], response: &str) -> Self {
+        Self {
+            input_tokens: messages
+                .iter()
+                .map(|msg| estimate_tokens(&msg.content))
+                .sum(),
+            output_tokens: estimate_tokens(response),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn add(self, other: Self) -> Self {
+        Self {
+            input_tokens: self.input_tokens.saturating_add(other.input_tokens),
+            output_tokens: self
+                .output_tokens
+                .saturating_add(other.output_tokens),
+        }
+    }
+}
+
+#[inline]
+#[must_use]
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Splits `input` into chunks of at most `max_chars` characters, breaking
+/// between lines where possible and inside overlong lines otherwise.
+#[inline]
+#[must_use]
+pub fn split(input: &str, max_chars: usize) -> Vec<&str> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0_usize;
+    let mut chars = 0_usize;
+    let mut last_break = None;
+
+    for (idx, ch) in input.char_indices() {
+        if chars == max_chars {
+            let end = last_break.unwrap_or(idx);
+            chunks.extend(input.get(start..end));
+            start = end;
+            chars =
+                input.get(start..idx).map_or(0, |rest| rest.chars().count());
+            last_break = None;
+        }
+        chars = chars.saturating_add(1);
+        if ch == '\n' {
+            last_break = Some(idx.saturating_add(1));
+        }
+    }
+    chunks.extend(input.get(start..).filter(|rest| !rest.is_empty()));
+
+    chunks
+}
+
+/// The request for one chunk: the conversation so far, then the prompt
+/// applied to this part of the input alone.
+#[inline]
+#[must_use]
+pub fn map_messages(
+    history: &[Message],
+    prompt: &str,
+    chunk: &str,
+    idx: usize,
+    total: usize,
+) -> Vec<Message> {
+    let mut messages = history.to_vec();
+    messages.push(Message::new(
+        Role::User,
+        format!(
+            "The input is too long to send at once, this is part {} of \
+             {total}. Apply the request to this part only, keeping every \
+             detail a combined answer would need.\n\nRequest:\n{prompt}\n\n\
+             Part {}:\n{chunk}",
+            idx.saturating_add(1),
+            idx.saturating_add(1)
+        ),
+    ));
+    messages
+}
+
+/// The final prompt asking the model to merge the per-chunk answers.
+#[inline]
+#[must_use]
+pub fn reduce_prompt(prompt: &str, partials: &[String]) -> String {
+    let parts = partials
+        .iter()
+        .enumerate()
+        .map(|(idx, partial)| {
+            format!("Answer for part {}:\n{partial}", idx.saturating_add(1))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        "The input was split into {} parts and the request was answered for \
+         each separately. Combine these answers into a single answer to the \
+         request, removing repetition.\n\nRequest:\n{prompt}\n\n{parts}",
+        partials.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_tokens, reduce_prompt, split};
+
+    #[test]
+    fn splits_between_lines_where_possible() {
+        assert_eq!(split("one\ntwo\nthree\n", 9), ["one\ntwo\n", "three\n"]);
+        assert_eq!(split("abcdefgh\nij", 4), ["abcd", "efgh", "\nij"]);
+        assert_eq!(split("", 4), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn counts_characters_rather_than_bytes() {
+        let input = "\u{e9}".repeat(5);
+
+        assert_eq!(split(&input, 2).len(), 3);
+        assert_eq!(estimate_tokens(&input), 2);
+    }
+
+    #[test]
+    fn reduce_prompt_numbers_the_partial_answers() {
+        let prompt = reduce_prompt(
+            "Sum up.",
+            &["First.".to_owned(), "Second.".to_owned()],
+        );
+
+        assert!(prompt.starts_with("The input was split into 2 parts"));
+        assert!(prompt.ends_with(
+            "Request:\nSum up.\n\nAnswer for part 1:\nFirst.\n\nAnswer for \
+             part 2:\nSecond."
+        ));
+    }
+}
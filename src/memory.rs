@@ -0,0 +1,328 @@
+use alloc::collections::BTreeSet;
+use core::cmp::Reverse;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::Config;
+
+pub const DEFAULT_MAX_INJECTED: usize = 5;
+
+const STOPWORDS: [&str; 24] = [
+    "the", "and", "for", "are", "but", "not", "you", "your", "with", "this",
+    "that", "have", "has", "was", "were", "what", "when", "how", "why", "who",
+    "can", "about", "from", "into",
+];
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum MemoryError {
+    #[error("Memory is disabled, set `memory.enabled = true` to use it.")]
+    Disabled,
+    #[error("Failed to locate data directory.")]
+    DataDir,
+    #[error("Failed to read memories: {0}")]
+    Read(io::Error),
+    #[error("Failed to write memories: {0}")]
+    Write(io::Error),
+    #[error("Memory file is corrupted: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("No memory with id {0}.")]
+    NotFound(usize),
+}
+
+#[non_exhaustive]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Memory {
+    pub id: usize,
+    pub fact: String,
+    pub created: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<PathBuf>,
+}
+
+/// Facts remembered across sessions, kept in a JSON file in the data
+/// directory. Facts sharing keywords with the prompt are added to the
+/// system prompt of each request.
+///
+/// Inside a project, detected by a `.llmcli.toml` or git root, new facts
+/// belong to that project and only global facts and the project's own are
+/// visible. With `memory.global` set, only global facts are used.
+pub struct MemoryStore {
+    path: PathBuf,
+    memories: Vec<Memory>,
+    max_injected: usize,
+    project: Option<PathBuf>,
+}
+
+impl MemoryStore {
+    #[inline]
+    pub fn open(config: &Config) -> Result<Self, MemoryError> {
+        let memory_config = config
+            .memory
+            .as_ref()
+            .filter(|memory| memory.enabled.unwrap_or(false))
+            .ok_or(MemoryError::Disabled)?;
+
+        let path = match memory_config.path {
+            Some(ref path) => path.clone(),
+            None => dirs::data_dir()
+                .ok_or(MemoryError::DataDir)?
+                .join("llmcli_memories.json"),
+        };
+
+        let mut store = Self {
+            path,
+            memories: Vec::new(),
+            max_injected: memory_config
+                .max_injected
+                .unwrap_or(DEFAULT_MAX_INJECTED),
+            project: if memory_config.global.unwrap_or(false) {
+                None
+            } else {
+                Config::project_root()
+            },
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    /// Rereads the file, picking up changes made by commands or other
+    /// running instances.
+    #[inline]
+    pub fn reload(&mut self) -> Result<(), MemoryError> {
+        self.memories = match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(MemoryError::Read(err)),
+        };
+        Ok(())
+    }
+
+    #[inline]
+    pub fn save(&self) -> Result<(), MemoryError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(MemoryError::Write)?;
+        }
+        let content = serde_json::to_string_pretty(&self.memories)?;
+        fs::write(&self.path, content).map_err(MemoryError::Write)
+    }
+
+    /// The project new facts are scoped to, `None` when global.
+    #[inline]
+    #[must_use]
+    pub fn project(&self) -> Option<&Path> {
+        self.project.as_deref()
+    }
+
+    /// The facts visible in the current scope.
+    #[inline]
+    #[must_use]
+    pub fn memories(&self) -> Vec<&Memory> {
+        self.memories
+            .iter()
+            .filter(|memory| self.is_visible(memory))
+            .collect()
+    }
+
+    /// Every stored fact, regardless of project.
+    #[inline]
+    #[must_use]
+    pub fn all_memories(&self) -> &[Memory] {
+        &self.memories
+    }
+
+    fn is_visible(&self, memory: &Memory) -> bool {
+        memory.project.is_none() || memory.project == self.project
+    }
+
+    #[inline]
+    pub fn add(&mut self, fact: String) -> usize {
+        let id = self
+            .memories
+            .iter()
+            .map(|memory| memory.id)
+            .max()
+            .map_or(1, |id| id.saturating_add(1));
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.memories.push(Memory {
+            id,
+            fact,
+            created,
+            project: self.project.clone(),
+        });
+        id
+    }
+
+    #[inline]
+    pub fn edit(&mut self, id: usize, fact: String) -> Result<(), MemoryError> {
+        let project = self.project.as_ref();
+        let memory = self
+            .memories
+            .iter_mut()
+            .find(|memory| {
+                memory.id == id
+                    && (memory.project.is_none()
+                        || memory.project.as_ref() == project)
+            })
+            .ok_or(MemoryError::NotFound(id))?;
+        memory.fact = fact;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn remove(&mut self, id: usize) -> Result<(), MemoryError> {
+        let idx = self
+            .memories
+            .iter()
+            .position(|memory| memory.id == id && self.is_visible(memory))
+            .ok_or(MemoryError::NotFound(id))?;
+        self.memories.remove(idx);
+        Ok(())
+    }
+
+    /// The facts sharing the most keywords with `prompt`, best first.
+    #[inline]
+    #[must_use]
+    pub fn relevant(&self, prompt: &str) -> Vec<&Memory> {
+        let prompt_keywords = keywords(prompt);
+        let mut scored: Vec<(usize, &Memory)> = self
+            .memories()
+            .into_iter()
+            .map(|memory| {
+                let score = keywords(&memory.fact)
+                    .intersection(&prompt_keywords)
+                    .count();
+                (score, memory)
+            })
+            .filter(|&(score, _)| score > 0)
+            .collect();
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+
+        scored
+            .into_iter()
+            .take(self.max_injected)
+            .map(|(_, memory)| memory)
+            .collect()
+    }
+}
+
+/// Formats memories as an addition to the system prompt.
+#[inline]
+#[must_use]
+pub fn prompt(memories: &[&Memory]) -> Option<String> {
+    if memories.is_empty() {
+        return None;
+    }
+
+    let facts: Vec<String> = memories
+        .iter()
+        .map(|memory| format!("- {}", memory.fact))
+        .collect();
+    Some(format!(
+        "Facts the user asked you to remember in earlier sessions:\n{}",
+        facts.join("\n")
+    ))
+}
+
+fn keywords(text: &str) -> BTreeSet<String> {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{prompt, MemoryStore};
+
+    fn store(name: &str) -> MemoryStore {
+        MemoryStore {
+            path: env::temp_dir()
+                .join(format!("llmcli-memory-{name}-{}.json", process::id())),
+            memories: Vec::new(),
+            max_injected: 2,
+            project: None,
+        }
+    }
+
+    fn facts(store: &MemoryStore, prompt: &str) -> Vec<String> {
+        store
+            .relevant(prompt)
+            .into_iter()
+            .map(|memory| memory.fact.clone())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_facts_by_shared_keywords_ignoring_stopwords() {
+        let mut store = store("rank");
+        store.add("The user writes Rust with the tokio runtime.".to_owned());
+        store.add("The user deploys Rust services on Fly.".to_owned());
+        store.add("The user prefers tabs.".to_owned());
+        store.add("What about this and that?".to_owned());
+
+        assert_eq!(
+            facts(&store, "Which tokio runtime flavour suits Rust?"),
+            [
+                "The user writes Rust with the tokio runtime.",
+                "The user deploys Rust services on Fly.",
+            ]
+        );
+        assert!(facts(&store, "What about that?").is_empty());
+    }
+
+    #[test]
+    fn saves_and_reloads_with_increasing_ids() {
+        let mut saved = store("save");
+        assert_eq!(saved.add("First fact.".to_owned()), 1);
+        assert_eq!(saved.add("Second fact.".to_owned()), 2);
+        assert_eq!(saved.remove(1).ok(), Some(()));
+        assert_eq!(saved.save().ok(), Some(()));
+
+        let mut loaded = store("save");
+        assert_eq!(loaded.reload().ok(), Some(()));
+        assert_eq!(loaded.add("Third fact.".to_owned()), 3);
+        assert_eq!(
+            prompt(&loaded.memories()),
+            Some(
+                "Facts the user asked you to remember in earlier sessions:\n\
+                 - Second fact.\n- Third fact."
+                    .to_owned()
+            )
+        );
+        assert!(loaded.remove(1).is_err());
+        assert_eq!(fs::remove_file(&loaded.path).ok(), Some(()));
+    }
+
+    #[test]
+    fn project_facts_stay_in_their_project() {
+        let mut store = store("scope");
+        store.add("Global fact.".to_owned());
+        store.project = Some(PathBuf::from("/work/app"));
+        store.add("App fact.".to_owned());
+        store.project = Some(PathBuf::from("/work/site"));
+        store.add("Site fact.".to_owned());
+
+        let visible: Vec<&str> = store
+            .memories()
+            .into_iter()
+            .map(|memory| memory.fact.as_str())
+            .collect();
+        assert_eq!(visible, ["Global fact.", "Site fact."]);
+        assert_eq!(store.all_memories().len(), 3);
+        assert!(store.remove(2).is_err());
+        assert!(store.edit(2, "Changed.".to_owned()).is_err());
+        assert_eq!(store.edit(1, "Changed.".to_owned()).ok(), Some(()));
+    }
+}
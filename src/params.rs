@@ -0,0 +1,360 @@
+use core::{
+    fmt::{self, Display, Formatter},
+    ops::RangeInclusive,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{config::Config, Chatbot};
+
+pub const BUILTIN_PRESETS: [&str; 3] = ["creative", "balanced", "precise"];
+
+pub const NAMES: [&str; 7] = [
+    "temperature",
+    "top_p",
+    "top_k",
+    "max_tokens",
+    "presence_penalty",
+    "frequency_penalty",
+    "repetition_penalty",
+];
+
+const FLOAT_RANGES: [(&str, RangeInclusive<f32>); 5] = [
+    ("temperature", 0.0..=2.0),
+    ("top_p", 0.0..=1.0),
+    ("presence_penalty", -2.0..=2.0),
+    ("frequency_penalty", -2.0..=2.0),
+    ("repetition_penalty", 1.0..=2.0),
+];
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ParamError {
+    #[error(
+        "Unknown parameter {0}, expected temperature, top_p, top_k, \
+         max_tokens, presence_penalty, frequency_penalty or \
+         repetition_penalty."
+    )]
+    Unknown(String),
+    #[error("Invalid value {1} for {0}.")]
+    InvalidValue(&'static str, String),
+}
+
+#[non_exhaustive]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    /// Multiplicative penalty of local and open model servers, where 1
+    /// means none.
+    pub repetition_penalty: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+impl GenerationParams {
+    #[inline]
+    #[must_use]
+    pub const fn new(
+        temperature: f32,
+        top_p: f32,
+        presence_penalty: f32,
+        frequency_penalty: f32,
+    ) -> Self {
+        Self {
+            temperature: Some(temperature),
+            top_p: Some(top_p),
+            top_k: None,
+            presence_penalty: Some(presence_penalty),
+            frequency_penalty: Some(frequency_penalty),
+            repetition_penalty: None,
+            max_tokens: None,
+        }
+    }
+
+    /// Fills the parameters left unset from `fallback`.
+    #[inline]
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            temperature: self.temperature.or(fallback.temperature),
+            top_p: self.top_p.or(fallback.top_p),
+            top_k: self.top_k.or(fallback.top_k),
+            presence_penalty: self
+                .presence_penalty
+                .or(fallback.presence_penalty),
+            frequency_penalty: self
+                .frequency_penalty
+                .or(fallback.frequency_penalty),
+            repetition_penalty: self
+                .repetition_penalty
+                .or(fallback.repetition_penalty),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+        }
+    }
+
+    /// Keeps only the parameters named in `supported`, for backends that
+    /// would reject the others.
+    #[inline]
+    #[must_use]
+    pub fn retain(self, supported: &[&str]) -> Self {
+        let keep = |name| supported.contains(&name);
+        Self {
+            temperature: self.temperature.filter(|_| keep("temperature")),
+            top_p: self.top_p.filter(|_| keep("top_p")),
+            top_k: self.top_k.filter(|_| keep("top_k")),
+            presence_penalty: self
+                .presence_penalty
+                .filter(|_| keep("presence_penalty")),
+            frequency_penalty: self
+                .frequency_penalty
+                .filter(|_| keep("frequency_penalty")),
+            repetition_penalty: self
+                .repetition_penalty
+                .filter(|_| keep("repetition_penalty")),
+            max_tokens: self.max_tokens.filter(|_| keep("max_tokens")),
+        }
+    }
+
+    /// Describes the parameters set here that `chatbot` does not send, if
+    /// any.
+    #[inline]
+    #[must_use]
+    pub fn ignored_note(&self, chatbot: &dyn Chatbot) -> Option<String> {
+        let supported = chatbot.supported_params();
+        let ignored: Vec<&str> = self
+            .values()
+            .into_iter()
+            .filter(|&(name, ref value)| {
+                value.is_some() && !supported.contains(&name)
+            })
+            .map(|(name, _)| name)
+            .collect();
+        (!ignored.is_empty()).then(|| {
+            format!(
+                "{} ignores unsupported {}.",
+                chatbot.name(),
+                ignored.join(", ")
+            )
+        })
+    }
+
+    fn values(&self) -> [(&'static str, Option<String>); 7] {
+        [
+            (
+                "temperature",
+                self.temperature.map(|value| value.to_string()),
+            ),
+            ("top_p", self.top_p.map(|value| value.to_string())),
+            ("top_k", self.top_k.map(|value| value.to_string())),
+            ("max_tokens", self.max_tokens.map(|value| value.to_string())),
+            (
+                "presence_penalty",
+                self.presence_penalty.map(|value| value.to_string()),
+            ),
+            (
+                "frequency_penalty",
+                self.frequency_penalty.map(|value| value.to_string()),
+            ),
+            (
+                "repetition_penalty",
+                self.repetition_penalty.map(|value| value.to_string()),
+            ),
+        ]
+    }
+
+    /// Sets the parameter `name` from `value`, or back to the backend's
+    /// default when `value` is `default`.
+    #[inline]
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ParamError> {
+        let field = match name {
+            "temperature" => &mut self.temperature,
+            "top_p" => &mut self.top_p,
+            "presence_penalty" => &mut self.presence_penalty,
+            "frequency_penalty" => &mut self.frequency_penalty,
+            "repetition_penalty" => &mut self.repetition_penalty,
+            "top_k" => {
+                self.top_k = parse_count("top_k", value)?;
+                return Ok(());
+            }
+            "max_tokens" => {
+                self.max_tokens = parse_count("max_tokens", value)?;
+                return Ok(());
+            }
+            _ => return Err(ParamError::Unknown(name.to_owned())),
+        };
+
+        *field = if value == "default" {
+            None
+        } else {
+            Some(parse_float(name, value)?)
+        };
+        Ok(())
+    }
+}
+
+/// Parses `value` for the float parameter `name` and checks that it is in
+/// the range providers accept. Also used by clap for the sampling flags.
+#[inline]
+pub fn parse_float(name: &str, value: &str) -> Result<f32, ParamError> {
+    let &(name, ref range) = FLOAT_RANGES
+        .iter()
+        .find(|&&(known, _)| known == name)
+        .ok_or_else(|| ParamError::Unknown(name.to_owned()))?;
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|parsed| range.contains(parsed))
+        .ok_or_else(|| ParamError::InvalidValue(name, value.to_owned()))
+}
+
+fn parse_count(
+    name: &'static str,
+    value: &str,
+) -> Result<Option<u32>, ParamError> {
+    if value == "default" {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .ok()
+        .filter(|&count| count > 0)
+        .map(Some)
+        .ok_or_else(|| ParamError::InvalidValue(name, value.to_owned()))
+}
+
+impl Display for GenerationParams {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (name, value) in self.values() {
+            let Some(value) = value else {
+                continue;
+            };
+            if !first {
+                write!(f, ", ")?;
+            }
+            write!(f, "{name}={value}")?;
+            first = false;
+        }
+        if first {
+            write!(f, "backend defaults")?;
+        }
+        Ok(())
+    }
+}
+
+/// Looks up a preset by name. Presets from the `[presets]` config section
+/// take precedence over the built-in ones of the same name.
+#[inline]
+#[must_use]
+pub fn preset(name: &str, config: &Config) -> Option<GenerationParams> {
+    if let Some(params) = config
+        .presets
+        .as_ref()
+        .and_then(|presets| presets.get(name))
+    {
+        return Some(*params);
+    }
+
+    match name {
+        "creative" => Some(GenerationParams::new(1.0, 0.95, 0.6, 0.3)),
+        "balanced" => Some(GenerationParams::new(0.7, 0.9, 0.0, 0.0)),
+        "precise" => Some(GenerationParams::new(0.2, 0.5, 0.0, 0.0)),
+        _ => None,
+    }
+}
+
+#[inline]
+#[must_use]
+pub fn preset_names(config: &Config) -> Vec<&str> {
+    let mut names: Vec<&str> = BUILTIN_PRESETS.to_vec();
+    for name in config.presets.iter().flat_map(|presets| presets.keys()) {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+
+    use super::{parse_float, preset, preset_names, GenerationParams};
+    use crate::config::Config;
+
+    #[test]
+    fn set_checks_ranges_and_resets_to_default() {
+        let mut params = GenerationParams::default();
+
+        assert_eq!(params.set("temperature", "0.5").ok(), Some(()));
+        assert_eq!(params.set("max_tokens", "256").ok(), Some(()));
+        assert_eq!(
+            params.set("top_p", "1.5").err().map(|err| err.to_string()),
+            Some("Invalid value 1.5 for top_p.".to_owned())
+        );
+        assert_eq!(
+            params.set("top_k", "0").err().map(|err| err.to_string()),
+            Some("Invalid value 0 for top_k.".to_owned())
+        );
+        assert!(params.set("seed", "1").err().is_some());
+        assert_eq!(params.to_string(), "temperature=0.5, max_tokens=256");
+
+        assert_eq!(params.set("temperature", "default").ok(), Some(()));
+        assert_eq!(params.set("max_tokens", "default").ok(), Some(()));
+        assert_eq!(params.to_string(), "backend defaults");
+    }
+
+    #[test]
+    fn parse_float_rejects_values_outside_the_range() {
+        assert_eq!(parse_float("presence_penalty", "-2").ok(), Some(-2.0));
+        assert_eq!(parse_float("presence_penalty", "-2.1").ok(), None);
+        assert_eq!(parse_float("repetition_penalty", "0.9").ok(), None);
+        assert_eq!(parse_float("temperature", "warm").ok(), None);
+    }
+
+    #[test]
+    fn or_keeps_set_values_and_retain_drops_unsupported_ones() {
+        let mut params = GenerationParams::default();
+        assert_eq!(params.set("top_k", "40").ok(), Some(()));
+        let params = params.or(GenerationParams::new(0.2, 0.5, 0.0, 0.0));
+
+        assert_eq!(
+            params.to_string(),
+            "temperature=0.2, top_p=0.5, top_k=40, presence_penalty=0, \
+             frequency_penalty=0"
+        );
+        assert_eq!(
+            params.retain(&["temperature", "top_k"]).to_string(),
+            "temperature=0.2, top_k=40"
+        );
+    }
+
+    #[test]
+    fn configured_presets_replace_builtin_ones() {
+        let mut precise = GenerationParams::default();
+        assert_eq!(precise.set("temperature", "0").ok(), Some(()));
+        let config = Config {
+            presets: Some(BTreeMap::from([
+                ("precise".to_owned(), precise),
+                ("terse".to_owned(), GenerationParams::default()),
+            ])),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            preset("precise", &config).map(|params| params.to_string()),
+            Some("temperature=0".to_owned())
+        );
+        assert!(preset("creative", &config).is_some());
+        assert!(preset("wild", &config).is_none());
+        assert_eq!(
+            preset_names(&config),
+            ["creative", "balanced", "precise", "terse"]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    chatbots, config::Config, guardrail, Chatbot, ChatbotCreationError,
+    Message, Role,
+};
+
+/// A named assistant from `[personas.<name>]`, addressed with `@<name>` at
+/// the start of a prompt.
+#[non_exhaustive]
+pub struct Persona {
+    name: String,
+    system_prompt: Option<String>,
+}
+
+impl Persona {
+    /// Splits `@<name> <prompt>` into the persona and the prompt, if `name`
+    /// is a configured persona.
+    #[inline]
+    #[must_use]
+    pub fn from_mention<'input>(
+        input: &'input str,
+        config: &Config,
+    ) -> Option<(Self, &'input str)> {
+        let mention = input.strip_prefix('@')?;
+        let end = mention
+            .find(|ch: char| ch.is_whitespace() || ch == ':')
+            .unwrap_or(mention.len());
+        let (name, prompt) = mention.split_at(end);
+        let prompt = prompt.trim_start_matches(':').trim_start();
+
+        if prompt.is_empty() {
+            return None;
+        }
+
+        Some((Self::from_name(name, config)?, prompt))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn from_name(name: &str, config: &Config) -> Option<Self> {
+        let persona = config.persona(name)?;
+
+        Some(Self {
+            name: name.to_owned(),
+            system_prompt: persona.system_prompt.clone(),
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn chatbot(
+        &self,
+        config: &Config,
+    ) -> Result<Box<dyn Chatbot>, ChatbotCreationError> {
+        let persona = config
+            .persona(&self.name)
+            .ok_or(ChatbotCreationError::UnknownChatbot)?;
+        chatbots::create(&persona.chatbot, persona.model.clone(), config)
+    }
+
+    /// The request for this persona: its system prompt first, and the replies
+    /// of every other speaker labeled with their name.
+    #[inline]
+    #[must_use]
+    pub fn messages(&self, messages: &[Message]) -> Vec<Message> {
+        let labeled = label_speakers(messages, Some(&self.name));
+        let system = self.system_prompt.as_ref().map_or_else(
+            || format!("You are {}.", self.name),
+            |system_prompt| {
+                format!("You are {}.\n\n{system_prompt}", self.name)
+            },
+        );
+        guardrail::apply(&labeled, Some(&system)).into_owned()
+    }
+}
+
+/// Prefixes assistant messages written by a persona other than `current`
+/// with the persona's name, so each model can tell who said what.
+#[inline]
+#[must_use]
+pub fn label_speakers(
+    messages: &[Message],
+    current: Option<&str>,
+) -> Vec<Message> {
+    messages
+        .iter()
+        .map(|msg| match msg.speaker {
+            Some(ref speaker)
+                if msg.role == Role::Assistant
+                    && Some(speaker.as_str()) != current =>
+            {
+                let mut labeled = msg.clone();
+                labeled.content = format!("{speaker}: {}", msg.content);
+                labeled
+            }
+            _ => msg.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,60 @@
+use alloc::collections::BTreeMap;
+
+use crate::config::Config;
+
+/// List prices in USD per million input tokens. Prices change often, so
+/// `[prices]` in the config takes precedence over this table.
+const INPUT_PRICES: [(&str, f64); 14] = [
+    ("gpt-4o", 2.5),
+    ("gpt-4o-mini", 0.15),
+    ("o1", 15.0),
+    ("o1-mini", 1.1),
+    ("gemini-2.0-flash-exp", 0.0),
+    ("gemini-1.5-flash", 0.075),
+    ("gemini-1.5-flash-8b", 0.0375),
+    ("gemini-1.5-pro", 1.25),
+    ("gemini-1.0-pro", 0.5),
+    ("deepseek-chat", 0.27),
+    ("deepseek-reasoner", 0.55),
+    ("mistral-large-latest", 2.0),
+    ("grok-2-latest", 2.0),
+    ("sonar", 1.0),
+];
+
+/// The price in USD per million input tokens for `model`, if known.
+#[inline]
+#[must_use]
+pub fn input_price(model: &str, config: &Config) -> Option<f64> {
+    listed_price(model, config.prices.as_ref())
+}
+
+/// Like `input_price`, with `prices` as the `[prices]` table.
+#[inline]
+#[must_use]
+pub fn listed_price(
+    model: &str,
+    prices: Option<&BTreeMap<String, f64>>,
+) -> Option<f64> {
+    prices
+        .and_then(|prices| prices.get(model).copied())
+        .or_else(|| {
+            INPUT_PRICES
+                .iter()
+                .find(|&&(name, _)| name == model)
+                .map(|&(_, price)| price)
+        })
+}
+
+#[inline]
+#[must_use]
+#[expect(
+    clippy::float_arithmetic,
+    reason = r#"
+        Prices are fractions of a cent.
+    "#
+)]
+pub fn cost(tokens: usize, price_per_million: f64) -> f64 {
+    let tokens =
+        u32::try_from(tokens).map_or_else(|_| f64::from(u32::MAX), f64::from);
+    tokens * price_per_million / 1_000_000.0
+}
@@ -0,0 +1,117 @@
+use serde::Serialize;
+
+/// Quotes shorter than this many words are usually terms or titles rather
+/// than citations, so they are not checked.
+const MIN_QUOTE_WORDS: usize = 3;
+
+const QUOTE_PAIRS: [(char, char); 3] =
+    [('"', '"'), ('\u{201c}', '\u{201d}'), ('\u{ab}', '\u{bb}')];
+
+/// A quoted span from a response and whether it occurs in the source.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteCheck {
+    pub quote: String,
+    pub found: bool,
+}
+
+/// Finds the spans of `text` wrapped in double quotes that are long enough
+/// to be citations.
+#[inline]
+#[must_use]
+pub fn extract(text: &str) -> Vec<&str> {
+    let mut quotes = Vec::new();
+
+    for (open, close) in QUOTE_PAIRS {
+        let mut rest = text;
+        while let Some((_, after_open)) = rest.split_once(open) {
+            let Some((quote, after_close)) = after_open.split_once(close)
+            else {
+                break;
+            };
+            if quote.split_whitespace().count() >= MIN_QUOTE_WORDS {
+                quotes.push(quote.trim());
+            }
+            rest = after_close;
+        }
+    }
+
+    quotes
+}
+
+/// Checks every quote in `response` against `source`.
+///
+/// Matching ignores case, whitespace differences and typographic quote and
+/// dash variants, which models often change when copying text. Parts of a
+/// quote elided with an ellipsis are checked separately.
+#[inline]
+#[must_use]
+pub fn verify(response: &str, source: &str) -> Vec<QuoteCheck> {
+    let source = normalize(source);
+
+    extract(response)
+        .into_iter()
+        .map(|quote| QuoteCheck {
+            quote: quote.to_owned(),
+            found: normalize(quote)
+                .split('\u{2026}')
+                .flat_map(|part| part.split("..."))
+                .map(|part| {
+                    part.trim_matches([' ', '.', ',', ';', ':', '[', ']'])
+                })
+                .filter(|part| !part.is_empty())
+                .all(|part| source.contains(part)),
+        })
+        .collect()
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .map(|ch| match ch {
+                    '\u{2018}' | '\u{2019}' => '\'',
+                    '\u{201c}' | '\u{201d}' => '"',
+                    '\u{2013}' | '\u{2014}' => '-',
+                    _ => ch,
+                })
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, verify};
+
+    #[test]
+    fn extracts_quotes_long_enough_to_be_citations() {
+        assert_eq!(
+            extract(
+                "The \"Rust\" book says \"ownership is a set of rules\" and \
+                 \u{201c}the borrow checker enforces them\u{201d}."
+            ),
+            [
+                "ownership is a set of rules",
+                "the borrow checker enforces them"
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_loosely_and_reports_fabricated_quotes() {
+        let source = "It\u{2019}s a truth universally acknowledged, that a \
+                      single man in possession of a good fortune, must be \
+                      in want of a wife.";
+        let checks = verify(
+            "Austen writes \"it's a TRUTH universally acknowledged \u{2026} \
+             in want of a wife\" and \"a single woman needs nothing\".",
+            source,
+        );
+        let found: Vec<bool> = checks.iter().map(|check| check.found).collect();
+
+        assert_eq!(found, [true, false]);
+    }
+}
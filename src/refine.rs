@@ -0,0 +1,65 @@
+use crate::{
+    params::GenerationParams, Chatbot, ChatbotChatError, Message, Role,
+};
+
+pub const DEFAULT_ROUNDS: usize = 2;
+
+const APPROVAL: &str = "LGTM";
+
+const CRITIC_PROMPT: &str = "You are a strict reviewer. Point out concrete \
+                             errors, omissions and unclear parts in the \
+                             answer to the request. If the answer needs no \
+                             changes, reply with LGTM and nothing else.";
+
+#[inline]
+pub async fn complete(
+    chatbot: &dyn Chatbot,
+    messages: &[Message],
+    params: GenerationParams,
+) -> Result<String, ChatbotChatError> {
+    chatbot
+        .send_message_once(messages, params)
+        .await
+        .map(|response| response.content)
+}
+
+#[inline]
+#[must_use]
+pub fn critique_messages(request: &str, answer: &str) -> Vec<Message> {
+    vec![
+        Message::new(Role::System, CRITIC_PROMPT.to_owned()),
+        Message::new(
+            Role::User,
+            format!("Request:\n{request}\n\nAnswer:\n{answer}"),
+        ),
+    ]
+}
+
+#[inline]
+#[must_use]
+pub fn revision_messages(
+    history: &[Message],
+    answer: &str,
+    critique: &str,
+) -> Vec<Message> {
+    let mut messages = history.to_vec();
+    messages.push(Message::new(Role::Assistant, answer.to_owned()));
+    messages.push(Message::new(
+        Role::User,
+        format!(
+            "A reviewer left this critique of your answer:\n{critique}\n\n\
+             Rewrite your answer to address it. Reply with the revised \
+             answer only."
+        ),
+    ));
+    messages
+}
+
+#[inline]
+#[must_use]
+pub fn is_approval(critique: &str) -> bool {
+    critique
+        .trim()
+        .trim_end_matches('.')
+        .eq_ignore_ascii_case(APPROVAL)
+}
@@ -0,0 +1,104 @@
+use alloc::collections::VecDeque;
+use std::collections::HashSet;
+
+/// Length of the word sequences compared.
+const NGRAM: usize = 4;
+
+/// Number of most recent words looked at. Nothing is reported before the
+/// response is this long.
+const WINDOW: usize = 200;
+
+/// The text is considered stuck when fewer than this percentage of the
+/// n-grams in the window are distinct.
+const MIN_DISTINCT_PERCENT: usize = 35;
+
+/// Watches a streamed response for a model stuck repeating itself.
+#[non_exhaustive]
+#[derive(Default)]
+pub struct RepetitionDetector {
+    words: VecDeque<String>,
+    partial: String,
+}
+
+impl RepetitionDetector {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk of the response and reports whether the most recent
+    /// words are mostly repeats. Words split across chunks are kept until
+    /// they are complete.
+    #[inline]
+    pub fn push(&mut self, text: &str) -> bool {
+        self.partial.push_str(text);
+        let Some(end) = self.partial.rfind(char::is_whitespace) else {
+            return false;
+        };
+        let complete: String = self.partial.drain(..end).collect();
+        for word in complete.split_whitespace() {
+            self.words.push_back(word.to_lowercase());
+            if self.words.len() > WINDOW {
+                self.words.pop_front();
+            }
+        }
+
+        self.words.len() >= WINDOW && self.is_looping()
+    }
+
+    fn is_looping(&self) -> bool {
+        let words: Vec<&str> = self.words.iter().map(String::as_str).collect();
+        let total = words.len().saturating_sub(NGRAM).saturating_add(1);
+        let distinct: HashSet<&[&str]> = words.windows(NGRAM).collect();
+        distinct.len().saturating_mul(100)
+            < total.saturating_mul(MIN_DISTINCT_PERCENT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RepetitionDetector, WINDOW};
+
+    #[test]
+    fn flags_a_response_stuck_in_a_loop() {
+        let mut detector = RepetitionDetector::new();
+
+        let flagged = (0..WINDOW)
+            .any(|_| detector.push("I will answer that right away. "));
+
+        assert!(flagged);
+    }
+
+    #[test]
+    fn leaves_varied_text_alone() {
+        let mut detector = RepetitionDetector::new();
+
+        let flagged = (0..WINDOW.saturating_mul(3))
+            .any(|idx| detector.push(&format!("word{idx} ")));
+
+        assert!(!flagged);
+    }
+
+    #[test]
+    fn waits_for_a_full_window() {
+        let mut detector = RepetitionDetector::new();
+
+        let flagged = (1..WINDOW).any(|_| detector.push("again "));
+
+        assert!(!flagged);
+        assert!(detector.push("again "));
+    }
+
+    #[test]
+    fn joins_words_split_across_chunks() {
+        let mut detector = RepetitionDetector::new();
+
+        detector.push("Hel");
+        detector.push("lo wor");
+        detector.push("ld ");
+
+        assert_eq!(detector.words, ["hello", "world"]);
+        assert_eq!(detector.partial, " ");
+    }
+}
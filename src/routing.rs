@@ -0,0 +1,42 @@
+/// Splits `@model: prompt` into the model and the prompt. The model ends at
+/// the first colon followed by a space, so tags such as `llama3:8b` and
+/// `chatbot:model` prefixes stay part of it.
+#[inline]
+#[must_use]
+pub fn parse_model_override(input: &str) -> Option<(&str, &str)> {
+    let (model, prompt) = input.strip_prefix('@')?.split_once(": ")?;
+    let prompt = prompt.trim_start();
+
+    if model.is_empty()
+        || model.contains(char::is_whitespace)
+        || prompt.is_empty()
+    {
+        return None;
+    }
+
+    Some((model, prompt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_model_override;
+
+    #[test]
+    fn keeps_colons_inside_the_model() {
+        assert_eq!(
+            parse_model_override("@llama3:8b: hello"),
+            Some(("llama3:8b", "hello"))
+        );
+        assert_eq!(
+            parse_model_override("@ollama:llama3:8b: hello"),
+            Some(("ollama:llama3:8b", "hello"))
+        );
+    }
+
+    #[test]
+    fn rejects_missing_prompt_or_separator() {
+        assert_eq!(parse_model_override("@gpt-4o:"), None);
+        assert_eq!(parse_model_override("@gpt-4o hello"), None);
+        assert_eq!(parse_model_override("gpt-4o: hello"), None);
+    }
+}
@@ -1,16 +1,47 @@
-use alloc::borrow::Cow;
-use std::{ffi::OsStr, fs, path::PathBuf};
+use alloc::{borrow::Cow, collections::BTreeMap};
+use core::{cmp::Reverse, time::Duration};
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use futures::io;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{config::Config, Message, Role};
+use crate::{
+    auth, config::Config, params::GenerationParams, Message, Provenance, Role,
+    TokenUsage,
+};
 
 #[non_exhaustive]
 #[derive(Serialize, Deserialize, Default)]
 pub struct Session {
     pub messages: Vec<Message>,
+    #[serde(skip)]
+    pub name: Option<String>,
+    #[serde(skip)]
+    pub audio: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<GenerationParams>,
+    #[serde(skip)]
+    pub last_usage: Option<TokenUsage>,
+    #[serde(skip)]
+    pub total_usage: TokenUsage,
+    /// Tokens per API key, for chatbots rotating between several, keyed by
+    /// chatbot name and the key's position in the config.
+    #[serde(skip)]
+    pub key_usage: BTreeMap<(String, usize), TokenUsage>,
+    /// Text every response is made to start with.
+    #[serde(skip)]
+    pub prefill: Option<String>,
+    /// Whether a line with the model, latency and cost follows each
+    /// response.
+    #[serde(skip)]
+    pub footer: bool,
 }
 
 #[non_exhaustive]
@@ -32,14 +63,75 @@ pub enum SessionError {
     NotFound,
     #[error("Failed to delete file: {0}.")]
     DeleteFile(io::Error),
+    #[error("Failed to archive session: {0}.")]
+    Archive(io::Error),
+    #[error(
+        "Session is corrupted: {0}. Use /recover <filename> to restore it."
+    )]
+    Corrupted(serde_json::Error),
+    #[error("No backups or readable messages found.")]
+    Unrecoverable,
 }
 
+#[non_exhaustive]
+pub enum Recovery {
+    Backup(usize),
+    Salvaged(usize),
+}
+
+/// A saved session with messages matching a search.
+#[non_exhaustive]
+pub struct SessionMatches {
+    pub name: String,
+    /// The day the session was last saved, as `YYYY-MM-DD`.
+    pub date: Option<String>,
+    pub hits: Vec<SearchHit>,
+}
+
+#[non_exhaustive]
+pub struct SearchHit {
+    /// Position of the message in the session, counting from 1.
+    pub number: usize,
+    pub speaker: String,
+    /// The matching line, shortened around the match.
+    pub snippet: String,
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+const DEFAULT_BACKUPS: usize = 3;
+
+const SNIPPET_CHARS: usize = 80;
+
 impl Session {
     #[inline]
     #[must_use]
     pub const fn new() -> Self {
         Self {
             messages: Vec::new(),
+            name: None,
+            audio: false,
+            params: None,
+            last_usage: None,
+            total_usage: TokenUsage::new(0, 0),
+            key_usage: BTreeMap::new(),
+            prefill: None,
+            footer: false,
+        }
+    }
+
+    /// Records the tokens a provider reported for the latest response,
+    /// which `provenance` produced.
+    #[inline]
+    pub fn record_usage(&mut self, usage: TokenUsage, provenance: &Provenance) {
+        self.last_usage = Some(usage);
+        self.total_usage = self.total_usage.add(usage);
+        if let Some(key) = provenance.key {
+            let per_key = self
+                .key_usage
+                .entry((provenance.chatbot.clone(), key))
+                .or_default();
+            *per_key = per_key.add(usage);
         }
     }
 
@@ -53,22 +145,83 @@ impl Session {
         let file_path = session_dir.join(filename).with_extension("json");
         let serialized = serde_json::to_string(self)?;
 
-        fs::write(&file_path, serialized).map_err(SessionError::WriteFile)?;
+        // Write to a temporary file first so an interrupted save never
+        // leaves a truncated session behind.
+        let temp_path = file_path.with_extension("json.tmp");
+        let mut file =
+            File::create(&temp_path).map_err(SessionError::WriteFile)?;
+        file.write_all(serialized.as_bytes())
+            .map_err(SessionError::WriteFile)?;
+        file.sync_all().map_err(SessionError::WriteFile)?;
+
+        if file_path.exists() {
+            Self::rotate_backups(&file_path, Self::backup_count(config))?;
+        }
+
+        fs::rename(&temp_path, &file_path).map_err(SessionError::WriteFile)?;
 
         Ok(())
     }
 
+    /// Restores a corrupted session from the newest readable backup, or
+    /// salvages the messages that still parse from the session file.
+    #[inline]
+    pub fn recover(
+        filename: &str,
+        config: &Config,
+    ) -> Result<(Self, Recovery), SessionError> {
+        let session_dir = Self::get_dir_path(config)?;
+        let file_path = session_dir.join(filename).with_extension("json");
+
+        for idx in 1..=Self::backup_count(config) {
+            let Ok(content) = fs::read_to_string(backup_path(&file_path, idx))
+            else {
+                continue;
+            };
+            if let Ok(session) = serde_json::from_str(&content) {
+                return Ok((session, Recovery::Backup(idx)));
+            }
+        }
+
+        let content =
+            fs::read_to_string(file_path).map_err(SessionError::ReadFile)?;
+        let messages = salvage_messages(&content);
+        if messages.is_empty() {
+            return Err(SessionError::Unrecoverable);
+        }
+
+        let count = messages.len();
+        Ok((
+            Self {
+                messages,
+                ..Self::new()
+            },
+            Recovery::Salvaged(count),
+        ))
+    }
+
     #[inline]
     pub fn load(filename: &str, config: &Config) -> Result<Self, SessionError> {
         let session_dir = Self::get_dir_path(config)?;
         let file_path = session_dir.join(filename).with_extension("json");
         let file_content =
             fs::read_to_string(file_path).map_err(SessionError::ReadFile)?;
-        let session: Self = serde_json::from_str(&file_content)?;
+        let mut session: Self = serde_json::from_str(&file_content)
+            .map_err(SessionError::Corrupted)?;
+        session.name = Some(filename.to_owned());
 
         Ok(session)
     }
 
+    #[inline]
+    pub fn exists(
+        filename: &str,
+        config: &Config,
+    ) -> Result<bool, SessionError> {
+        let session_dir = Self::get_dir_path(config)?;
+        Ok(session_dir.join(filename).with_extension("json").exists())
+    }
+
     #[inline]
     pub fn list_all(config: &Config) -> Result<Vec<String>, SessionError> {
         let session_dir = Self::get_dir_path(config)?;
@@ -88,6 +241,55 @@ impl Session {
         Ok(session_files)
     }
 
+    /// Finds the messages of every saved session that contain `pattern`,
+    /// ignoring case. Sessions that fail to load are skipped, and the most
+    /// recently saved come first.
+    #[inline]
+    pub fn search(
+        pattern: &str,
+        config: &Config,
+    ) -> Result<Vec<SessionMatches>, SessionError> {
+        let session_dir = Self::get_dir_path(config)?;
+        let needle = pattern.to_lowercase();
+        let mut found: Vec<(SystemTime, SessionMatches)> = Vec::new();
+        for name in Self::list_all(config)? {
+            let Ok(session) = Self::load(&name, config) else {
+                continue;
+            };
+            let hits: Vec<SearchHit> = session
+                .messages
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, msg)| {
+                    Some(SearchHit {
+                        number: idx.saturating_add(1),
+                        speaker: speaker(msg, "Assistant"),
+                        snippet: snippet(&msg.content, &needle)?,
+                    })
+                })
+                .collect();
+            if hits.is_empty() {
+                continue;
+            }
+
+            let modified =
+                fs::metadata(session_dir.join(&name).with_extension("json"))
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+            found.push((
+                modified.unwrap_or(UNIX_EPOCH),
+                SessionMatches {
+                    date: modified.and_then(iso_date),
+                    name,
+                    hits,
+                },
+            ));
+        }
+        found.sort_by_key(|&(modified, _)| Reverse(modified));
+
+        Ok(found.into_iter().map(|(_, matches)| matches).collect())
+    }
+
     #[inline]
     pub fn delete(filename: &str, config: &Config) -> Result<(), SessionError> {
         let session_dir = Self::get_dir_path(config)?;
@@ -102,11 +304,155 @@ impl Session {
         }
     }
 
+    /// Applies the `[sessions]` retention policy, keeping the newest
+    /// `max_sessions` sessions and dropping any older than `max_age_days`.
+    /// Expired sessions are moved to `archive_path` when it is set. Returns
+    /// the names of the affected sessions.
+    #[inline]
+    pub fn gc(
+        config: &Config,
+        dry_run: bool,
+    ) -> Result<Vec<String>, SessionError> {
+        let Some(retention) = config.sessions.as_ref() else {
+            return Ok(Vec::new());
+        };
+
+        let session_dir = Self::get_dir_path(config)?;
+        let entries =
+            fs::read_dir(&*session_dir).map_err(SessionError::ReadDir)?;
+        let mut sessions: Vec<(PathBuf, SystemTime)> = entries
+            .filter_map(Result::ok)
+            .filter(|file| file.path().extension() == Some(OsStr::new("json")))
+            .map(|file| {
+                let modified = file
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(UNIX_EPOCH);
+                (file.path(), modified)
+            })
+            .collect();
+        sessions.sort_by_key(|&(_, modified)| Reverse(modified));
+
+        let cutoff = retention.max_age_days.and_then(|days| {
+            SystemTime::now().checked_sub(Duration::from_secs(
+                days.saturating_mul(SECONDS_PER_DAY),
+            ))
+        });
+        let keep = retention.max_sessions.unwrap_or(usize::MAX);
+
+        let mut expired = Vec::new();
+        for (idx, (path, modified)) in sessions.into_iter().enumerate() {
+            let too_old = cutoff.is_some_and(|cutoff| modified < cutoff);
+            if idx < keep && !too_old {
+                continue;
+            }
+
+            if !dry_run {
+                // Backups go along with their session, as nothing would
+                // ever rotate them out again.
+                let mut files = backups_of(&path);
+                files.push(path.clone());
+                if let Some(ref archive_dir) = retention.archive_path {
+                    fs::create_dir_all(archive_dir)
+                        .map_err(SessionError::Archive)?;
+                    for file in files {
+                        let target = archive_dir
+                            .join(file.file_name().unwrap_or_default());
+                        move_file(&file, &target)
+                            .map_err(SessionError::Archive)?;
+                    }
+                } else {
+                    for file in files {
+                        fs::remove_file(&file)
+                            .map_err(SessionError::DeleteFile)?;
+                    }
+                }
+            }
+
+            expired.push(
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+
+        Ok(expired)
+    }
+
     #[inline]
     pub fn add_message(&mut self, role: Role, content: String) {
         self.messages.push(Message::new(role, content));
     }
 
+    /// Renders the conversation as Markdown. Assistant messages are labeled
+    /// with the persona, or the chatbot that wrote them, falling back to
+    /// `assistant`.
+    #[inline]
+    #[must_use]
+    pub fn to_markdown(&self, assistant: &str) -> String {
+        let title = self.name.as_deref().unwrap_or("Conversation");
+        let messages = self
+            .messages
+            .iter()
+            .map(|msg| {
+                format!(
+                    "**{}:**\n\n{}",
+                    speaker(msg, assistant),
+                    msg.content.trim_end()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n");
+
+        format!("# {title}\n\n{messages}\n")
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn request_messages(&self) -> Vec<Message> {
+        self.messages
+            .iter()
+            .filter(|msg| msg.role != Role::Note)
+            .cloned()
+            .collect()
+    }
+
+    #[inline]
+    pub fn dir(config: &Config) -> Result<PathBuf, SessionError> {
+        Self::get_dir_path(config).map(Cow::into_owned)
+    }
+
+    fn backup_count(config: &Config) -> usize {
+        config
+            .sessions
+            .as_ref()
+            .and_then(|sessions| sessions.backups)
+            .unwrap_or(DEFAULT_BACKUPS)
+    }
+
+    fn rotate_backups(
+        file_path: &Path,
+        backups: usize,
+    ) -> Result<(), SessionError> {
+        if backups == 0 {
+            return Ok(());
+        }
+
+        for idx in (1..backups).rev() {
+            let from = backup_path(file_path, idx);
+            if from.exists() {
+                fs::rename(from, backup_path(file_path, idx.saturating_add(1)))
+                    .map_err(SessionError::WriteFile)?;
+            }
+        }
+
+        fs::copy(file_path, backup_path(file_path, 1))
+            .map_err(SessionError::WriteFile)?;
+
+        Ok(())
+    }
+
     fn get_dir_path(config: &Config) -> Result<Cow<'_, PathBuf>, SessionError> {
         if let Some(ref path) = config.session_path {
             return Ok(Cow::Borrowed(path));
@@ -125,3 +471,269 @@ impl Session {
         Ok(Cow::Owned(session_dir))
     }
 }
+
+/// Names who wrote `msg`. Assistant messages are labeled with the persona,
+/// or the chatbot that wrote them, falling back to `assistant`.
+#[inline]
+#[must_use]
+pub fn speaker(msg: &Message, assistant: &str) -> String {
+    match msg.role {
+        Role::System => "System".to_owned(),
+        Role::User => "You".to_owned(),
+        Role::Assistant => msg.speaker.clone().unwrap_or_else(|| {
+            msg.provenance
+                .as_ref()
+                .map_or_else(|| assistant.to_owned(), ToString::to_string)
+        }),
+        Role::Note => "Note".to_owned(),
+    }
+}
+
+/// Returns the first line of `content` containing the lowercase `needle`,
+/// cut to `SNIPPET_CHARS` characters around the match.
+fn snippet(content: &str, needle: &str) -> Option<String> {
+    let line = content
+        .lines()
+        .find(|line| line.to_lowercase().contains(needle))?
+        .trim();
+    let lower = line.to_lowercase();
+    let at = lower
+        .find(needle)
+        .and_then(|byte| lower.get(..byte))
+        .map_or(0, |prefix| prefix.chars().count());
+    let start = at.saturating_sub(SNIPPET_CHARS.checked_div(3).unwrap_or(0));
+    let len = line.chars().count();
+    let text: String = line.chars().skip(start).take(SNIPPET_CHARS).collect();
+
+    Some(format!(
+        "{}{text}{}",
+        if start > 0 { "..." } else { "" },
+        if start.saturating_add(SNIPPET_CHARS) < len {
+            "..."
+        } else {
+            ""
+        }
+    ))
+}
+
+fn iso_date(time: SystemTime) -> Option<String> {
+    let (date, _) = auth::amz_dates(time);
+    Some(format!(
+        "{}-{}-{}",
+        date.get(..4)?,
+        date.get(4..6)?,
+        date.get(6..)?
+    ))
+}
+
+fn backup_path(file_path: &Path, idx: usize) -> PathBuf {
+    file_path.with_extension(format!("json.bak{idx}"))
+}
+
+/// The backups of the session at `file_path`, however many were kept.
+fn backups_of(file_path: &Path) -> Vec<PathBuf> {
+    let Some(dir) = file_path.parent() else {
+        return Vec::new();
+    };
+    let prefix = format!(
+        "{}.bak",
+        file_path.file_name().unwrap_or_default().to_string_lossy()
+    );
+
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name().is_some_and(|name| {
+                        name.to_string_lossy()
+                            .strip_prefix(&prefix)
+                            .is_some_and(|idx| {
+                                idx.bytes().all(|byte| byte.is_ascii_digit())
+                            })
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Renames `from` to `to`, copying and removing it instead when the two are
+/// on different filesystems.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Parses the leading messages of a session file that was cut off mid-write,
+/// stopping at the first message that is incomplete or malformed.
+fn salvage_messages(content: &str) -> Vec<Message> {
+    let mut messages = Vec::new();
+    let Some(mut rest) = content
+        .find("\"messages\"")
+        .and_then(|key| content.get(key..))
+        .and_then(|tail| tail.get(tail.find('[')?..))
+        .and_then(|tail| tail.get(1..))
+    else {
+        return messages;
+    };
+
+    loop {
+        rest = rest.trim_start().trim_start_matches(',').trim_start();
+        let mut values =
+            serde_json::Deserializer::from_str(rest).into_iter::<Message>();
+        let Some(Ok(message)) = values.next() else {
+            break;
+        };
+        let offset = values.byte_offset();
+        messages.push(message);
+        let Some(tail) = rest.get(offset..) else {
+            break;
+        };
+        rest = tail;
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{backup_path, backups_of, move_file, Recovery, Session};
+    use crate::{
+        config::{Config, SessionsConfig},
+        Role,
+    };
+
+    fn config(name: &str, backups: usize) -> (Config, PathBuf) {
+        let dir =
+            env::temp_dir().join(format!("llmcli-{name}-{}", process::id()));
+        assert_eq!(fs::create_dir_all(&dir).ok(), Some(()));
+        let config = Config {
+            session_path: Some(dir.clone()),
+            sessions: Some(SessionsConfig {
+                max_sessions: None,
+                max_age_days: None,
+                archive_path: None,
+                backups: Some(backups),
+                gc_on_startup: None,
+            }),
+            ..Config::default()
+        };
+        (config, dir)
+    }
+
+    fn saying(content: &str) -> Session {
+        let mut session = Session::new();
+        session.add_message(Role::User, content.to_owned());
+        session
+    }
+
+    fn first_message(session: &Session) -> Option<&str> {
+        session.messages.first().map(|msg| msg.content.as_str())
+    }
+
+    #[test]
+    fn finds_backups_and_moves_them_with_the_session() {
+        let dir = env::temp_dir().join(format!("llmcli-gc-{}", process::id()));
+        let archive = dir.join("archive");
+        assert_eq!(fs::create_dir_all(&archive).ok(), Some(()));
+        let session = dir.join("chat.json");
+        for path in [
+            session.clone(),
+            backup_path(&session, 1),
+            backup_path(&session, 12),
+            dir.join("chat.json.bak1.tmp"),
+            dir.join("other.json.bak1"),
+        ] {
+            assert_eq!(fs::write(path, "{}").ok(), Some(()));
+        }
+
+        let mut backups = backups_of(&session);
+        backups.sort();
+        assert_eq!(
+            backups,
+            vec![backup_path(&session, 1), backup_path(&session, 12)]
+        );
+
+        assert_eq!(
+            move_file(&session, &archive.join("chat.json")).ok(),
+            Some(())
+        );
+        assert!(!session.exists());
+        assert!(archive.join("chat.json").exists());
+
+        assert_eq!(fs::remove_dir_all(&dir).ok(), Some(()));
+    }
+
+    #[test]
+    fn saves_through_a_temporary_file_and_rotates_backups() {
+        let (config, dir) = config("save", 2);
+        for content in ["one", "two", "three"] {
+            assert_eq!(saying(content).save("chat", &config).ok(), Some(()));
+        }
+
+        let file = dir.join("chat.json");
+        let loaded = Session::load("chat", &config).ok();
+        assert_eq!(loaded.as_ref().and_then(first_message), Some("three"));
+        let backup = |idx| fs::read_to_string(backup_path(&file, idx)).ok();
+        assert!(backup(1).is_some_and(|json| json.contains("\"two\"")));
+        assert!(backup(2).is_some_and(|json| json.contains("\"one\"")));
+        assert_eq!(backup(3), None);
+        assert!(!dir.join("chat.json.tmp").exists());
+
+        assert_eq!(fs::remove_dir_all(&dir).ok(), Some(()));
+    }
+
+    #[test]
+    fn recovers_from_the_newest_readable_backup() {
+        let (config, dir) = config("recover", 2);
+        for content in ["one", "two", "three"] {
+            assert_eq!(saying(content).save("chat", &config).ok(), Some(()));
+        }
+        let file = dir.join("chat.json");
+        assert_eq!(fs::write(&file, "{\"messages\": [").ok(), Some(()));
+        assert_eq!(fs::write(backup_path(&file, 1), "").ok(), Some(()));
+
+        let recovered = Session::recover("chat", &config).ok();
+
+        assert!(matches!(recovered, Some((_, Recovery::Backup(2)))));
+        assert_eq!(
+            recovered
+                .as_ref()
+                .and_then(|recovered| first_message(&recovered.0)),
+            Some("one")
+        );
+
+        assert_eq!(fs::remove_dir_all(&dir).ok(), Some(()));
+    }
+
+    #[test]
+    fn salvages_the_messages_before_a_cut() {
+        let (config, dir) = config("salvage", 0);
+        let mut session = saying("kept");
+        session.add_message(Role::Assistant, "cut off".to_owned());
+        assert_eq!(session.save("chat", &config).ok(), Some(()));
+        let file = dir.join("chat.json");
+        let json = fs::read_to_string(&file).unwrap_or_default();
+        let (kept, _) = json.split_once("cut off").unwrap_or_default();
+        assert_eq!(fs::write(&file, kept).ok(), Some(()));
+
+        let recovered = Session::recover("chat", &config).ok();
+
+        assert!(matches!(recovered, Some((_, Recovery::Salvaged(1)))));
+        assert_eq!(
+            recovered
+                .as_ref()
+                .and_then(|recovered| first_message(&recovered.0)),
+            Some("kept")
+        );
+
+        assert_eq!(fs::remove_dir_all(&dir).ok(), Some(()));
+    }
+}
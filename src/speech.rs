@@ -0,0 +1,376 @@
+use std::env;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    base64, chatbots,
+    config::{AudioConfig, Config},
+    ChatbotChatError, ChatbotCreationError,
+};
+
+const GEMINI_BASE_URL: &str =
+    "https://generativelanguage.googleapis.com/v1beta/models/";
+
+const DEFAULT_MODEL: &str = "gemini-2.5-flash-preview-tts";
+
+const DEFAULT_VOICE: &str = "Kore";
+
+const DEFAULT_SAMPLE_RATE: u32 = 24_000;
+
+const OPENAI_URL: &str = "https://api.openai.com/v1/audio/speech";
+
+const OPENAI_MODELS: [&str; 3] = ["gpt-4o-mini-tts", "tts-1", "tts-1-hd"];
+
+const OPENAI_DEFAULT_VOICE: &str = "alloy";
+
+#[async_trait]
+pub trait SpeechSynthesizer: Send + Sync {
+    /// Reads `text` aloud and returns the audio as a WAV file.
+    async fn synthesize(&self, text: &str)
+        -> Result<Vec<u8>, ChatbotChatError>;
+}
+
+#[inline]
+pub fn create(
+    config: &Config,
+) -> Result<Box<dyn SpeechSynthesizer>, ChatbotCreationError> {
+    let audio = config.audio.as_ref();
+    if provider(config) == "OpenAI" {
+        return OpenAISpeech::create(audio, config);
+    }
+
+    GeminiSpeech::create(audio, config)
+}
+
+/// The provider whose API key the configured audio model needs.
+#[inline]
+#[must_use]
+pub fn provider(config: &Config) -> &'static str {
+    if config
+        .audio
+        .as_ref()
+        .and_then(|audio| audio.model.as_deref())
+        .is_some_and(|model| OPENAI_MODELS.contains(&model))
+    {
+        "OpenAI"
+    } else {
+        "Gemini"
+    }
+}
+
+#[derive(Serialize)]
+struct TextPart<'text> {
+    text: &'text str,
+}
+
+#[derive(Serialize)]
+struct Content<'text> {
+    parts: Vec<TextPart<'text>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PrebuiltVoiceConfig<'voice> {
+    voice_name: &'voice str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VoiceConfig<'voice> {
+    prebuilt_voice_config: PrebuiltVoiceConfig<'voice>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpeechConfig<'voice> {
+    voice_config: VoiceConfig<'voice>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig<'voice> {
+    response_modalities: [&'static str; 1],
+    speech_config: SpeechConfig<'voice>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpeechRequest<'text, 'voice> {
+    contents: Vec<Content<'text>>,
+    generation_config: GenerationConfig<'voice>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InlineData {
+    mime_type: String,
+    data: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AudioPart {
+    inline_data: Option<InlineData>,
+}
+
+#[derive(Deserialize)]
+struct AudioContent {
+    parts: Vec<AudioPart>,
+}
+
+#[derive(Deserialize)]
+struct AudioCandidate {
+    content: AudioContent,
+}
+
+#[derive(Deserialize)]
+struct SpeechResponse {
+    candidates: Vec<AudioCandidate>,
+}
+
+pub struct GeminiSpeech {
+    model: String,
+    voice: String,
+    api_key: String,
+    client: Client,
+}
+
+impl GeminiSpeech {
+    fn create(
+        audio: Option<&AudioConfig>,
+        config: &Config,
+    ) -> Result<Box<dyn SpeechSynthesizer>, ChatbotCreationError> {
+        let api_key = if let Some(api_key) = config.api_key("gemini") {
+            api_key
+        } else {
+            env::var("GEMINI_API_KEY")?
+        };
+
+        let client = match config
+            .chatbot("gemini")
+            .and_then(|chatbot| chatbot.headers.as_ref())
+        {
+            Some(headers) => chatbots::client_with_headers(headers)?,
+            None => Client::new(),
+        };
+
+        Ok(Box::new(Self {
+            model: audio
+                .and_then(|audio| audio.model.clone())
+                .unwrap_or_else(|| DEFAULT_MODEL.to_owned()),
+            voice: audio
+                .and_then(|audio| audio.voice.clone())
+                .unwrap_or_else(|| DEFAULT_VOICE.to_owned()),
+            api_key,
+            client,
+        }))
+    }
+}
+
+#[async_trait]
+impl SpeechSynthesizer for GeminiSpeech {
+    #[inline]
+    async fn synthesize(
+        &self,
+        text: &str,
+    ) -> Result<Vec<u8>, ChatbotChatError> {
+        let body = SpeechRequest {
+            contents: vec![Content {
+                parts: vec![TextPart { text }],
+            }],
+            generation_config: GenerationConfig {
+                response_modalities: ["AUDIO"],
+                speech_config: SpeechConfig {
+                    voice_config: VoiceConfig {
+                        prebuilt_voice_config: PrebuiltVoiceConfig {
+                            voice_name: &self.voice,
+                        },
+                    },
+                },
+            },
+        };
+
+        let resp = self
+            .client
+            .post(format!(
+                "{GEMINI_BASE_URL}{}:generateContent?key={}",
+                self.model, self.api_key
+            ))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(ChatbotChatError::RateLimited);
+        }
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::UnexpectedResponse);
+        }
+
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                Invalid JSON from the API indicates a critical error so we
+                hide that detail from the end user, as they cannot address
+                this issue.
+            "#
+        )]
+        let speech_resp: SpeechResponse = resp
+            .json()
+            .await
+            .map_err(|_| ChatbotChatError::UnexpectedResponse)?;
+
+        let audio = speech_resp
+            .candidates
+            .into_iter()
+            .flat_map(|candidate| candidate.content.parts)
+            .find_map(|part| part.inline_data)
+            .ok_or(ChatbotChatError::UnexpectedResponse)?;
+        let pcm = base64::decode(&audio.data)
+            .ok_or(ChatbotChatError::UnexpectedResponse)?;
+
+        Ok(wav(&pcm, sample_rate(&audio.mime_type)))
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIRequest<'req> {
+    model: &'req str,
+    input: &'req str,
+    voice: &'req str,
+    response_format: &'static str,
+}
+
+pub struct OpenAISpeech {
+    model: String,
+    voice: String,
+    api_key: String,
+    client: Client,
+}
+
+impl OpenAISpeech {
+    fn create(
+        audio: Option<&AudioConfig>,
+        config: &Config,
+    ) -> Result<Box<dyn SpeechSynthesizer>, ChatbotCreationError> {
+        let api_key = if let Some(api_key) = config.api_key("openai") {
+            api_key
+        } else {
+            env::var("OPENAI_API_KEY")?
+        };
+
+        let client = match config
+            .chatbot("openai")
+            .and_then(|chatbot| chatbot.headers.as_ref())
+        {
+            Some(headers) => chatbots::client_with_headers(headers)?,
+            None => Client::new(),
+        };
+
+        Ok(Box::new(Self {
+            model: audio
+                .and_then(|audio| audio.model.clone())
+                .unwrap_or_else(|| OPENAI_MODELS[0].to_owned()),
+            voice: audio
+                .and_then(|audio| audio.voice.clone())
+                .unwrap_or_else(|| OPENAI_DEFAULT_VOICE.to_owned()),
+            api_key,
+            client,
+        }))
+    }
+}
+
+#[async_trait]
+impl SpeechSynthesizer for OpenAISpeech {
+    #[inline]
+    async fn synthesize(
+        &self,
+        text: &str,
+    ) -> Result<Vec<u8>, ChatbotChatError> {
+        let body = OpenAIRequest {
+            model: &self.model,
+            input: text,
+            voice: &self.voice,
+            response_format: "wav",
+        };
+
+        let resp = self
+            .client
+            .post(OPENAI_URL)
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    ChatbotChatError::Timeout
+                } else {
+                    ChatbotChatError::NetworkError(err)
+                }
+            })?;
+
+        if !resp.status().is_success() {
+            return Err(ChatbotChatError::from_response(resp).await);
+        }
+
+        // The endpoint answers with the audio file itself rather than JSON.
+        let audio =
+            resp.bytes().await.map_err(ChatbotChatError::NetworkError)?;
+
+        Ok(audio.to_vec())
+    }
+}
+
+/// Reads the sample rate from a mime type like
+/// `audio/L16;codec=pcm;rate=24000`.
+fn sample_rate(mime_type: &str) -> u32 {
+    mime_type
+        .split(';')
+        .find_map(|param| param.trim().strip_prefix("rate="))
+        .and_then(|rate| rate.parse().ok())
+        .unwrap_or(DEFAULT_SAMPLE_RATE)
+}
+
+/// Wraps 16-bit mono PCM samples in a WAV header.
+#[expect(
+    clippy::little_endian_bytes,
+    reason = r#"
+        WAV headers are little-endian whatever the host is.
+    "#
+)]
+fn wav(pcm: &[u8], sample_rate: u32) -> Vec<u8> {
+    const HEADER_LEN: u32 = 36;
+    const BYTES_PER_SAMPLE: u16 = 2;
+
+    let data_len = u32::try_from(pcm.len()).unwrap_or(u32::MAX);
+    let mut wav = Vec::with_capacity(pcm.len().saturating_add(44));
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&HEADER_LEN.saturating_add(data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16_u32.to_le_bytes());
+    wav.extend_from_slice(&1_u16.to_le_bytes());
+    wav.extend_from_slice(&1_u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(
+        &sample_rate
+            .saturating_mul(u32::from(BYTES_PER_SAMPLE))
+            .to_le_bytes(),
+    );
+    wav.extend_from_slice(&BYTES_PER_SAMPLE.to_le_bytes());
+    wav.extend_from_slice(&16_u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend_from_slice(pcm);
+
+    wav
+}
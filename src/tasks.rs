@@ -0,0 +1,298 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    path::Path,
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    auth, params::GenerationParams, refine, Chatbot, ChatbotChatError, Message,
+    Role,
+};
+
+/// Content lines longer than this many bytes are folded, as RFC 5545
+/// requires.
+const ICS_LINE_BYTES: usize = 75;
+
+const TASKS_PROMPT: &str = "List the action items agreed on or implied in \
+                            this conversation. Reply with only a JSON array \
+                            of objects with a required \"task\" string and \
+                            optional \"owner\" and \"due\" strings, using \
+                            YYYY-MM-DD for dates. Reply with [] if there \
+                            are none.";
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum TaskError {
+    #[error("{0}")]
+    Chatbot(#[from] ChatbotChatError),
+    #[error("The model did not return a valid task list: {0}")]
+    Invalid(serde_json::Error),
+    #[error("The model returned a task without a description.")]
+    EmptyTask,
+    #[error("No TODO file configured, set `tasks.file` to save tasks.")]
+    NoFile,
+    #[error("Failed to write the TODO file: {0}")]
+    Write(#[from] io::Error),
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub enum TaskFormat {
+    #[default]
+    #[serde(rename = "markdown")]
+    Markdown,
+    #[serde(rename = "todo.txt")]
+    TodoTxt,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Deserialize)]
+pub struct ActionItem {
+    pub task: String,
+    pub owner: Option<String>,
+    pub due: Option<String>,
+}
+
+impl ActionItem {
+    #[inline]
+    #[must_use]
+    pub fn to_line(&self, format: TaskFormat) -> String {
+        let mut line = match format {
+            TaskFormat::Markdown => format!("- [ ] {}", self.task),
+            TaskFormat::TodoTxt => self.task.clone(),
+        };
+        if let Some(ref owner) = self.owner {
+            match format {
+                TaskFormat::Markdown => line.push_str(" (owner: "),
+                TaskFormat::TodoTxt => line.push_str(" @"),
+            }
+            line.push_str(&owner.replace(char::is_whitespace, "_"));
+            if matches!(format, TaskFormat::Markdown) {
+                line.push(')');
+            }
+        }
+        if let Some(ref due) = self.due {
+            match format {
+                TaskFormat::Markdown => line.push_str(" (due: "),
+                TaskFormat::TodoTxt => line.push_str(" due:"),
+            }
+            line.push_str(due);
+            if matches!(format, TaskFormat::Markdown) {
+                line.push(')');
+            }
+        }
+        line
+    }
+
+    /// The due date as `YYYYMMDD`, if it is a valid `YYYY-MM-DD` date.
+    fn ics_date(&self) -> Option<String> {
+        let due = self.due.as_deref()?.trim();
+        let mut parts = due.split('-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        let valid = parts.next().is_none()
+            && year.len() == 4
+            && month.len() == 2
+            && day.len() == 2
+            && due.chars().all(|ch| ch.is_ascii_digit() || ch == '-')
+            && month
+                .parse::<u8>()
+                .is_ok_and(|month| (1..=12).contains(&month))
+            && day.parse::<u8>().is_ok_and(|day| (1..=31).contains(&day));
+        valid.then(|| format!("{year}{month}{day}"))
+    }
+}
+
+/// Asks `chatbot` for the action items in `history`. Answers that are not
+/// a valid list are sent back once with the parse error before giving up.
+#[inline]
+pub async fn extract(
+    chatbot: &dyn Chatbot,
+    history: &[Message],
+) -> Result<Vec<ActionItem>, TaskError> {
+    let mut messages = history.to_vec();
+    messages.push(Message::new(Role::User, TASKS_PROMPT.to_owned()));
+
+    let response =
+        refine::complete(chatbot, &messages, GenerationParams::default())
+            .await?;
+    let err = match parse(&response) {
+        Ok(items) => return Ok(items),
+        Err(TaskError::Invalid(err)) => err,
+        Err(err) => return Err(err),
+    };
+
+    messages.push(Message::new(Role::Assistant, response));
+    messages.push(Message::new(
+        Role::User,
+        format!("That is not valid: {err}. {TASKS_PROMPT}"),
+    ));
+    let response =
+        refine::complete(chatbot, &messages, GenerationParams::default())
+            .await?;
+    parse(&response)
+}
+
+fn parse(response: &str) -> Result<Vec<ActionItem>, TaskError> {
+    let json = response
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    let items: Vec<ActionItem> =
+        serde_json::from_str(json).map_err(TaskError::Invalid)?;
+    if items.iter().any(|item| item.task.trim().is_empty()) {
+        return Err(TaskError::EmptyTask);
+    }
+    Ok(items)
+}
+
+/// Appends `items` to the TODO file at `path`, one per line.
+#[inline]
+pub fn append(
+    path: &Path,
+    format: TaskFormat,
+    items: &[ActionItem],
+) -> Result<(), TaskError> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for item in items {
+        writeln!(file, "{}", item.to_line(format))?;
+    }
+    Ok(())
+}
+
+/// Writes the items that have a due date to `path` as all-day iCalendar
+/// events, replacing the file. Returns the number of events written.
+#[inline]
+pub fn write_ics(
+    path: &Path,
+    items: &[ActionItem],
+) -> Result<usize, TaskError> {
+    let (_, stamp) = auth::amz_dates(SystemTime::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        concat!("PRODID:-//llmcli//", env!("CARGO_PKG_VERSION"), "//EN")
+            .to_owned(),
+    ];
+    let mut count = 0_usize;
+    for item in items {
+        let Some(date) = item.ics_date() else {
+            continue;
+        };
+        let uid = auth::sha256_hex(format!("{}\n{date}", item.task).as_bytes());
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.push(format!("UID:{uid}@llmcli"));
+        lines.push(format!("DTSTAMP:{stamp}"));
+        lines.push(format!("DTSTART;VALUE=DATE:{date}"));
+        lines.push(format!("SUMMARY:{}", escape_text(&item.task)));
+        if let Some(ref owner) = item.owner {
+            lines.push(format!("DESCRIPTION:Owner: {}", escape_text(owner)));
+        }
+        lines.push("END:VEVENT".to_owned());
+        count = count.saturating_add(1);
+    }
+    lines.push("END:VCALENDAR".to_owned());
+
+    let mut ics = String::new();
+    for line in &lines {
+        fold_line(line, &mut ics);
+    }
+    fs::write(path, ics)?;
+    Ok(count)
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Appends `line` to `ics` with CRLF endings, continuing it on lines that
+/// start with a space when it is too long.
+fn fold_line(line: &str, ics: &mut String) {
+    let mut width = 0_usize;
+    for ch in line.chars() {
+        if width.saturating_add(ch.len_utf8()) > ICS_LINE_BYTES {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(ch);
+        width = width.saturating_add(ch.len_utf8());
+    }
+    ics.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_text, fold_line, ActionItem, ICS_LINE_BYTES};
+
+    fn due(date: &str) -> ActionItem {
+        ActionItem {
+            task: "Ship it".to_owned(),
+            owner: None,
+            due: Some(date.to_owned()),
+        }
+    }
+
+    #[test]
+    fn escapes_ics_text() {
+        assert_eq!(
+            escape_text("Review a,b; c\\d\r\nthen merge"),
+            r"Review a\,b\; c\\d\nthen merge"
+        );
+    }
+
+    #[test]
+    fn folds_long_lines_without_splitting_characters() {
+        let line = format!("SUMMARY:{}", "\u{e9}".repeat(60));
+        let mut ics = String::new();
+        fold_line(&line, &mut ics);
+
+        let physical: Vec<&str> = ics.split_terminator("\r\n").collect();
+        assert!(physical.len() > 1);
+        assert!(physical.iter().all(|part| part.len() <= ICS_LINE_BYTES));
+        assert!(physical.iter().skip(1).all(|part| part.starts_with(' ')));
+        let unfolded: String = physical
+            .iter()
+            .map(|part| part.strip_prefix(' ').unwrap_or(part))
+            .collect();
+        assert_eq!(unfolded, line);
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let mut ics = String::new();
+        fold_line("BEGIN:VCALENDAR", &mut ics);
+        assert_eq!(ics, "BEGIN:VCALENDAR\r\n");
+    }
+
+    #[test]
+    fn only_valid_dates_become_events() {
+        assert_eq!(due("2026-10-16").ics_date().as_deref(), Some("20261016"));
+        assert_eq!(due(" 2026-01-31 ").ics_date().as_deref(), Some("20260131"));
+        assert_eq!(due("2026-13-01").ics_date(), None);
+        assert_eq!(due("next friday").ics_date(), None);
+        assert_eq!(due("2026-1-5").ics_date(), None);
+    }
+}
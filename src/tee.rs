@@ -0,0 +1,81 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+
+pub struct Tee {
+    file: File,
+    path: PathBuf,
+    markdown: bool,
+}
+
+impl Tee {
+    #[inline]
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            file,
+            path,
+            markdown: false,
+        })
+    }
+
+    /// Like `open`, but every response is preceded by the prompt and role
+    /// headers, so a Markdown preview of the file renders the conversation
+    /// live as it streams.
+    #[inline]
+    pub fn open_markdown(path: PathBuf) -> io::Result<Self> {
+        Ok(Self {
+            markdown: true,
+            ..Self::open(path)?
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn start_response(
+        &mut self,
+        prompt: Option<&str>,
+        speaker: &str,
+    ) -> io::Result<()> {
+        if !self.markdown {
+            return Ok(());
+        }
+
+        let mut header = String::new();
+        if let Some(prompt) = prompt {
+            header.push_str("## You\n\n");
+            header.push_str(prompt.trim_end());
+            header.push_str("\n\n");
+        }
+        header.push_str("## ");
+        header.push_str(speaker);
+        header.push_str("\n\n");
+        self.write_chunk(&header)
+    }
+
+    #[inline]
+    pub fn write_chunk(&mut self, text: &str) -> io::Result<()> {
+        self.file.write_all(text.as_bytes())?;
+        self.file.flush()
+    }
+
+    /// Removes the last `len` bytes written, such as a partial response
+    /// that is about to be streamed again.
+    #[inline]
+    pub fn discard(&mut self, len: u64) -> io::Result<()> {
+        let size = self.file.metadata()?.len();
+        self.file.set_len(size.saturating_sub(len))
+    }
+
+    #[inline]
+    pub fn finish_response(&mut self) -> io::Result<()> {
+        self.write_chunk(if self.markdown { "\n\n" } else { "\n" })
+    }
+}
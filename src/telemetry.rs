@@ -0,0 +1,102 @@
+use std::time::Instant;
+
+use opentelemetry::{
+    global::{self, BoxedSpan},
+    trace::{Span as _, Status, Tracer as _},
+    KeyValue,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig as _};
+use opentelemetry_sdk::{runtime, trace::TracerProvider};
+use thiserror::Error;
+
+use crate::TokenUsage;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+#[error("Failed to set up trace export: {0}.")]
+pub struct TelemetryError(String);
+
+pub struct Telemetry {
+    provider: TracerProvider,
+}
+
+impl Telemetry {
+    #[inline]
+    pub fn init(endpoint: &str) -> Result<Self, TelemetryError> {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|err| TelemetryError(err.to_string()))?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .build();
+
+        global::set_tracer_provider(provider.clone());
+
+        Ok(Self { provider })
+    }
+
+    #[inline]
+    pub fn shutdown(self) -> Result<(), TelemetryError> {
+        self.provider
+            .shutdown()
+            .map_err(|err| TelemetryError(err.to_string()))
+    }
+}
+
+pub struct RequestSpan {
+    span: BoxedSpan,
+    start: Instant,
+}
+
+impl RequestSpan {
+    #[inline]
+    #[must_use]
+    pub fn start(provider: &str, model: &str) -> Self {
+        let mut span = global::tracer("llmcli").start("chat");
+        span.set_attribute(KeyValue::new("llm.provider", provider.to_owned()));
+        span.set_attribute(KeyValue::new("llm.model", model.to_owned()));
+
+        Self {
+            span,
+            start: Instant::now(),
+        }
+    }
+
+    /// Ends the span. Token counts are only recorded when the provider
+    /// reported them; the response length is always recorded in characters.
+    #[inline]
+    pub fn finish(
+        mut self,
+        response_chars: usize,
+        usage: Option<TokenUsage>,
+        error_class: Option<&'static str>,
+    ) {
+        let latency_ms =
+            i64::try_from(self.start.elapsed().as_millis()).unwrap_or(i64::MAX);
+        self.span
+            .set_attribute(KeyValue::new("llm.latency_ms", latency_ms));
+        self.span.set_attribute(KeyValue::new(
+            "llm.response.chars",
+            i64::try_from(response_chars).unwrap_or(i64::MAX),
+        ));
+        if let Some(usage) = usage {
+            self.span.set_attribute(KeyValue::new(
+                "llm.usage.prompt_tokens",
+                i64::try_from(usage.prompt_tokens).unwrap_or(i64::MAX),
+            ));
+            self.span.set_attribute(KeyValue::new(
+                "llm.usage.completion_tokens",
+                i64::try_from(usage.completion_tokens).unwrap_or(i64::MAX),
+            ));
+        }
+
+        if let Some(class) = error_class {
+            self.span.set_attribute(KeyValue::new("error.type", class));
+            self.span.set_status(Status::error(class));
+        }
+
+        self.span.end();
+    }
+}
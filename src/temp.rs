@@ -0,0 +1,75 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io,
+    path::PathBuf,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Creates a new file with `extension` in the temporary directory that
+/// only the user can read. The name is unique and the file must not exist
+/// yet, so nobody else can plant or read it.
+#[inline]
+pub fn private_file(
+    prefix: &str,
+    extension: &str,
+) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        let path = env::temp_dir().join(format!(
+            "{prefix}-{}-{nanos}-{}.{extension}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt as _;
+            options.mode(0o600);
+        }
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::private_file;
+
+    #[test]
+    fn files_are_unique_and_private() {
+        let first =
+            private_file("llmcli-test", "md").ok().map(|(path, _)| path);
+        let second =
+            private_file("llmcli-test", "md").ok().map(|(path, _)| path);
+        assert!(first.is_some());
+        assert_ne!(first, second);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            let mode = first
+                .as_ref()
+                .and_then(|path| fs::metadata(path).ok())
+                .map(|metadata| metadata.permissions().mode() & 0o777);
+            assert_eq!(mode, Some(0o600));
+        }
+
+        for path in first.iter().chain(&second) {
+            assert_eq!(fs::remove_file(path).ok(), Some(()));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::{io, process::Command};
+
+use thiserror::Error;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum TmuxError {
+    #[error("Failed to run tmux: {0}.")]
+    Spawn(#[from] io::Error),
+    #[error("tmux send-keys failed: {0}")]
+    Failed(String),
+}
+
+/// Types `text` into the tmux pane `target`. Keys are sent literally, so
+/// nothing runs unless `enter` is set or the user presses Enter in the pane.
+#[inline]
+pub fn send_keys(
+    target: &str,
+    text: &str,
+    enter: bool,
+) -> Result<(), TmuxError> {
+    run(&["send-keys", "-t", target, "-l", text])?;
+    if enter {
+        run(&["send-keys", "-t", target, "Enter"])?;
+    }
+    Ok(())
+}
+
+fn run(args: &[&str]) -> Result<(), TmuxError> {
+    let output = Command::new("tmux").args(args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(TmuxError::Failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ))
+    }
+}
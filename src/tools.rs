@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::{Config, ToolsConfig},
+    tools::{
+        calculator::Calculator,
+        fs::{Allowlist, ListDir, ReadFile, WriteFile},
+    },
+    ui::Printer,
+};
+
+pub mod calculator;
+pub mod fs;
+
+pub const DEFAULT_MAX_ROUNDS: usize = 4;
+
+const TOOL_INFO: &str = "tool ";
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ToolError {
+    #[error("Unknown tool `{0}`.")]
+    Unknown(String),
+    #[error("Invalid input: {0}.")]
+    InvalidInput(String),
+}
+
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// One line telling the model what the tool does and what input it
+    /// expects.
+    fn description(&self) -> &'static str;
+
+    fn call(&self, input: &str) -> Result<String, ToolError>;
+}
+
+/// A tool invocation and its result, kept on the assistant message so the
+/// numbers in an answer can be traced back to where they were computed.
+#[non_exhaustive]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ToolCall {
+    pub tool: String,
+    pub input: String,
+    pub output: String,
+}
+
+/// The tools exposed to the model.
+///
+/// Models call a tool by replying with a fenced block tagged `tool <name>`
+/// whose body is the tool input; the results are sent back and the model is
+/// asked again, up to `max_rounds` times per prompt.
+pub struct Toolbox {
+    tools: Vec<Box<dyn Tool>>,
+    max_rounds: usize,
+}
+
+impl Toolbox {
+    /// Returns `None` unless at least one known tool is enabled in the
+    /// `[tools]` config section.
+    #[inline]
+    #[must_use]
+    pub fn from_config(config: &Config, printer: Printer) -> Option<Self> {
+        let tools_config = config.tools.as_ref()?;
+        let tools: Vec<Box<dyn Tool>> = tools_config
+            .enabled
+            .iter()
+            .flatten()
+            .filter_map(|name| create(name, tools_config, printer))
+            .collect();
+
+        (!tools.is_empty()).then(|| Self {
+            tools,
+            max_rounds: tools_config.max_rounds.unwrap_or(DEFAULT_MAX_ROUNDS),
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn max_rounds(&self) -> usize {
+        self.max_rounds
+    }
+
+    /// Instructions describing the protocol and the available tools, meant
+    /// to be merged into the system prompt.
+    #[inline]
+    #[must_use]
+    pub fn prompt(&self) -> String {
+        let tools = self
+            .tools
+            .iter()
+            .map(|tool| format!("- {}: {}", tool.name(), tool.description()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "You can use tools. To call one, reply with a fenced code block \
+             whose info string is `tool <name>` and whose body is the tool \
+             input, then stop and wait for the result. If the input \
+             contains ``` itself, open and close the block with more \
+             backticks, e.g. ````. Always use a tool instead of working out \
+             what it computes yourself. Tools:\n{tools}"
+        )
+    }
+
+    /// Runs every tool call in `response`. Failures are reported back to the
+    /// model as the output, so it can correct its input.
+    #[inline]
+    #[must_use]
+    pub fn run(&self, response: &str) -> Vec<ToolCall> {
+        parse_calls(response)
+            .into_iter()
+            .map(|(name, input)| {
+                let output = self
+                    .tools
+                    .iter()
+                    .find(|tool| tool.name() == name)
+                    .ok_or_else(|| ToolError::Unknown(name.to_owned()))
+                    .and_then(|tool| tool.call(&input))
+                    .unwrap_or_else(|err| format!("Error: {err}"));
+                ToolCall {
+                    tool: name.to_owned(),
+                    input,
+                    output,
+                }
+            })
+            .collect()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn results_message(calls: &[ToolCall]) -> String {
+        let results = calls
+            .iter()
+            .map(|call| {
+                format!("{} `{}` => {}", call.tool, call.input, call.output)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("Tool results:\n{results}")
+    }
+}
+
+#[inline]
+#[must_use]
+pub fn create(
+    name: &str,
+    config: &ToolsConfig,
+    printer: Printer,
+) -> Option<Box<dyn Tool>> {
+    let allowlist = || Allowlist::new(config.allowed_roots.as_deref());
+    match name {
+        "calculator" => Some(Box::new(Calculator)),
+        "read_file" => Some(Box::new(ReadFile::new(allowlist()))),
+        "list_dir" => Some(Box::new(ListDir::new(allowlist()))),
+        "write_file" => Some(Box::new(WriteFile::new(
+            allowlist(),
+            config.auto_approve.clone().unwrap_or_default(),
+            printer,
+        ))),
+        _ => None,
+    }
+}
+
+/// Finds the tool calls in `response`. As in Markdown, a call may open with
+/// more than three backticks and then only closes on a fence at least that
+/// long, so inputs can contain fenced blocks of their own.
+fn parse_calls(response: &str) -> Vec<(&str, String)> {
+    let mut calls = Vec::new();
+    let mut lines = response.lines();
+
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        let ticks = line
+            .len()
+            .saturating_sub(line.trim_start_matches('`').len());
+        let Some(name) = line
+            .get(ticks..)
+            .and_then(|info| info.strip_prefix(TOOL_INFO))
+            .filter(|_| ticks >= 3)
+        else {
+            continue;
+        };
+        let input: Vec<&str> = lines
+            .by_ref()
+            .take_while(|line| {
+                let line = line.trim();
+                line.len() < ticks || line.bytes().any(|byte| byte != b'`')
+            })
+            .collect();
+        calls.push((name.trim(), input.join("\n").trim().to_owned()));
+    }
+
+    calls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_calls;
+
+    #[test]
+    fn parses_calls_between_fences() {
+        let response = "Let me check.\n```tool calculator\n2 + 2\n```\nDone.";
+
+        assert_eq!(
+            parse_calls(response),
+            vec![("calculator", "2 + 2".to_owned())]
+        );
+    }
+
+    #[test]
+    fn longer_fences_keep_nested_code_blocks() {
+        let response = "````tool write_file\nREADME.md\n# Usage\n```sh\n\
+                        llmcli gemini\n```\n````\n```tool calculator\n1\n```";
+
+        assert_eq!(
+            parse_calls(response),
+            vec![
+                (
+                    "write_file",
+                    "README.md\n# Usage\n```sh\nllmcli gemini\n```".to_owned()
+                ),
+                ("calculator", "1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_other_code_blocks() {
+        assert!(parse_calls("```rust\nfn main() {}\n```").is_empty());
+    }
+}
@@ -0,0 +1,434 @@
+use core::{f64::consts, iter::Peekable, str::Chars};
+
+use crate::tools::{Tool, ToolError};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Time,
+    Volume,
+    Data,
+    Temperature,
+}
+
+/// Units with their dimension and size in the dimension's base unit.
+/// Temperatures are converted separately, their factor is unused.
+const UNITS: &[(&[&str], Dimension, f64)] = &[
+    (
+        &["mm", "millimeter", "millimeters"],
+        Dimension::Length,
+        0.001,
+    ),
+    (
+        &["cm", "centimeter", "centimeters"],
+        Dimension::Length,
+        0.01,
+    ),
+    (
+        &["m", "meter", "meters", "metre", "metres"],
+        Dimension::Length,
+        1.0,
+    ),
+    (
+        &["km", "kilometer", "kilometers"],
+        Dimension::Length,
+        1_000.0,
+    ),
+    (&["in", "inch", "inches"], Dimension::Length, 0.0254),
+    (&["ft", "foot", "feet"], Dimension::Length, 0.3048),
+    (&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    (&["mi", "mile", "miles"], Dimension::Length, 1_609.344),
+    (&["nmi"], Dimension::Length, 1_852.0),
+    (
+        &["mg", "milligram", "milligrams"],
+        Dimension::Mass,
+        0.000_001,
+    ),
+    (&["g", "gram", "grams"], Dimension::Mass, 0.001),
+    (&["kg", "kilogram", "kilograms"], Dimension::Mass, 1.0),
+    (&["t", "tonne", "tonnes"], Dimension::Mass, 1_000.0),
+    (
+        &["oz", "ounce", "ounces"],
+        Dimension::Mass,
+        0.028_349_523_125,
+    ),
+    (
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        0.453_592_37,
+    ),
+    (
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        0.001,
+    ),
+    (&["s", "sec", "second", "seconds"], Dimension::Time, 1.0),
+    (&["min", "minute", "minutes"], Dimension::Time, 60.0),
+    (&["h", "hr", "hour", "hours"], Dimension::Time, 3_600.0),
+    (&["d", "day", "days"], Dimension::Time, 86_400.0),
+    (&["wk", "week", "weeks"], Dimension::Time, 604_800.0),
+    (&["yr", "year", "years"], Dimension::Time, 31_557_600.0),
+    (
+        &["ml", "milliliter", "milliliters"],
+        Dimension::Volume,
+        0.001,
+    ),
+    (
+        &["l", "liter", "liters", "litre", "litres"],
+        Dimension::Volume,
+        1.0,
+    ),
+    (
+        &["gal", "gallon", "gallons"],
+        Dimension::Volume,
+        3.785_411_784,
+    ),
+    (&["qt", "quart", "quarts"], Dimension::Volume, 0.946_352_946),
+    (&["pt", "pint", "pints"], Dimension::Volume, 0.473_176_473),
+    (&["cup", "cups"], Dimension::Volume, 0.236_588_236_5),
+    (&["b", "byte", "bytes"], Dimension::Data, 1.0),
+    (&["kb"], Dimension::Data, 1e3),
+    (&["mb"], Dimension::Data, 1e6),
+    (&["gb"], Dimension::Data, 1e9),
+    (&["tb"], Dimension::Data, 1e12),
+    (&["kib"], Dimension::Data, 1_024.0),
+    (&["mib"], Dimension::Data, 1_048_576.0),
+    (&["gib"], Dimension::Data, 1_073_741_824.0),
+    (&["tib"], Dimension::Data, 1_099_511_627_776.0),
+    (&["c", "celsius"], Dimension::Temperature, 1.0),
+    (&["f", "fahrenheit"], Dimension::Temperature, 1.0),
+    (&["k", "kelvin"], Dimension::Temperature, 1.0),
+];
+
+/// Evaluates arithmetic expressions and converts between units, so numbers
+/// in answers are computed rather than guessed.
+#[non_exhaustive]
+#[derive(Default)]
+pub struct Calculator;
+
+impl Tool for Calculator {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "calculator"
+    }
+
+    #[inline]
+    fn description(&self) -> &'static str {
+        "evaluates an arithmetic expression such as `2 * (3 + 4) ^ 2 / \
+         sqrt(16)` (functions: sqrt, abs, ln, log, exp, sin, cos, tan, \
+         round, floor, ceil; constants: pi, e), or converts units with \
+         `<value> <unit> to <unit>`, e.g. `12 km to mi` or `100 f to c`"
+    }
+
+    #[inline]
+    fn call(&self, input: &str) -> Result<String, ToolError> {
+        if let Some((from, to)) = input
+            .rsplit_once(" to ")
+            .or_else(|| input.rsplit_once(" in "))
+        {
+            if let Ok(converted) = convert(from, to) {
+                return Ok(converted);
+            }
+        }
+
+        evaluate(input).map(format_number)
+    }
+}
+
+fn evaluate(expression: &str) -> Result<f64, ToolError> {
+    let mut parser = Parser {
+        chars: expression.chars().peekable(),
+    };
+    let value = parser.expression()?;
+    parser.skip_whitespace();
+    if let Some(ch) = parser.chars.next() {
+        return Err(ToolError::InvalidInput(format!("unexpected `{ch}`")));
+    }
+    if !value.is_finite() {
+        return Err(ToolError::InvalidInput(
+            "result is not a finite number".to_owned(),
+        ));
+    }
+    Ok(value)
+}
+
+fn unit(name: &str) -> Option<(&'static str, Dimension, f64)> {
+    let name = name.trim().to_lowercase();
+    UNITS.iter().find_map(|&(names, dimension, factor)| {
+        names.contains(&name.as_str()).then(|| {
+            (
+                names.first().copied().unwrap_or_default(),
+                dimension,
+                factor,
+            )
+        })
+    })
+}
+
+#[expect(
+    clippy::float_arithmetic,
+    reason = r#"
+        Unit conversion is floating point math by nature.
+    "#
+)]
+fn convert(from: &str, to: &str) -> Result<String, ToolError> {
+    let from = from.trim();
+    let split = from.trim_end_matches(char::is_alphabetic).len();
+    let (expression, from_unit) = from
+        .get(..split)
+        .zip(from.get(split..))
+        .ok_or_else(|| ToolError::InvalidInput("missing unit".to_owned()))?;
+
+    let unknown =
+        |name: &str| ToolError::InvalidInput(format!("unknown unit `{name}`"));
+    let (from_name, from_dimension, from_factor) =
+        unit(from_unit).ok_or_else(|| unknown(from_unit))?;
+    let (to_name, to_dimension, to_factor) =
+        unit(to).ok_or_else(|| unknown(to.trim()))?;
+    if from_dimension != to_dimension {
+        return Err(ToolError::InvalidInput(format!(
+            "cannot convert {from_name} to {to_name}"
+        )));
+    }
+
+    let value = evaluate(expression)?;
+    let converted = if from_dimension == Dimension::Temperature {
+        let kelvin = match from_name {
+            "c" => value + 273.15,
+            "f" => (value - 32.0) * 5.0 / 9.0 + 273.15,
+            _ => value,
+        };
+        match to_name {
+            "c" => kelvin - 273.15,
+            "f" => (kelvin - 273.15) * 9.0 / 5.0 + 32.0,
+            _ => kelvin,
+        }
+    } else {
+        value * from_factor / to_factor
+    };
+
+    Ok(format!(
+        "{} {from_name} = {} {to_name}",
+        format_number(value),
+        format_number(converted)
+    ))
+}
+
+/// Rounds away binary floating point noise such as `0.1 + 0.2`.
+#[expect(
+    clippy::float_arithmetic,
+    reason = r#"
+        Rounding to a fixed number of decimals needs float math.
+    "#
+)]
+fn format_number(value: f64) -> String {
+    const SCALE: f64 = 1e12;
+    let rounded = (value * SCALE).round() / SCALE;
+    if rounded.is_finite() {
+        rounded.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+struct Parser<'input> {
+    chars: Peekable<Chars<'input>>,
+}
+
+#[expect(
+    clippy::float_arithmetic,
+    reason = r#"
+        Evaluating arithmetic is the whole point of the calculator.
+    "#
+)]
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().copied()
+    }
+
+    fn expression(&mut self) -> Result<f64, ToolError> {
+        let mut value = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, ToolError> {
+        let mut value = self.unary()?;
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.chars.next();
+            let rhs = self.unary()?;
+            if op != '*' && rhs == 0.0 {
+                return Err(ToolError::InvalidInput(
+                    "division by zero".to_owned(),
+                ));
+            }
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value.rem_euclid(rhs),
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64, ToolError> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(-self.unary()?)
+            }
+            Some('+') => {
+                self.chars.next();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<f64, ToolError> {
+        let base = self.primary()?;
+        if self.peek() == Some('^') {
+            self.chars.next();
+            let exponent = self.unary()?;
+            return Ok(base.powf(exponent));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<f64, ToolError> {
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.expression()?;
+                if self.peek() != Some(')') {
+                    return Err(ToolError::InvalidInput(
+                        "missing `)`".to_owned(),
+                    ));
+                }
+                self.chars.next();
+                Ok(value)
+            }
+            Some(ch) if ch.is_ascii_digit() || ch == '.' => self.number(),
+            Some(ch) if ch.is_alphabetic() => self.identifier(),
+            Some(ch) => {
+                Err(ToolError::InvalidInput(format!("unexpected `{ch}`")))
+            }
+            None => Err(ToolError::InvalidInput(
+                "unexpected end of expression".to_owned(),
+            )),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, ToolError> {
+        let mut literal = String::new();
+        while let Some(ch) = self
+            .chars
+            .next_if(|&ch| ch.is_ascii_digit() || matches!(ch, '.' | '_' | ','))
+        {
+            if ch == '.' || ch.is_ascii_digit() {
+                literal.push(ch);
+            }
+        }
+        #[expect(
+            clippy::map_err_ignore,
+            reason = r#"
+                The literal itself tells the model more than the parser
+                error does.
+            "#
+        )]
+        literal.parse().map_err(|_| {
+            ToolError::InvalidInput(format!("invalid number `{literal}`"))
+        })
+    }
+
+    fn identifier(&mut self) -> Result<f64, ToolError> {
+        let mut name = String::new();
+        while let Some(ch) = self.chars.next_if(|ch| ch.is_alphanumeric()) {
+            name.push(ch);
+        }
+
+        match name.to_lowercase().as_str() {
+            "pi" => return Ok(consts::PI),
+            "e" => return Ok(consts::E),
+            _ => {}
+        }
+
+        if self.peek() != Some('(') {
+            return Err(ToolError::InvalidInput(format!(
+                "unknown constant `{name}`"
+            )));
+        }
+        let arg = self.primary()?;
+
+        match name.to_lowercase().as_str() {
+            "sqrt" => Ok(arg.sqrt()),
+            "abs" => Ok(arg.abs()),
+            "ln" => Ok(arg.ln()),
+            "log" => Ok(arg.log10()),
+            "exp" => Ok(arg.exp()),
+            "sin" => Ok(arg.sin()),
+            "cos" => Ok(arg.cos()),
+            "tan" => Ok(arg.tan()),
+            "round" => Ok(arg.round()),
+            "floor" => Ok(arg.floor()),
+            "ceil" => Ok(arg.ceil()),
+            _ => Err(ToolError::InvalidInput(format!(
+                "unknown function `{name}`"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Calculator;
+    use crate::tools::Tool as _;
+
+    fn eval(input: &str) -> Option<String> {
+        Calculator.call(input).ok()
+    }
+
+    #[test]
+    fn evaluates_arithmetic_with_precedence() {
+        assert_eq!(eval("2 * (3 + 4) ^ 2 / sqrt(16)").as_deref(), Some("24.5"));
+        assert_eq!(eval("-2 ^ 2").as_deref(), Some("-4"));
+        assert_eq!(eval("0.1 + 0.2").as_deref(), Some("0.3"));
+        assert_eq!(eval("1,000 * 3").as_deref(), Some("3000"));
+        assert_eq!(eval("-7 % 3").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!(eval("1 / 0"), None);
+        assert_eq!(eval("(1 + 2"), None);
+        assert_eq!(eval("foo(2)"), None);
+        assert_eq!(eval("2 +"), None);
+    }
+
+    #[test]
+    fn converts_units_within_a_dimension() {
+        assert_eq!(
+            eval("12 km to mi").as_deref(),
+            Some("12 km = 7.456454306848 mi")
+        );
+        assert_eq!(
+            eval("100 f to c").as_deref(),
+            Some("100 f = 37.777777777778 c")
+        );
+        assert_eq!(
+            eval("1 gib in mb").as_deref(),
+            Some("1 gib = 1073.741824 mb")
+        );
+        assert_eq!(eval("1 kg to m"), None);
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diff, index,
+    tools::{Tool, ToolError},
+    ui::Printer,
+};
+
+const MAX_READ_BYTES: usize = 64 * 1024;
+
+/// Directories the filesystem tools may look into. Paths are canonicalized
+/// before the check, so `..` and symlinks cannot escape a root.
+#[derive(Clone)]
+pub struct Allowlist {
+    roots: Vec<PathBuf>,
+}
+
+impl Allowlist {
+    /// Uses the current directory when no roots are configured. Roots that
+    /// do not exist are skipped.
+    #[inline]
+    #[must_use]
+    pub fn new(roots: Option<&[PathBuf]>) -> Self {
+        let roots = roots.map_or_else(
+            || env::current_dir().into_iter().collect(),
+            <[PathBuf]>::to_vec,
+        );
+
+        Self {
+            roots: roots
+                .iter()
+                .filter_map(|root| root.canonicalize().ok())
+                .collect(),
+        }
+    }
+
+    fn absolute(&self, input: &str) -> PathBuf {
+        let input = input.trim();
+        let path = Path::new(if input.is_empty() { "." } else { input });
+        match self.roots.first() {
+            Some(root) if path.is_relative() => root.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    fn check(&self, path: PathBuf) -> Result<PathBuf, ToolError> {
+        if self.roots.iter().any(|root| path.starts_with(root)) {
+            Ok(path)
+        } else {
+            Err(ToolError::InvalidInput(format!(
+                "{} is outside the allowed directories",
+                path.display()
+            )))
+        }
+    }
+
+    fn resolve(&self, input: &str) -> Result<PathBuf, ToolError> {
+        let path = self.absolute(input);
+        let path = path.canonicalize().map_err(|err| {
+            ToolError::InvalidInput(format!("{}: {err}", path.display()))
+        })?;
+        self.check(path)
+    }
+
+    /// Like `resolve`, but the file itself does not have to exist yet, only
+    /// its directory.
+    fn resolve_new(&self, input: &str) -> Result<PathBuf, ToolError> {
+        let path = self.absolute(input);
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name())
+        else {
+            return Err(ToolError::InvalidInput(format!(
+                "{} is not a file path",
+                path.display()
+            )));
+        };
+        let parent = parent.canonicalize().map_err(|err| {
+            ToolError::InvalidInput(format!("{}: {err}", parent.display()))
+        })?;
+        let path = parent.join(name);
+        // Writing through a symlink would land wherever it points, which
+        // may be outside every root.
+        if fs::symlink_metadata(&path).is_ok_and(|meta| meta.is_symlink()) {
+            return Err(ToolError::InvalidInput(format!(
+                "{} is a symlink",
+                path.display()
+            )));
+        }
+        self.check(path)
+    }
+
+    /// The path relative to the root containing it, used to match
+    /// auto-approve patterns.
+    fn relative<'path>(&self, path: &'path Path) -> &'path Path {
+        self.roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+    }
+}
+
+/// Copies `path` to the first free `<path>.bak<n>`, so earlier backups
+/// are never overwritten.
+fn backup(path: &Path) -> io::Result<PathBuf> {
+    let mut source = fs::File::open(path)?;
+    for idx in 1_usize.. {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(format!(".bak{idx}"));
+        let backup = PathBuf::from(backup);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&backup)
+        {
+            Ok(mut file) => {
+                io::copy(&mut source, &mut file)?;
+                return Ok(backup);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::other("no free backup name"))
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of
+/// characters, including `/`, and `?` for a single character.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut pattern_chars = pattern.chars();
+    match pattern_chars.next() {
+        None => text.is_empty(),
+        Some('*') => {
+            let rest = pattern_chars.as_str();
+            text.char_indices()
+                .map(|(idx, _)| idx)
+                .chain([text.len()])
+                .any(|idx| {
+                    text.get(idx..)
+                        .is_some_and(|tail| wildcard_match(rest, tail))
+                })
+        }
+        Some(expected) => {
+            let mut text_chars = text.chars();
+            text_chars.next().is_some_and(|actual| {
+                (expected == '?' || expected == actual)
+                    && wildcard_match(
+                        pattern_chars.as_str(),
+                        text_chars.as_str(),
+                    )
+            })
+        }
+    }
+}
+
+#[non_exhaustive]
+pub struct ReadFile {
+    allowlist: Allowlist,
+}
+
+impl ReadFile {
+    #[inline]
+    #[must_use]
+    pub const fn new(allowlist: Allowlist) -> Self {
+        Self { allowlist }
+    }
+}
+
+impl Tool for ReadFile {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "read_file"
+    }
+
+    #[inline]
+    fn description(&self) -> &'static str {
+        "returns the contents of a text file; the input is its path, \
+         relative to the project directory"
+    }
+
+    /// Paths that do not exist are completed from the repository index when
+    /// a single indexed file ends with them.
+    #[inline]
+    fn call(&self, input: &str) -> Result<String, ToolError> {
+        let path = self.allowlist.resolve(input).or_else(|err| {
+            match *index::complete_path(input).as_slice() {
+                [] => Err(err),
+                [ref path] => self.allowlist.resolve(&path.to_string_lossy()),
+                ref paths => Err(ToolError::InvalidInput(format!(
+                    "{input} is ambiguous, did you mean one of: {}",
+                    paths
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))),
+            }
+        })?;
+        let bytes = fs::read(&path).map_err(|err| {
+            ToolError::InvalidInput(format!("{}: {err}", path.display()))
+        })?;
+
+        let truncated = bytes.len() > MAX_READ_BYTES;
+        let text = String::from_utf8_lossy(
+            bytes.get(..MAX_READ_BYTES).unwrap_or(&bytes),
+        );
+
+        if truncated {
+            Ok(format!(
+                "{text}\n[truncated after {MAX_READ_BYTES} of {} bytes]",
+                bytes.len()
+            ))
+        } else {
+            Ok(text.into_owned())
+        }
+    }
+}
+
+#[non_exhaustive]
+pub struct ListDir {
+    allowlist: Allowlist,
+}
+
+impl ListDir {
+    #[inline]
+    #[must_use]
+    pub const fn new(allowlist: Allowlist) -> Self {
+        Self { allowlist }
+    }
+}
+
+impl Tool for ListDir {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "list_dir"
+    }
+
+    #[inline]
+    fn description(&self) -> &'static str {
+        "lists the entries of a directory, directories ending in `/`; the \
+         input is its path, relative to the project directory"
+    }
+
+    #[inline]
+    fn call(&self, input: &str) -> Result<String, ToolError> {
+        let path = self.allowlist.resolve(input)?;
+        let entries = fs::read_dir(&path).map_err(|err| {
+            ToolError::InvalidInput(format!("{}: {err}", path.display()))
+        })?;
+
+        let mut names: Vec<String> = entries
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                    format!("{name}/")
+                } else {
+                    name
+                }
+            })
+            .collect();
+        names.sort_unstable();
+
+        Ok(names.join("\n"))
+    }
+}
+
+/// Writes a file after showing a diff against its current contents and
+/// asking for confirmation.
+///
+/// Paths matching an auto-approve pattern, or every path with `--yes`, are
+/// written without asking. Overwritten files are first copied to the first
+/// free `<name>.bak<n>`.
+#[non_exhaustive]
+pub struct WriteFile {
+    allowlist: Allowlist,
+    auto_approve: Vec<String>,
+    printer: Printer,
+}
+
+impl WriteFile {
+    #[inline]
+    #[must_use]
+    pub const fn new(
+        allowlist: Allowlist,
+        auto_approve: Vec<String>,
+        printer: Printer,
+    ) -> Self {
+        Self {
+            allowlist,
+            auto_approve,
+            printer,
+        }
+    }
+
+    /// Whether every write is applied without asking, so that the model is
+    /// not told the user reviews them.
+    fn approves_everything(&self) -> bool {
+        self.printer.assumes_yes()
+            || self
+                .auto_approve
+                .iter()
+                .any(|pattern| pattern.chars().all(|char| char == '*'))
+    }
+
+    fn confirm(&self, path: &Path, old: &str, new: &str) -> io::Result<bool> {
+        let relative = self.allowlist.relative(path);
+        let relative_text = relative.to_string_lossy();
+        if self
+            .auto_approve
+            .iter()
+            .any(|pattern| wildcard_match(pattern, &relative_text))
+        {
+            return Ok(true);
+        }
+
+        // Strict mode keeps its output machine readable, so the diff is
+        // only shown to people.
+        if !self.printer.is_strict() {
+            self.printer.print_app_message(&format!(
+                "The model wants to write {}:",
+                relative.display()
+            ))?;
+            self.printer.print_diff(&diff::lines(old, new))?;
+        }
+        self.printer.confirm("Apply this change?")
+    }
+}
+
+impl Tool for WriteFile {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "write_file"
+    }
+
+    #[inline]
+    fn description(&self) -> &'static str {
+        if self.approves_everything() {
+            "writes a text file; the first line of the input is its path, \
+             relative to the project directory, and the remaining lines are \
+             the complete new contents. Writes are applied without review"
+        } else if self.auto_approve.is_empty() {
+            "writes a text file; the first line of the input is its path, \
+             relative to the project directory, and the remaining lines are \
+             the complete new contents. The user reviews every write"
+        } else {
+            "writes a text file; the first line of the input is its path, \
+             relative to the project directory, and the remaining lines are \
+             the complete new contents. The user reviews writes outside the \
+             paths they approved in advance"
+        }
+    }
+
+    #[inline]
+    fn call(&self, input: &str) -> Result<String, ToolError> {
+        let (path, contents) = input.split_once('\n').unwrap_or((input, ""));
+        let path = self.allowlist.resolve_new(path)?;
+        let io_error = |err: io::Error| {
+            ToolError::InvalidInput(format!("{}: {err}", path.display()))
+        };
+
+        let existing = match fs::read_to_string(&path) {
+            Ok(existing) => Some(existing),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(io_error(err)),
+        };
+        let mut contents = contents.to_owned();
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        if existing.as_deref() == Some(contents.as_str()) {
+            return Ok("The file already has these contents.".to_owned());
+        }
+
+        if !self
+            .confirm(&path, existing.as_deref().unwrap_or_default(), &contents)
+            .map_err(io_error)?
+        {
+            return Ok("The user declined the change.".to_owned());
+        }
+
+        let backup = if existing.is_some() {
+            Some(backup(&path).map_err(io_error)?)
+        } else {
+            None
+        };
+        fs::write(&path, &contents).map_err(io_error)?;
+
+        let previous = backup
+            .map(|backup| {
+                format!(
+                    " The previous version is in {}.",
+                    self.allowlist.relative(&backup).display()
+                )
+            })
+            .unwrap_or_default();
+        Ok(format!(
+            "Wrote {} lines to {}.{previous}",
+            contents.lines().count(),
+            self.allowlist.relative(&path).display()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::slice;
+    use std::{env, fs, process};
+
+    use super::{backup, Allowlist};
+
+    #[test]
+    fn backups_are_numbered_instead_of_overwritten() {
+        let dir =
+            env::temp_dir().join(format!("llmcli-backup-{}", process::id()));
+        assert_eq!(fs::create_dir_all(&dir).ok(), Some(()));
+        let path = dir.join("notes.txt");
+        let read = |name| fs::read_to_string(dir.join(name)).ok();
+
+        assert_eq!(fs::write(&path, "first").ok(), Some(()));
+        assert_eq!(backup(&path).ok(), Some(dir.join("notes.txt.bak1")));
+        assert_eq!(fs::write(&path, "second").ok(), Some(()));
+        assert_eq!(backup(&path).ok(), Some(dir.join("notes.txt.bak2")));
+
+        assert_eq!(read("notes.txt.bak1").as_deref(), Some("first"));
+        assert_eq!(read("notes.txt.bak2").as_deref(), Some("second"));
+
+        assert_eq!(fs::remove_dir_all(dir).ok(), Some(()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn new_files_cannot_be_symlinks() {
+        let dir =
+            env::temp_dir().join(format!("llmcli-symlink-{}", process::id()));
+        assert_eq!(fs::create_dir_all(&dir).ok(), Some(()));
+        assert_eq!(
+            std::os::unix::fs::symlink("/etc/passwd", dir.join("link")).ok(),
+            Some(())
+        );
+        let allowlist = Allowlist::new(Some(slice::from_ref(&dir)));
+
+        assert!(allowlist.resolve_new("link").ok().is_none());
+        assert!(allowlist.resolve_new("plain.txt").ok().is_some());
+
+        assert_eq!(fs::remove_dir_all(dir).ok(), Some(()));
+    }
+}
@@ -0,0 +1,114 @@
+use core::{future::Future, time::Duration};
+
+use futures::{future, stream, SinkExt as _, StreamExt as _};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{self, handshake::client::Request, Message as Frame},
+};
+
+use crate::{ChatbotChatError, ResponseStream, StreamItem};
+
+#[cfg(feature = "grpc")]
+pub mod grpc;
+
+/// Sends `message` over a WebSocket opened with `request` and streams the
+/// answer.
+///
+/// `parse` reads each text frame into the text it adds, if any, and
+/// whether it is the last frame of the answer. Servers that close the
+/// connection when done need never report a last frame.
+#[inline]
+pub async fn websocket_stream<F>(
+    request: Request,
+    message: String,
+    mut parse: F,
+) -> Result<ResponseStream, ChatbotChatError>
+where
+    F: FnMut(&str) -> Result<(Option<String>, bool), ChatbotChatError>
+        + Send
+        + 'static,
+{
+    let (mut socket, _) =
+        connect_async(request).await.map_err(websocket_error)?;
+    socket
+        .send(Frame::Text(message))
+        .await
+        .map_err(websocket_error)?;
+
+    let stream = socket
+        .take_while(|frame| {
+            future::ready(!matches!(*frame, Ok(Frame::Close(_))))
+        })
+        .filter_map(move |frame| {
+            future::ready(match frame {
+                Ok(Frame::Text(text)) => Some(parse(&text)),
+                Ok(_) => None,
+                Err(err) => Some(Err(websocket_error(err))),
+            })
+        })
+        .scan(false, |finished, frame| {
+            if *finished {
+                return future::ready(None);
+            }
+            *finished = !matches!(frame, Ok((_, false)));
+            future::ready(Some(frame))
+        })
+        .filter_map(|frame| {
+            future::ready(match frame {
+                Ok((text, _)) => text.map(|text| Ok(StreamItem::Text(text))),
+                Err(err) => Some(Err(err)),
+            })
+        })
+        .boxed();
+
+    Ok(stream)
+}
+
+/// What a job that produces its output over time reports when polled. Both
+/// states carry the whole output so far.
+#[non_exhaustive]
+pub enum PollState {
+    Running(String),
+    Finished(String),
+}
+
+/// Turns a job that has to be polled for its output into a response stream.
+/// `poll` is called every `interval` until the job finishes and each call
+/// yields the text added since the previous one.
+#[inline]
+pub fn polling_stream<F, Fut>(interval: Duration, poll: F) -> ResponseStream
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<PollState, ChatbotChatError>> + Send + 'static,
+{
+    stream::unfold(Some((poll, 0, false)), move |state| async move {
+        let (mut poll, mut seen, mut started) = state?;
+        loop {
+            if started {
+                tokio::time::sleep(interval).await;
+            }
+            started = true;
+
+            let (output, finished) = match poll().await {
+                Ok(PollState::Running(output)) => (output, false),
+                Ok(PollState::Finished(output)) => (output, true),
+                Err(err) => return Some((Err(err), None)),
+            };
+            let added = output.get(seen..).unwrap_or_default().to_owned();
+            seen = output.len();
+
+            if !added.is_empty() {
+                let next = (!finished).then_some((poll, seen, started));
+                return Some((Ok(StreamItem::Text(added)), next));
+            }
+            if finished {
+                return None;
+            }
+        }
+    })
+    .boxed()
+}
+
+fn websocket_error(err: tungstenite::Error) -> ChatbotChatError {
+    ChatbotChatError::WebSocket(Box::new(err))
+}
@@ -0,0 +1,72 @@
+use futures::{future, StreamExt as _};
+use tonic::{
+    client::Grpc,
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    transport::{Channel, ClientTlsConfig, Endpoint},
+    Request, Status,
+};
+
+use crate::{ChatbotChatError, ResponseStream, StreamItem};
+
+/// A TLS endpoint for `url` that connects on the first call made through
+/// the returned channel.
+#[inline]
+pub fn channel(url: String) -> Result<Channel, ChatbotChatError> {
+    let endpoint = Endpoint::from_shared(url)
+        .map_err(|err| grpc_error(Status::invalid_argument(err.to_string())))?
+        .tls_config(ClientTlsConfig::new().with_native_roots())
+        .map_err(|err| grpc_error(Status::unavailable(err.to_string())))?;
+
+    Ok(endpoint.connect_lazy())
+}
+
+/// Calls the server streaming method at `path`, such as
+/// `/package.Service/Method`, and streams the answer. `parse` reads each
+/// message into the item it adds, if any.
+#[inline]
+pub async fn server_streaming<Req, Resp, F>(
+    channel: Channel,
+    path: &'static str,
+    request: Request<Req>,
+    mut parse: F,
+) -> Result<ResponseStream, ChatbotChatError>
+where
+    Req: prost::Message + Send + Sync + 'static,
+    Resp: prost::Message + Default + Send + Sync + 'static,
+    F: FnMut(Resp) -> Result<Option<StreamItem>, ChatbotChatError>
+        + Send
+        + 'static,
+{
+    let mut client = Grpc::new(channel);
+    client
+        .ready()
+        .await
+        .map_err(|err| grpc_error(Status::unavailable(err.to_string())))?;
+
+    let messages = client
+        .server_streaming(
+            request,
+            PathAndQuery::from_static(path),
+            ProstCodec::<Req, Resp>::default(),
+        )
+        .await
+        .map_err(grpc_error)?
+        .into_inner();
+    drop(client);
+
+    let stream = messages
+        .filter_map(move |message| {
+            future::ready(match message {
+                Ok(message) => parse(message).transpose(),
+                Err(status) => Some(Err(grpc_error(status))),
+            })
+        })
+        .boxed();
+
+    Ok(stream)
+}
+
+fn grpc_error(status: Status) -> ChatbotChatError {
+    ChatbotChatError::Grpc(Box::new(status))
+}
@@ -1,21 +1,173 @@
-use std::io;
+use core::{
+    sync::atomic::{AtomicU16, Ordering},
+    time::Duration,
+};
+use std::{
+    io::{self, IsTerminal as _, Write},
+    sync::OnceLock,
+    time::Instant,
+};
 
 use crossterm::{
-    execute,
+    cursor, execute,
     style::{
         Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor,
     },
+    terminal,
 };
 
+use crate::{config::TerminalConfig, diff::DiffLine, Chatbot};
+
+const DIFF_CONTEXT: usize = 2;
+
+const DEFAULT_WIDTH: u16 = 80;
+
+static TERMINAL_WIDTH: AtomicU16 = AtomicU16::new(0);
+
+static PRINTER: OnceLock<Printer> = OnceLock::new();
+
+/// Keeps the cached terminal width current by listening for SIGWINCH, so
+/// output laid out while a response streams follows the window when it is
+/// resized. Must be called from within the Tokio runtime.
+#[inline]
+pub fn watch_resize() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{self, SignalKind};
+
+        let Ok(mut resized) = unix::signal(SignalKind::window_change()) else {
+            return;
+        };
+        TERMINAL_WIDTH.store(query_width(), Ordering::Relaxed);
+        tokio::spawn(async move {
+            while resized.recv().await.is_some() {
+                TERMINAL_WIDTH.store(query_width(), Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+/// Makes `printer` the one `notify` prints with. Only the first call has an
+/// effect.
+#[inline]
+pub fn install(printer: Printer) {
+    PRINTER.get_or_init(|| printer);
+}
+
+/// Prints a notice with the installed printer, for code that has none at
+/// hand, such as the chatbot wrappers. Before `install` it is printed
+/// without colors.
+#[inline]
+pub fn notify(message: &str) {
+    let printer = PRINTER.get().copied().unwrap_or(Printer::new(true));
+    // A notice that cannot be written is no reason to fail the request it
+    // is about, and stderr, where it would be reported, is what failed.
+    printer.print_notice(message).unwrap_or_default();
+}
+
+/// Like `notify`, for an error with a `code` naming its class that does not
+/// end the task at hand.
+#[inline]
+pub fn notify_error(code: &str, message: &str) {
+    let printer = PRINTER.get().copied().unwrap_or(Printer::new(true));
+    // As with `notify`, there is nowhere left to report a failure to.
+    printer
+        .print_error_notice(code, message)
+        .unwrap_or_default();
+}
+
+/// The current terminal width in columns. Without a resize watcher the
+/// terminal is asked every time.
+#[inline]
+#[must_use]
+pub fn terminal_width() -> u16 {
+    match TERMINAL_WIDTH.load(Ordering::Relaxed) {
+        0 => query_width(),
+        width => width,
+    }
+}
+
+fn query_width() -> u16 {
+    terminal::size()
+        .ok()
+        .map(|(columns, _)| columns)
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+#[derive(Clone, Copy)]
 pub struct Printer {
     no_color: bool,
+    strict: bool,
+    assume_yes: bool,
 }
 
 impl Printer {
     #[inline]
     #[must_use]
     pub const fn new(no_color: bool) -> Self {
-        Self { no_color }
+        Self {
+            no_color,
+            strict: false,
+            assume_yes: false,
+        }
+    }
+
+    /// Strict mode for automation: no colors or other escape sequences,
+    /// errors, warnings and messages from llmcli as single JSON lines on
+    /// stderr and no questions asked.
+    #[inline]
+    #[must_use]
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        if strict {
+            self.no_color = true;
+        }
+        self
+    }
+
+    /// Answers yes to every confirmation instead of asking.
+    #[inline]
+    #[must_use]
+    pub const fn assume_yes(mut self, assume_yes: bool) -> Self {
+        self.assume_yes = assume_yes;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn assumes_yes(&self) -> bool {
+        self.assume_yes
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn no_color(&self) -> bool {
+        self.no_color
+    }
+
+    /// Asks a yes or no question. `--yes` answers yes up front, and without
+    /// a terminal or in strict mode the answer is no.
+    #[inline]
+    pub fn confirm(&self, question: &str) -> io::Result<bool> {
+        if self.assume_yes {
+            return Ok(true);
+        }
+        if self.strict || !io::stdin().is_terminal() {
+            return Ok(false);
+        }
+
+        self.print_app_message(&format!("{question} [y/N]"))?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+
+        Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
     }
 
     #[inline]
@@ -36,12 +188,33 @@ impl Printer {
 
     #[inline]
     pub fn print_app_message(&self, message: &str) -> io::Result<()> {
+        self.write_message(&mut io::stdout(), message)
+    }
+
+    /// Prints a message about a request in progress, such as a retry, on
+    /// stderr so that it never ends up in a piped answer.
+    #[inline]
+    pub fn print_notice(&self, message: &str) -> io::Result<()> {
+        self.write_message(&mut io::stderr(), message)
+    }
+
+    fn write_message(
+        self,
+        out: &mut impl Write,
+        message: &str,
+    ) -> io::Result<()> {
+        if self.strict {
+            return print_json_line(&serde_json::json!({
+                "level": "info",
+                "message": message,
+            }));
+        }
+
         if self.no_color {
-            println!("llmcli: {message}");
-            Ok(())
+            writeln!(out, "llmcli: {message}")
         } else {
             execute!(
-                io::stdout(),
+                out,
                 SetForegroundColor(Color::Blue),
                 SetAttribute(Attribute::Bold),
                 Print("llmcli: "),
@@ -72,13 +245,199 @@ impl Printer {
     }
 
     #[inline]
-    pub fn print_error_message(&self, message: &str) -> io::Result<()> {
+    pub fn print_chatbot_message(
+        &self,
+        name: &str,
+        message: &str,
+    ) -> io::Result<()> {
+        self.print_chatbot_prefix(name)?;
+        println!("{message}");
+        Ok(())
+    }
+
+    /// Prints a piece of a model's reasoning dimmed, so it reads apart from
+    /// the answer that follows.
+    #[inline]
+    pub fn print_reasoning(&self, text: &str) -> io::Result<()> {
+        if self.no_color {
+            print!("{text}");
+            io::stdout().flush()
+        } else {
+            execute!(
+                io::stdout(),
+                SetForegroundColor(Color::DarkGrey),
+                SetAttribute(Attribute::Dim),
+                Print(text),
+                ResetColor,
+                SetAttribute(Attribute::Reset),
+            )
+        }
+    }
+
+    /// Prints a dimmed line of details under a response.
+    #[inline]
+    pub fn print_footer(&self, text: &str) -> io::Result<()> {
+        self.print_reasoning(&format!("{text}\n"))
+    }
+
+    /// Prints the sources an answer cites as a numbered list, matching the
+    /// `[1]` style markers in the answer.
+    #[inline]
+    pub fn print_sources(&self, urls: &[String]) -> io::Result<()> {
+        self.print_app_message("Sources:")?;
+        for (idx, url) in urls.iter().enumerate() {
+            let line = format!("  [{}] {url}\n", idx.saturating_add(1));
+            self.print_reasoning(&line)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn print_code_block(&self, code: &str) -> io::Result<()> {
         if self.no_color {
-            println!("Error: {message}");
+            println!("{code}");
             Ok(())
         } else {
             execute!(
                 io::stdout(),
+                SetForegroundColor(Color::Green),
+                Print(code),
+                ResetColor,
+                Print("\n"),
+            )
+        }
+    }
+
+    /// Prints changed lines with a little surrounding context, eliding long
+    /// unchanged stretches.
+    #[inline]
+    pub fn print_diff(&self, diff: &[DiffLine<'_>]) -> io::Result<()> {
+        let changed: Vec<usize> = diff
+            .iter()
+            .enumerate()
+            .filter(|&(_, line)| !matches!(*line, DiffLine::Same(_)))
+            .map(|(idx, _)| idx)
+            .collect();
+        let near_change = |idx: usize| {
+            changed
+                .iter()
+                .any(|&change| change.abs_diff(idx) <= DIFF_CONTEXT)
+        };
+
+        let mut elided = false;
+        for (idx, line) in diff.iter().enumerate() {
+            let (prefix, text, color) = match *line {
+                DiffLine::Same(text) if near_change(idx) => (" ", text, None),
+                DiffLine::Same(_) => {
+                    if !elided {
+                        println!("  ...");
+                    }
+                    elided = true;
+                    continue;
+                }
+                DiffLine::Removed(text) => ("-", text, Some(Color::Red)),
+                DiffLine::Added(text) => ("+", text, Some(Color::Green)),
+            };
+            elided = false;
+
+            match color {
+                Some(color) if !self.no_color => execute!(
+                    io::stdout(),
+                    SetForegroundColor(color),
+                    Print(prefix),
+                    Print(" "),
+                    Print(text),
+                    ResetColor,
+                    Print("\n"),
+                )?,
+                _ => println!("{prefix} {text}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn print_warning_message(&self, message: &str) -> io::Result<()> {
+        self.write_warning(&mut io::stdout(), message)
+    }
+
+    /// Prints advice the user may act on, such as a better fitting model,
+    /// on stderr so that it never ends up in a piped answer.
+    #[inline]
+    pub fn print_advice(&self, message: &str) -> io::Result<()> {
+        self.write_warning(&mut io::stderr(), message)
+    }
+
+    fn write_warning(
+        self,
+        out: &mut impl Write,
+        message: &str,
+    ) -> io::Result<()> {
+        if self.strict {
+            return print_json_line(&serde_json::json!({
+                "level": "warning",
+                "message": message,
+            }));
+        }
+
+        if self.no_color {
+            writeln!(out, "Warning: {message}")
+        } else {
+            execute!(
+                out,
+                SetForegroundColor(Color::Yellow),
+                SetAttribute(Attribute::Bold),
+                Print("Warning: "),
+                ResetColor,
+                SetAttribute(Attribute::Reset),
+                Print(message),
+                Print("\n"),
+            )
+        }
+    }
+
+    #[inline]
+    pub fn print_error_message(&self, message: &str) -> io::Result<()> {
+        self.print_error("error", message)
+    }
+
+    /// Prints an error with a stable `code` naming its class, which is what
+    /// scripts see in strict mode.
+    #[inline]
+    pub fn print_error(&self, code: &str, message: &str) -> io::Result<()> {
+        self.write_error(&mut io::stdout(), code, message)
+    }
+
+    /// Prints an error that does not end the task at hand on stderr, so that
+    /// it never ends up in a piped answer.
+    #[inline]
+    pub fn print_error_notice(
+        &self,
+        code: &str,
+        message: &str,
+    ) -> io::Result<()> {
+        self.write_error(&mut io::stderr(), code, message)
+    }
+
+    fn write_error(
+        self,
+        out: &mut impl Write,
+        code: &str,
+        message: &str,
+    ) -> io::Result<()> {
+        if self.strict {
+            return print_json_line(&serde_json::json!({
+                "code": code,
+                "message": message,
+            }));
+        }
+
+        if self.no_color {
+            writeln!(out, "Error: {message}")
+        } else {
+            execute!(
+                out,
                 SetForegroundColor(Color::Red),
                 SetAttribute(Attribute::Bold),
                 Print("Error: "),
@@ -90,3 +449,178 @@ impl Printer {
         }
     }
 }
+
+/// Writes one line of strict mode output to stderr.
+fn print_json_line(line: &serde_json::Value) -> io::Result<()> {
+    let mut stderr = io::stderr();
+    writeln!(stderr, "{line}")?;
+    stderr.flush()
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reports streaming progress on stderr when stdout is piped, so the clean
+/// response can go to a file or another program while the user still sees
+/// that something is happening.
+pub struct Progress {
+    enabled: bool,
+    started: Instant,
+    last_update: Instant,
+    chars: usize,
+    drawn: usize,
+}
+
+impl Progress {
+    #[inline]
+    #[must_use]
+    pub fn start(printer: &Printer) -> Self {
+        let now = Instant::now();
+        Self {
+            enabled: !printer.is_strict()
+                && !io::stdout().is_terminal()
+                && io::stderr().is_terminal(),
+            started: now,
+            last_update: now,
+            chars: 0,
+            drawn: 0,
+        }
+    }
+
+    #[inline]
+    pub fn update(&mut self, text: &str) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        self.chars = self.chars.saturating_add(text.chars().count());
+        if self.last_update.elapsed() < PROGRESS_INTERVAL {
+            return Ok(());
+        }
+        self.last_update = Instant::now();
+
+        let width = usize::from(terminal_width());
+        let line: String = format!(
+            "llmcli: {} chars received, {:.1}s",
+            self.chars,
+            self.started.elapsed().as_secs_f64()
+        )
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect();
+
+        let mut stderr = io::stderr();
+        Self::clear(&mut stderr, self.drawn, width)?;
+        write!(stderr, "{line}")?;
+        self.drawn = line.chars().count();
+        stderr.flush()
+    }
+
+    #[inline]
+    pub fn finish(&self) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        Self::clear(
+            &mut io::stderr(),
+            self.drawn,
+            usize::from(terminal_width()),
+        )
+    }
+
+    /// Erases the last drawn line. After the window was narrowed it may
+    /// have wrapped onto several rows, which all have to go.
+    fn clear(
+        stderr: &mut io::Stderr,
+        drawn: usize,
+        width: usize,
+    ) -> io::Result<()> {
+        let wrapped_rows = drawn
+            .saturating_sub(1)
+            .checked_div(width)
+            .unwrap_or_default();
+        if let Ok(rows @ 1..) = u16::try_from(wrapped_rows) {
+            execute!(stderr, cursor::MoveToPreviousLine(rows))?;
+        }
+        execute!(
+            stderr,
+            Print("\r"),
+            terminal::Clear(terminal::ClearType::FromCursorDown),
+        )
+    }
+}
+
+/// Terminal title and OSC 9 integration. Everything is skipped when stdout
+/// is not a terminal.
+pub struct TerminalIntegration {
+    title: bool,
+    notifications: bool,
+    progress: bool,
+}
+
+impl TerminalIntegration {
+    #[inline]
+    #[must_use]
+    pub fn new(config: Option<&TerminalConfig>, printer: &Printer) -> Self {
+        let is_terminal = io::stdout().is_terminal() && !printer.is_strict();
+        let enabled = |option: Option<bool>, default: bool| {
+            is_terminal && option.unwrap_or(default)
+        };
+
+        Self {
+            title: enabled(config.and_then(|config| config.title), true),
+            notifications: enabled(
+                config.and_then(|config| config.notifications),
+                false,
+            ),
+            progress: enabled(config.and_then(|config| config.progress), false),
+        }
+    }
+
+    #[inline]
+    pub fn set_title(
+        &self,
+        session: Option<&str>,
+        chatbot: &dyn Chatbot,
+    ) -> io::Result<()> {
+        if !self.title {
+            return Ok(());
+        }
+
+        let title = format!(
+            "llmcli - {} - {} {}",
+            session.unwrap_or("new session"),
+            chatbot.name(),
+            chatbot.model()
+        );
+        execute!(io::stdout(), terminal::SetTitle(title))
+    }
+
+    #[inline]
+    pub fn start_progress(&self) -> io::Result<()> {
+        if !self.progress {
+            return Ok(());
+        }
+
+        // OSC 9;4 state 3 is an indeterminate progress indicator.
+        execute!(io::stdout(), Print("\x1b]9;4;3;0\x07"))
+    }
+
+    #[inline]
+    pub fn finish_progress(&self, success: bool, name: &str) -> io::Result<()> {
+        if self.progress {
+            execute!(io::stdout(), Print("\x1b]9;4;0;0\x07"))?;
+        }
+
+        if self.notifications {
+            let message = if success {
+                format!("{name} finished responding")
+            } else {
+                format!("{name} failed to respond")
+            };
+            execute!(io::stdout(), Print(format!("\x1b]9;{message}\x07")))?;
+        }
+
+        Ok(())
+    }
+}
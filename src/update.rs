@@ -0,0 +1,310 @@
+use core::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::Client;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{auth, config::Config};
+
+const RELEASES_URL: &str =
+    "https://api.github.com/repos/matee8/llmcli/releases/latest";
+
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const CHECK_INTERVAL: Duration = Duration::from_hours(24);
+
+/// The startup check gives up quickly, so a slow network never delays the
+/// prompt noticeably.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_mins(5);
+
+const HIGHLIGHTS: usize = 3;
+
+const ARCHIVE_SUFFIXES: [&str; 5] =
+    [".tar.gz", ".tgz", ".zip", ".sha256", ".sig"];
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    #[error("Failed to fetch the latest release: {0}.")]
+    Request(#[from] reqwest::Error),
+    #[error("No release binary for {0}.")]
+    NoAsset(String),
+    #[error("No checksum published for {0}, refusing to install it.")]
+    NoChecksum(String),
+    #[error("Checksum of {0} does not match, refusing to install it.")]
+    ChecksumMismatch(String),
+    #[error(
+        "llmcli was installed with cargo, update it with `cargo install \
+         llmcli`."
+    )]
+    CargoInstall,
+    #[error("Failed to replace the executable: {0}.")]
+    Replace(#[from] io::Error),
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    body: Option<String>,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+impl Release {
+    fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn is_newer(&self) -> bool {
+        parse_version(self.version()) > parse_version(CURRENT_VERSION)
+    }
+}
+
+/// A newer release and the first bullet points of its changelog.
+#[non_exhaustive]
+pub struct Notice {
+    pub version: String,
+    pub highlights: Vec<String>,
+}
+
+impl Display for Notice {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "llmcli {} is available (you have {CURRENT_VERSION})",
+            self.version
+        )?;
+        if !self.highlights.is_empty() {
+            write!(f, ": {}", self.highlights.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Looks for a newer release if `[update] check` is on and the last check
+/// was more than a day ago. Failures are silent, as the check is only a
+/// courtesy.
+#[inline]
+pub async fn check(config: &Config) -> Option<Notice> {
+    let enabled = config
+        .update
+        .as_ref()
+        .and_then(|update| update.check)
+        .unwrap_or(false);
+    if !enabled || !check_due() {
+        return None;
+    }
+
+    let release = latest_release(CHECK_TIMEOUT).await.ok();
+    mark_checked();
+    let release = release.filter(Release::is_newer)?;
+
+    Some(Notice {
+        version: release.version().to_owned(),
+        highlights: release
+            .body
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                line.trim()
+                    .strip_prefix("- ")
+                    .or_else(|| line.trim().strip_prefix("* "))
+            })
+            .take(HIGHLIGHTS)
+            .map(str::to_owned)
+            .collect(),
+    })
+}
+
+/// Replaces the running executable with the binary from the latest release
+/// for this platform, after checking it against the release's
+/// `<asset>.sha256` file.
+///
+/// Returns the installed version, or `None` when already up to date.
+#[inline]
+pub async fn self_update() -> Result<Option<String>, UpdateError> {
+    let exe = env::current_exe()?;
+    if exe
+        .components()
+        .any(|component| component.as_os_str() == ".cargo")
+    {
+        return Err(UpdateError::CargoInstall);
+    }
+
+    let release = latest_release(DOWNLOAD_TIMEOUT).await?;
+    if !release.is_newer() {
+        return Ok(None);
+    }
+
+    let platform = format!("{}-{}", env::consts::ARCH, env::consts::OS);
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| {
+            asset.name.contains(env::consts::ARCH)
+                && asset.name.contains(env::consts::OS)
+                && !ARCHIVE_SUFFIXES
+                    .iter()
+                    .any(|suffix| asset.name.ends_with(suffix))
+        })
+        .ok_or(UpdateError::NoAsset(platform))?;
+
+    let checksum_name = format!("{}.sha256", asset.name);
+    let checksum_asset = release
+        .assets
+        .iter()
+        .find(|checksum| checksum.name == checksum_name)
+        .ok_or_else(|| UpdateError::NoChecksum(asset.name.clone()))?;
+
+    let client = client(DOWNLOAD_TIMEOUT)?;
+    let checksum = client
+        .get(&checksum_asset.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let binary = client
+        .get(&asset.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    if !checksum_matches(&binary, &checksum) {
+        return Err(UpdateError::ChecksumMismatch(asset.name.clone()));
+    }
+    replace_executable(&exe, &binary)?;
+
+    Ok(Some(release.version().to_owned()))
+}
+
+fn client(timeout: Duration) -> Result<Client, reqwest::Error> {
+    Client::builder()
+        .user_agent(concat!("llmcli/", env!("CARGO_PKG_VERSION")))
+        .timeout(timeout)
+        .build()
+}
+
+async fn latest_release(timeout: Duration) -> Result<Release, reqwest::Error> {
+    client(timeout)?
+        .get(RELEASES_URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+/// Writes the new binary next to the old one and swaps them. The old
+/// binary is moved aside first, since a running executable cannot be
+/// overwritten on Windows.
+fn replace_executable(exe: &Path, binary: &[u8]) -> io::Result<()> {
+    let new = exe.with_extension("new");
+    let old = exe.with_extension("old");
+    fs::write(&new, binary)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+    }
+
+    fs::rename(exe, &old)?;
+    if let Err(err) = fs::rename(&new, exe) {
+        fs::rename(&old, exe)?;
+        return Err(err);
+    }
+    // Windows keeps the running binary locked, so the old copy may stay
+    // until the next update replaces it. The new one is in place either way.
+    fs::remove_file(&old).unwrap_or_default();
+
+    Ok(())
+}
+
+/// Compares `data` with a checksum file in the `sha256sum` format, a hex
+/// digest optionally followed by the file name.
+fn checksum_matches(data: &[u8], checksum_file: &str) -> bool {
+    checksum_file
+        .split_whitespace()
+        .next()
+        .is_some_and(|expected| {
+            expected.eq_ignore_ascii_case(&auth::sha256_hex(data))
+        })
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+fn state_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("llmcli_update_check"))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn check_due() -> bool {
+    let last_check = state_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse::<u64>().ok());
+
+    last_check.is_none_or(|last_check| {
+        now_secs().saturating_sub(last_check) >= CHECK_INTERVAL.as_secs()
+    })
+}
+
+fn mark_checked() {
+    if let Some(path) = state_path() {
+        // Without the mark the check only runs again sooner.
+        fs::write(path, now_secs().to_string()).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::checksum_matches;
+
+    const HELLO_SHA256: &str =
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn accepts_a_matching_checksum_file() {
+        assert!(checksum_matches(b"hello", HELLO_SHA256));
+        assert!(checksum_matches(
+            b"hello",
+            &format!("{}  llmcli-x86_64-linux\n", HELLO_SHA256.to_uppercase())
+        ));
+    }
+
+    #[test]
+    fn rejects_a_wrong_or_empty_checksum() {
+        assert!(!checksum_matches(b"hello!", HELLO_SHA256));
+        assert!(!checksum_matches(b"hello", ""));
+        assert!(!checksum_matches(b"hello", "not-a-digest"));
+    }
+}